@@ -0,0 +1,382 @@
+//! 운영자용 `/admin` API. `ADMIN_TOKEN`이 설정된 경우에만 마운트되며
+//! 모든 요청에 `Authorization: Bearer <token>` 이 필요하다.
+
+use crate::{
+    allocator,
+    blocklist::{self, BlockEntry, BlockKind},
+    purge, warm, AppState,
+};
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc};
+use tracing::{info, warn};
+
+pub fn router(token: Arc<str>) -> Router<AppState> {
+    Router::new()
+        .route("/memstats", get(memstats))
+        .route("/config", get(effective_config))
+        .route("/cache/:key", delete(purge_emoji))
+        .route("/cache/:key/preview", get(preview_entry))
+        .route("/blocklist", get(list_blocklist).post(add_blocklist))
+        .route("/blocklist/:kind/:value", delete(remove_blocklist))
+        .route("/errors", get(list_errors))
+        .route("/slo", get(slo_summary))
+        .route("/reports", get(list_reports))
+        .route("/reports/:emoji_id", get(get_report).delete(dismiss_report))
+        .route("/warm", post(warm_from_logs))
+        .route("/maintenance", post(run_maintenance))
+        .route(
+            "/archive/:guild_id",
+            get(crate::archive::manifest_handler).post(crate::archive::archive_handler),
+        )
+        .route("/custom/deleted", get(crate::custom::deleted_handler))
+        .route("/custom/review", get(crate::custom::review_queue_handler))
+        .route(
+            "/custom/review/*path",
+            get(crate::custom::review_image_handler).post(crate::custom::review_handler),
+        )
+        .route("/custom/:name/restore", post(crate::custom::restore_handler))
+        .route("/custom/:guild_id/:name/restore", post(crate::custom::guild_restore_handler))
+        .route_layer(middleware::from_fn_with_state(token, require_token))
+}
+
+async fn require_token(State(token): State<Arc<str>>, req: Request, next: Next) -> Response {
+    let presented = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match presented {
+        Some(t) if constant_time_eq(t.as_bytes(), token.as_bytes()) => next.run(req).await,
+        _ => {
+            warn!("rejected admin request to {}", req.uri().path());
+            (StatusCode::UNAUTHORIZED, "unauthorized").into_response()
+        }
+    }
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn memstats() -> impl IntoResponse {
+    Json(allocator::stats())
+}
+
+/// 부팅 로그에 남긴 것과 같은 유효 설정 (비밀 값은 가림)
+async fn effective_config(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.config.summary())
+}
+
+/// 로컬 캐시(본 캐시 + stale 보관분 + 검증자 + 2차 계층, 패스스루/원본 포함)에서 이모지를 지우고, CDN purge가 설정돼 있으면
+/// 엣지에도 전파한다. `(로컬에 있었는지, CDN 상태)`를 돌려준다.
+pub(crate) async fn purge_everywhere(state: &AppState, key: &str) -> (bool, &'static str) {
+    // 특정 변형의 캐시 키(`123@64`, `slack/ws/name:original` 등)가 와도 모든 변형을 지운다
+    let (emoji_id, _) = crate::Variant::from_key(key);
+    let emoji_id = emoji_id.as_str();
+    let mut cached = false;
+    for key in crate::Variant::keys(&state.config, emoji_id).into_iter().map(|(_, key)| key) {
+        cached |= state.cache.contains_key(&key) || state.stale.contains_key(&key);
+        state.cache.invalidate(&key).await;
+        state.stale.invalidate(&key).await;
+        state.validators.invalidate(&key).await;
+        state.tiers.remove(&key).await;
+    }
+    // 모양 옵션을 켠 결과는 꼽을 수 없으니 메모리 캐시를 훑는다 (2차 계층에는 없다)
+    let effect_keys: Vec<String> = state
+        .cache
+        .iter()
+        .chain(state.stale.iter())
+        .map(|(key, _)| key.to_string())
+        .filter(|key| crate::effects::is_effect_key(key) && crate::Variant::from_key(key).0 == emoji_id)
+        .collect();
+    for key in effect_keys {
+        cached = true;
+        state.cache.invalidate(&key).await;
+        state.stale.invalidate(&key).await;
+        state.validators.invalidate(&key).await;
+    }
+    info!("purge - emoji: {}, was cached: {}", emoji_id, cached);
+
+    let cdn = match &state.purger {
+        Some(purger) => {
+            let purger = purger.clone();
+            let id = emoji_id.to_string();
+            let submitted = state.tasks.submit("cdn_purge", 3, move || {
+                let (purger, id) = (purger.clone(), id.clone());
+                async move {
+                    purger.purge_tags(&[purge::emoji_tag(&id)]).await?;
+                    info!("{} purge done - emoji: {}", purger.provider().name(), id);
+                    Ok(())
+                }
+            });
+            if submitted { "queued" } else { "dropped" }
+        }
+        None => "disabled",
+    };
+    (cached, cdn)
+}
+
+async fn purge_emoji(State(state): State<AppState>, Path(emoji_id): Path<String>) -> impl IntoResponse {
+    let (cached, cdn) = purge_everywhere(&state, &emoji_id).await;
+    Json(serde_json::json!({
+        "emoji_id": emoji_id,
+        "cached": cached,
+        "cdn": cdn,
+    }))
+}
+
+/// 캐시 항목의 바이트를 그대로 보여 준다 (지금 무엇이 캐시돼 있는지 운영자가 눈으로 확인하는 용도).
+/// 메모리 → 만료 보관분 → 2차 계층 순으로 찾되 서빙 경로를 타지 않으므로 인기도 집계·히트 메트릭·계층 승격에
+/// 영향이 없다. 엣지나 브라우저가 담아 두지 않도록 `no-store`로 답하고, 무엇을 봤는지 로그에 남긴다.
+async fn preview_entry(State(state): State<AppState>, Path(key): Path<String>) -> Response {
+    let found = match state.cache.get(&key).await {
+        Some(entry) => Some((entry, "memory")),
+        None => match state.stale.get(&key).await {
+            Some(entry) => Some((entry, "stale")),
+            None => state
+                .tiers
+                .get(&key)
+                .await
+                .map(|(stored, tier)| (crate::Cached::stored_at(Arc::new(stored.bytes), stored.stored), tier)),
+        },
+    };
+    let Some((entry, layer)) = found else {
+        return (StatusCode::NOT_FOUND, "no cached bytes for this key").into_response();
+    };
+    info!("cache preview - key: {}, layer: {}, {} bytes", key, layer, entry.bytes.len());
+    let filename: String = key.chars().map(|c| if c.is_ascii_alphanumeric() || "@-_.".contains(c) { c } else { '_' }).collect();
+    let extension = entry.content_type.as_str().trim_start_matches("image/");
+    (
+        [
+            (header::CONTENT_TYPE, entry.content_type.as_str().to_string()),
+            (header::CONTENT_DISPOSITION, format!("inline; filename=\"{filename}.{extension}\"")),
+            (header::CACHE_CONTROL, "private, no-store".to_string()),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            (header::HeaderName::from_static("x-cache-layer"), layer.to_string()),
+        ],
+        entry.bytes.as_ref().clone(),
+    )
+        .into_response()
+}
+
+async fn list_blocklist(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.blocklist.entries())
+}
+
+/// 차단 항목을 추가하고 이미 캐시된 해당 콘텐츠를 제거한다
+async fn add_blocklist(State(state): State<AppState>, Json(req): Json<BlockEntry>) -> Response {
+    let entry = match BlockEntry::new(req.kind, &req.value) {
+        Ok(e) => e,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let added = match state.blocklist.insert(entry.clone()) {
+        Ok(added) => added,
+        Err(e) => {
+            warn!("failed to persist blocklist: {e:#}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "failed to persist blocklist").into_response();
+        }
+    };
+    info!("blocklist add - {:?}: {}", entry.kind, entry.value);
+
+    // 영향받는 캐시 키 찾기: ID는 그대로, 해시는 캐시된 바이트를 훑어서
+    let keys: Vec<String> = match entry.kind {
+        BlockKind::Id => vec![entry.value.clone()],
+        BlockKind::Hash => state
+            .cache
+            .iter()
+            .chain(state.stale.iter())
+            .filter(|(_, cached)| blocklist::content_hash(&cached.bytes).starts_with(&entry.value))
+            .map(|(k, _)| k.as_ref().clone())
+            .collect(),
+        // Discord 키는 이모지 ID로 시작하고, 다른 소스는 `소스/…`로 시작한다
+        BlockKind::Source => state
+            .cache
+            .iter()
+            .chain(state.stale.iter())
+            .map(|(k, _)| k.as_ref().clone())
+            .filter(|k| match k.split_once('/') {
+                Some((source, _)) => source == entry.value,
+                None => entry.value == "discord",
+            })
+            .collect(),
+    };
+    let mut purged = 0;
+    for key in &keys {
+        if purge_everywhere(&state, key).await.0 {
+            purged += 1;
+        }
+    }
+
+    let status = if added { StatusCode::CREATED } else { StatusCode::OK };
+    (status, Json(serde_json::json!({ "added": added, "purged": purged }))).into_response()
+}
+
+async fn remove_blocklist(
+    State(state): State<AppState>,
+    Path((kind, value)): Path<(String, String)>,
+) -> Response {
+    let Some(kind) = BlockKind::parse(&kind) else {
+        return (StatusCode::BAD_REQUEST, "unknown kind").into_response();
+    };
+    let entry = match BlockEntry::new(kind, &value) {
+        Ok(e) => e,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match state.blocklist.remove(&entry) {
+        Ok(true) => {
+            info!("blocklist remove - {:?}: {}", entry.kind, entry.value);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "not in blocklist").into_response(),
+        Err(e) => {
+            warn!("failed to persist blocklist: {e:#}");
+            (StatusCode::INTERNAL_SERVER_ERROR, "failed to persist blocklist").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ErrorsParams {
+    emoji: Option<String>,
+    limit: Option<usize>,
+}
+
+/// `GET /admin/errors?emoji=…&limit=…`: 최근 실패한 이미지 요청 (최근 것부터, 기본 100개)
+async fn list_errors(State(state): State<AppState>, Query(params): Query<ErrorsParams>) -> impl IntoResponse {
+    Json(state.recent_errors.list(params.emoji.as_deref(), params.limit.unwrap_or(100)))
+}
+
+/// `GET /admin/slo`: 소스별 캐시 미스 지연 백분위수와 SLO 소진율 (최근 `SLO_WINDOW_SECS`)
+async fn slo_summary(State(state): State<AppState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "target_ms": state.slo.target().as_millis() as u64,
+        "objective": state.slo.objective(),
+        "window_secs": state.slo.window().as_secs(),
+        "sources": state.slo.snapshot(),
+    }))
+}
+
+/// 신고 수 내림차순 목록. 이미 차단된 이모지인지 함께 보여준다.
+async fn list_reports(State(state): State<AppState>) -> impl IntoResponse {
+    let list: Vec<_> = state
+        .reports
+        .store
+        .list()
+        .into_iter()
+        .map(|(emoji_id, rec)| {
+            serde_json::json!({
+                "emoji_id": emoji_id,
+                "blocked": state.blocklist.blocks_id(&emoji_id),
+                "count": rec.count,
+                "reasons": rec.reasons,
+                "first_reported": rec.first_reported,
+                "last_reported": rec.last_reported,
+            })
+        })
+        .collect();
+    Json(list)
+}
+
+async fn get_report(State(state): State<AppState>, Path(emoji_id): Path<String>) -> Response {
+    match state.reports.store.get(&emoji_id) {
+        Some(rec) => Json(rec).into_response(),
+        None => (StatusCode::NOT_FOUND, "no reports").into_response(),
+    }
+}
+
+/// 검토가 끝난 신고를 목록에서 치운다
+async fn dismiss_report(State(state): State<AppState>, Path(emoji_id): Path<String>) -> StatusCode {
+    if state.reports.store.dismiss(&emoji_id) {
+        info!("reports dismissed - emoji: {}", emoji_id);
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+#[derive(Default, Deserialize)]
+struct WarmRequest {
+    /// 미리 가져올 상위 이모지 수
+    top: Option<usize>,
+    /// 현재 파일 외에 읽을 최근 회전 로그 수
+    archives: Option<usize>,
+    concurrency: Option<usize>,
+}
+
+/// `LOG_DIR`의 최근 접근 로그에서 많이 요청된 이모지를 골라 백그라운드에서 미리 가져온다
+async fn warm_from_logs(State(state): State<AppState>, req: Option<Json<WarmRequest>>) -> Response {
+    let Some(log) = state.config.log_file.clone() else {
+        return (StatusCode::BAD_REQUEST, "file logging (LOG_DIR) is not enabled").into_response();
+    };
+    let req = req.map(|Json(r)| r).unwrap_or_default();
+    let top = req.top.unwrap_or(warm::DEFAULT_TOP);
+    let archives = req.archives.unwrap_or(1);
+    let concurrency = req.concurrency.unwrap_or(warm::DEFAULT_CONCURRENCY);
+
+    let scanned = tokio::task::spawn_blocking(move || {
+        let files = warm::recent_logs(&log.dir, archives)?;
+        let mut counts = HashMap::new();
+        for file in &files {
+            warm::count_file(file, &mut counts)?;
+        }
+        anyhow::Ok((files.len(), warm::rank(counts, top)))
+    })
+    .await;
+    let (files, ids) = match scanned {
+        Ok(Ok(v)) => v,
+        Ok(Err(e)) => {
+            warn!("warm log scan failed: {e:#}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "failed to read access logs").into_response();
+        }
+        Err(e) => {
+            warn!("warm log scan task failed: {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "failed to read access logs").into_response();
+        }
+    };
+
+    info!("warm - {} emojis from {} log files", ids.len(), files);
+    let queued = ids.len();
+    let tasks = state.tasks.clone();
+    let submitted = tasks.submit("warm", 0, move || {
+        let (state, ids) = (state.clone(), ids.clone());
+        async move {
+            let (ok, failed) = warm::warm_local(state, ids, concurrency).await;
+            info!("warm done - {} ok, {} failed", ok, failed);
+            Ok(())
+        }
+    });
+    if !submitted {
+        return (StatusCode::SERVICE_UNAVAILABLE, "task queue full").into_response();
+    }
+    (
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "files": files, "queued": queued })),
+    )
+        .into_response()
+}
+
+/// 예약 주기를 기다리지 않고 2차 캐시 정리를 바로 돌린다
+async fn run_maintenance(State(state): State<AppState>) -> Response {
+    let Some(reports) = state.tiers.maintain().await else {
+        return (StatusCode::CONFLICT, "maintenance already running").into_response();
+    };
+    let tiers: serde_json::Map<_, _> = reports
+        .into_iter()
+        .map(|(name, report)| (name.to_string(), serde_json::json!(report)))
+        .collect();
+    let mut body = serde_json::json!({ "tiers": tiers });
+    // 커스텀 이모트 저장소의 참조 없는 블롭도 함께 정리
+    if let Some(store) = &state.custom {
+        body["custom"] = serde_json::json!(crate::custom::run_gc(store).await);
+    }
+    Json(body).into_response()
+}
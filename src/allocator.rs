@@ -0,0 +1,92 @@
+//! 전역 할당자 선택과 메모리 통계.
+//!
+//! 이미지 작업은 크기가 제각각인 버퍼를 대량으로 할당/해제해서 시스템 할당자를
+//! 심하게 단편화시킨다. `jemalloc` / `mimalloc` 피처로 할당자를 바꿀 수 있다.
+
+use serde_json::{json, Value};
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc")))]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+/// 현재 할당자의 통계를 JSON으로 돌려준다. 알 수 없는 값은 `null`.
+#[cfg(feature = "jemalloc")]
+pub fn stats() -> Value {
+    use tikv_jemalloc_ctl::{epoch, stats};
+
+    // jemalloc 통계는 epoch를 올려야 갱신된다
+    let _ = epoch::advance();
+    let allocated = stats::allocated::read().ok();
+    let active = stats::active::read().ok();
+    let resident = stats::resident::read().ok();
+    let fragmentation = match (allocated, resident) {
+        (Some(a), Some(r)) if r > 0 => Some(1.0 - a as f64 / r as f64),
+        _ => None,
+    };
+    json!({
+        "allocator": "jemalloc",
+        "resident_bytes": resident,
+        "active_bytes": active,
+        "allocated_bytes": allocated,
+        "mapped_bytes": stats::mapped::read().ok(),
+        "retained_bytes": stats::retained::read().ok(),
+        "fragmentation": fragmentation,
+    })
+}
+
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc")))]
+pub fn stats() -> Value {
+    let (mut elapsed, mut user, mut system) = (0usize, 0usize, 0usize);
+    let (mut rss, mut peak_rss, mut commit, mut peak_commit, mut faults) =
+        (0usize, 0usize, 0usize, 0usize, 0usize);
+    // SAFETY: 모든 포인터가 유효한 지역 변수를 가리킨다
+    unsafe {
+        libmimalloc_sys::mi_process_info(
+            &mut elapsed,
+            &mut user,
+            &mut system,
+            &mut rss,
+            &mut peak_rss,
+            &mut commit,
+            &mut peak_commit,
+            &mut faults,
+        );
+    }
+    // mimalloc은 살아있는 할당량을 따로 알려주지 않으므로 commit 대비 RSS로 근사
+    let fragmentation = (rss > 0).then(|| 1.0 - commit.min(rss) as f64 / rss as f64);
+    json!({
+        "allocator": "mimalloc",
+        "resident_bytes": rss,
+        "active_bytes": commit,
+        "allocated_bytes": null,
+        "peak_resident_bytes": peak_rss,
+        "peak_active_bytes": peak_commit,
+        "fragmentation": fragmentation,
+    })
+}
+
+#[cfg(not(any(feature = "jemalloc", feature = "mimalloc")))]
+pub fn stats() -> Value {
+    json!({
+        "allocator": "system",
+        "resident_bytes": proc_status_bytes("VmRSS:"),
+        "active_bytes": proc_status_bytes("RssAnon:"),
+        "allocated_bytes": null,
+        "fragmentation": null,
+    })
+}
+
+/// `/proc/self/status` 의 kB 값을 바이트로 읽는다 (Linux 전용)
+#[cfg(not(any(feature = "jemalloc", feature = "mimalloc")))]
+fn proc_status_bytes(field: &str) -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|l| l.strip_prefix(field))
+        .and_then(|v| v.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+}
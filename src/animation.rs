@@ -0,0 +1,252 @@
+//! 애니메이션 GIF·APNG 디코드.
+//!
+//! 두 포맷 모두 프레임이 캔버스 일부만 덮는 조각이고, 다음 프레임을 그리기 전에 그 자리를 어떻게 할지
+//! (폐기 방식)와 조각을 캔버스에 어떻게 얹을지(블렌딩)가 프레임마다 정해져 있다. 합성은 `image`의
+//! `GifDecoder`와 `PngDecoder::apng()`에 맡기고, 여기서는 합성된 캔버스 전체를 프레임마다 모아 브라우저와 같은
+//! 지연·반복 규칙을 붙인다. 결과는 [`crate::imaging`]이 애니메이션 WebP와 똑같이 리사이즈한다.
+//!
+//! - GIF: 10ms 이하 지연은 브라우저처럼 100ms로 본다. 반복 횟수는 `NETSCAPE2.0` 확장을 따르고 없으면 한 번 재생.
+//!   `image`는 확장이 없는 것과 무한 반복을 구분하지 않으므로 반복 횟수만 블록 구조에서 직접 읽는다.
+//! - APNG: 첫 `fcTL`이 `IDAT`보다 뒤에 있으면 `IDAT`은 애니메이션에 들어가지 않는 기본 이미지다. 16비트 APNG는
+//!   지원하지 않는다.
+
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder},
+    error::{DecodingError, ImageFormatHint},
+    metadata::LoopCount,
+    AnimationDecoder, Delay, ImageDecoder, ImageError, ImageFormat, RgbaImage,
+};
+use std::io::Cursor;
+
+/// 한 애니메이션에서 풀어 놓을 최대 픽셀 수 (프레임 수 × 캔버스 넓이). 작은 파일이 수 GB로 불어나지 않도록.
+const MAX_DECODED_PIXELS: u64 = 64 * 1024 * 1024;
+
+/// 합성된 애니메이션
+pub struct Animation {
+    pub width: u32,
+    pub height: u32,
+    /// 재생 횟수 (0이면 무한, WebP `ANIM`과 같은 뜻)
+    pub loop_count: u16,
+    pub frames: Vec<Frame>,
+}
+
+/// 합성이 끝난 캔버스 한 장
+pub struct Frame {
+    pub image: RgbaImage,
+    pub delay_ms: u32,
+}
+
+/// 프레임이 둘 이상인 GIF나 APNG인지 (본문을 풀지 않고 블록·청크 구조만 훑는다)
+pub fn is_animated(data: &[u8]) -> bool {
+    is_animated_gif(data) || is_apng(data)
+}
+
+pub fn is_animated_gif(data: &[u8]) -> bool {
+    let mut images = 0;
+    let walked = walk_gif(data, |block| {
+        if let GifBlock::Image = block {
+            images += 1;
+        }
+        images < 2
+    });
+    walked.is_ok() && images >= 2
+}
+
+/// `acTL`이 첫 `IDAT` 앞에 있고 프레임이 둘 이상인 PNG
+pub fn is_apng(data: &[u8]) -> bool {
+    let Some(chunks) = png_chunks(data) else {
+        return false;
+    };
+    for (kind, body) in chunks {
+        match kind {
+            b"acTL" => return body.len() >= 8 && be32(&body[0..4]) > 1,
+            b"IDAT" | b"IEND" => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// 포맷을 보고 GIF나 APNG로 디코드한다
+pub fn decode(data: &[u8]) -> Result<Animation, ImageError> {
+    if data.starts_with(b"GIF8") {
+        let decoder = GifDecoder::new(Cursor::new(data))?;
+        let (width, height) = decoder.dimensions();
+        // `NETSCAPE2.0`의 n은 처음 재생 뒤 더 반복할 횟수
+        let loop_count = match gif_loops(data) {
+            None => 1,
+            Some(0) => 0,
+            Some(n) => n.saturating_add(1),
+        };
+        collect(ImageFormat::Gif, width, height, loop_count, decoder, |ms| if ms <= 10 { 100 } else { ms })
+    } else {
+        let decoder = PngDecoder::new(Cursor::new(data))?;
+        let (width, height) = decoder.dimensions();
+        let decoder = decoder.apng()?;
+        let loop_count = match decoder.loop_count() {
+            LoopCount::Infinite => 0,
+            LoopCount::Finite(n) => n.get().min(u16::MAX as u32) as u16,
+        };
+        collect(ImageFormat::Png, width, height, loop_count, decoder, |ms| ms)
+    }
+}
+
+/// 합성된 프레임을 모은다. 프레임을 하나 풀 때마다 예산을 확인해 도중에 멈출 수 있게 한다.
+fn collect<'a>(
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    loop_count: u16,
+    decoder: impl AnimationDecoder<'a>,
+    delay_ms: impl Fn(u32) -> u32,
+) -> Result<Animation, ImageError> {
+    check_budget(width, height, 0).map_err(|e| decoding(format, e))?;
+    let mut frames = Vec::new();
+    for frame in decoder.into_frames() {
+        let frame = frame?;
+        check_budget(width, height, frames.len() + 1).map_err(|e| decoding(format, e))?;
+        let delay = delay_ms(millis(frame.delay()));
+        frames.push(Frame { image: frame.into_buffer(), delay_ms: delay });
+    }
+    if frames.is_empty() {
+        return Err(decoding(format, "animation has no frames".into()));
+    }
+    Ok(Animation { width, height, loop_count, frames })
+}
+
+fn millis(delay: Delay) -> u32 {
+    let (numer, denom) = delay.numer_denom_ms();
+    numer / denom.max(1)
+}
+
+fn decoding(format: ImageFormat, message: String) -> ImageError {
+    ImageError::Decoding(DecodingError::new(ImageFormatHint::Exact(format), message))
+}
+
+fn check_budget(width: u32, height: u32, frames: usize) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err("empty canvas".into());
+    }
+    if (width as u64 * height as u64).saturating_mul(frames as u64 + 1) > MAX_DECODED_PIXELS {
+        return Err(format!("animation too large ({width}x{height}, {} frames)", frames + 1));
+    }
+    Ok(())
+}
+
+// ---- GIF 블록 구조 ----
+
+enum GifBlock {
+    /// 애플리케이션 확장 `NETSCAPE2.0`/`ANIMEXTS1.0`의 반복 횟수
+    Loop(u16),
+    Image,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Result<u8, String> {
+        let b = *self.data.get(self.pos).ok_or("unexpected end of file")?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self.data.get(self.pos..self.pos + n).ok_or("unexpected end of file")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    /// 길이 바이트로 나뉜 데이터 하위 블록들을 건너뛰고 첫 하위 블록만 돌려준다
+    fn sub_blocks(&mut self) -> Result<&'a [u8], String> {
+        let mut first = None;
+        loop {
+            let len = self.u8()? as usize;
+            if len == 0 {
+                return Ok(first.unwrap_or_default());
+            }
+            let block = self.take(len)?;
+            first.get_or_insert(block);
+        }
+    }
+}
+
+/// 블록마다 `visit`을 부른다 (`visit`이 거짓이면 멈춤). 이미지 데이터는 풀지 않고 건너뛴다.
+/// 끝 표시(`;`) 없이 끝난 파일은 거기까지로 본다.
+fn walk_gif(data: &[u8], mut visit: impl FnMut(GifBlock) -> bool) -> Result<(), String> {
+    if !(data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")) {
+        return Err("not a GIF".into());
+    }
+    let mut r = Reader { data, pos: 10 };
+    let packed = r.u8()?;
+    r.take(2)?; // 배경색 번호, 픽셀 종횡비
+    if packed & 0x80 != 0 {
+        r.take(3 << ((packed & 7) + 1))?;
+    }
+    while let Ok(introducer) = r.u8() {
+        let block = match introducer {
+            b';' => break,
+            b'!' => match r.u8()? {
+                0xFF => {
+                    let len = r.u8()? as usize;
+                    let id = r.take(len)?;
+                    match (id, r.sub_blocks()?) {
+                        (b"NETSCAPE2.0" | b"ANIMEXTS1.0", [1, lo, hi, ..]) => GifBlock::Loop(u16::from_le_bytes([*lo, *hi])),
+                        _ => continue,
+                    }
+                }
+                _ => {
+                    r.sub_blocks()?;
+                    continue;
+                }
+            },
+            b',' => {
+                r.take(8)?; // 위치, 크기
+                let packed = r.u8()?;
+                if packed & 0x80 != 0 {
+                    r.take(3 << ((packed & 7) + 1))?;
+                }
+                r.u8()?; // LZW 최소 부호 길이
+                r.sub_blocks()?;
+                GifBlock::Image
+            }
+            other => return Err(format!("unknown GIF block 0x{other:02x}")),
+        };
+        if !visit(block) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// `NETSCAPE2.0` 확장의 반복 횟수
+fn gif_loops(data: &[u8]) -> Option<u16> {
+    let mut loops = None;
+    let _ = walk_gif(data, |block| {
+        if let GifBlock::Loop(n) = block {
+            loops = Some(n);
+        }
+        true
+    });
+    loops
+}
+
+// ---- PNG 청크 구조 ----
+
+fn be32(b: &[u8]) -> u32 {
+    u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+}
+
+/// PNG 청크 (종류, 내용). CRC는 확인하지 않는다 (업스트림 검증을 이미 거쳤다).
+fn png_chunks(data: &[u8]) -> Option<impl Iterator<Item = (&[u8; 4], &[u8])>> {
+    let mut rest = data.strip_prefix(b"\x89PNG\r\n\x1a\n")?;
+    Some(std::iter::from_fn(move || {
+        let len = be32(rest.get(0..4)?) as usize;
+        let kind: &[u8; 4] = rest.get(4..8)?.try_into().ok()?;
+        let body = rest.get(8..8 + len)?;
+        rest = rest.get(12 + len..).unwrap_or_default();
+        Some((kind, body))
+    }))
+}
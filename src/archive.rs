@@ -0,0 +1,189 @@
+//! Discord 길드 이모지 보관 (`/admin/archive/:guild_id`).
+//!
+//! 봇 토큰으로 길드의 이모지 목록을 받아 이모지마다 원본(`/original`)과 `OUTPUT_SIZES`의 리사이즈 결과를
+//! 미리 만들어 둔다. 원본은 보관 미러(`MIRROR_DIR`)에, 리사이즈 결과는 캐시 계층에 남고, 무엇을 남겼는지
+//! 매니페스트를 미러의 `archives/<길드 ID>.json`에 쓴다. 길드가 지워져도 매니페스트에 있는 이모지는
+//! 미러에서 계속 제공된다.
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::{blocklist, response::RequestInputs, search::GuildEmoji, warm, AppState, Mode, Variant};
+
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    pub guild_id: String,
+    /// 보관을 마친 시각 (유닉스 초)
+    pub archived_at: u64,
+    pub emojis: Vec<ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub id: String,
+    pub name: String,
+    pub animated: bool,
+    /// 보관한 원본 (받지 못했으면 `None`)
+    pub original: Option<ArchivedOriginal>,
+    /// 캐시에 만들어 둔 리사이즈 크기
+    pub sizes: Vec<u32>,
+    /// 만들지 못한 변형과 상태 코드 (`original: 502`, `64: 504` 등)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedOriginal {
+    pub content_type: String,
+    pub bytes: usize,
+    /// 본문의 SHA-1 (ETag·차단 목록 해시와 같은 값)
+    pub sha1: String,
+}
+
+/// 길드 이모지 보관을 백그라운드 작업으로 건다
+pub(crate) async fn archive_handler(State(state): State<AppState>, Path(guild_id): Path<String>) -> Response {
+    if !valid_guild_id(&guild_id) {
+        return (StatusCode::BAD_REQUEST, "guild id must be numeric").into_response();
+    }
+    let Some(guilds) = state.guilds.clone() else {
+        return (StatusCode::BAD_REQUEST, "DISCORD_BOT_TOKEN is not set").into_response();
+    };
+    if state.mirror.is_none() {
+        return (StatusCode::BAD_REQUEST, "origin mirror (MIRROR_DIR) is not enabled").into_response();
+    }
+    let list = match guilds.list(&guild_id).await {
+        Ok(list) => list,
+        Err(e) => {
+            warn!("archive - guild emoji list failed for {}: {:#}", guild_id, e);
+            return (StatusCode::BAD_GATEWAY, "failed to list guild emojis").into_response();
+        }
+    };
+    // 차단된 이모지는 보관하지도 않는다
+    let emojis: Vec<GuildEmoji> = list.iter().filter(|e| !state.blocklist.blocks_id(&e.id)).cloned().collect();
+
+    info!("archive - {} emojis from guild {}", emojis.len(), guild_id);
+    let queued = emojis.len();
+    let tasks = state.tasks.clone();
+    let id = guild_id.clone();
+    let submitted = tasks.submit("archive", 0, move || {
+        let (state, guild_id, emojis) = (state.clone(), id.clone(), emojis.clone());
+        async move { archive(state, guild_id, emojis).await }
+    });
+    if !submitted {
+        return (StatusCode::SERVICE_UNAVAILABLE, "task queue full").into_response();
+    }
+    (StatusCode::ACCEPTED, Json(serde_json::json!({ "guild_id": guild_id, "queued": queued }))).into_response()
+}
+
+/// 마지막으로 쓴 매니페스트 (보관이 끝나기 전이나 한 적이 없으면 404)
+pub(crate) async fn manifest_handler(State(state): State<AppState>, Path(guild_id): Path<String>) -> Response {
+    if !valid_guild_id(&guild_id) {
+        return (StatusCode::BAD_REQUEST, "guild id must be numeric").into_response();
+    }
+    let Some(mirror) = &state.mirror else {
+        return (StatusCode::BAD_REQUEST, "origin mirror (MIRROR_DIR) is not enabled").into_response();
+    };
+    match tokio::fs::read(mirror.manifest_path(&guild_id)).await {
+        Ok(body) => ([(header::CONTENT_TYPE, "application/json")], body).into_response(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (StatusCode::NOT_FOUND, "no archive").into_response(),
+        Err(e) => {
+            warn!("archive - manifest read failed for {}: {}", guild_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "failed to read manifest").into_response()
+        }
+    }
+}
+
+async fn archive(state: AppState, guild_id: String, emojis: Vec<GuildEmoji>) -> anyhow::Result<()> {
+    let mut tasks = JoinSet::new();
+    let mut entries = Vec::with_capacity(emojis.len());
+    for emoji in emojis {
+        if tasks.len() >= warm::DEFAULT_CONCURRENCY {
+            if let Some(entry) = tasks.join_next().await {
+                entries.push(entry?);
+            }
+        }
+        tasks.spawn(archive_emoji(state.clone(), emoji));
+    }
+    while let Some(entry) = tasks.join_next().await {
+        entries.push(entry?);
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let archived = entries.iter().filter(|e| e.original.is_some()).count();
+    let manifest = Manifest { guild_id: guild_id.clone(), archived_at: now_secs(), emojis: entries };
+    let path = state.mirror.as_ref().expect("checked by the handler").manifest_path(&guild_id);
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    // 읽는 쪽이 반쯤 쓰인 매니페스트를 보지 않도록 임시 파일에 쓰고 교체
+    let tmp = path.with_extension(format!("tmp.{}", uuid::Uuid::new_v4().simple()));
+    tokio::fs::write(&tmp, serde_json::to_vec_pretty(&manifest)?).await?;
+    if let Err(e) = tokio::fs::rename(&tmp, &path).await {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        return Err(e.into());
+    }
+    info!("archive done - guild {}: {}/{} originals", guild_id, archived, manifest.emojis.len());
+    Ok(())
+}
+
+/// 원본을 먼저 받아 미러에 남기고(업스트림이 지운 뒤에는 리사이즈도 그 원본으로 한다) 크기별로 캐시를 채운다
+async fn archive_emoji(state: AppState, emoji: GuildEmoji) -> ManifestEntry {
+    let mut entry = ManifestEntry {
+        id: emoji.id.clone(),
+        name: emoji.name,
+        animated: emoji.animated,
+        original: None,
+        sizes: Vec::new(),
+        failed: Vec::new(),
+    };
+    let res = crate::serve_emoji(state.clone(), emoji.id.clone(), Variant::Original, None, RequestInputs::default(), Mode::Warm).await;
+    if res.status().is_success() {
+        let content_type = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        match axum::body::to_bytes(res.into_body(), usize::MAX).await {
+            Ok(body) => {
+                entry.original = Some(ArchivedOriginal {
+                    content_type,
+                    bytes: body.len(),
+                    sha1: blocklist::content_hash(&body),
+                })
+            }
+            Err(e) => {
+                warn!("archive - original body failed for {}: {}", emoji.id, e);
+                entry.failed.push("original: body".into());
+            }
+        }
+    } else {
+        entry.failed.push(format!("original: {}", res.status().as_u16()));
+    }
+    for &size in &state.config.output_sizes {
+        let res = crate::serve_emoji(state.clone(), emoji.id.clone(), Variant::Resized(size), None, RequestInputs::default(), Mode::Warm).await;
+        if res.status().is_success() {
+            entry.sizes.push(size);
+        } else {
+            entry.failed.push(format!("{size}: {}", res.status().as_u16()));
+        }
+    }
+    entry
+}
+
+/// Discord 스노플레이크 (경로에 그대로 쓰므로 숫자만)
+fn valid_guild_id(id: &str) -> bool {
+    (1..=20).contains(&id.len()) && id.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
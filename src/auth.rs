@@ -0,0 +1,45 @@
+//! API 키로 요청을 인증된 요청과 익명 요청으로 가른다.
+//!
+//! `API_KEYS`에 있는 키를 `Authorization: Bearer <키>`나 `X-Api-Key: <키>`로 보낸 요청은 인증된 요청이다.
+//! 인증된 요청은 익명 요청 제한(`ANON_RATE_LIMIT`)을 받지 않고, 공유 캐시 수명이 긴 `EMOJI_CACHE_AUTH_*`
+//! 정책으로 응답받으며, 비공개 커스텀 이모트를 서명 없이 볼 수 있다. 테넌트 키(`TENANT_<이름>_KEY`)도 같다.
+//! 맞지 않는 키는 거절하지 않고 익명으로 본다 (이미지 주소에 키를 잘못 붙인 클라이언트가 깨지지 않도록).
+
+use axum::http::{header, HeaderMap};
+
+use crate::admin;
+
+/// 요청의 인증 상태. [`crate::middleware::auth`]가 요청 확장에 넣는다.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Auth {
+    #[default]
+    Anonymous,
+    ApiKey,
+}
+
+impl Auth {
+    /// 헤더의 키가 `keys` 중 하나와 맞으면 [`Auth::ApiKey`]
+    pub fn classify(keys: &[String], headers: &HeaderMap) -> Self {
+        for key in presented_keys(headers) {
+            if keys.iter().any(|k| admin::constant_time_eq(k.as_bytes(), key.as_bytes())) {
+                return Auth::ApiKey;
+            }
+        }
+        Auth::Anonymous
+    }
+
+    pub fn is_authenticated(self) -> bool {
+        self == Auth::ApiKey
+    }
+}
+
+/// 요청이 내민 키 (`Authorization: Bearer`, `X-Api-Key` 순)
+pub fn presented_keys(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .into_iter()
+        .chain(headers.get("x-api-key").and_then(|v| v.to_str().ok()))
+        .map(str::trim)
+}
@@ -0,0 +1,77 @@
+//! 애니메이션 AVIF 인코더 (`avif` 기능).
+//!
+//! 리사이즈를 마친 애니메이션 WebP의 프레임을 풀어 libavif의 `avifenc` 명령으로 AVIF
+//! 이미지 시퀀스를 만든다. 프레임은 알파를 포함한 YUV 4:4:4 Y4M(`C444alpha`) 파일로 넘기고,
+//! 프레임마다 `--duration`(ms)을 붙여 원래 지연을 유지한다. 실패하면 호출하는 쪽이 WebP를 그대로 쓴다.
+
+use anyhow::Context;
+use image::{codecs::webp::WebPDecoder, AnimationDecoder, RgbaImage};
+use std::{io::Cursor, time::Instant};
+use tokio::process::Command;
+
+use crate::subprocess::{run_with_deadline, Scratch};
+
+pub struct Options<'a> {
+    pub binary: &'a str,
+    pub speed: u8,
+    pub quality: u8,
+}
+
+/// 애니메이션 WebP를 같은 크기·지연의 애니메이션 AVIF로.
+/// `deadline`이 지나면 프로세스를 죽이고 실패로 돌려준다.
+pub async fn encode_animated(webp: Vec<u8>, options: &Options<'_>, deadline: Option<Instant>) -> anyhow::Result<Vec<u8>> {
+    let id = uuid::Uuid::new_v4();
+    let frames = tokio::task::spawn_blocking(move || write_frames(&webp, id)).await??;
+    let dst = Scratch::new(format!("avif-{id}.avif"));
+
+    let mut command = Command::new(options.binary);
+    command
+        .arg("--timescale")
+        .arg("1000")
+        .arg("-s")
+        .arg(options.speed.to_string())
+        .arg("-q")
+        .arg(options.quality.to_string());
+    for (frame, delay_ms) in &frames {
+        command.arg("--duration").arg(delay_ms.max(&1).to_string()).arg(frame.path());
+    }
+    command.arg(dst.path());
+    run_with_deadline(command, deadline).await?;
+    tokio::fs::read(dst.path()).await.with_context(|| format!("reading {}", dst.path().display()))
+}
+
+/// 프레임마다 Y4M 파일 하나와 지연(ms)
+fn write_frames(webp: &[u8], id: uuid::Uuid) -> anyhow::Result<Vec<(Scratch, u32)>> {
+    let frames = WebPDecoder::new(Cursor::new(webp))?.into_frames().collect_frames()?;
+    frames
+        .iter()
+        .enumerate()
+        .map(|(i, frame)| {
+            let path = Scratch::new(format!("avif-{id}-{i}.y4m"));
+            std::fs::write(path.path(), y4m(frame.buffer())).with_context(|| format!("writing {}", path.path().display()))?;
+            let (num, den) = frame.delay().numer_denom_ms();
+            Ok((path, num / den.max(1)))
+        })
+        .collect()
+}
+
+/// 한 프레임짜리 Y4M. BT.601 전체 범위로 변환하고 알파는 네 번째 평면으로 붙인다.
+fn y4m(frame: &RgbaImage) -> Vec<u8> {
+    let (w, h) = frame.dimensions();
+    let n = (w * h) as usize;
+    let mut out = format!("YUV4MPEG2 W{w} H{h} F30:1 Ip A1:1 C444alpha XCOLORRANGE=FULL\nFRAME\n").into_bytes();
+    let header = out.len();
+    out.resize(header + n * 4, 0);
+    let (y, rest) = out[header..].split_at_mut(n);
+    let (u, rest) = rest.split_at_mut(n);
+    let (v, a) = rest.split_at_mut(n);
+    for (i, px) in frame.pixels().enumerate() {
+        let [r, g, b, alpha] = px.0;
+        let (r, g, b) = (f32::from(r), f32::from(g), f32::from(b));
+        y[i] = (0.299 * r + 0.587 * g + 0.114 * b).round() as u8;
+        u[i] = (-0.168_736 * r - 0.331_264 * g + 0.5 * b + 128.0).round() as u8;
+        v[i] = (0.5 * r - 0.418_688 * g - 0.081_312 * b + 128.0).round() as u8;
+        a[i] = alpha;
+    }
+    out
+}
@@ -0,0 +1,169 @@
+//! 부하 테스트 하위 명령.
+//!
+//! `emoji-resizer bench --target <URL> --ids-file <파일> [--concurrency N] [--rounds N]`
+//! 이모지 ID 목록을 실행 중인 인스턴스에 재생하고 지연 백분위수와 캐시 히트율을 출력한다.
+//! 배포 전에 성능 회귀를 수치로 확인하는 용도.
+
+use anyhow::{bail, Context};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+struct Sample {
+    latency: Duration,
+    /// 0이면 연결 실패 등으로 응답을 받지 못함
+    status: u16,
+    x_cache: Option<String>,
+}
+
+/// ID 파일: 한 줄에 하나, `#` 뒤는 주석. `/e/<id>.webp` 형태의 경로여도 된다.
+fn read_ids(path: &PathBuf) -> anyhow::Result<Vec<String>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    Ok(text
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim())
+        .filter(|l| !l.is_empty())
+        .map(|l| {
+            let name = l.trim_start_matches("/e/");
+            name.split('.').next().unwrap_or(name).to_string()
+        })
+        .collect())
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn ms(d: Duration) -> String {
+    format!("{:.1}ms", d.as_secs_f64() * 1000.0)
+}
+
+pub async fn cli(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let mut target = "http://127.0.0.1:53292".to_string();
+    let mut ids_file = None;
+    let mut concurrency = 16usize;
+    let mut rounds = 1usize;
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().with_context(|| format!("{flag} needs a value"));
+        match arg.as_str() {
+            "--target" => target = value(&arg)?.trim_end_matches('/').to_string(),
+            "--ids-file" => ids_file = Some(PathBuf::from(value(&arg)?)),
+            "--concurrency" => concurrency = value(&arg)?.parse().context("invalid --concurrency")?,
+            "--rounds" => rounds = value(&arg)?.parse().context("invalid --rounds")?,
+            other => bail!("unknown argument {other:?}"),
+        }
+    }
+    let Some(ids_file) = ids_file else {
+        bail!("usage: emoji-resizer bench --ids-file <file> [--target URL] [--concurrency N] [--rounds N]");
+    };
+
+    let ids = read_ids(&ids_file)?;
+    if ids.is_empty() {
+        bail!("{} has no emoji ids", ids_file.display());
+    }
+    let urls: Arc<Vec<String>> = Arc::new(
+        (0..rounds.max(1))
+            .flat_map(|_| ids.iter().map(|id| format!("{target}/e/{id}.webp")))
+            .collect(),
+    );
+    println!(
+        "bench: {} requests ({} ids x {} rounds) against {} with concurrency {}",
+        urls.len(),
+        ids.len(),
+        rounds.max(1),
+        target,
+        concurrency
+    );
+
+    let http = reqwest::Client::new();
+    let next = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+    let workers: Vec<_> = (0..concurrency.max(1))
+        .map(|_| {
+            let (http, urls, next) = (http.clone(), urls.clone(), next.clone());
+            tokio::spawn(async move {
+                let mut samples = Vec::new();
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(url) = urls.get(i) else { break };
+                    let start = Instant::now();
+                    let sample = match http.get(url).send().await {
+                        Ok(res) => {
+                            let status = res.status().as_u16();
+                            let x_cache = res
+                                .headers()
+                                .get("x-cache")
+                                .and_then(|v| v.to_str().ok())
+                                .map(str::to_owned);
+                            // 본문까지 받아야 실제 지연이다
+                            let _ = res.bytes().await;
+                            Sample { latency: start.elapsed(), status, x_cache }
+                        }
+                        Err(_) => Sample { latency: start.elapsed(), status: 0, x_cache: None },
+                    };
+                    samples.push(sample);
+                }
+                samples
+            })
+        })
+        .collect();
+
+    let mut samples = Vec::new();
+    for worker in workers {
+        samples.extend(worker.await?);
+    }
+    let elapsed = started.elapsed();
+    report(&samples, elapsed);
+    Ok(())
+}
+
+fn report(samples: &[Sample], elapsed: Duration) {
+    let mut latencies: Vec<Duration> = samples.iter().map(|s| s.latency).collect();
+    latencies.sort();
+
+    let mut statuses: BTreeMap<u16, usize> = BTreeMap::new();
+    let mut caches: BTreeMap<&str, usize> = BTreeMap::new();
+    for s in samples {
+        *statuses.entry(s.status).or_default() += 1;
+        *caches.entry(s.x_cache.as_deref().unwrap_or("-")).or_default() += 1;
+    }
+
+    let total = samples.len().max(1);
+    println!(
+        "\n{} requests in {:.2}s ({:.1} req/s)",
+        samples.len(),
+        elapsed.as_secs_f64(),
+        samples.len() as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+    );
+    println!(
+        "latency  p50 {}  p90 {}  p99 {}  max {}",
+        ms(percentile(&latencies, 50.0)),
+        ms(percentile(&latencies, 90.0)),
+        ms(percentile(&latencies, 99.0)),
+        ms(latencies.last().copied().unwrap_or_default()),
+    );
+    let status_line: Vec<String> = statuses
+        .iter()
+        .map(|(code, n)| match code {
+            0 => format!("error:{n}"),
+            _ => format!("{code}:{n}"),
+        })
+        .collect();
+    println!("status   {}", status_line.join("  "));
+    let cache_line: Vec<String> = caches
+        .iter()
+        .map(|(kind, n)| format!("{kind}:{n} ({:.1}%)", *n as f64 * 100.0 / total as f64))
+        .collect();
+    println!("x-cache  {}", cache_line.join("  "));
+}
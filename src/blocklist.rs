@@ -0,0 +1,186 @@
+//! 운영자가 제공하면 안 되는 콘텐츠(신고/모더레이션 takedown)를 막는 차단 목록.
+//!
+//! 항목은 이모지 ID, SHA-1 해시 접두사(업스트림 원본 또는 출력 바이트, 즉 ETag 값),
+//! 소스 전체 세 종류다. 차단된 요청은 `410 Gone`으로 응답한다.
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::RwLock,
+};
+use tracing::info;
+
+/// 해시 접두사 최소 길이 (너무 짧으면 무관한 콘텐츠까지 막힌다)
+const MIN_HASH_PREFIX: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockKind {
+    Id,
+    Hash,
+    Source,
+}
+
+impl BlockKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            BlockKind::Id => "id",
+            BlockKind::Hash => "hash",
+            BlockKind::Source => "source",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "id" => Some(BlockKind::Id),
+            "hash" => Some(BlockKind::Hash),
+            "source" => Some(BlockKind::Source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct BlockEntry {
+    pub kind: BlockKind,
+    pub value: String,
+}
+
+impl BlockEntry {
+    /// 값을 정규화(해시는 소문자)하고 검증한다
+    pub fn new(kind: BlockKind, value: &str) -> anyhow::Result<Self> {
+        let value = value.trim();
+        if value.is_empty() || value.contains(char::is_whitespace) {
+            bail!("invalid {} entry {value:?}", kind.as_str());
+        }
+        let value = match kind {
+            BlockKind::Hash => {
+                let v = value.to_ascii_lowercase();
+                if v.len() < MIN_HASH_PREFIX || v.len() > 40 || !v.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    bail!("hash prefix must be {MIN_HASH_PREFIX}..=40 hex digits: {value:?}");
+                }
+                v
+            }
+            BlockKind::Id | BlockKind::Source => value.to_string(),
+        };
+        Ok(Self { kind, value })
+    }
+
+    /// `kind:value` 한 줄 형식
+    fn parse_line(line: &str) -> anyhow::Result<Self> {
+        let (kind, value) = line.split_once(':').context("expected kind:value")?;
+        let kind = BlockKind::parse(kind.trim()).with_context(|| format!("unknown kind {kind:?}"))?;
+        Self::new(kind, value)
+    }
+}
+
+pub struct Blocklist {
+    entries: RwLock<BTreeSet<BlockEntry>>,
+    /// 설정되면 관리자 수정 사항을 이 파일에 저장한다
+    file: Option<PathBuf>,
+}
+
+impl Blocklist {
+    /// 환경변수 항목(`seed`)과 차단 목록 파일을 합쳐서 불러온다
+    pub fn load(seed: Vec<BlockEntry>, file: Option<PathBuf>) -> anyhow::Result<Self> {
+        let mut entries: BTreeSet<BlockEntry> = seed.into_iter().collect();
+        if let Some(path) = &file {
+            match std::fs::read_to_string(path) {
+                Ok(text) => {
+                    for (n, line) in text.lines().enumerate() {
+                        let line = line.trim();
+                        if line.is_empty() || line.starts_with('#') {
+                            continue;
+                        }
+                        let entry = BlockEntry::parse_line(line)
+                            .with_context(|| format!("{}:{}", path.display(), n + 1))?;
+                        entries.insert(entry);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+            }
+        }
+        info!("blocklist loaded: {} entries", entries.len());
+        Ok(Self {
+            entries: RwLock::new(entries),
+            file,
+        })
+    }
+
+    fn contains(&self, kind: BlockKind, value: &str) -> bool {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .any(|e| e.kind == kind && e.value == value)
+    }
+
+    pub fn blocks_id(&self, emoji_id: &str) -> bool {
+        self.contains(BlockKind::Id, emoji_id)
+    }
+
+    pub fn blocks_source(&self, source: &str) -> bool {
+        self.contains(BlockKind::Source, source)
+    }
+
+    /// 콘텐츠 SHA-1(16진수)이 차단된 접두사로 시작하는지
+    pub fn blocks_hash(&self, hex: &str) -> bool {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .any(|e| e.kind == BlockKind::Hash && hex.starts_with(&e.value))
+    }
+
+    pub fn blocks_content(&self, bytes: &[u8]) -> bool {
+        self.blocks_hash(&content_hash(bytes))
+    }
+
+    pub fn entries(&self) -> Vec<BlockEntry> {
+        self.entries.read().unwrap().iter().cloned().collect()
+    }
+
+    /// 항목을 추가한다. 새로 추가됐으면 `true`.
+    pub fn insert(&self, entry: BlockEntry) -> anyhow::Result<bool> {
+        let mut entries = self.entries.write().unwrap();
+        let added = entries.insert(entry);
+        if added {
+            self.persist(&entries)?;
+        }
+        Ok(added)
+    }
+
+    /// 항목을 제거한다. 있었으면 `true`.
+    pub fn remove(&self, entry: &BlockEntry) -> anyhow::Result<bool> {
+        let mut entries = self.entries.write().unwrap();
+        let removed = entries.remove(entry);
+        if removed {
+            self.persist(&entries)?;
+        }
+        Ok(removed)
+    }
+
+    fn persist(&self, entries: &BTreeSet<BlockEntry>) -> anyhow::Result<()> {
+        let Some(path) = &self.file else {
+            return Ok(());
+        };
+        let mut text = String::from("# emoji-resizer blocklist (kind:value)\n");
+        for e in entries {
+            text.push_str(&format!("{}:{}\n", e.kind.as_str(), e.value));
+        }
+        // 부분 기록을 피하려고 임시 파일에 쓴 뒤 교체
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, text).with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("replacing {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// ETag와 같은 SHA-1 16진수 문자열
+pub fn content_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(bytes))
+}
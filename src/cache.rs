@@ -0,0 +1,634 @@
+//! moka 메모리 캐시 뒤에 두는 2차 캐시 계층.
+//!
+//! 각 계층 호출은 짧은 타임아웃으로 감싸서, 느려진 백엔드가 캐시 히트를 Discord에서
+//! 직접 가져오는 것보다 느리게 만들지 않도록 한다. 타임아웃/에러가 나면 다음 계층
+//! (마지막에는 업스트림)으로 넘어간다.
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::metrics::{
+    CACHE_BACKEND_ERRORS_TOTAL, CACHE_BACKEND_SECONDS, CACHE_CORRUPT_TOTAL, CACHE_DUAL_WRITES_TOTAL, CACHE_LEASES_TOTAL,
+    CACHE_MAINTENANCE_RECLAIMED_BYTES_TOTAL, CACHE_MAINTENANCE_REMOVED_TOTAL, CACHE_MAINTENANCE_RUNS_TOTAL,
+    CACHE_MAINTENANCE_SECONDS,
+};
+
+#[async_trait]
+pub trait Tier: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn get(&self, key: &str) -> anyhow::Result<Option<TierEntry>>;
+    async fn put(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()>;
+    async fn remove(&self, key: &str) -> anyhow::Result<()>;
+
+    /// 차지하고 있는 바이트 수 (알 수 없으면 `None`)
+    async fn usage(&self) -> anyhow::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// 만료 항목과 고아 파일을 정리한다 (정리할 것이 없는 계층은 `None`)
+    async fn maintain(&self) -> anyhow::Result<Option<MaintenanceReport>> {
+        Ok(None)
+    }
+
+    /// 같은 키를 여러 인스턴스가 동시에 만들지 않도록 `ttl` 동안 유효한 임대를 `holder` 이름으로 잡는다.
+    /// 다른 쪽이 쥐고 있으면 `false`. 인스턴스끼리 공유하지 않는 계층은 늘 `true`.
+    async fn lease(&self, _key: &str, _holder: &str, _ttl: Duration) -> anyhow::Result<bool> {
+        Ok(true)
+    }
+
+    /// `holder`가 잡은 임대를 푼다. 만료돼 이미 다른 쪽이 잡았으면 건드리지 않는다.
+    async fn release(&self, _key: &str, _holder: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// 계층에서 꺼낸 항목과 그 항목을 저장한 시각 (`Age` 계산용)
+pub struct TierEntry {
+    pub bytes: Vec<u8>,
+    pub stored: SystemTime,
+}
+
+/// 한 계층의 정리 결과
+#[derive(Debug, Default, Serialize)]
+pub struct MaintenanceReport {
+    /// 살펴본 파일 수
+    pub scanned: u64,
+    /// TTL이 지나 지운 항목
+    pub expired: u64,
+    /// 중단된 기록의 임시 파일이나 경로 규칙에 맞지 않는 파일
+    pub orphaned: u64,
+    /// 비어서 지운 샤드 디렉터리
+    pub removed_dirs: u64,
+    pub reclaimed_bytes: u64,
+}
+
+/// 이보다 오래된 임시 파일은 기록 도중 죽은 프로세스가 남긴 것으로 본다
+const TMP_GRACE: Duration = Duration::from_secs(3600);
+
+/// 임대가 풀리기를 기다리는 동안 계층을 다시 확인하는 간격
+const LEASE_POLL: Duration = Duration::from_millis(25);
+
+/// zstd 프레임 시그니처. 이미지 포맷은 이 바이트로 시작하지 않으므로 압축 여부를
+/// 별도 표시 없이 구분할 수 있다 (압축 설정을 바꿔도 기존 파일을 그대로 읽음).
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// 로컬 디렉터리 계층. 키의 SHA-1로 파일 경로를 만들고(`ab/abcdef….bin`),
+/// 수정 시각이 TTL보다 오래된 파일은 읽을 때 지운다.
+pub struct DiskTier {
+    dir: PathBuf,
+    ttl: Duration,
+    /// zstd 압축 레벨 (`None`이면 원본 그대로 저장)
+    zstd_level: Option<i32>,
+}
+
+impl DiskTier {
+    pub fn new(dir: PathBuf, ttl: Duration, zstd_level: Option<i32>) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir, ttl, zstd_level })
+    }
+
+    /// 압축해도 이득이 없는 이미 압축된 이미지(WebP/PNG/GIF/JPEG)는 건너뛰고,
+    /// 나머지도 실제로 작아질 때만 압축본을 저장한다.
+    fn encode<'a>(&self, bytes: &'a [u8]) -> std::borrow::Cow<'a, [u8]> {
+        let Some(level) = self.zstd_level else {
+            return bytes.into();
+        };
+        if is_compressed_image(bytes) {
+            return bytes.into();
+        }
+        match zstd::bulk::compress(bytes, level) {
+            Ok(packed) if packed.len() < bytes.len() => packed.into(),
+            _ => bytes.into(),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let hex = format!("{:x}", Sha1::digest(key.as_bytes()));
+        self.dir.join(&hex[..2]).join(format!("{hex}.bin"))
+    }
+
+    /// 항목 옆의 임대 파일 (`ab/abcdef….lease`). 내용은 쥔 쪽 이름, 수정 시각이 잡은 시각.
+    fn lease_path(&self, key: &str) -> PathBuf {
+        self.path(key).with_extension("lease")
+    }
+}
+
+#[async_trait]
+impl Tier for DiskTier {
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<TierEntry>> {
+        let path = self.path(key);
+        let meta = match tokio::fs::metadata(&path).await {
+            Ok(m) => m,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // 파일은 임시 파일을 바꿔치기해서 쓰므로 수정 시각이 곧 저장 시각
+        let stored = meta.modified().unwrap_or_else(|_| SystemTime::now());
+        let age = SystemTime::now().duration_since(stored).unwrap_or_default();
+        if age > self.ttl {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Ok(None);
+        }
+        let bytes = match tokio::fs::read(&path).await {
+            // 풀리지 않는 압축본은 손상된 항목으로 넘긴다 (`Tiers::get`이 지우고 다시 가져옴)
+            Ok(bytes) if bytes.starts_with(&ZSTD_MAGIC) => zstd::stream::decode_all(&bytes[..]).unwrap_or_default(),
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(TierEntry { bytes, stored }))
+    }
+
+    async fn put(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        let path = self.path(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // 읽는 쪽이 반쯤 쓰인 파일을 보지 않도록 임시 파일에 쓰고 교체
+        let tmp = path.with_extension(format!("tmp.{}", uuid::Uuid::new_v4().simple()));
+        tokio::fs::write(&tmp, self.encode(bytes)).await?;
+        if let Err(e) = tokio::fs::rename(&tmp, &path).await {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(self.path(key)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn usage(&self) -> anyhow::Result<Option<u64>> {
+        let dir = self.dir.clone();
+        let total = tokio::task::spawn_blocking(move || -> std::io::Result<u64> {
+            let mut total = 0;
+            for shard in std::fs::read_dir(dir)? {
+                let shard = shard?;
+                if !shard.file_type()?.is_dir() {
+                    continue;
+                }
+                for entry in std::fs::read_dir(shard.path())? {
+                    total += entry?.metadata()?.len();
+                }
+            }
+            Ok(total)
+        })
+        .await??;
+        Ok(Some(total))
+    }
+
+    async fn maintain(&self) -> anyhow::Result<Option<MaintenanceReport>> {
+        let (dir, ttl) = (self.dir.clone(), self.ttl);
+        let report = tokio::task::spawn_blocking(move || sweep(&dir, ttl)).await??;
+        Ok(Some(report))
+    }
+
+    /// 여러 인스턴스가 같은 `CACHE_DIR`을 나눠 쓸 때(공유 볼륨 등) `create_new`로 임대 파일을 만든다.
+    /// 쥔 쪽이 죽어 `ttl`이 지난 파일은 치우고 한 번 더 시도한다.
+    async fn lease(&self, key: &str, holder: &str, ttl: Duration) -> anyhow::Result<bool> {
+        let path = self.lease_path(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        for _ in 0..2 {
+            match tokio::fs::OpenOptions::new().write(true).create_new(true).open(&path).await {
+                Ok(mut file) => {
+                    file.write_all(holder.as_bytes()).await?;
+                    return Ok(true);
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+            let expired = match tokio::fs::metadata(&path).await {
+                Ok(meta) => meta.modified().ok().and_then(|t| t.elapsed().ok()).is_some_and(|age| age > ttl),
+                Err(e) if e.kind() == ErrorKind::NotFound => true,
+                Err(e) => return Err(e.into()),
+            };
+            if !expired {
+                return Ok(false);
+            }
+            let _ = tokio::fs::remove_file(&path).await;
+        }
+        Ok(false)
+    }
+
+    async fn release(&self, key: &str, holder: &str) -> anyhow::Result<()> {
+        let path = self.lease_path(key);
+        match tokio::fs::read_to_string(&path).await {
+            Ok(current) if current == holder => match tokio::fs::remove_file(&path).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            },
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 샤드 디렉터리(`ab/`)만 훑는다. 같은 디렉터리에 다른 파일이 있어도 건드리지 않도록.
+fn sweep(dir: &Path, ttl: Duration) -> std::io::Result<MaintenanceReport> {
+    let mut report = MaintenanceReport::default();
+    let now = SystemTime::now();
+    for shard in std::fs::read_dir(dir)? {
+        let shard = shard?;
+        let prefix = shard.file_name().to_string_lossy().into_owned();
+        if !shard.file_type()?.is_dir() || !is_shard_name(&prefix) {
+            continue;
+        }
+        let mut remaining = 0;
+        for entry in std::fs::read_dir(shard.path())? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if !meta.is_file() {
+                remaining += 1;
+                continue;
+            }
+            report.scanned += 1;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let age = meta
+                .modified()
+                .ok()
+                .and_then(|t| now.duration_since(t).ok())
+                .unwrap_or_default();
+            let removal = if name.contains(".tmp.") || name.ends_with(".lease") {
+                (age > TMP_GRACE).then_some(&mut report.orphaned)
+            } else if !is_entry_name(&prefix, &name) {
+                Some(&mut report.orphaned)
+            } else {
+                (age > ttl).then_some(&mut report.expired)
+            };
+            let Some(counter) = removal else {
+                remaining += 1;
+                continue;
+            };
+            match std::fs::remove_file(entry.path()) {
+                Ok(()) => {
+                    *counter += 1;
+                    report.reclaimed_bytes += meta.len();
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => {
+                    warn!("disk cache maintenance could not remove {}: {}", entry.path().display(), e);
+                    remaining += 1;
+                }
+            }
+        }
+        // 빈 샤드는 지워서 디렉터리 목록을 작게 유지 (동시에 put이 만들면 실패해도 무방)
+        if remaining == 0 && std::fs::remove_dir(shard.path()).is_ok() {
+            report.removed_dirs += 1;
+        }
+    }
+    Ok(report)
+}
+
+fn is_shard_name(name: &str) -> bool {
+    name.len() == 2 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// `DiskTier::path`가 만드는 이름인지: 샤드 접두사로 시작하는 SHA-1 hex + `.bin`
+fn is_entry_name(prefix: &str, name: &str) -> bool {
+    name.strip_suffix(".bin").is_some_and(|hex| {
+        hex.len() == 40 && hex.starts_with(prefix) && hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    })
+}
+
+/// 캐시에 넣는 값은 모두 완성된 이미지다. 크래시로 잘리거나 0으로 채워진 파일을
+/// 걸러내기 위해 시그니처와 포맷별 끝 표시(WebP는 RIFF 길이)를 확인한다.
+fn is_intact(bytes: &[u8]) -> bool {
+    if bytes.len() < MIN_PAYLOAD {
+        return false;
+    }
+    if bytes.starts_with(b"RIFF") {
+        let riff = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        return &bytes[8..12] == b"WEBP" && riff + 8 == bytes.len();
+    }
+    if bytes.starts_with(b"\x89PNG") {
+        return bytes.ends_with(&[0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82]);
+    }
+    if bytes.starts_with(b"GIF8") {
+        return bytes.ends_with(&[0x3B]);
+    }
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return bytes.ends_with(&[0xFF, 0xD9]);
+    }
+    // 그 밖의 포맷(AVIF 등)은 시그니처만 확인
+    image::guess_format(bytes).is_ok() || crate::imaging::is_avif_sequence(bytes)
+}
+
+/// 가장 작은 정상 이미지보다 짧으면 손상으로 본다
+const MIN_PAYLOAD: usize = 16;
+
+fn is_compressed_image(bytes: &[u8]) -> bool {
+    (bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP")
+        || bytes.starts_with(b"\x89PNG")
+        || bytes.starts_with(b"GIF8")
+        || bytes.starts_with(&[0xFF, 0xD8, 0xFF])
+}
+
+/// 세대 `generation`의 계층 키 (`v2/<키>`). 빈 세대는 세대를 두기 전의 키 그대로다.
+fn generation_key<'a>(generation: &str, key: &'a str) -> std::borrow::Cow<'a, str> {
+    if generation.is_empty() {
+        key.into()
+    } else {
+        format!("{generation}/{key}").into()
+    }
+}
+
+/// 순서대로 조회하는 2차 캐시 계층 목록
+pub struct Tiers {
+    tiers: Vec<Box<dyn Tier>>,
+    timeout: Duration,
+    /// 계층 키 앞에 붙이는 캐시 세대 (`CACHE_GENERATION`, 빈 값이면 키 그대로)
+    generation: String,
+    /// 옮겨 가는 동안 함께 기록할 이전 세대와 그 기한 (`CACHE_GENERATION_PREVIOUS`, `CACHE_DUAL_WRITE_SECS`)
+    previous: Option<(String, Instant)>,
+    /// 예약 정리와 수동 정리가 겹치지 않도록
+    maintenance: tokio::sync::Mutex<()>,
+    /// 이 인스턴스가 쥔 임대: 키 → 쥔 쪽 이름과 결과 기록(`put`)에 넘겼는지
+    leases: Mutex<HashMap<String, (String, bool)>>,
+}
+
+/// 키 하나를 만드는 동안 쥐는 임대. 결과를 [`Tiers::hand_off`]로 넘기면 기록이 끝난 뒤
+/// `put`이 풀고, 넘기지 않고 놓으면(에러 응답 등) 바로 푼다.
+pub struct Lease {
+    tiers: Arc<Tiers>,
+    key: String,
+    holder: String,
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let mut leases = self.tiers.leases.lock().unwrap();
+        if leases.get(&self.key).is_none_or(|(holder, handed_off)| *handed_off || *holder != self.holder) {
+            return;
+        }
+        leases.remove(&self.key);
+        let (tiers, key, holder) = (self.tiers.clone(), self.key.clone(), self.holder.clone());
+        tokio::spawn(async move { tiers.release(&key, &holder).await });
+    }
+}
+
+/// [`Tiers::claim`]의 결과
+pub enum Claim {
+    /// 임대를 잡았으니 직접 만든다
+    Leader(Lease),
+    /// 기다리는 동안 다른 쪽이 만든 결과가 계층에 올라왔다
+    Follower(TierEntry, &'static str),
+    /// 임대가 풀리지 않은 채 기다릴 시간이 끝났다 (조율 없이 직접 만든다)
+    Expired,
+}
+
+impl Tiers {
+    pub fn new(tiers: Vec<Box<dyn Tier>>, timeout: Duration) -> Self {
+        Self {
+            tiers,
+            timeout,
+            generation: String::new(),
+            previous: None,
+            maintenance: tokio::sync::Mutex::new(()),
+            leases: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 계층 키를 `generation`으로 나눈다. 인코더 설정을 바꿔 세대를 올릴 때 `previous`를 주면 `window` 동안
+    /// 새로 만든 항목을 이전 세대 키에도 기록해서, 롤링 배포 중 아직 옛 버전인 인스턴스도 히트를 낸다.
+    /// 읽기와 임대는 현재 세대만 쓰고, 지우기는 두 세대 모두에서 한다 (퍼지가 옛 인스턴스에도 닿도록).
+    pub fn generation(mut self, generation: String, previous: Option<String>, window: Duration) -> Self {
+        self.previous = previous.map(|previous| (previous, Instant::now() + window));
+        self.generation = generation;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+    }
+
+    /// 현재 세대의 계층 키
+    fn scoped<'a>(&self, key: &'a str) -> std::borrow::Cow<'a, str> {
+        generation_key(&self.generation, key)
+    }
+
+    /// 처음으로 값을 돌려준 계층의 항목과 이름. 느리거나 실패한 계층은 건너뛰고,
+    /// 손상된 항목은 지운 뒤 다음 계층(마지막에는 업스트림)으로 넘어간다.
+    pub async fn get(&self, key: &str) -> Option<(TierEntry, &'static str)> {
+        let key = &*self.scoped(key);
+        for tier in &self.tiers {
+            let Some(Some(entry)) = self.timed(tier.as_ref(), "get", tier.get(key)).await else {
+                continue;
+            };
+            if is_intact(&entry.bytes) {
+                return Some((entry, tier.name()));
+            }
+            warn!("{} cache entry for {} is corrupted ({} bytes), dropping", tier.name(), key, entry.bytes.len());
+            metrics::counter!(CACHE_CORRUPT_TOTAL, "backend" => tier.name()).increment(1);
+            self.timed(tier.as_ref(), "remove", tier.remove(key)).await;
+        }
+        None
+    }
+
+    /// 모든 계층에 기록 (write-through). 하나라도 실패하면 에러를 돌려줘서
+    /// 백그라운드 작업이 다시 시도하게 한다 (기록은 멱등이라 전체를 다시 써도 무방).
+    pub async fn put(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        let mut failed = Vec::new();
+        let scoped = self.scoped(key);
+        for tier in &self.tiers {
+            if self.timed(tier.as_ref(), "put", tier.put(&scoped, bytes)).await.is_none() {
+                failed.push(tier.name());
+            }
+        }
+        // 이전 세대 사본은 덤이라 실패해도 다시 시도하지 않는다
+        if let Some((previous, _)) = self.previous.as_ref().filter(|(_, until)| Instant::now() < *until) {
+            let mirrored = generation_key(previous, key);
+            for tier in &self.tiers {
+                let outcome = match self.timed(tier.as_ref(), "put", tier.put(&mirrored, bytes)).await {
+                    Some(()) => "ok",
+                    None => "error",
+                };
+                metrics::counter!(CACHE_DUAL_WRITES_TOTAL, "backend" => tier.name(), "outcome" => outcome).increment(1);
+            }
+        }
+        // 넘겨받은 임대는 기록이 실패해도 푼다 (기다리던 쪽은 항목이 없으면 직접 만든다)
+        let handed_off = {
+            let mut leases = self.leases.lock().unwrap();
+            match leases.get(key) {
+                Some((_, true)) => leases.remove(key).map(|(holder, _)| holder),
+                _ => None,
+            }
+        };
+        if let Some(holder) = handed_off {
+            self.release(key, &holder).await;
+        }
+        anyhow::ensure!(failed.is_empty(), "cache put failed on {}", failed.join(", "));
+        Ok(())
+    }
+
+    pub async fn remove(&self, key: &str) {
+        let mut keys = vec![self.scoped(key)];
+        keys.extend(self.previous.as_ref().map(|(previous, _)| generation_key(previous, key)));
+        for tier in &self.tiers {
+            for key in &keys {
+                self.timed(tier.as_ref(), "remove", tier.remove(key)).await;
+            }
+        }
+    }
+
+    /// 같은 키를 만드는 쪽이 클러스터에 하나만 있도록 임대를 잡는다. 다른 쪽이 쥐고 있으면
+    /// 결과가 계층에 올라오거나 임대가 풀릴 때까지 기다리되, `ttl`이나 `deadline`을 넘기지 않는다.
+    pub async fn claim(self: &Arc<Self>, key: &str, ttl: Duration, deadline: Option<Instant>) -> Claim {
+        let give_up = deadline.map_or(Instant::now() + ttl, |at| at.min(Instant::now() + ttl));
+        let mut waited = false;
+        loop {
+            if let Some(lease) = self.lease(key, ttl).await {
+                // 마지막으로 확인한 뒤 앞선 쪽이 결과를 올리고 임대를 풀었을 수 있다
+                if waited {
+                    if let Some((entry, tier)) = self.get(key).await {
+                        drop(lease);
+                        metrics::counter!(CACHE_LEASES_TOTAL, "outcome" => "follower").increment(1);
+                        return Claim::Follower(entry, tier);
+                    }
+                }
+                metrics::counter!(CACHE_LEASES_TOTAL, "outcome" => "leader").increment(1);
+                return Claim::Leader(lease);
+            }
+            if Instant::now() + LEASE_POLL > give_up {
+                metrics::counter!(CACHE_LEASES_TOTAL, "outcome" => "expired").increment(1);
+                return Claim::Expired;
+            }
+            tokio::time::sleep(LEASE_POLL).await;
+            waited = true;
+            if let Some((entry, tier)) = self.get(key).await {
+                metrics::counter!(CACHE_LEASES_TOTAL, "outcome" => "follower").increment(1);
+                return Claim::Follower(entry, tier);
+            }
+        }
+    }
+
+    /// 결과 기록을 앞둔 키의 임대를 `put`에 넘긴다 (쥐고 있지 않으면 아무 일도 없음)
+    pub fn hand_off(&self, key: &str) {
+        if let Some((_, handed_off)) = self.leases.lock().unwrap().get_mut(key) {
+            *handed_off = true;
+        }
+    }
+
+    /// 모든 계층에서 임대를 잡는다. 한 계층이라도 다른 쪽이 쥐고 있으면 잡은 것을 되돌리고 `None`.
+    /// 느리거나 실패한 계층은 조율 없이 넘어간다 (임대는 중복 인코딩을 줄이는 용도일 뿐).
+    async fn lease(self: &Arc<Self>, key: &str, ttl: Duration) -> Option<Lease> {
+        let holder = uuid::Uuid::new_v4().simple().to_string();
+        let scoped = self.scoped(key);
+        for (i, tier) in self.tiers.iter().enumerate() {
+            if self.timed(tier.as_ref(), "lease", tier.lease(&scoped, &holder, ttl)).await == Some(false) {
+                for tier in &self.tiers[..i] {
+                    self.timed(tier.as_ref(), "release", tier.release(&scoped, &holder)).await;
+                }
+                return None;
+            }
+        }
+        self.leases.lock().unwrap().insert(key.to_string(), (holder.clone(), false));
+        Some(Lease { tiers: self.clone(), key: key.to_string(), holder })
+    }
+
+    async fn release(&self, key: &str, holder: &str) {
+        let scoped = self.scoped(key);
+        for tier in &self.tiers {
+            self.timed(tier.as_ref(), "release", tier.release(&scoped, holder)).await;
+        }
+    }
+
+    /// 계층별 사용량. 디렉터리를 훑을 수 있으므로 타임아웃을 적용하지 않는다.
+    pub async fn usage(&self) -> Vec<(&'static str, u64)> {
+        let mut out = Vec::new();
+        for tier in &self.tiers {
+            match tier.usage().await {
+                Ok(Some(bytes)) => out.push((tier.name(), bytes)),
+                Ok(None) => {}
+                Err(e) => warn!("{} cache usage check failed: {:#}", tier.name(), e),
+            }
+        }
+        out
+    }
+
+    /// 모든 계층을 정리한다. 이미 정리 중이면 `None`.
+    pub async fn maintain(&self) -> Option<Vec<(&'static str, MaintenanceReport)>> {
+        let _running = self.maintenance.try_lock().ok()?;
+        let mut out = Vec::new();
+        for tier in &self.tiers {
+            let start = Instant::now();
+            let result = tier.maintain().await;
+            let elapsed = start.elapsed();
+            let outcome = match result {
+                Ok(Some(report)) => {
+                    info!(
+                        "{} cache maintenance - scanned: {}, expired: {}, orphaned: {}, dirs: {}, reclaimed: {} bytes, took {:?}",
+                        tier.name(),
+                        report.scanned,
+                        report.expired,
+                        report.orphaned,
+                        report.removed_dirs,
+                        report.reclaimed_bytes,
+                        elapsed
+                    );
+                    metrics::counter!(CACHE_MAINTENANCE_REMOVED_TOTAL, "backend" => tier.name(), "reason" => "expired")
+                        .increment(report.expired);
+                    metrics::counter!(CACHE_MAINTENANCE_REMOVED_TOTAL, "backend" => tier.name(), "reason" => "orphaned")
+                        .increment(report.orphaned);
+                    metrics::counter!(CACHE_MAINTENANCE_RECLAIMED_BYTES_TOTAL, "backend" => tier.name())
+                        .increment(report.reclaimed_bytes);
+                    out.push((tier.name(), report));
+                    "ok"
+                }
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("{} cache maintenance failed: {:#}", tier.name(), e);
+                    "error"
+                }
+            };
+            metrics::histogram!(CACHE_MAINTENANCE_SECONDS, "backend" => tier.name()).record(elapsed.as_secs_f64());
+            metrics::counter!(CACHE_MAINTENANCE_RUNS_TOTAL, "backend" => tier.name(), "outcome" => outcome).increment(1);
+        }
+        Some(out)
+    }
+
+    async fn timed<T>(
+        &self,
+        tier: &dyn Tier,
+        op: &'static str,
+        fut: impl std::future::Future<Output = anyhow::Result<T>>,
+    ) -> Option<T> {
+        let start = Instant::now();
+        let (outcome, value) = match tokio::time::timeout(self.timeout, fut).await {
+            Ok(Ok(v)) => ("ok", Some(v)),
+            Ok(Err(e)) => {
+                warn!("{} cache {} failed: {:#}", tier.name(), op, e);
+                ("error", None)
+            }
+            Err(_) => {
+                warn!("{} cache {} timed out after {:?}", tier.name(), op, self.timeout);
+                ("timeout", None)
+            }
+        };
+        metrics::histogram!(CACHE_BACKEND_SECONDS, "backend" => tier.name(), "op" => op)
+            .record(start.elapsed().as_secs_f64());
+        if outcome != "ok" {
+            metrics::counter!(CACHE_BACKEND_ERRORS_TOTAL, "backend" => tier.name(), "op" => op, "reason" => outcome)
+                .increment(1);
+        }
+        value
+    }
+}
@@ -0,0 +1,1068 @@
+//! 환경변수 기반 런타임 설정. `CONFIG_FILE`을 주면 그 파일의 값을 환경변수가 없는 자리에 채운다 ([`apply_file`]).
+
+use crate::{
+    blocklist::{BlockEntry, BlockKind},
+    experiment::{self, Experiment},
+    imaging::{Budget, WebpOptions, TARGET_SIZE},
+    logging::{FileLogConfig, Rotation},
+    privacy::IpMode,
+    purge::CdnProvider,
+    s3::S3Config,
+    tenant::Tenant,
+    upstream::{self, ClientSettings, Source},
+};
+use anyhow::{anyhow, bail, Context};
+use serde::{Deserialize, Serialize, Serializer};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env,
+    net::SocketAddr,
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+/// 유효 설정. 직렬화하면 비밀 값(토큰, 키, 솔트, 웹훅 주소)은 가려지고 기간은 초 단위가 된다
+/// (부팅 로그와 `GET /admin/config`).
+#[derive(Clone, Debug, Serialize)]
+pub struct Config {
+    /// 서버가 듣는 주소 (`BIND_ADDR`, 기본값: `0.0.0.0:53292`)
+    pub bind_addr: SocketAddr,
+    /// 애니메이션 프레임 병렬 처리 스레드 수 (`ANIMATION_PARALLELISM`, 기본값: CPU 코어 수)
+    pub animation_parallelism: usize,
+    /// `/e` (Discord 이모지) 응답의 Cache-Control (`EMOJI_CACHE_*`)
+    pub emoji_cache: CachePolicy,
+    /// API 키로 인증된 `/e` 응답의 Cache-Control (`EMOJI_CACHE_AUTH_*`, 기본값: `EMOJI_CACHE_*`에 `s-maxage` 30일)
+    pub emoji_cache_auth: CachePolicy,
+    /// `?passthrough`가 없을 때 Discord 원본을 재인코딩 없이 제공할지 (`PASSTHROUGH_DEFAULT`)
+    pub passthrough: bool,
+    /// 허용하는 출력 크기 목록. `?size`는 가장 가까운 값으로 맞춘다 (`OUTPUT_SIZES`, 기본값: `160`)
+    pub output_sizes: Vec<u32>,
+    /// `?size`가 없을 때의 크기. `OUTPUT_SIZES` 중 하나 (`DEFAULT_SIZE`, 기본값: `OUTPUT_SIZES` 중 160에 가장 가까운 값)
+    pub default_size: u32,
+    /// 애니메이션 출력 크기 예산 (`ANIMATED_MAX_KB`, 기본값: 256, 0이면 끔 / `ANIMATED_MIN_FPS`, 기본값: 5)
+    pub animated_budget: Option<Budget>,
+    /// `/e/:id/original`로 받을 원본의 최대 크기 (`ORIGINAL_MAX_BYTES`, 기본값: 4 MiB)
+    pub original_max_bytes: u64,
+    /// 변환할 업스트림 원본의 최대 크기 (`UPSTREAM_MAX_BYTES`, 기본값: 16 MiB, 0이면 제한 없음)
+    pub upstream_max_bytes: u64,
+    /// 관리자 purge 시 함께 무효화할 CDN (`CDN_PURGE_PROVIDER`)
+    pub cdn_purge: Option<CdnProvider>,
+    /// 이모지 원본을 가져올 CDN 주소 (`UPSTREAM_BASE_URL`, 기본값: `https://cdn.discordapp.com`)
+    pub upstream_base: String,
+    /// 관리 API 토큰 (`ADMIN_TOKEN`, 미설정 시 `/admin` 비활성)
+    #[serde(serialize_with = "redact::optional")]
+    pub admin_token: Option<String>,
+    /// 업스트림 요청 전체 타임아웃 (`UPSTREAM_TIMEOUT_MS`, 기본값: 10초)
+    #[serde(serialize_with = "secs")]
+    pub upstream_timeout: Duration,
+    /// 소스별 업스트림 클라이언트 (`UPSTREAM_<DISCORD|SLACK|FEDI|TWITCH>_HTTP`, `_POOL_SIZE`, `_TIMEOUT_MS`,
+    /// `_HEDGE_MS`, 공통 `UPSTREAM_H2_FALLBACK`, `UPSTREAM_IP_FAMILY`)
+    pub upstreams: upstream::Settings,
+    /// 메모리 캐시 항목 수 (`CACHE_CAPACITY`, 기본값: 50000)
+    pub cache_capacity: u64,
+    /// 메모리 캐시 항목 수명 (`CACHE_TTL_SECS`, 기본값: 24시간)
+    #[serde(serialize_with = "secs")]
+    pub cache_ttl: Duration,
+    /// 메모리 캐시가 가득 찼을 때 밀어낼 항목을 고르는 방식 (`CACHE_EVICTION`, 기본값: `tinylfu`)
+    pub cache_eviction: CacheEviction,
+    /// 창 안에서 이만큼 본 키부터 메모리 캐시에 넣는다 (`CACHE_ADMIT_AFTER`, 기본값: 1 = 처음부터)
+    pub cache_admit_after: u32,
+    /// 입장 횟수를 세는 창 (`CACHE_ADMIT_WINDOW_SECS`, 기본값: 1시간)
+    #[serde(serialize_with = "secs")]
+    pub cache_admit_window: Duration,
+    /// 업스트림 장애 시 대신 제공할 만료 항목 보관 개수 (`STALE_CACHE_CAPACITY`, 0이면 비활성)
+    pub stale_capacity: u64,
+    /// 만료 항목 보관 기간 (`STALE_CACHE_TTL_SECS`, 기본값: 7일)
+    #[serde(serialize_with = "secs")]
+    pub stale_ttl: Duration,
+    /// 바이트가 캐시에서 밀려난 뒤에도 남겨 두는 검증자(ETag 등) 개수 (`VALIDATOR_INDEX_CAPACITY`, 0이면 비활성)
+    pub validator_capacity: u64,
+    /// 관리자 수정 사항을 저장하는 차단 목록 파일 (`BLOCKLIST_FILE`)
+    pub blocklist_file: Option<PathBuf>,
+    /// 환경변수로 지정한 차단 항목 (`BLOCKLIST_IDS`, `BLOCKLIST_HASH_PREFIXES`, `BLOCKLIST_SOURCES`)
+    pub blocklist: Vec<BlockEntry>,
+    /// IP당 신고 허용 횟수 (`REPORT_RATE_LIMIT`, 기본값: 5)
+    pub report_rate_limit: u32,
+    /// 신고 횟수 제한 윈도 (`REPORT_RATE_WINDOW_SECS`, 기본값: 1시간)
+    #[serde(serialize_with = "secs")]
+    pub report_rate_window: Duration,
+    /// 설정되면 신고에 hCaptcha 토큰을 요구 (`HCAPTCHA_SECRET`)
+    #[serde(serialize_with = "redact::optional")]
+    pub hcaptcha_secret: Option<String>,
+    /// 프록시가 붙인 `X-Forwarded-For`를 클라이언트 IP로 믿을지 (`TRUST_FORWARDED_FOR`)
+    pub trust_forwarded_for: bool,
+    /// 익명 요청 제한을 받지 않고 비공개 소스를 볼 수 있는 API 키 (`API_KEYS`, 쉼표 구분)
+    #[serde(serialize_with = "redact::list")]
+    pub api_keys: Vec<String>,
+    /// 변환 기본값을 따로 가진 테넌트 (`TENANTS`, 이름마다 `TENANT_<이름>_KEY`, `_SIZE`, `_WEBP_*`)
+    pub tenants: Vec<Tenant>,
+    /// API 키 없는 요청의 IP당 허용 횟수 (`ANON_RATE_LIMIT`, 기본값: 0 = 제한 없음)
+    pub anon_rate_limit: u32,
+    /// 익명 요청 제한 윈도 (`ANON_RATE_WINDOW_SECS`, 기본값: 60)
+    #[serde(serialize_with = "secs")]
+    pub anon_rate_window: Duration,
+    /// 로그에 클라이언트 IP를 남기는 방식 (`PRIVACY_IP_MODE`: full/truncate/hash)
+    pub ip_mode: IpMode,
+    /// 해시 모드 솔트 (`PRIVACY_IP_SALT`, 미설정 시 프로세스마다 무작위)
+    #[serde(serialize_with = "redact::optional")]
+    pub ip_salt: Option<String>,
+    /// 보관하는 접근/신고 데이터의 보관 기간 (`PRIVACY_RETENTION_DAYS`, 기본값: 30일)
+    #[serde(serialize_with = "secs")]
+    pub retention: Duration,
+    /// stdout 로그 출력 여부 (`LOG_STDOUT`, 기본값: true)
+    pub log_stdout: bool,
+    /// 파일 로그 (`LOG_DIR` 설정 시, `LOG_ROTATION`, `LOG_MAX_SIZE_MB`, `LOG_COMPRESS`, `LOG_MAX_FILES`)
+    pub log_file: Option<FileLogConfig>,
+    /// 디스크 캐시 계층 디렉터리 (`CACHE_DIR`, 미설정 시 비활성)
+    pub cache_dir: Option<PathBuf>,
+    /// 디스크 캐시 항목 수명 (`CACHE_DISK_TTL_SECS`, 기본값: 7일)
+    #[serde(serialize_with = "secs")]
+    pub cache_disk_ttl: Duration,
+    /// 디스크 캐시 zstd 압축 레벨 (`CACHE_DISK_COMPRESS`, `CACHE_DISK_ZSTD_LEVEL`, 기본값: 끔 / 3)
+    pub cache_disk_zstd: Option<i32>,
+    /// S3 호환 오브젝트 저장소 캐시 계층 (`CACHE_S3_*`, `CACHE_S3_BUCKET` 미설정 시 비활성)
+    pub cache_s3: Option<S3Config>,
+    /// 이보다 큰 항목은 메모리 캐시에 두지 않고 2차 계층에만 (`CACHE_SPILL_KB`, 기본값: 0 = 끔, 2차 계층이 있을 때만)
+    pub cache_spill_bytes: Option<usize>,
+    /// 업스트림 원본을 보관할 디렉터리 (`MIRROR_DIR`, 미설정 시 비활성)
+    pub mirror_dir: Option<PathBuf>,
+    /// 업스트림에서 마지막으로 확인한 뒤 보관 원본을 남겨 둘 기간 (`MIRROR_RETENTION_DAYS`, 기본값: 90일, 0이면 계속)
+    #[serde(serialize_with = "secs")]
+    pub mirror_retention: Duration,
+    /// 2차 캐시 정리(만료/고아 파일 삭제) 주기 (`CACHE_MAINTENANCE_INTERVAL_SECS`, 기본값: 1시간, 0이면 끔)
+    #[serde(serialize_with = "secs")]
+    pub cache_maintenance_interval: Duration,
+    /// 선제 갱신할 인기 이모지 수 (`PREWARM_TOP`, 기본값: 100, 0이면 비활성)
+    pub prewarm_top: usize,
+    /// 메모리 캐시 만료 얼마 전에 갱신할지 (`PREWARM_LEAD_SECS`, 기본값: 600)
+    #[serde(serialize_with = "secs")]
+    pub prewarm_lead: Duration,
+    /// 갱신 대상 점검 주기 (`PREWARM_INTERVAL_SECS`, 기본값: 60)
+    #[serde(serialize_with = "secs")]
+    pub prewarm_interval: Duration,
+    /// 한 크기를 새로 만들면 함께 미리 만들어 둘 크기 (`SIBLING_WARM_SIZES`, `OUTPUT_SIZES` 중에서, 기본값: 없음)
+    pub sibling_warm_sizes: Vec<u32>,
+    /// 부팅 때 캐시를 채울 이모지 ID 목록 파일 (`WARM_SEED_FILE`, 미설정 시 없음)
+    pub warm_seed_file: Option<PathBuf>,
+    /// 시드의 이 비율이 캐시될 때까지 `/readyz`를 준비 안 됨으로 (`READY_WARM_FRACTION`, 0~1, 기본값: 0 = 기다리지 않음)
+    pub ready_warm_fraction: f64,
+    /// 시드를 기다리는 최대 시간 (`READY_WARM_TIMEOUT_SECS`, 기본값: 300, 0이면 목록을 다 돌 때까지)
+    #[serde(serialize_with = "secs")]
+    pub ready_warm_timeout: Duration,
+    /// 백그라운드 작업 동시 실행 수 (`TASK_CONCURRENCY`, 기본값: 4)
+    pub task_concurrency: usize,
+    /// 대기 중인 백그라운드 작업 최대 수 (`TASK_QUEUE_CAPACITY`, 기본값: 1000)
+    pub task_queue_capacity: usize,
+    /// 처리 중 인코딩 작업이 이 수 이상이면 저하 모드 (`DEGRADE_ENCODE_IN_FLIGHT`, 기본값: CPU 코어 수 x 4, 0이면 비활성)
+    pub degrade_in_flight: usize,
+    /// 동시에 돌리는 리사이즈/인코딩 수 (`PROCESSING_CONCURRENCY`, 기본값: CPU 코어 수)
+    pub processing_concurrency: usize,
+    /// 그중 선제 갱신·캐시 채우기 같은 백그라운드 작업이 쓸 수 있는 최대 수
+    /// (`PROCESSING_BACKGROUND_MAX`, 기본값: 코어 수의 절반, 최소 1)
+    pub processing_background_max: usize,
+    /// 인코딩을 기다리는 사용자 요청이 이 수 이상이면 새 요청에 429 (`ADMISSION_QUEUE_SOFT`, 기본값: 0 = 끔)
+    pub admission_queue_soft: usize,
+    /// 이 수 이상이면 503 (`ADMISSION_QUEUE_HARD`, 기본값: 0 = 끔)
+    pub admission_queue_hard: usize,
+    /// 거절 응답의 `Retry-After` 기준값. 실제 값은 이것부터 두 배 사이에서 흔든다 (`ADMISSION_RETRY_AFTER_SECS`, 기본값: 1)
+    #[serde(serialize_with = "secs")]
+    pub admission_retry_after: Duration,
+    /// 상주 메모리가 이 이상이면 저하 모드 (`DEGRADE_MEMORY_MB`, 기본값: 0 = 비활성)
+    pub degrade_memory_bytes: u64,
+    /// 2차 캐시 계층 호출 타임아웃 (`CACHE_BACKEND_TIMEOUT_MS`, 기본값: 50ms)
+    #[serde(serialize_with = "secs")]
+    pub cache_backend_timeout: Duration,
+    /// 캐시에 없는 키를 만드는 동안 2차 계층에 잡아 두는 임대 기간 (`CACHE_LEASE_MS`, 기본값: 5000ms, 0이면 끔)
+    #[serde(serialize_with = "secs")]
+    pub cache_lease: Duration,
+    /// 2차 계층 키 앞에 붙이는 캐시 세대 (`CACHE_GENERATION`, 기본값: 없음). 인코더 설정을 바꿔 예전 결과를 버릴 때 올린다.
+    pub cache_generation: String,
+    /// 옮겨 가는 동안 함께 기록할 이전 세대 (`CACHE_GENERATION_PREVIOUS`, 미설정 시 끔, 빈 값이면 세대 없는 키)
+    pub cache_generation_previous: Option<String>,
+    /// 이전 세대에 함께 기록하는 기간, 부팅 시각부터 (`CACHE_DUAL_WRITE_SECS`, 기본값: 3600)
+    #[serde(serialize_with = "secs")]
+    pub cache_dual_write: Duration,
+    /// Slack 워크스페이스 이름 → 봇 토큰 (`SLACK_TOKENS`: `이름=xoxb-…` 쉼표 구분, 미설정 시 `/slack` 비활성)
+    #[serde(serialize_with = "redact::values")]
+    pub slack_tokens: HashMap<String, String>,
+    /// Slack Web API 주소 (`SLACK_API_BASE`, 기본값: `https://slack.com/api`)
+    pub slack_api_base: String,
+    /// `emoji.list` 결과 재사용 기간 (`SLACK_EMOJI_LIST_TTL_SECS`, 기본값: 600)
+    #[serde(serialize_with = "secs")]
+    pub slack_list_ttl: Duration,
+    /// 이모지를 조회할 수 있는 Mastodon/Misskey 인스턴스 (`FEDI_INSTANCES`, 쉼표 구분, 미설정 시 `/fedi` 비활성)
+    pub fedi_instances: Vec<String>,
+    /// 인스턴스 이모지 목록 재사용 기간 (`FEDI_EMOJI_LIST_TTL_SECS`, 기본값: 600)
+    #[serde(serialize_with = "secs")]
+    pub fedi_list_ttl: Duration,
+    /// 커스텀 이모트 저장 위치 (`CUSTOM_DIR`, 미설정 시 `/custom` 비활성)
+    pub custom_dir: Option<PathBuf>,
+    /// 업로드/삭제 토큰 (`CUSTOM_UPLOAD_TOKEN`, 미설정 시 `ADMIN_TOKEN`, 둘 다 없으면 읽기 전용)
+    #[serde(serialize_with = "redact::optional")]
+    pub custom_upload_token: Option<String>,
+    /// 비공개 이모트 주소 서명 키 (`CUSTOM_SIGNING_KEY`, 미설정 시 비공개 이모트를 쓸 수 없음)
+    #[serde(serialize_with = "redact::optional")]
+    pub custom_signing_key: Option<String>,
+    /// 서명한 주소의 기본 유효 기간 (`CUSTOM_SIGNED_URL_TTL_SECS`, 기본값: 3600)
+    #[serde(serialize_with = "secs")]
+    pub custom_signed_ttl: Duration,
+    /// 업로드 본문 최대 크기 (`CUSTOM_MAX_UPLOAD_BYTES`, 기본값: 2 MiB)
+    pub custom_max_upload_bytes: usize,
+    /// 저장할 때 맞출 최대 가로/세로 (`CUSTOM_MAX_DIMENSION`, 기본값: 512)
+    pub custom_max_dimension: u32,
+    /// 참조가 없는 커스텀 이모트 블롭 정리 주기 (`CUSTOM_GC_INTERVAL_SECS`, 기본값: 3600, 0이면 끔)
+    #[serde(serialize_with = "secs")]
+    pub custom_gc_interval: Duration,
+    /// 이모트마다 남기는 예전 버전 수 (`CUSTOM_MAX_VERSIONS`, 0이면 남기지 않음)
+    pub custom_max_versions: usize,
+    /// 지운 이모트를 복원할 수 있게 남겨 두는 기간 (`CUSTOM_DELETED_RETENTION_SECS`, 0이면 바로 삭제)
+    #[serde(serialize_with = "secs")]
+    pub custom_deleted_retention: Duration,
+    /// 길드 ID → 그 길드 네임스페이스 전용 업로드 키 (`CUSTOM_GUILD_TOKENS`: `길드=키` 쉼표 구분)
+    #[serde(serialize_with = "redact::values")]
+    pub custom_guild_tokens: HashMap<String, String>,
+    /// 길드별 최대 이모트 수 (`CUSTOM_GUILD_MAX_EMOTES`, 기본값: 200, 0이면 제한 없음)
+    pub custom_guild_max_emotes: usize,
+    /// 길드별 현재 버전 용량 합 한도 (`CUSTOM_GUILD_MAX_BYTES`, 기본값: 50 MiB, 0이면 제한 없음)
+    pub custom_guild_max_bytes: u64,
+    /// 업로드 검사 웹훅 (`CUSTOM_MODERATION_URL`, 미설정 시 검사하지 않음)
+    pub custom_moderation_url: Option<String>,
+    /// 이 점수 이상이면 검토 대기열로 (`CUSTOM_MODERATION_THRESHOLD`, 기본값: 0.8)
+    pub custom_moderation_threshold: f64,
+    /// 모든 업로드를 관리자 승인 뒤에 공개 (`CUSTOM_MODERATION_MANUAL`)
+    pub custom_moderation_manual: bool,
+    /// Twitch 앱 자격 증명 (`TWITCH_CLIENT_ID`, `TWITCH_CLIENT_SECRET`, 둘 다 있어야 `/twitch` 활성)
+    pub twitch_client_id: Option<String>,
+    #[serde(serialize_with = "redact::optional")]
+    pub twitch_client_secret: Option<String>,
+    /// Twitch Helix API 주소 (`TWITCH_API_BASE`, 기본값: `https://api.twitch.tv/helix`)
+    pub twitch_api_base: String,
+    /// Twitch 토큰 발급 주소 (`TWITCH_AUTH_BASE`, 기본값: `https://id.twitch.tv/oauth2`)
+    pub twitch_auth_base: String,
+    /// 배지/치어모트 목록 재사용 기간 (`TWITCH_LIST_TTL_SECS`, 기본값: 600)
+    #[serde(serialize_with = "secs")]
+    pub twitch_list_ttl: Duration,
+    /// 검색에 길드 이모지를 포함할 때 쓰는 봇 토큰 (`DISCORD_BOT_TOKEN`)
+    #[serde(serialize_with = "redact::optional")]
+    pub discord_bot_token: Option<String>,
+    /// 검색할 길드 ID 목록 (`DISCORD_GUILD_IDS`, 쉼표 구분)
+    pub discord_guild_ids: Vec<String>,
+    /// Discord API 주소 (`DISCORD_API_BASE`, 기본값: `https://discord.com/api/v10`)
+    pub discord_api_base: String,
+    /// 길드 이모지 목록 재사용 기간 (`GUILD_EMOJI_LIST_TTL_SECS`, 기본값: 600)
+    #[serde(serialize_with = "secs")]
+    pub guild_emoji_list_ttl: Duration,
+    /// 운영 알림을 보낼 Discord 웹훅 (`DISCORD_WEBHOOK_URL`, 미설정 시 비활성)
+    #[serde(serialize_with = "redact::optional")]
+    pub webhook_url: Option<String>,
+    /// 10초 동안의 업스트림 실패 비율이 이 이상이면 알림 (`NOTIFY_ERROR_RATE`, 기본값: 0.5, 0이면 끔)
+    pub notify_error_rate: f64,
+    /// 디스크 캐시 사용량이 이 이상이면 알림 (`NOTIFY_DISK_CACHE_MB`, 기본값: 0 = 끔)
+    pub notify_disk_bytes: u64,
+    /// 같은 종류의 알림을 다시 보내기까지의 간격 (`NOTIFY_COOLDOWN_SECS`, 기본값: 900)
+    #[serde(serialize_with = "secs")]
+    pub notify_cooldown: Duration,
+    /// 캐시 미스 SLO 소진율이 이 이상이면 알림 (`NOTIFY_SLO_BURN_RATE`, 기본값: 0 = 끔)
+    pub notify_slo_burn_rate: f64,
+    /// 소스별 캐시 미스 p99가 이 이상이면 알림 (`NOTIFY_MISS_P99_MS`, 기본값: 0 = 끔)
+    pub notify_miss_p99_ms: u64,
+    /// libvips로 처리할 작업 (`VIPS_OPERATIONS`: `static`, `animated`, `foreign` 쉼표 구분, `vips` 기능으로 빌드했을 때만)
+    pub vips_operations: Vec<VipsOperation>,
+    /// `vips` 실행 파일 (`VIPS_BINARY`, 기본값: `vips`)
+    pub vips_binary: String,
+    /// 정적 WebP 인코더 설정 (`WEBP_METHOD`, `WEBP_TARGET_SIZE`, `WEBP_NEAR_LOSSLESS`, `CWEBP_BINARY`,
+    /// `libwebp` 기능으로 빌드했을 때만)
+    pub webp: WebpOptions,
+    /// 캐시 미스 일부에 적용할 실험 인코더 설정 (`ENCODER_EXPERIMENT`, `ENCODER_EXPERIMENT_PERCENT`,
+    /// `ENCODER_EXPERIMENT_WEBP_*`, 이름이 없으면 끔)
+    pub encoder_experiment: Option<Experiment>,
+    /// `Accept: image/avif` 클라이언트에게 애니메이션을 AVIF로 (`AVIF_ANIMATED`, 기본값: false, `avif` 기능으로 빌드했을 때만)
+    pub avif_animated: bool,
+    /// `avifenc` 실행 파일 (`AVIFENC_BINARY`, 기본값: `avifenc`)
+    pub avif_binary: String,
+    /// 인코딩 속도 0(느림, 작음)~10(빠름) (`AVIF_SPEED`, 기본값: 6)
+    pub avif_speed: u8,
+    /// 화질 0~100 (`AVIF_QUALITY`, 기본값: 60)
+    pub avif_quality: u8,
+    /// 포맷·크기별 손실 압축 화질 (`QUALITY`, `QUALITY_<크기>`, 기본값: 비어 있음 — 지금처럼 무손실 WebP, AVIF는 `AVIF_QUALITY`)
+    pub quality: QualityMatrix,
+    /// 이미지가 아닌 응답(JSON, 메트릭 등)을 gzip/br로 압축할지 (`RESPONSE_COMPRESSION`, 기본값: true)
+    pub response_compression: bool,
+    /// 요청 일부를 따라 보낼 스테이징 인스턴스 주소 (`SHADOW_URL`, 미설정 시 비활성)
+    pub shadow_url: Option<String>,
+    /// 섀도잉할 요청 비율 0~100 (`SHADOW_PERCENT`, 기본값: 1)
+    pub shadow_percent: f64,
+    /// 동시에 띄워 둘 섀도 요청 수 (`SHADOW_CONCURRENCY`, 기본값: 16)
+    pub shadow_concurrency: usize,
+    /// 섀도 요청 타임아웃 (`SHADOW_TIMEOUT_MS`, 기본값: 5초)
+    #[serde(serialize_with = "secs")]
+    pub shadow_timeout: Duration,
+    /// 응답 전에 클라이언트가 끊었을 때 하던 작업을 어떻게 할지 (`CLIENT_DISCONNECT`: cancel/finish, 기본값: cancel)
+    pub client_disconnect: ClientDisconnect,
+    /// `/admin/errors`에 남길 최근 실패 수 (`RECENT_ERRORS`, 기본값: 200, 0이면 비활성)
+    pub recent_errors: usize,
+    /// 캐시 미스 하나가 이 안에 끝나야 SLO를 지킨 것으로 본다 (`SLO_MISS_MS`, 기본값: 1000)
+    #[serde(serialize_with = "secs")]
+    pub slo_miss_target: Duration,
+    /// 목표 시간 안에 끝나야 하는 미스 비율 (`SLO_MISS_TARGET`, 기본값: 0.99)
+    pub slo_miss_objective: f64,
+    /// 백분위수와 소진율을 계산하는 최근 구간 (`SLO_WINDOW_SECS`, 기본값: 300)
+    #[serde(serialize_with = "secs")]
+    pub slo_window: Duration,
+}
+
+impl Config {
+    /// 비밀 값을 가린 설정 요약 (부팅 로그와 `GET /admin/config`)
+    pub fn summary(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    pub fn from_env() -> anyhow::Result<Self> {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let retention = Duration::from_secs(env_or::<u64>("PRIVACY_RETENTION_DAYS", 30)? * 86400);
+        let log_file = match env::var_os("LOG_DIR") {
+            Some(dir) if !dir.is_empty() => Some(FileLogConfig {
+                dir: PathBuf::from(dir),
+                rotation: env_or("LOG_ROTATION", Rotation::Daily)?,
+                max_bytes: env_or::<u64>("LOG_MAX_SIZE_MB", 100)? * 1024 * 1024,
+                compress: env_flag("LOG_COMPRESS", true)?,
+                max_files: env_or("LOG_MAX_FILES", 30)?,
+                retention,
+            }),
+            _ => None,
+        };
+        let upstream_timeout = Duration::from_millis(env_or("UPSTREAM_TIMEOUT_MS", 10_000)?);
+        let output_sizes = output_sizes()?;
+        let default_size = default_size(&output_sizes)?;
+        let sibling_warm_sizes = sibling_warm_sizes(&output_sizes)?;
+        let webp = webp_options()?;
+        let ready_warm_fraction: f64 = env_or("READY_WARM_FRACTION", 0.0)?;
+        if !(0.0..=1.0).contains(&ready_warm_fraction) {
+            bail!("invalid READY_WARM_FRACTION {ready_warm_fraction}: must be between 0 and 1");
+        }
+        let shadow_percent: f64 = env_or("SHADOW_PERCENT", 1.0)?;
+        if !(0.0..=100.0).contains(&shadow_percent) {
+            bail!("invalid SHADOW_PERCENT {shadow_percent}: must be between 0 and 100");
+        }
+        let slo_miss_objective: f64 = env_or("SLO_MISS_TARGET", 0.99)?;
+        if !(0.0 < slo_miss_objective && slo_miss_objective < 1.0) {
+            bail!("invalid SLO_MISS_TARGET {slo_miss_objective}: must be between 0 and 1 (exclusive)");
+        }
+        let (cache_generation, cache_generation_previous) = cache_generations()?;
+        let encoder_experiment = encoder_experiment(&webp)?;
+        let tenants = tenants(&output_sizes, default_size, &webp)?;
+        let quality = QualityMatrix::from_env(&output_sizes)?;
+        let emoji_cache = CachePolicy::from_env("EMOJI_CACHE", CachePolicy::default())?;
+        let emoji_cache_auth = CachePolicy::from_env(
+            "EMOJI_CACHE_AUTH",
+            CachePolicy { s_maxage: emoji_cache.s_maxage.max(Some(30 * 24 * 3600)), ..emoji_cache.clone() },
+        )?;
+        Ok(Self {
+            bind_addr: env_or("BIND_ADDR", SocketAddr::from(([0, 0, 0, 0], 53292)))?,
+            animation_parallelism: env_or("ANIMATION_PARALLELISM", cores)?.max(1),
+            emoji_cache,
+            emoji_cache_auth,
+            passthrough: env_flag("PASSTHROUGH_DEFAULT", false)?,
+            output_sizes,
+            default_size,
+            animated_budget: match env_or::<usize>("ANIMATED_MAX_KB", 256)? {
+                0 => None,
+                kb => Some(Budget {
+                    max_bytes: kb * 1024,
+                    min_fps: env_or("ANIMATED_MIN_FPS", 5.0)?,
+                }),
+            },
+            original_max_bytes: env_or("ORIGINAL_MAX_BYTES", 4 << 20)?,
+            upstream_max_bytes: env_or("UPSTREAM_MAX_BYTES", 16 << 20)?,
+            cdn_purge: CdnProvider::from_env()?,
+            upstream_base: env::var("UPSTREAM_BASE_URL")
+                .ok()
+                .filter(|v| !v.is_empty())
+                .map_or_else(|| "https://cdn.discordapp.com".into(), |v| v.trim_end_matches('/').into()),
+            admin_token: env::var("ADMIN_TOKEN").ok().filter(|v| !v.is_empty()),
+            upstream_timeout,
+            upstreams: upstream_settings(upstream_timeout)?,
+            cache_capacity: env_or("CACHE_CAPACITY", 50_000)?,
+            cache_ttl: Duration::from_secs(env_or("CACHE_TTL_SECS", 24 * 3600)?.max(1)),
+            cache_eviction: env_or("CACHE_EVICTION", CacheEviction::TinyLfu)?,
+            cache_admit_after: env_or("CACHE_ADMIT_AFTER", 1)?,
+            cache_admit_window: Duration::from_secs(env_or("CACHE_ADMIT_WINDOW_SECS", 3600)?),
+            stale_capacity: env_or("STALE_CACHE_CAPACITY", 10_000)?,
+            stale_ttl: Duration::from_secs(env_or("STALE_CACHE_TTL_SECS", 7 * 24 * 3600)?),
+            validator_capacity: env_or("VALIDATOR_INDEX_CAPACITY", 200_000)?,
+            blocklist_file: env::var_os("BLOCKLIST_FILE").map(PathBuf::from),
+            blocklist: [
+                ("BLOCKLIST_IDS", BlockKind::Id),
+                ("BLOCKLIST_HASH_PREFIXES", BlockKind::Hash),
+                ("BLOCKLIST_SOURCES", BlockKind::Source),
+            ]
+            .into_iter()
+            .flat_map(|(name, kind)| env_list(name).into_iter().map(move |v| (name, kind, v)))
+            .map(|(name, kind, v)| BlockEntry::new(kind, &v).with_context(|| format!("invalid {name}")))
+            .collect::<anyhow::Result<_>>()?,
+            report_rate_limit: env_or("REPORT_RATE_LIMIT", 5)?,
+            report_rate_window: Duration::from_secs(env_or("REPORT_RATE_WINDOW_SECS", 3600)?),
+            hcaptcha_secret: env::var("HCAPTCHA_SECRET").ok().filter(|s| !s.is_empty()),
+            trust_forwarded_for: env_flag("TRUST_FORWARDED_FOR", false)?,
+            api_keys: env_list("API_KEYS"),
+            tenants,
+            anon_rate_limit: env_or("ANON_RATE_LIMIT", 0)?,
+            anon_rate_window: Duration::from_secs(env_or("ANON_RATE_WINDOW_SECS", 60)?.max(1)),
+            ip_mode: env_or("PRIVACY_IP_MODE", IpMode::Full)?,
+            ip_salt: env::var("PRIVACY_IP_SALT").ok().filter(|s| !s.is_empty()),
+            retention,
+            log_stdout: env_flag("LOG_STDOUT", true)?,
+            log_file,
+            cache_dir: env::var_os("CACHE_DIR").filter(|d| !d.is_empty()).map(PathBuf::from),
+            cache_disk_ttl: Duration::from_secs(env_or("CACHE_DISK_TTL_SECS", 7 * 24 * 3600)?),
+            cache_disk_zstd: env_flag("CACHE_DISK_COMPRESS", false)?
+                .then(|| env_or("CACHE_DISK_ZSTD_LEVEL", 3))
+                .transpose()?,
+            cache_s3: S3Config::from_env()?,
+            cache_spill_bytes: match env_or::<usize>("CACHE_SPILL_KB", 0)? {
+                0 => None,
+                kb => Some(kb * 1024),
+            },
+            mirror_dir: env::var_os("MIRROR_DIR").filter(|d| !d.is_empty()).map(PathBuf::from),
+            mirror_retention: Duration::from_secs(env_or::<u64>("MIRROR_RETENTION_DAYS", 90)? * 86400),
+            cache_maintenance_interval: Duration::from_secs(env_or("CACHE_MAINTENANCE_INTERVAL_SECS", 3600)?),
+            prewarm_top: env_or("PREWARM_TOP", 100)?,
+            prewarm_lead: Duration::from_secs(env_or("PREWARM_LEAD_SECS", 600)?),
+            prewarm_interval: Duration::from_secs(env_or("PREWARM_INTERVAL_SECS", 60)?.max(1)),
+            sibling_warm_sizes,
+            warm_seed_file: env::var_os("WARM_SEED_FILE").filter(|v| !v.is_empty()).map(PathBuf::from),
+            ready_warm_fraction,
+            ready_warm_timeout: Duration::from_secs(env_or("READY_WARM_TIMEOUT_SECS", 300)?),
+            task_concurrency: env_or("TASK_CONCURRENCY", 4)?,
+            task_queue_capacity: env_or("TASK_QUEUE_CAPACITY", 1000)?,
+            degrade_in_flight: env_or("DEGRADE_ENCODE_IN_FLIGHT", cores * 4)?,
+            processing_concurrency: env_or("PROCESSING_CONCURRENCY", cores)?.max(1),
+            processing_background_max: env_or("PROCESSING_BACKGROUND_MAX", (cores / 2).max(1))?.max(1),
+            admission_queue_soft: env_or("ADMISSION_QUEUE_SOFT", 0)?,
+            admission_queue_hard: env_or("ADMISSION_QUEUE_HARD", 0)?,
+            admission_retry_after: Duration::from_secs(env_or("ADMISSION_RETRY_AFTER_SECS", 1)?),
+            degrade_memory_bytes: env_or::<u64>("DEGRADE_MEMORY_MB", 0)? << 20,
+            cache_backend_timeout: Duration::from_millis(env_or("CACHE_BACKEND_TIMEOUT_MS", 50)?),
+            cache_lease: Duration::from_millis(env_or("CACHE_LEASE_MS", 5000)?),
+            cache_generation,
+            cache_generation_previous,
+            cache_dual_write: Duration::from_secs(env_or("CACHE_DUAL_WRITE_SECS", 3600)?),
+            slack_tokens: env_pairs("SLACK_TOKENS", "workspace=token")?,
+            slack_api_base: env_base("SLACK_API_BASE", "https://slack.com/api"),
+            slack_list_ttl: Duration::from_secs(env_or("SLACK_EMOJI_LIST_TTL_SECS", 600)?),
+            fedi_instances: env_list("FEDI_INSTANCES"),
+            fedi_list_ttl: Duration::from_secs(env_or("FEDI_EMOJI_LIST_TTL_SECS", 600)?),
+            custom_dir: env::var_os("CUSTOM_DIR").filter(|v| !v.is_empty()).map(PathBuf::from),
+            custom_upload_token: env::var("CUSTOM_UPLOAD_TOKEN").ok().filter(|v| !v.is_empty()),
+            custom_signing_key: env::var("CUSTOM_SIGNING_KEY").ok().filter(|v| !v.is_empty()),
+            custom_signed_ttl: Duration::from_secs(env_or("CUSTOM_SIGNED_URL_TTL_SECS", 3600)?.max(1)),
+            custom_max_upload_bytes: env_or("CUSTOM_MAX_UPLOAD_BYTES", 2 << 20)?,
+            custom_max_dimension: env_or("CUSTOM_MAX_DIMENSION", 512)?,
+            custom_gc_interval: Duration::from_secs(env_or("CUSTOM_GC_INTERVAL_SECS", 3600)?),
+            custom_max_versions: env_or("CUSTOM_MAX_VERSIONS", 10)?,
+            custom_deleted_retention: Duration::from_secs(env_or("CUSTOM_DELETED_RETENTION_SECS", 30 * 24 * 3600)?),
+            custom_guild_tokens: env_pairs("CUSTOM_GUILD_TOKENS", "guild=token")?,
+            custom_guild_max_emotes: env_or("CUSTOM_GUILD_MAX_EMOTES", 200)?,
+            custom_guild_max_bytes: env_or("CUSTOM_GUILD_MAX_BYTES", 50 << 20)?,
+            custom_moderation_url: env::var("CUSTOM_MODERATION_URL").ok().filter(|v| !v.is_empty()),
+            custom_moderation_threshold: env_or("CUSTOM_MODERATION_THRESHOLD", 0.8)?,
+            custom_moderation_manual: env_flag("CUSTOM_MODERATION_MANUAL", false)?,
+            twitch_client_id: env::var("TWITCH_CLIENT_ID").ok().filter(|v| !v.is_empty()),
+            twitch_client_secret: env::var("TWITCH_CLIENT_SECRET").ok().filter(|v| !v.is_empty()),
+            twitch_api_base: env_base("TWITCH_API_BASE", "https://api.twitch.tv/helix"),
+            twitch_auth_base: env_base("TWITCH_AUTH_BASE", "https://id.twitch.tv/oauth2"),
+            twitch_list_ttl: Duration::from_secs(env_or("TWITCH_LIST_TTL_SECS", 600)?),
+            discord_bot_token: env::var("DISCORD_BOT_TOKEN").ok().filter(|v| !v.is_empty()),
+            discord_guild_ids: env_list("DISCORD_GUILD_IDS"),
+            discord_api_base: env_base("DISCORD_API_BASE", "https://discord.com/api/v10"),
+            guild_emoji_list_ttl: Duration::from_secs(env_or("GUILD_EMOJI_LIST_TTL_SECS", 600)?),
+            webhook_url: env::var("DISCORD_WEBHOOK_URL").ok().filter(|v| !v.is_empty()),
+            notify_error_rate: env_or("NOTIFY_ERROR_RATE", 0.5)?,
+            notify_disk_bytes: env_or::<u64>("NOTIFY_DISK_CACHE_MB", 0)? << 20,
+            notify_cooldown: Duration::from_secs(env_or("NOTIFY_COOLDOWN_SECS", 900)?),
+            notify_slo_burn_rate: env_or("NOTIFY_SLO_BURN_RATE", 0.0)?,
+            notify_miss_p99_ms: env_or("NOTIFY_MISS_P99_MS", 0)?,
+            vips_operations: env_list("VIPS_OPERATIONS")
+                .iter()
+                .map(|op| op.parse())
+                .collect::<anyhow::Result<_>>()
+                .context("invalid VIPS_OPERATIONS")?,
+            vips_binary: env::var("VIPS_BINARY").ok().filter(|v| !v.trim().is_empty()).unwrap_or_else(|| "vips".into()),
+            webp,
+            encoder_experiment,
+            avif_animated: env_flag("AVIF_ANIMATED", false)?,
+            avif_binary: env::var("AVIFENC_BINARY").ok().filter(|v| !v.trim().is_empty()).unwrap_or_else(|| "avifenc".into()),
+            avif_speed: env_range("AVIF_SPEED", 6, 10)?,
+            avif_quality: env_range("AVIF_QUALITY", 60, 100)?,
+            quality,
+            response_compression: env_flag("RESPONSE_COMPRESSION", true)?,
+            client_disconnect: env_or("CLIENT_DISCONNECT", ClientDisconnect::Cancel)?,
+            shadow_url: env::var("SHADOW_URL").ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()),
+            shadow_percent,
+            shadow_concurrency: env_or("SHADOW_CONCURRENCY", 16)?,
+            shadow_timeout: Duration::from_millis(env_or("SHADOW_TIMEOUT_MS", 5_000)?),
+            recent_errors: env_or("RECENT_ERRORS", 200)?,
+            slo_miss_target: Duration::from_millis(env_or("SLO_MISS_MS", 1000)?),
+            slo_miss_objective,
+            slo_window: Duration::from_secs(env_or("SLO_WINDOW_SECS", 300)?),
+        })
+    }
+}
+
+/// `CACHE_GENERATION`과 `CACHE_GENERATION_PREVIOUS`. 이전 세대는 빈 값도 뜻이 있으므로(세대 없는 키) 있는지로 판단한다.
+fn cache_generations() -> anyhow::Result<(String, Option<String>)> {
+    let valid = |name: &str, value: &str| {
+        if !value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')) {
+            bail!("invalid {name} {value:?}: use letters, digits, '.', '-' or '_'");
+        }
+        Ok(())
+    };
+    let current = env::var("CACHE_GENERATION").unwrap_or_default().trim().to_string();
+    valid("CACHE_GENERATION", &current)?;
+    let previous = env::var("CACHE_GENERATION_PREVIOUS").ok().map(|v| v.trim().to_string());
+    if let Some(previous) = &previous {
+        valid("CACHE_GENERATION_PREVIOUS", previous)?;
+        if *previous == current {
+            bail!("CACHE_GENERATION_PREVIOUS must differ from CACHE_GENERATION ({current:?})");
+        }
+    }
+    Ok((current, previous))
+}
+
+fn webp_options() -> anyhow::Result<WebpOptions> {
+    let default = WebpOptions::default();
+    Ok(WebpOptions {
+        method: env_range("WEBP_METHOD", default.method, 6)?,
+        target_size: env_or("WEBP_TARGET_SIZE", default.target_size)?,
+        near_lossless: env_range("WEBP_NEAR_LOSSLESS", default.near_lossless, 100)?,
+        quality: None,
+        binary: env::var("CWEBP_BINARY").ok().filter(|v| !v.trim().is_empty()).unwrap_or(default.binary),
+    })
+}
+
+/// 테넌트 설정의 빠진 값은 전역 기본값을 따른다. 기본 크기는 `OUTPUT_SIZES` 중 하나여야 한다.
+fn tenants(output_sizes: &[u32], default_size: u32, default: &WebpOptions) -> anyhow::Result<Vec<Tenant>> {
+    let mut tenants: Vec<Tenant> = Vec::new();
+    for name in env_list("TENANTS") {
+        if !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') {
+            bail!("invalid tenant name {name:?} in TENANTS: use letters, digits or '_'");
+        }
+        let prefix = format!("TENANT_{}", name.to_ascii_uppercase());
+        let Some(key) = env::var(format!("{prefix}_KEY")).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) else {
+            bail!("{prefix}_KEY is required for tenant {name:?}");
+        };
+        if tenants.iter().any(|t| t.name == name || t.key == key) {
+            bail!("tenant {name:?} is listed twice or shares a key with another tenant");
+        }
+        let size = env_or(&format!("{prefix}_SIZE"), default_size)?;
+        if !output_sizes.contains(&size) {
+            bail!("invalid {prefix}_SIZE {size}: must be one of OUTPUT_SIZES {output_sizes:?}");
+        }
+        let webp = WebpOptions {
+            method: env_range(&format!("{prefix}_WEBP_METHOD"), default.method, 6)?,
+            target_size: env_or(&format!("{prefix}_WEBP_TARGET_SIZE"), default.target_size)?,
+            near_lossless: env_range(&format!("{prefix}_WEBP_NEAR_LOSSLESS"), default.near_lossless, 100)?,
+            quality: None,
+            binary: default.binary.clone(),
+        };
+        tenants.push(Tenant { name, key, size, webp });
+    }
+    Ok(tenants)
+}
+
+/// 실험 설정의 빠진 값은 기본 설정을 따른다 (`cwebp` 실행 파일도 같은 것을 쓴다)
+fn encoder_experiment(control: &WebpOptions) -> anyhow::Result<Option<Experiment>> {
+    let Some(name) = env::var("ENCODER_EXPERIMENT").ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    if name == experiment::CONTROL || !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') {
+        bail!("invalid ENCODER_EXPERIMENT {name:?}: use letters, digits, '-' or '_' (and not {:?})", experiment::CONTROL);
+    }
+    let percent: f64 = env_or("ENCODER_EXPERIMENT_PERCENT", 5.0)?;
+    if !(0.0..=100.0).contains(&percent) {
+        bail!("invalid ENCODER_EXPERIMENT_PERCENT {percent}: must be between 0 and 100");
+    }
+    let webp = WebpOptions {
+        method: env_range("ENCODER_EXPERIMENT_WEBP_METHOD", control.method, 6)?,
+        target_size: env_or("ENCODER_EXPERIMENT_WEBP_TARGET_SIZE", control.target_size)?,
+        near_lossless: env_range("ENCODER_EXPERIMENT_WEBP_NEAR_LOSSLESS", control.near_lossless, 100)?,
+        quality: None,
+        binary: control.binary.clone(),
+    };
+    Ok(Some(Experiment { name, percent, webp }))
+}
+
+/// 소스마다 `UPSTREAM_<소스>_*`로 바꾼 값, 없으면 [`upstream::Settings::defaults`]
+fn upstream_settings(timeout: Duration) -> anyhow::Result<upstream::Settings> {
+    let source = |source: Source| -> anyhow::Result<ClientSettings> {
+        let default = upstream::Settings::defaults(source, timeout);
+        let name = source.env_name();
+        let rate_limit = match env_or::<f64>(&format!("UPSTREAM_{name}_RATE_LIMIT"), 0.0)? {
+            r if r < 0.0 || !r.is_finite() => bail!("invalid UPSTREAM_{name}_RATE_LIMIT {r}: must be 0 or more"),
+            0.0 => default.rate_limit,
+            r => Some(r),
+        };
+        Ok(ClientSettings {
+            http: env_or(&format!("UPSTREAM_{name}_HTTP"), default.http)?,
+            pool_size: env_or(&format!("UPSTREAM_{name}_POOL_SIZE"), default.pool_size)?,
+            timeout: Duration::from_millis(env_or(&format!("UPSTREAM_{name}_TIMEOUT_MS"), timeout.as_millis() as u64)?),
+            hedge_after: match env_or::<u64>(&format!("UPSTREAM_{name}_HEDGE_MS"), 0)? {
+                0 => default.hedge_after,
+                ms => Some(Duration::from_millis(ms)),
+            },
+            rate_limit,
+            burst: env_or(&format!("UPSTREAM_{name}_BURST"), rate_limit.map_or(default.burst, |r| r.ceil() as u32))?.max(1),
+            max_concurrency: match env_or::<usize>(&format!("UPSTREAM_{name}_MAX_CONCURRENCY"), 0)? {
+                0 => default.max_concurrency,
+                n => Some(n),
+            },
+            max_redirects: env_range(&format!("UPSTREAM_{name}_MAX_REDIRECTS"), default.max_redirects, 10)?,
+            redirect_hosts: match env_list(&format!("UPSTREAM_{name}_REDIRECT_HOSTS")) {
+                hosts if hosts.is_empty() => default.redirect_hosts,
+                hosts => hosts.into_iter().map(|h| h.to_ascii_lowercase()).collect(),
+            },
+        })
+    };
+    Ok(upstream::Settings {
+        discord: source(Source::Discord)?,
+        slack: source(Source::Slack)?,
+        fedi: source(Source::Fedi)?,
+        twitch: source(Source::Twitch)?,
+        h2_fallback: env_flag("UPSTREAM_H2_FALLBACK", true)?,
+        ip_family: env_or("UPSTREAM_IP_FAMILY", upstream::IpFamily::Auto)?,
+    })
+}
+
+/// 순수 Rust 경로 대신 libvips에 맡길 수 있는 작업
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VipsOperation {
+    /// 정적 WebP 리사이즈
+    Static,
+    /// 애니메이션 WebP 리사이즈 (큰 애니메이션도 프레임을 전부 메모리에 올리지 않음)
+    Animated,
+    /// 내장 디코더가 없는 포맷(GIF, PNG, AVIF 등)을 WebP로
+    Foreign,
+}
+
+impl FromStr for VipsOperation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "static" => Ok(VipsOperation::Static),
+            "animated" => Ok(VipsOperation::Animated),
+            "foreign" => Ok(VipsOperation::Foreign),
+            other => bail!("unknown vips operation {other:?} (expected static, animated or foreign)"),
+        }
+    }
+}
+
+/// 포맷별 화질 0~100. 비어 있는 포맷은 해당 인코더의 기존 설정을 따른다.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Quality {
+    /// 정적 WebP를 이 화질의 손실 압축으로 (`cwebp -q`, `libwebp` 기능일 때만)
+    pub webp: Option<u8>,
+    /// 애니메이션 AVIF 화질 (`AVIF_QUALITY` 대신)
+    pub avif: Option<u8>,
+}
+
+impl Quality {
+    /// `webp=80,avif=55` 꼴의 `name` 환경변수
+    fn from_env(name: &str) -> anyhow::Result<Self> {
+        let mut quality = Quality::default();
+        for (format, value) in env_pairs(name, "format=quality, e.g. webp=80,avif=55")? {
+            let value: u8 = match value.parse() {
+                Ok(v) if v <= 100 => v,
+                _ => bail!("invalid {name} quality {value:?} for {format}: must be between 0 and 100"),
+            };
+            match format.to_ascii_lowercase().as_str() {
+                "webp" => quality.webp = Some(value),
+                "avif" => quality.avif = Some(value),
+                other => bail!("unknown format {other:?} in {name} (expected webp or avif; this service never encodes other formats)"),
+            }
+        }
+        Ok(quality)
+    }
+
+    /// 비어 있는 포맷은 `fallback` 것으로
+    fn or(self, fallback: Quality) -> Quality {
+        Quality { webp: self.webp.or(fallback.webp), avif: self.avif.or(fallback.avif) }
+    }
+}
+
+/// 기본 화질과 출력 크기별 덮어쓰기. 작은 크기는 손실이 덜 보이므로 더 낮은 화질로 줄일 수 있다.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct QualityMatrix {
+    /// `QUALITY`
+    pub default: Quality,
+    /// `QUALITY_<크기>`, 비어 있는 포맷은 `default`를 따른다
+    pub sizes: BTreeMap<u32, Quality>,
+}
+
+impl QualityMatrix {
+    fn from_env(output_sizes: &[u32]) -> anyhow::Result<Self> {
+        let mut sizes = BTreeMap::new();
+        for &size in output_sizes {
+            let quality = Quality::from_env(&format!("QUALITY_{size}"))?;
+            if quality != Quality::default() {
+                sizes.insert(size, quality);
+            }
+        }
+        Ok(Self { default: Quality::from_env("QUALITY")?, sizes })
+    }
+
+    /// `size` 출력에 쓸 화질
+    pub fn at(&self, size: u32) -> Quality {
+        self.sizes.get(&size).copied().unwrap_or_default().or(self.default)
+    }
+
+    /// `size` 출력에 쓸 WebP 설정. 정한 화질이 없으면 `webp` 그대로.
+    pub fn webp_options<'a>(&self, size: u32, webp: &'a WebpOptions) -> Cow<'a, WebpOptions> {
+        match self.at(size).webp {
+            Some(quality) if webp.quality != Some(quality) => Cow::Owned(WebpOptions { quality: Some(quality), ..webp.clone() }),
+            _ => Cow::Borrowed(webp),
+        }
+    }
+}
+
+/// 메모리 캐시 축출 정책
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheEviction {
+    /// 가득 찼을 때 밀어낼 항목보다 자주 쓰이지 않는 새 항목은 들이지 않는다 (moka 기본값)
+    TinyLfu,
+    /// 빈도와 상관없이 모두 들이고 가장 오래 안 쓴 것부터 밀어낸다. 최근성이 중요한 트래픽용.
+    Lru,
+}
+
+impl CacheEviction {
+    pub fn policy(self) -> moka::policy::EvictionPolicy {
+        match self {
+            CacheEviction::TinyLfu => moka::policy::EvictionPolicy::tiny_lfu(),
+            CacheEviction::Lru => moka::policy::EvictionPolicy::lru(),
+        }
+    }
+}
+
+impl FromStr for CacheEviction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "tinylfu" | "tiny_lfu" | "tiny-lfu" => Ok(CacheEviction::TinyLfu),
+            "lru" => Ok(CacheEviction::Lru),
+            other => bail!("unknown cache eviction policy {other:?} (expected tinylfu or lru)"),
+        }
+    }
+}
+
+/// 응답 전에 클라이언트가 끊었을 때의 처리
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientDisconnect {
+    /// 업스트림 요청과 인코딩을 멈춘다 (캐시에 남지 않는다)
+    Cancel,
+    /// 끝까지 만들어 캐시에 넣는다
+    Finish,
+}
+
+impl FromStr for ClientDisconnect {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "cancel" => Ok(ClientDisconnect::Cancel),
+            "finish" => Ok(ClientDisconnect::Finish),
+            other => bail!("unknown client disconnect policy {other:?} (expected cancel or finish)"),
+        }
+    }
+}
+
+/// 라우트/소스별 Cache-Control 정책
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CachePolicy {
+    pub max_age: u64,
+    /// 공유 캐시(CDN) 전용 수명. 스노플레이크 ID 이모지는 사실상 불변이라 길게 줘도 된다.
+    pub s_maxage: Option<u64>,
+    pub stale_while_revalidate: Option<u64>,
+    pub immutable: bool,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            max_age: 86400,
+            s_maxage: None,
+            stale_while_revalidate: Some(600),
+            immutable: false,
+        }
+    }
+}
+
+impl CachePolicy {
+    /// `{prefix}_MAX_AGE`, `{prefix}_S_MAXAGE`, `{prefix}_STALE_WHILE_REVALIDATE`,
+    /// `{prefix}_IMMUTABLE` 환경변수로 기본값을 덮어쓴다. 초 단위, 0이면 해당 지시자 생략.
+    pub fn from_env(prefix: &str, default: CachePolicy) -> anyhow::Result<Self> {
+        let optional = |name: &str, default: Option<u64>| {
+            env_or(&format!("{prefix}_{name}"), default.unwrap_or(0)).map(|v| (v > 0).then_some(v))
+        };
+        Ok(Self {
+            max_age: env_or(&format!("{prefix}_MAX_AGE"), default.max_age)?,
+            s_maxage: optional("S_MAXAGE", default.s_maxage)?,
+            stale_while_revalidate: optional("STALE_WHILE_REVALIDATE", default.stale_while_revalidate)?,
+            immutable: env_flag(&format!("{prefix}_IMMUTABLE"), default.immutable)?,
+        })
+    }
+
+    /// `Cache-Control` 헤더 값
+    pub fn header_value(&self) -> String {
+        let mut v = format!("public, max-age={}", self.max_age);
+        if let Some(s) = self.s_maxage {
+            v.push_str(&format!(", s-maxage={s}"));
+        }
+        if let Some(s) = self.stale_while_revalidate {
+            v.push_str(&format!(", stale-while-revalidate={s}"));
+        }
+        if self.immutable {
+            v.push_str(", immutable");
+        }
+        v
+    }
+}
+
+/// `SIBLING_WARM_SIZES`: 함께 만들어 둘 크기. `OUTPUT_SIZES` 밖의 크기는 요청될 일이 없으므로 거부한다
+fn sibling_warm_sizes(output_sizes: &[u32]) -> anyhow::Result<Vec<u32>> {
+    let mut sizes = env_list("SIBLING_WARM_SIZES")
+        .iter()
+        .map(|v| v.parse::<u32>().map_err(|e| anyhow!("invalid SIBLING_WARM_SIZES entry {v:?}: {e}")))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if let Some(bad) = sizes.iter().find(|s| !output_sizes.contains(s)) {
+        bail!("invalid SIBLING_WARM_SIZES entry {bad}: not in OUTPUT_SIZES {output_sizes:?}");
+    }
+    sizes.sort_unstable();
+    sizes.dedup();
+    Ok(sizes)
+}
+
+/// `OUTPUT_SIZES`: 쉼표로 구분한 픽셀 크기. 정렬/중복 제거해서 돌려준다. 비어 있으면 `DEFAULT_SIZE` 하나만
+fn output_sizes() -> anyhow::Result<Vec<u32>> {
+    let mut sizes = env_list("OUTPUT_SIZES")
+        .iter()
+        .map(|v| v.parse::<u32>().map_err(|e| anyhow!("invalid OUTPUT_SIZES entry {v:?}: {e}")))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if sizes.is_empty() {
+        sizes.push(env_or("DEFAULT_SIZE", TARGET_SIZE)?);
+    }
+    if let Some(bad) = sizes.iter().find(|s| !(16..=1024).contains(*s)) {
+        bail!("invalid OUTPUT_SIZES entry {bad}: must be between 16 and 1024");
+    }
+    sizes.sort_unstable();
+    sizes.dedup();
+    Ok(sizes)
+}
+
+/// `DEFAULT_SIZE`: `OUTPUT_SIZES` 밖의 크기는 응답할 수 없으므로 거부한다
+fn default_size(output_sizes: &[u32]) -> anyhow::Result<u32> {
+    let size = env_or("DEFAULT_SIZE", snap(output_sizes, TARGET_SIZE).unwrap_or(TARGET_SIZE))?;
+    if !output_sizes.contains(&size) {
+        bail!("invalid DEFAULT_SIZE {size}: must be one of OUTPUT_SIZES {output_sizes:?}");
+    }
+    Ok(size)
+}
+
+/// `sizes` 중 `requested`와 가장 가까운 크기 (같은 거리면 큰 쪽)
+fn snap(sizes: &[u32], requested: u32) -> Option<u32> {
+    sizes.iter().copied().min_by_key(|&s| (s.abs_diff(requested), std::cmp::Reverse(s)))
+}
+
+impl Config {
+    /// 요청 크기와 가장 가까운 허용 크기 (같은 거리면 큰 쪽)
+    pub fn snap_size(&self, requested: u32) -> u32 {
+        snap(&self.output_sizes, requested).unwrap_or(self.default_size)
+    }
+}
+
+/// `CONFIG_FILE`(설정 파일 경로)이 있으면 그 값을 아직 설정되지 않은 환경변수로 옮기고 경로를 돌려준다.
+/// 같은 이름의 환경변수가 이미 있으면 그쪽이 이긴다. 환경변수를 바꾸므로 Tokio 런타임이나 다른 스레드를
+/// 띄우기 전, `main`의 맨 처음에 부를 것 (그래서 `TOKIO_WORKER_THREADS`도 파일로 줄 수 있다).
+pub fn apply_file() -> anyhow::Result<Option<PathBuf>> {
+    let Some(path) = env::var_os("CONFIG_FILE").filter(|p| !p.is_empty()).map(PathBuf::from) else {
+        return Ok(None);
+    };
+    let text = std::fs::read_to_string(&path).with_context(|| format!("failed to read CONFIG_FILE {}", path.display()))?;
+    for (name, value) in parse_file(&text).with_context(|| format!("invalid CONFIG_FILE {}", path.display()))? {
+        if env::var_os(&name).is_none() {
+            env::set_var(name, value);
+        }
+    }
+    Ok(Some(path))
+}
+
+/// 설정 파일 (`CONFIG_FILE`). 최상위 키만 쓴 TOML이고 키는 환경변수 이름이다 (대소문자 무관).
+#[derive(Debug, Deserialize)]
+#[serde(transparent)]
+struct ConfigFile {
+    values: BTreeMap<String, FileValue>,
+}
+
+/// 설정 파일의 값 하나. 배열은 쉼표 목록 환경변수가 된다.
+#[derive(Debug, Deserialize)]
+#[serde(untagged, expecting = "a string, number, boolean or array of them (tables are not supported)")]
+enum FileValue {
+    Scalar(FileScalar),
+    List(Vec<FileScalar>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged, expecting = "a string, number or boolean")]
+enum FileScalar {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl std::fmt::Display for FileScalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::String(v) => f.write_str(v),
+            Self::Integer(v) => write!(f, "{v}"),
+            Self::Float(v) => write!(f, "{v}"),
+            Self::Bool(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+impl ConfigFile {
+    /// (환경변수 이름, 값) 목록. 이름은 대문자로 맞춘다 (`cache_capacity` → `CACHE_CAPACITY`).
+    fn vars(&self) -> Vec<(String, String)> {
+        self.values
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    FileValue::Scalar(v) => v.to_string(),
+                    FileValue::List(items) => items.iter().map(ToString::to_string).collect::<Vec<_>>().join(","),
+                };
+                (name.to_ascii_uppercase(), value)
+            })
+            .collect()
+    }
+}
+
+/// 설정 파일을 (환경변수 이름, 값) 목록으로 읽는다 (이름순). 이름이 대소문자만 다르게 두 번 나오면 에러.
+pub fn parse_file(text: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let file: ConfigFile = toml::from_str(text)?;
+    let mut vars = file.vars();
+    vars.sort_by(|a, b| a.0.cmp(&b.0));
+    if let Some(pair) = vars.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        bail!("duplicate key {}", pair[0].0);
+    }
+    Ok(vars)
+}
+
+/// 환경변수를 파싱하고, 없으면 기본값을 쓴다. 값이 있는데 파싱에 실패하면 에러.
+pub(crate) fn env_or<T>(name: &str, default: T) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env::var(name) {
+        Ok(v) if !v.trim().is_empty() => v.trim().parse().map_err(|e| anyhow!("invalid {name}: {v:?}: {e}")),
+        _ => Ok(default),
+    }
+}
+
+/// `0..=max` 범위의 정수 환경변수
+fn env_range(name: &str, default: u8, max: u8) -> anyhow::Result<u8> {
+    let value = env_or(name, default)?;
+    if value > max {
+        bail!("invalid {name} {value}: must be between 0 and {max}");
+    }
+    Ok(value)
+}
+
+/// `1/0`, `true/false`, `yes/no`, `on/off` 를 받는 불리언 환경변수
+fn env_flag(name: &str, default: bool) -> anyhow::Result<bool> {
+    match env::var(name) {
+        Ok(v) => match v.trim().to_ascii_lowercase().as_str() {
+            "" => Ok(default),
+            "1" | "true" | "yes" | "on" => Ok(true),
+            "0" | "false" | "no" | "off" => Ok(false),
+            _ => bail!("invalid {name}: {v:?} (expected true/false)"),
+        },
+        Err(_) => Ok(default),
+    }
+}
+
+/// API 주소 환경변수 (끝의 `/`는 뗀다)
+fn env_base(name: &str, default: &str) -> String {
+    env::var(name)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map_or_else(|| default.into(), |v| v.trim().trim_end_matches('/').into())
+}
+
+/// `이름=토큰` 쉼표 구분 목록 환경변수
+fn env_pairs(name: &str, expected: &str) -> anyhow::Result<HashMap<String, String>> {
+    env_list(name)
+        .into_iter()
+        .map(|pair| match pair.split_once('=') {
+            Some((key, token)) if !key.trim().is_empty() && !token.trim().is_empty() => {
+                Ok((key.trim().to_string(), token.trim().to_string()))
+            }
+            _ => Err(anyhow!("invalid {name} entry (expected {expected})")),
+        })
+        .collect()
+}
+
+/// 쉼표로 구분된 목록 환경변수 (빈 항목은 무시)
+fn env_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .map(|v| {
+            v.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 기간은 초 단위로 내보낸다 (`CACHE_LEASE_MS`처럼 밀리초로 받은 값은 소수)
+pub(crate) fn secs<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(d.as_secs_f64())
+}
+
+pub(crate) fn optional_secs<S: Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_some(&d.map(|d| d.as_secs_f64()))
+}
+
+/// 설정 요약에서 비밀 값을 가린다. 설정했는지는 알 수 있게 값만 바꾼다.
+pub(crate) mod redact {
+    use super::*;
+
+    const REDACTED: &str = "<redacted>";
+
+    pub fn secret<S: Serializer>(_: &str, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(REDACTED)
+    }
+
+    pub fn optional<S: Serializer>(v: &Option<String>, s: S) -> Result<S::Ok, S::Error> {
+        match v {
+            Some(_) => s.serialize_some(REDACTED),
+            None => s.serialize_none(),
+        }
+    }
+
+    /// 이름은 남기고 토큰만 가린다
+    pub fn values<S: Serializer>(v: &HashMap<String, String>, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_map(v.keys().collect::<BTreeSet<_>>().into_iter().map(|k| (k, REDACTED)))
+    }
+
+    pub fn list<S: Serializer>(v: &[String], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(v.iter().map(|_| REDACTED))
+    }
+}
@@ -0,0 +1,1631 @@
+//! 직접 올린 커스텀 이모트 (`/custom/:name`, `/custom/:guild_id/:name`, `/c/:hash`).
+//!
+//! `PUT`으로 올린 WebP·PNG·GIF·JPEG(정적, 또는 애니메이션 WebP·GIF·APNG)를 검증하고 `CUSTOM_MAX_DIMENSION` 박스 안으로
+//! 줄여 WebP로 다시 인코딩한 뒤 `CUSTOM_DIR`에 저장한다. 저장본은 내용 해시로 주소를 정하므로
+//! (`blobs/<sha1>.webp`) 같은 이미지를 여러 이름으로 올려도 한 벌만 남는다. 색인은 SQLite(`index.db`)로
+//! 이름 → 메타데이터(소유자, 태그, 올린 시각, 크기)와 버전 기록을 담고 목록·이름 변경은 쿼리로 한다.
+//!
+//! `GET`은 저장본을 원본 삼아 다른 소스와 같은 리사이즈/캐시 경로로 제공한다. `/c/:hash`는
+//! 내용이 바뀌지 않는 주소로, 그 해시를 가리키는 이름이 하나라도 있는 동안 유효하다.
+//! 어느 이름도 가리키지 않는 블롭은 주기적인 정리([`Store::gc`])에서 지운다.
+//!
+//! 덮어쓴 내용은 예전 버전으로 남아(`CUSTOM_MAX_VERSIONS`개까지) `/custom/:name@v2`로 볼 수 있고,
+//! 삭제는 표시만 해 두었다가 `CUSTOM_DELETED_RETENTION_SECS`가 지나면 정리한다. 관리 API로
+//! 삭제를 되돌리거나 예전 버전을 새 버전으로 복원할 수 있어 실수로 덮어써도 잃지 않는다.
+//! 업로드와 삭제에는 `CUSTOM_UPLOAD_TOKEN`(없으면 `ADMIN_TOKEN`)이 필요하다.
+//!
+//! `/custom/:guild_id/:name`은 길드(커뮤니티)별 네임스페이스다. 색인 키는 `길드/이름`이고,
+//! `CUSTOM_GUILD_TOKENS`의 길드 키는 그 길드 안에서만 쓸 수 있으며, 이모트 수와 용량은
+//! `CUSTOM_GUILD_MAX_EMOTES`/`CUSTOM_GUILD_MAX_BYTES`로 제한한다.
+//!
+//! 검사([`crate::moderation`])에 걸린 업로드는 색인 대신 검토 대기열(`review` 테이블)에 들어가고,
+//! 관리 API에서 승인해야 공개된다.
+//!
+//! 비공개(`?private=1`)로 올린 이모트는 이름 주소가 서명([`crate::signing`])과 함께 오거나 API 키로 요청할 때만 열린다.
+//! 업로드 토큰으로 `POST /custom/:name/sign`을 불러 짧게 유효한 주소를 받아 메시지에 붙이면 되고,
+//! 해시 주소(`/c/`)와 검색·공개 목록에는 나오지 않는다.
+
+use anyhow::Context;
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Extension, Json,
+};
+use image::{ImageFormat, ImageReader};
+use rayon::ThreadPool;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow},
+    Row, Sqlite, Transaction,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::Cursor,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    admin, blocklist, canonical_url,
+    effects::Effects,
+    imaging::{self, ImagingError},
+    metrics, middleware,
+    moderation::Verdict,
+    path_extension, pick_variant, purge, redirect_static_gif, serve_image, signing, AppState, EmojiParams, Fetch, Mode,
+    Origin, PathExtension, RequestInputs,
+};
+
+/// 업로드로 받는 포맷. 어느 것이든 WebP로 다시 인코딩해 저장한다.
+const UPLOAD_FORMATS: [ImageFormat; 4] = [ImageFormat::WebP, ImageFormat::Png, ImageFormat::Gif, ImageFormat::Jpeg];
+
+/// 서명한 주소의 최대 유효 기간 (`?ttl=`로 더 길게 달라고 해도 여기까지)
+const MAX_SIGNED_TTL: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// 저장된 이모트 하나의 메타데이터
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    /// 정규화한 WebP의 SHA-1
+    pub hash: String,
+    pub bytes: u64,
+    pub width: u32,
+    pub height: u32,
+    pub animated: bool,
+    /// 유닉스 시각(초)
+    pub uploaded_at: u64,
+    /// 올린 사람 (업로드 때 `?owner=`로 지정, 대시보드 표시용)
+    pub owner: Option<String>,
+    /// 소문자 태그, 정렬/중복 제거됨
+    pub tags: Vec<String>,
+    /// 현재 버전 번호 (첫 업로드가 1)
+    pub version: u32,
+    /// 예전 버전, 오래된 것부터
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<Version>,
+    /// 지운 시각. 있으면 목록/조회에서 빠지고 복원이나 정리를 기다린다
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<u64>,
+    /// 서명한 주소로만 제공
+    pub private: bool,
+}
+
+/// 덮어쓰기 전 내용 하나
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Version {
+    pub version: u32,
+    pub hash: String,
+    pub bytes: u64,
+    pub width: u32,
+    pub height: u32,
+    pub animated: bool,
+    pub uploaded_at: u64,
+}
+
+impl Entry {
+    fn snapshot(&self) -> Version {
+        Version {
+            version: self.version,
+            hash: self.hash.clone(),
+            bytes: self.bytes,
+            width: self.width,
+            height: self.height,
+            animated: self.animated,
+            uploaded_at: self.uploaded_at,
+        }
+    }
+
+    fn latest_version(&self) -> u32 {
+        self.history.iter().map(|v| v.version).fold(self.version, u32::max)
+    }
+
+    /// 현재 내용을 기록에 넣고 `content`를 다음 번호의 새 버전으로 삼는다
+    fn push_version(&mut self, content: Version, max_versions: usize) {
+        let next = self.latest_version() + 1;
+        self.history.push(self.snapshot());
+        if self.history.len() > max_versions {
+            self.history.drain(..self.history.len() - max_versions);
+        }
+        self.version = next;
+        self.hash = content.hash;
+        self.bytes = content.bytes;
+        self.width = content.width;
+        self.height = content.height;
+        self.animated = content.animated;
+        self.uploaded_at = content.uploaded_at;
+    }
+
+    fn find_version(&self, version: u32) -> Option<Version> {
+        if version == self.version {
+            return Some(self.snapshot());
+        }
+        self.history.iter().find(|v| v.version == version).cloned()
+    }
+
+    fn hashes(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.hash.as_str()).chain(self.history.iter().map(|v| v.hash.as_str()))
+    }
+}
+
+/// `PATCH /custom/:name` 본문. 빠진 필드는 그대로 둔다.
+#[derive(Debug, Default, Deserialize)]
+pub struct Update {
+    pub name: Option<String>,
+    pub owner: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub private: Option<bool>,
+}
+
+pub enum Updated {
+    Done(String, Entry),
+    NotFound,
+    NameTaken,
+}
+
+/// 검토를 기다리는 업로드
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pending {
+    /// 승인하면 그대로 저장할 항목
+    pub entry: Entry,
+    /// 검사 웹훅 점수 (수동 검토면 없음)
+    pub score: Option<f64>,
+    pub reason: String,
+    pub submitted_at: u64,
+}
+
+/// 길드 네임스페이스의 한도 (0이면 제한 없음)
+#[derive(Clone, Copy, Debug)]
+pub struct Quota {
+    pub max_emotes: usize,
+    pub max_bytes: u64,
+}
+
+pub enum QuotaExceeded {
+    Count,
+    Bytes,
+}
+
+pub enum Restored {
+    Done(Entry),
+    NotFound,
+    NoSuchVersion,
+}
+
+/// 업로드 결과
+pub struct Stored {
+    /// 같은 이름으로 있던 항목 (교체한 경우)
+    pub previous: Option<Entry>,
+    /// 저장한 뒤의 항목 (버전 번호 포함)
+    pub current: Entry,
+    /// 같은 내용의 블롭이 이미 있어서 새로 쓰지 않았는지
+    pub deduplicated: bool,
+}
+
+/// 블롭 정리 결과
+#[derive(Debug, Default, Serialize)]
+pub struct GcReport {
+    /// 보관 기간이 지나 완전히 지운 이모트
+    pub expired: u64,
+    pub scanned: u64,
+    pub removed: u64,
+    pub reclaimed_bytes: u64,
+}
+
+/// 색인 스키마. 단계를 덧붙이기만 하며, 적용한 단계 수는 `PRAGMA user_version`에 남긴다.
+const MIGRATIONS: &[&str] = &[
+    // 1: 이름 → 저장본 메타데이터
+    "CREATE TABLE emotes (
+        key TEXT PRIMARY KEY,
+        hash TEXT NOT NULL,
+        bytes INTEGER NOT NULL,
+        width INTEGER NOT NULL,
+        height INTEGER NOT NULL,
+        animated INTEGER NOT NULL,
+        uploaded_at INTEGER NOT NULL
+    );",
+    // 2: 블롭을 내용 해시로 찾는다
+    "CREATE INDEX emotes_hash ON emotes (hash);",
+    // 3: 소유자와 태그. 이름을 바꾸면 태그가 따라가도록 `ON UPDATE CASCADE`
+    "ALTER TABLE emotes ADD COLUMN owner TEXT;
+    CREATE INDEX emotes_owner ON emotes (owner);
+    CREATE TABLE emote_tags (
+        key TEXT NOT NULL REFERENCES emotes (key) ON DELETE CASCADE ON UPDATE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (key, tag)
+    );
+    CREATE INDEX emote_tags_tag ON emote_tags (tag);",
+    // 4: 버전 기록과 삭제 표시
+    "ALTER TABLE emotes ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+    ALTER TABLE emotes ADD COLUMN deleted_at INTEGER;
+    CREATE TABLE versions (
+        key TEXT NOT NULL REFERENCES emotes (key) ON DELETE CASCADE ON UPDATE CASCADE,
+        version INTEGER NOT NULL,
+        hash TEXT NOT NULL,
+        bytes INTEGER NOT NULL,
+        width INTEGER NOT NULL,
+        height INTEGER NOT NULL,
+        animated INTEGER NOT NULL,
+        uploaded_at INTEGER NOT NULL,
+        PRIMARY KEY (key, version)
+    );
+    CREATE INDEX versions_hash ON versions (hash);",
+    // 5: 길드 네임스페이스 (키 `길드/이름`의 길드 부분, 사용량 집계용)
+    "ALTER TABLE emotes ADD COLUMN guild TEXT;
+    CREATE INDEX emotes_guild ON emotes (guild);",
+    // 6: 검토 대기열. 승인 전이라 버전 기록은 없고, 태그는 쉼표로 이어 한 칸에 둔다
+    "CREATE TABLE review (
+        key TEXT PRIMARY KEY,
+        hash TEXT NOT NULL,
+        bytes INTEGER NOT NULL,
+        width INTEGER NOT NULL,
+        height INTEGER NOT NULL,
+        animated INTEGER NOT NULL,
+        uploaded_at INTEGER NOT NULL,
+        owner TEXT,
+        tags TEXT NOT NULL,
+        score REAL,
+        reason TEXT NOT NULL,
+        submitted_at INTEGER NOT NULL
+    );",
+    // 7: 서명한 주소로만 여는 비공개 이모트
+    "ALTER TABLE emotes ADD COLUMN private INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE review ADD COLUMN private INTEGER NOT NULL DEFAULT 0;",
+];
+
+const EMOTE_COLUMNS: &str = "key, hash, bytes, width, height, animated, uploaded_at, owner, private, version, deleted_at";
+
+pub struct Store {
+    dir: PathBuf,
+    /// `index.db` (이름 → 메타데이터, 태그, 버전 기록)
+    db: SqlitePool,
+    /// 블롭 기록, 색인 기록, 정리가 서로 끼어들지 않도록 한 번에 하나씩
+    writes: tokio::sync::Mutex<()>,
+    max_versions: usize,
+    /// 지운 이모트를 남겨 두는 기간 (0이면 바로 지운다)
+    deleted_retention: Duration,
+}
+
+impl Store {
+    /// 디렉터리를 만들고 색인을 연다 (없으면 만들고, 예전 스키마면 올린다).
+    /// 이름으로 저장하던 예전 배치(`emotes/<이름>.webp`)는 해시 주소로 옮긴다.
+    pub async fn open(dir: PathBuf, max_versions: usize, deleted_retention: Duration) -> anyhow::Result<Self> {
+        tokio::fs::create_dir_all(dir.join("blobs")).await.with_context(|| format!("creating {}", dir.display()))?;
+        let path = dir.join("index.db");
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .foreign_keys(true);
+        let db = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect_with(options)
+            .await
+            .with_context(|| format!("opening {}", path.display()))?;
+        migrate(&db).await.with_context(|| format!("migrating {}", path.display()))?;
+        let store = Self { dir, db, writes: tokio::sync::Mutex::new(()), max_versions, deleted_retention };
+        store.migrate_named_blobs().await?;
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM emotes").fetch_one(&store.db).await?;
+        info!("custom emote store opened at {}: {} emotes", store.dir.display(), count);
+        Ok(store)
+    }
+
+    async fn migrate_named_blobs(&self) -> anyhow::Result<()> {
+        let legacy = self.dir.join("emotes");
+        if !tokio::fs::try_exists(&legacy).await.unwrap_or(false) {
+            return Ok(());
+        }
+        let rows: Vec<(String, String)> = sqlx::query_as("SELECT key, hash FROM emotes").fetch_all(&self.db).await?;
+        for (name, hash) in rows {
+            let (from, to) = (legacy.join(format!("{name}.webp")), self.blob_path(&hash));
+            if tokio::fs::try_exists(&from).await.unwrap_or(false) && !tokio::fs::try_exists(&to).await.unwrap_or(false) {
+                tokio::fs::rename(&from, &to).await.with_context(|| format!("moving {}", from.display()))?;
+            }
+        }
+        // 옮기지 않은 파일(같은 내용의 사본 등)은 색인에 없으므로 지운다
+        tokio::fs::remove_dir_all(&legacy).await.with_context(|| format!("removing {}", legacy.display()))?;
+        info!("migrated custom emotes to content-addressed blobs");
+        Ok(())
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.dir.join("blobs").join(format!("{hash}.webp"))
+    }
+
+    /// `filter`(`e`가 `emotes` 행)에 맞는 항목을 이름순으로, 태그와 버전 기록까지 채워서
+    async fn load(&self, filter: &str, binds: &[Option<&str>]) -> anyhow::Result<Vec<(String, Entry)>> {
+        let sql = format!("SELECT {EMOTE_COLUMNS} FROM emotes e WHERE {filter} ORDER BY key");
+        let mut query = sqlx::query(&sql);
+        for bind in binds {
+            query = query.bind(*bind);
+        }
+        let mut entries: BTreeMap<String, Entry> = BTreeMap::new();
+        for row in query.fetch_all(&self.db).await? {
+            entries.insert(row.try_get("key")?, entry_from_row(&row)?);
+        }
+        if entries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let sql = format!("SELECT key, tag FROM emote_tags WHERE key IN (SELECT key FROM emotes e WHERE {filter}) ORDER BY tag");
+        let mut query = sqlx::query_as::<_, (String, String)>(&sql);
+        for bind in binds {
+            query = query.bind(*bind);
+        }
+        for (key, tag) in query.fetch_all(&self.db).await? {
+            if let Some(entry) = entries.get_mut(&key) {
+                entry.tags.push(tag);
+            }
+        }
+        let sql = format!(
+            "SELECT key, version, hash, bytes, width, height, animated, uploaded_at FROM versions \
+             WHERE key IN (SELECT key FROM emotes e WHERE {filter}) ORDER BY version"
+        );
+        let mut query = sqlx::query(&sql);
+        for bind in binds {
+            query = query.bind(*bind);
+        }
+        for row in query.fetch_all(&self.db).await? {
+            let key: String = row.try_get("key")?;
+            if let Some(entry) = entries.get_mut(&key) {
+                entry.history.push(version_from_row(&row)?);
+            }
+        }
+        Ok(entries.into_iter().collect())
+    }
+
+    /// 지운 것까지 포함한 항목 하나
+    async fn find(&self, name: &str) -> anyhow::Result<Option<Entry>> {
+        Ok(self.load("e.key = ?", &[Some(name)]).await?.into_iter().next().map(|(_, e)| e))
+    }
+
+    /// 지우지 않은 항목
+    pub async fn get(&self, name: &str) -> anyhow::Result<Option<Entry>> {
+        Ok(self.load("e.key = ? AND e.deleted_at IS NULL", &[Some(name)]).await?.into_iter().next().map(|(_, e)| e))
+    }
+
+    /// 이름순 목록. `tag`/`owner`가 있으면 그에 맞는 것만.
+    pub async fn list(&self, tag: Option<&str>, owner: Option<&str>) -> anyhow::Result<Vec<(String, Entry)>> {
+        self.load(
+            "e.deleted_at IS NULL \
+             AND (?1 IS NULL OR EXISTS (SELECT 1 FROM emote_tags t WHERE t.key = e.key AND t.tag = ?1)) \
+             AND (?2 IS NULL OR e.owner = ?2)",
+            &[tag, owner],
+        )
+        .await
+    }
+
+    /// 지운 뒤 아직 정리되지 않은 항목 (복원 후보)
+    pub async fn deleted(&self) -> anyhow::Result<Vec<(String, Entry)>> {
+        self.load("e.deleted_at IS NOT NULL", &[]).await
+    }
+
+    /// 이름 바꾸기/태그·소유자 수정. 값 검증은 호출하는 쪽에서 한다.
+    /// 지운 이모트가 차지한 이름으로는 바꿀 수 없다 (복원할 수 있어야 하므로).
+    pub async fn update(&self, name: &str, update: Update) -> anyhow::Result<Updated> {
+        let _write = self.writes.lock().await;
+        let mut tx = self.db.begin().await?;
+        let live: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM emotes WHERE key = ? AND deleted_at IS NULL")
+            .bind(name)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if live.is_none() {
+            return Ok(Updated::NotFound);
+        }
+        let target = update.name.unwrap_or_else(|| name.to_string());
+        if target != name {
+            let taken: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM emotes WHERE key = ?").bind(&target).fetch_optional(&mut *tx).await?;
+            if taken.is_some() {
+                return Ok(Updated::NameTaken);
+            }
+            sqlx::query("UPDATE emotes SET key = ?, guild = ? WHERE key = ?")
+                .bind(&target)
+                .bind(split_key(&target).0)
+                .bind(name)
+                .execute(&mut *tx)
+                .await?;
+        }
+        if let Some(owner) = update.owner {
+            sqlx::query("UPDATE emotes SET owner = ? WHERE key = ?")
+                .bind(Some(owner).filter(|o| !o.is_empty()))
+                .bind(&target)
+                .execute(&mut *tx)
+                .await?;
+        }
+        if let Some(tags) = update.tags {
+            save_tags(&mut tx, &target, &tags).await?;
+        }
+        if let Some(private) = update.private {
+            sqlx::query("UPDATE emotes SET private = ? WHERE key = ?").bind(private).bind(&target).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        match self.get(&target).await? {
+            Some(entry) => Ok(Updated::Done(target, entry)),
+            None => Ok(Updated::NotFound),
+        }
+    }
+
+    /// 지우지 않은 이름이 (예전 버전까지 포함해) 이 해시를 가리키는지
+    pub async fn is_referenced(&self, hash: &str) -> anyhow::Result<bool> {
+        self.points_to(hash, "").await
+    }
+
+    /// 지우지 않은 공개 이름이 이 해시를 가리키는지 (`/c/` 주소로 열어도 되는지)
+    pub async fn is_public(&self, hash: &str) -> anyhow::Result<bool> {
+        self.points_to(hash, "AND e.private = 0").await
+    }
+
+    async fn points_to(&self, hash: &str, filter: &str) -> anyhow::Result<bool> {
+        let sql = format!(
+            "SELECT EXISTS (SELECT 1 FROM emotes e WHERE e.deleted_at IS NULL {filter} AND (e.hash = ?1 \
+             OR EXISTS (SELECT 1 FROM versions v WHERE v.key = e.key AND v.hash = ?1)))"
+        );
+        let (found,): (bool,) = sqlx::query_as(&sql).bind(hash).fetch_one(&self.db).await?;
+        Ok(found)
+    }
+
+    /// 저장본 바이트. 어느 이름도 가리키지 않는 해시면 `None`.
+    pub async fn read(&self, hash: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        if !self.is_referenced(hash).await? {
+            return Ok(None);
+        }
+        let path = self.blob_path(hash);
+        tokio::fs::read(&path)
+            .await
+            .map(Some)
+            .with_context(|| format!("reading {}", path.display()))
+    }
+
+    /// 저장한다. 같은 내용의 블롭이 이미 있으면 다시 쓰지 않는다. 같은 이름이 있으면(지운 것
+    /// 포함) 그 기록을 이어받아 새 버전이 된다. `entry`의 버전 필드는 여기서 정한다.
+    /// `quota`가 있으면 같은 길드의 이모트 수/용량을 넘는 업로드는 저장하지 않는다.
+    pub async fn put(
+        &self,
+        name: &str,
+        bytes: &[u8],
+        entry: Entry,
+        quota: Option<Quota>,
+    ) -> anyhow::Result<Result<Stored, QuotaExceeded>> {
+        let _write = self.writes.lock().await;
+        if let Some(quota) = quota {
+            // 쓰기 잠금 안이므로 확인과 저장 사이에 다른 업로드가 끼어들지 않는다
+            let (guild, _) = split_key(name);
+            let (count, used) = self.usage(guild, Some(name)).await?;
+            if quota.max_emotes > 0 && count >= quota.max_emotes {
+                return Ok(Err(QuotaExceeded::Count));
+            }
+            if quota.max_bytes > 0 && used + entry.bytes > quota.max_bytes {
+                return Ok(Err(QuotaExceeded::Bytes));
+            }
+        }
+        let deduplicated = self.write_blob(&entry.hash, bytes).await?;
+        let (previous, current) = self.insert(name, entry).await?;
+        Ok(Ok(Stored { previous, current, deduplicated }))
+    }
+
+    /// 블롭을 쓴다. 이미 있으면 그대로 두고 `true`. 쓰기 잠금을 잡은 채로 부를 것.
+    async fn write_blob(&self, hash: &str, bytes: &[u8]) -> anyhow::Result<bool> {
+        let path = self.blob_path(hash);
+        if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            return Ok(true);
+        }
+        let tmp = path.with_extension("webp.tmp");
+        tokio::fs::write(&tmp, bytes).await.with_context(|| format!("writing {}", tmp.display()))?;
+        tokio::fs::rename(&tmp, &path).await.with_context(|| format!("replacing {}", path.display()))?;
+        Ok(false)
+    }
+
+    /// 색인에 넣고 (바뀌기 전의 살아 있던 항목, 새 항목)을 돌려준다. 쓰기 잠금을 잡은 채로 부를 것.
+    async fn insert(&self, name: &str, entry: Entry) -> anyhow::Result<(Option<Entry>, Entry)> {
+        let existing = self.find(name).await?;
+        let stored = match existing.clone() {
+            Some(mut merged) => {
+                // 같은 내용을 다시 올리면 버전을 늘리지 않는다
+                if merged.hash != entry.hash || merged.deleted_at.is_some() {
+                    merged.push_version(entry.snapshot(), self.max_versions);
+                }
+                merged.owner = entry.owner;
+                merged.tags = entry.tags;
+                merged.private = entry.private;
+                merged.deleted_at = None;
+                merged
+            }
+            None => Entry { version: 1, history: Vec::new(), deleted_at: None, ..entry },
+        };
+        let mut tx = self.db.begin().await?;
+        save(&mut tx, name, &stored).await?;
+        tx.commit().await?;
+        Ok((existing.filter(|e| e.deleted_at.is_none()), stored))
+    }
+
+    /// 검토 대기열에 넣는다. 블롭은 쓰지만 승인 전까지 색인에는 없다.
+    /// 같은 이름의 대기 항목이 있으면 새 업로드로 바꾼다.
+    pub async fn quarantine(&self, name: &str, bytes: &[u8], pending: Pending) -> anyhow::Result<()> {
+        let _write = self.writes.lock().await;
+        self.write_blob(&pending.entry.hash, bytes).await?;
+        let mut tx = self.db.begin().await?;
+        save_pending(&mut tx, name, &pending).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// 제출 순서대로 검토 대기 항목
+    pub async fn review_queue(&self) -> anyhow::Result<Vec<(String, Pending)>> {
+        let rows = sqlx::query("SELECT * FROM review ORDER BY submitted_at, key").fetch_all(&self.db).await?;
+        rows.iter().map(|row| Ok((row.try_get("key")?, pending_from_row(row)?))).collect()
+    }
+
+    async fn pending(&self, name: &str) -> anyhow::Result<Option<Pending>> {
+        let row = sqlx::query("SELECT * FROM review WHERE key = ?").bind(name).fetch_optional(&self.db).await?;
+        row.as_ref().map(pending_from_row).transpose()
+    }
+
+    /// 검토 대기 중인 업로드의 저장본
+    pub async fn read_pending(&self, name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some(pending) = self.pending(name).await? else {
+            return Ok(None);
+        };
+        let path = self.blob_path(&pending.entry.hash);
+        tokio::fs::read(&path)
+            .await
+            .map(Some)
+            .with_context(|| format!("reading {}", path.display()))
+    }
+
+    /// 승인: 대기열에서 빼서 일반 업로드처럼 색인에 넣는다 (관리자 판단이므로 길드 한도는 보지 않는다)
+    pub async fn approve(&self, name: &str) -> anyhow::Result<Option<Stored>> {
+        let _write = self.writes.lock().await;
+        let Some(pending) = self.take_pending(name).await? else {
+            return Ok(None);
+        };
+        let (previous, current) = self.insert(name, pending.entry).await?;
+        Ok(Some(Stored { previous, current, deduplicated: true }))
+    }
+
+    /// 거절: 대기열에서만 뺀다. 블롭은 [`Store::gc`]가 정리한다.
+    pub async fn reject(&self, name: &str) -> anyhow::Result<Option<Pending>> {
+        let _write = self.writes.lock().await;
+        self.take_pending(name).await
+    }
+
+    async fn take_pending(&self, name: &str) -> anyhow::Result<Option<Pending>> {
+        let taken = self.pending(name).await?;
+        if taken.is_some() {
+            sqlx::query("DELETE FROM review WHERE key = ?").bind(name).execute(&self.db).await?;
+        }
+        Ok(taken)
+    }
+
+    /// 네임스페이스의 지우지 않은 이모트 수와 현재 버전 용량 합 (`except`는 빼고 센다)
+    pub async fn usage(&self, guild: Option<&str>, except: Option<&str>) -> anyhow::Result<(usize, u64)> {
+        let (count, bytes): (i64, i64) = sqlx::query_as(
+            "SELECT COUNT(*), COALESCE(SUM(bytes), 0) FROM emotes \
+             WHERE deleted_at IS NULL AND guild IS ?1 AND (?2 IS NULL OR key != ?2)",
+        )
+        .bind(guild)
+        .bind(except)
+        .fetch_one(&self.db)
+        .await?;
+        Ok((count as usize, bytes as u64))
+    }
+
+    /// 지운 것으로 표시한다 (보관 기간이 0이면 바로 지운다). 블롭은 [`Store::gc`]가 정리한다.
+    pub async fn remove(&self, name: &str) -> anyhow::Result<Option<Entry>> {
+        let _write = self.writes.lock().await;
+        let Some(removed) = self.get(name).await? else {
+            return Ok(None);
+        };
+        if self.deleted_retention.is_zero() {
+            sqlx::query("DELETE FROM emotes WHERE key = ?").bind(name).execute(&self.db).await?;
+        } else {
+            sqlx::query("UPDATE emotes SET deleted_at = ? WHERE key = ?")
+                .bind(now_secs() as i64)
+                .bind(name)
+                .execute(&self.db)
+                .await?;
+        }
+        Ok(Some(removed))
+    }
+
+    /// 삭제를 되돌리고, `version`이 현재와 다르면 그 내용을 새 버전으로 되살린다
+    pub async fn restore(&self, name: &str, version: Option<u32>) -> anyhow::Result<Restored> {
+        let _write = self.writes.lock().await;
+        let Some(mut entry) = self.find(name).await? else {
+            return Ok(Restored::NotFound);
+        };
+        if let Some(version) = version.filter(|v| *v != entry.version) {
+            let Some(target) = entry.history.iter().find(|v| v.version == version).cloned() else {
+                return Ok(Restored::NoSuchVersion);
+            };
+            entry.push_version(target, self.max_versions);
+        }
+        entry.deleted_at = None;
+        let mut tx = self.db.begin().await?;
+        save(&mut tx, name, &entry).await?;
+        tx.commit().await?;
+        Ok(Restored::Done(entry))
+    }
+
+    /// 어느 이름도 가리키지 않는 블롭과 남은 임시 파일을 지운다.
+    /// 쓰기 잠금을 잡고 하므로 기록 중인 업로드의 블롭을 지우는 일은 없다.
+    /// 보관 기간이 지난 삭제 항목도 여기서 완전히 지운다.
+    pub async fn gc(&self) -> anyhow::Result<GcReport> {
+        let _write = self.writes.lock().await;
+        let mut report = GcReport::default();
+        let cutoff = now_secs().saturating_sub(self.deleted_retention.as_secs());
+        report.expired = sqlx::query("DELETE FROM emotes WHERE deleted_at IS NOT NULL AND deleted_at <= ?")
+            .bind(cutoff as i64)
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+        // 검토 대기 중인 업로드의 블롭도 남긴다
+        let referenced: HashSet<String> = sqlx::query_as::<_, (String,)>(
+            "SELECT hash FROM emotes UNION SELECT hash FROM versions UNION SELECT hash FROM review",
+        )
+        .fetch_all(&self.db)
+        .await?
+        .into_iter()
+        .map(|(hash,)| hash)
+        .collect();
+        let blobs = self.dir.join("blobs");
+        let mut entries = tokio::fs::read_dir(&blobs).await.with_context(|| format!("reading {}", blobs.display()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            report.scanned += 1;
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            if file_name.strip_suffix(".webp").is_some_and(|hash| referenced.contains(hash)) {
+                continue;
+            }
+            let len = entry.metadata().await.map_or(0, |m| m.len());
+            match tokio::fs::remove_file(entry.path()).await {
+                Ok(()) => {
+                    report.removed += 1;
+                    report.reclaimed_bytes += len;
+                }
+                Err(e) => warn!("failed to remove {}: {}", entry.path().display(), e),
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// 아직 적용하지 않은 [`MIGRATIONS`] 단계를 차례로 적용한다
+async fn migrate(db: &SqlitePool) -> anyhow::Result<()> {
+    let (applied,): (i64,) = sqlx::query_as("PRAGMA user_version").fetch_one(db).await?;
+    for (step, sql) in MIGRATIONS.iter().enumerate().skip(applied as usize) {
+        let mut tx = db.begin().await?;
+        sqlx::raw_sql(sql).execute(&mut *tx).await?;
+        sqlx::raw_sql(&format!("PRAGMA user_version = {}", step + 1)).execute(&mut *tx).await?;
+        tx.commit().await?;
+    }
+    Ok(())
+}
+
+fn entry_from_row(row: &SqliteRow) -> anyhow::Result<Entry> {
+    Ok(Entry {
+        hash: row.try_get("hash")?,
+        bytes: row.try_get::<i64, _>("bytes")? as u64,
+        width: row.try_get("width")?,
+        height: row.try_get("height")?,
+        animated: row.try_get("animated")?,
+        uploaded_at: row.try_get::<i64, _>("uploaded_at")? as u64,
+        owner: row.try_get("owner")?,
+        tags: Vec::new(),
+        version: row.try_get("version")?,
+        history: Vec::new(),
+        deleted_at: row.try_get::<Option<i64>, _>("deleted_at")?.map(|at| at as u64),
+        private: row.try_get("private")?,
+    })
+}
+
+fn pending_from_row(row: &SqliteRow) -> anyhow::Result<Pending> {
+    let tags: String = row.try_get("tags")?;
+    Ok(Pending {
+        entry: Entry {
+            hash: row.try_get("hash")?,
+            bytes: row.try_get::<i64, _>("bytes")? as u64,
+            width: row.try_get("width")?,
+            height: row.try_get("height")?,
+            animated: row.try_get("animated")?,
+            uploaded_at: row.try_get::<i64, _>("uploaded_at")? as u64,
+            owner: row.try_get("owner")?,
+            tags: tags.split(',').filter(|t| !t.is_empty()).map(String::from).collect(),
+            version: 0,
+            history: Vec::new(),
+            deleted_at: None,
+            private: row.try_get("private")?,
+        },
+        score: row.try_get("score")?,
+        reason: row.try_get("reason")?,
+        submitted_at: row.try_get::<i64, _>("submitted_at")? as u64,
+    })
+}
+
+fn version_from_row(row: &SqliteRow) -> anyhow::Result<Version> {
+    Ok(Version {
+        version: row.try_get("version")?,
+        hash: row.try_get("hash")?,
+        bytes: row.try_get::<i64, _>("bytes")? as u64,
+        width: row.try_get("width")?,
+        height: row.try_get("height")?,
+        animated: row.try_get("animated")?,
+        uploaded_at: row.try_get::<i64, _>("uploaded_at")? as u64,
+    })
+}
+
+/// 항목 하나를 (태그와 버전 기록까지) 통째로 기록한다
+async fn save(tx: &mut Transaction<'_, Sqlite>, key: &str, entry: &Entry) -> anyhow::Result<()> {
+    sqlx::query(
+        "INSERT INTO emotes (key, guild, hash, bytes, width, height, animated, uploaded_at, owner, private, version, deleted_at) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+         ON CONFLICT (key) DO UPDATE SET guild = excluded.guild, hash = excluded.hash, bytes = excluded.bytes, \
+         width = excluded.width, height = excluded.height, animated = excluded.animated, uploaded_at = excluded.uploaded_at, \
+         owner = excluded.owner, private = excluded.private, version = excluded.version, deleted_at = excluded.deleted_at",
+    )
+    .bind(key)
+    .bind(split_key(key).0)
+    .bind(&entry.hash)
+    .bind(entry.bytes as i64)
+    .bind(entry.width)
+    .bind(entry.height)
+    .bind(entry.animated)
+    .bind(entry.uploaded_at as i64)
+    .bind(&entry.owner)
+    .bind(entry.private)
+    .bind(entry.version)
+    .bind(entry.deleted_at.map(|at| at as i64))
+    .execute(&mut **tx)
+    .await?;
+    save_tags(tx, key, &entry.tags).await?;
+    sqlx::query("DELETE FROM versions WHERE key = ?").bind(key).execute(&mut **tx).await?;
+    for v in &entry.history {
+        sqlx::query(
+            "INSERT INTO versions (key, version, hash, bytes, width, height, animated, uploaded_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(key)
+        .bind(v.version)
+        .bind(&v.hash)
+        .bind(v.bytes as i64)
+        .bind(v.width)
+        .bind(v.height)
+        .bind(v.animated)
+        .bind(v.uploaded_at as i64)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn save_tags(tx: &mut Transaction<'_, Sqlite>, key: &str, tags: &[String]) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM emote_tags WHERE key = ?").bind(key).execute(&mut **tx).await?;
+    for tag in tags {
+        sqlx::query("INSERT OR IGNORE INTO emote_tags (key, tag) VALUES (?, ?)").bind(key).bind(tag).execute(&mut **tx).await?;
+    }
+    Ok(())
+}
+
+async fn save_pending(tx: &mut Transaction<'_, Sqlite>, key: &str, pending: &Pending) -> anyhow::Result<()> {
+    let entry = &pending.entry;
+    sqlx::query(
+        "INSERT OR REPLACE INTO review (key, hash, bytes, width, height, animated, uploaded_at, owner, tags, private, score, reason, submitted_at) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(key)
+    .bind(&entry.hash)
+    .bind(entry.bytes as i64)
+    .bind(entry.width)
+    .bind(entry.height)
+    .bind(entry.animated)
+    .bind(entry.uploaded_at as i64)
+    .bind(&entry.owner)
+    .bind(entry.tags.join(","))
+    .bind(entry.private)
+    .bind(pending.score)
+    .bind(&pending.reason)
+    .bind(pending.submitted_at as i64)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// 색인을 읽지 못했을 때
+fn storage_failed(what: &str, e: anyhow::Error) -> Response {
+    error!("Custom emote index query failed for {}: {:#}", what, e);
+    (StatusCode::INTERNAL_SERVER_ERROR, "storage failed").into_response()
+}
+
+/// 정리 한 번 실행하고 결과를 로그/메트릭에 남긴다
+pub async fn run_gc(store: &Store) -> Option<GcReport> {
+    match store.gc().await {
+        Ok(report) => {
+            ::metrics::counter!(metrics::CUSTOM_GC_REMOVED_TOTAL).increment(report.removed);
+            ::metrics::counter!(metrics::CUSTOM_GC_RECLAIMED_BYTES_TOTAL).increment(report.reclaimed_bytes);
+            info!(
+                "custom emote gc - expired: {}, scanned: {}, removed: {}, reclaimed: {} bytes",
+                report.expired, report.scanned, report.removed, report.reclaimed_bytes
+            );
+            Some(report)
+        }
+        Err(e) => {
+            error!("custom emote gc failed: {:#}", e);
+            None
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// 2~32자의 영문/숫자/`_`/`-` (Discord 이모지 이름 규칙과 비슷하게)
+fn valid_name(name: &str) -> bool {
+    (2..=32).contains(&name.len()) && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+/// 태그 정규화: 소문자로, 1~32자의 영문/숫자/`_`/`-`, 최대 16개. 맞지 않으면 `None`.
+fn normalize_tags<S: AsRef<str>>(tags: impl IntoIterator<Item = S>) -> Option<Vec<String>> {
+    let mut out = Vec::new();
+    for tag in tags {
+        let tag = tag.as_ref().trim().to_ascii_lowercase();
+        if tag.is_empty() {
+            continue;
+        }
+        if tag.len() > 32 || !tag.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-') {
+            return None;
+        }
+        out.push(tag);
+    }
+    out.sort();
+    out.dedup();
+    (out.len() <= 16).then_some(out)
+}
+
+fn valid_owner(owner: &str) -> bool {
+    owner.len() <= 64 && !owner.chars().any(char::is_control)
+}
+
+/// 색인 키 `길드/이름`을 (길드, 이름)으로. 길드 없는 이모트는 이름만이 키다.
+pub(crate) fn split_key(key: &str) -> (Option<&str>, &str) {
+    match key.split_once('/') {
+        Some((guild, name)) => (Some(guild), name),
+        None => (None, key),
+    }
+}
+
+fn key(guild: Option<&str>, name: &str) -> String {
+    match guild {
+        Some(guild) => format!("{guild}/{name}"),
+        None => name.to_string(),
+    }
+}
+
+/// 라우트 경로(`이름.webp`, `길드/이름`)를 (길드, 확장자 뗀 이름)으로. 형식이 맞지 않으면 `None`.
+fn parse_path(path: &str) -> Option<(Option<String>, String)> {
+    let (guild, name) = split_key(path);
+    if guild.is_some_and(|g| !valid_name(g)) || name.contains('/') {
+        return None;
+    }
+    Some((guild.map(String::from), strip_extension(name).to_string()))
+}
+
+/// 목록/업로드 응답에 쓰는 항목 표현
+fn describe(key: &str, entry: &Entry) -> serde_json::Value {
+    let (guild, name) = split_key(key);
+    serde_json::json!({
+        "name": name,
+        "guild": guild,
+        "url": format!("/custom/{key}.webp"),
+        // 비공개 이모트는 해시 주소로 열리지 않는다
+        "hash_url": (!entry.private).then(|| format!("/c/{}.webp", entry.hash)),
+        "emote": entry,
+    })
+}
+
+/// SHA-1 16진수 40자
+fn valid_hash(hash: &str) -> bool {
+    hash.len() == 40 && hash.bytes().all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase())
+}
+
+/// `foo.webp` → `foo`
+fn strip_extension(name: &str) -> &str {
+    name.split('.').next().unwrap_or(name)
+}
+
+/// 올린 이미지를 `max_dimension` 박스 안으로 줄여(키우지는 않는다) WebP로 다시 인코딩한다.
+/// CPU를 오래 쓰므로 `spawn_blocking` 안에서 호출할 것.
+fn normalize(
+    body: &[u8],
+    max_dimension: u32,
+    webp: &imaging::WebpOptions,
+    pool: &ThreadPool,
+    deadline: Option<Instant>,
+) -> Result<imaging::Resized, ImagingError> {
+    let (width, height) = ImageReader::new(Cursor::new(body))
+        .with_guessed_format()
+        .map_err(|e| ImagingError::Decode(e.into()))?
+        .into_dimensions()
+        .map_err(ImagingError::Decode)?;
+    let size = width.max(height).min(max_dimension);
+    let cancel = imaging::Cancel::at(deadline);
+    if imaging::is_animated(body) {
+        imaging::resize_animated_webp(body, size, &Effects::NONE, pool, &cancel, None)
+    } else {
+        imaging::resize_static(body, size, webp, &Effects::NONE, &cancel)
+    }
+}
+
+/// 업로드/삭제 권한 확인. 전역 토큰은 어느 네임스페이스에나, 길드 키는 그 길드에만 쓸 수 있다.
+/// 쓸 수 있는 토큰이 하나도 없으면 쓰기 기능 자체를 끈다.
+fn authorize(state: &AppState, headers: &HeaderMap, guild: Option<&str>) -> Result<(), (StatusCode, &'static str)> {
+    let global = state.config.custom_upload_token.as_ref().or(state.config.admin_token.as_ref());
+    let scoped = guild.and_then(|g| state.config.custom_guild_tokens.get(g));
+    if global.is_none() && scoped.is_none() {
+        return Err((StatusCode::FORBIDDEN, "uploads disabled"));
+    }
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match presented {
+        Some(t) if [global, scoped].into_iter().flatten().any(|token| admin::constant_time_eq(t.as_bytes(), token.as_bytes())) => Ok(()),
+        _ => Err((StatusCode::UNAUTHORIZED, "unauthorized")),
+    }
+}
+
+/// 길드 네임스페이스에 적용할 한도
+fn quota(state: &AppState, guild: Option<&str>) -> Option<Quota> {
+    guild.map(|_| Quota {
+        max_emotes: state.config.custom_guild_max_emotes,
+        max_bytes: state.config.custom_guild_max_bytes,
+    })
+}
+
+fn rejected(status: StatusCode, reason: &'static str) -> Response {
+    ::metrics::counter!(metrics::CUSTOM_UPLOADS_TOTAL, "outcome" => "rejected").increment(1);
+    (status, reason).into_response()
+}
+
+/// 업로드 쿼리: `?owner=…&tags=a,b&private=1`. 교체할 때 빠지면 기존 값을 유지한다.
+#[derive(Debug, Default, Deserialize)]
+pub struct UploadParams {
+    owner: Option<String>,
+    tags: Option<String>,
+    private: Option<String>,
+}
+
+pub async fn upload_handler(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Query(params): Query<UploadParams>,
+    deadline: Option<Extension<middleware::Deadline>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(store) = state.custom.clone() else {
+        return (StatusCode::NOT_FOUND, "custom emotes disabled").into_response();
+    };
+    let Some((guild, name)) = parse_path(&path) else {
+        return (StatusCode::NOT_FOUND, "emote not found").into_response();
+    };
+    if let Err(rejection) = authorize(&state, &headers, guild.as_deref()) {
+        return rejection.into_response();
+    }
+    if !valid_name(&name) {
+        return rejected(StatusCode::BAD_REQUEST, "name must be 2-32 characters of [A-Za-z0-9_-]");
+    }
+    let tags = match params.tags.as_deref().map(|t| normalize_tags(t.split(','))) {
+        Some(None) => return rejected(StatusCode::BAD_REQUEST, "tags must be up to 16 of [a-z0-9_-]{1,32}"),
+        Some(Some(tags)) => Some(tags),
+        None => None,
+    };
+    if params.owner.as_deref().is_some_and(|o| !valid_owner(o)) {
+        return rejected(StatusCode::BAD_REQUEST, "owner must be at most 64 characters");
+    }
+    let private = params.private.as_deref().map(|v| matches!(v, "1" | "true" | "yes"));
+    if private == Some(true) && state.config.custom_signing_key.is_none() {
+        return rejected(StatusCode::BAD_REQUEST, "private emotes need CUSTOM_SIGNING_KEY");
+    }
+    if !image::guess_format(&body).is_ok_and(|format| UPLOAD_FORMATS.contains(&format)) {
+        return rejected(StatusCode::UNSUPPORTED_MEDIA_TYPE, "upload must be a WebP, PNG, GIF or JPEG image");
+    }
+
+    let pool = state.frame_pool.clone();
+    let max_dimension = state.config.custom_max_dimension;
+    let webp = state.config.webp.clone();
+    let deadline = deadline.map(|Extension(d)| d.0);
+    let out = match tokio::task::spawn_blocking(move || normalize(&body, max_dimension, &webp, &pool, deadline)).await {
+        Ok(Ok(out)) => out,
+        Ok(Err(ImagingError::Decode(e))) => {
+            warn!("Rejected custom emote upload {}: {}", name, e);
+            return rejected(StatusCode::UNSUPPORTED_MEDIA_TYPE, "decode failed");
+        }
+        Ok(Err(ImagingError::Cancelled)) => return rejected(StatusCode::GATEWAY_TIMEOUT, "request deadline exceeded"),
+        Ok(Err(ImagingError::Encode(e))) => {
+            error!("Encode error for custom emote {}: {}", name, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "encode failed").into_response();
+        }
+        Err(e) => {
+            error!("Normalize task failed for custom emote {}: {}", name, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "processing failed").into_response();
+        }
+    };
+    if state.blocklist.blocks_content(&out.bytes) {
+        warn!("Blocked content uploaded as custom emote {}", name);
+        return rejected(StatusCode::FORBIDDEN, "content blocked");
+    }
+
+    let name = key(guild.as_deref(), &name);
+    let existing = match store.get(&name).await {
+        Ok(existing) => existing,
+        Err(e) => return storage_failed(&name, e),
+    };
+    let entry = Entry {
+        owner: params.owner.filter(|o| !o.is_empty()).or_else(|| existing.as_ref().and_then(|e| e.owner.clone())),
+        private: private.or_else(|| existing.as_ref().map(|e| e.private)).unwrap_or_default(),
+        tags: tags.or_else(|| existing.map(|e| e.tags)).unwrap_or_default(),
+        hash: blocklist::content_hash(&out.bytes),
+        bytes: out.bytes.len() as u64,
+        width: out.resized.0,
+        height: out.resized.1,
+        animated: imaging::is_animated_webp(&out.bytes),
+        uploaded_at: now_secs(),
+        version: 0,
+        history: Vec::new(),
+        deleted_at: None,
+    };
+    if let Some(moderator) = &state.moderation {
+        if let Verdict::Quarantine { score, reason } = moderator.check(&name, &out.bytes).await {
+            let pending = Pending { entry, score, reason: reason.to_string(), submitted_at: now_secs() };
+            if let Err(e) = store.quarantine(&name, &out.bytes, pending).await {
+                error!("Failed to queue custom emote {} for review: {:#}", name, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "storage failed").into_response();
+            }
+            ::metrics::counter!(metrics::CUSTOM_UPLOADS_TOTAL, "outcome" => "quarantined").increment(1);
+            info!("custom emote quarantined - {}: {}", name, reason);
+            let (guild, short) = split_key(&name);
+            let body = serde_json::json!({ "name": short, "guild": guild, "status": "pending", "reason": reason, "score": score });
+            return (StatusCode::ACCEPTED, Json(body)).into_response();
+        }
+    }
+    let stored = match store.put(&name, &out.bytes, entry.clone(), quota(&state, guild.as_deref())).await {
+        Ok(Ok(stored)) => stored,
+        Ok(Err(QuotaExceeded::Count)) => return rejected(StatusCode::FORBIDDEN, "guild emote limit reached"),
+        Ok(Err(QuotaExceeded::Bytes)) => return rejected(StatusCode::FORBIDDEN, "guild storage quota exceeded"),
+        Err(e) => {
+            error!("Failed to store custom emote {}: {:#}", name, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "storage failed").into_response();
+        }
+    };
+    // 다른 내용으로 교체했으면 예전 이미지로 만든 변형을 로컬/CDN 캐시에서 지운다
+    if let Some(previous) = stored.previous.as_ref().filter(|p| p.hash != entry.hash) {
+        admin::purge_everywhere(&state, &format!("custom/{name}")).await;
+        forget_unreferenced(&state, &store, &previous.hash).await;
+    }
+    if stored.previous.as_ref().is_some_and(|p| !p.private) && stored.current.private {
+        hide(&state, &store, &name, &stored.current).await;
+    }
+    let (status, outcome) = match stored.previous {
+        Some(_) => (StatusCode::OK, "replaced"),
+        None => (StatusCode::CREATED, "created"),
+    };
+    ::metrics::counter!(metrics::CUSTOM_UPLOADS_TOTAL, "outcome" => outcome).increment(1);
+    info!(
+        "custom emote {} - {}: {}x{} → {}x{}, {} bytes{}",
+        outcome,
+        name,
+        out.original.0,
+        out.original.1,
+        entry.width,
+        entry.height,
+        entry.bytes,
+        if stored.deduplicated { " (deduplicated)" } else { "" }
+    );
+    let mut body = describe(&name, &stored.current);
+    body["deduplicated"] = stored.deduplicated.into();
+    (status, Json(body)).into_response()
+}
+
+/// 더 이상 아무 이름도 가리키지 않는 해시의 `/c/` 변형을 캐시에서 지운다
+async fn forget_unreferenced(state: &AppState, store: &Store, hash: &str) {
+    // 확인하지 못하면 지우는 쪽으로 (캐시를 한 번 더 채우는 것뿐이다)
+    if !store.is_referenced(hash).await.unwrap_or(false) {
+        admin::purge_everywhere(state, &format!("c/{hash}")).await;
+    }
+}
+
+/// 비공개로 돌린 이모트가 공개일 때 엣지에 남은 사본을 지운다
+async fn hide(state: &AppState, store: &Store, name: &str, entry: &Entry) {
+    admin::purge_everywhere(state, &format!("custom/{name}")).await;
+    for hash in entry.hashes() {
+        if !store.is_public(hash).await.unwrap_or(false) {
+            admin::purge_everywhere(state, &format!("c/{hash}")).await;
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListParams {
+    tag: Option<String>,
+    owner: Option<String>,
+    guild: Option<String>,
+}
+
+/// `GET /custom?tag=…&owner=…&guild=…`: 대시보드용 목록. `guild`를 주면 그 길드의 사용량도 함께.
+/// 비공개 이모트는 그 네임스페이스에 쓸 수 있는 토큰을 보냈을 때만 나온다.
+pub async fn list_handler(State(state): State<AppState>, Query(params): Query<ListParams>, headers: HeaderMap) -> Response {
+    let Some(store) = state.custom.clone() else {
+        return (StatusCode::NOT_FOUND, "custom emotes disabled").into_response();
+    };
+    let tag = params.tag.map(|t| t.trim().to_ascii_lowercase());
+    let guild = params.guild.as_deref();
+    let listed = match store.list(tag.as_deref(), params.owner.as_deref()).await {
+        Ok(listed) => listed,
+        Err(e) => return storage_failed("list", e),
+    };
+    let emotes: Vec<_> = listed
+        .iter()
+        .filter(|(key, _)| guild.is_none_or(|g| split_key(key).0 == Some(g)))
+        .filter(|(key, entry)| !entry.private || authorize(&state, &headers, split_key(key).0).is_ok())
+        .map(|(key, entry)| describe(key, entry))
+        .collect();
+    let mut body = serde_json::json!({ "emotes": emotes });
+    if let Some(quota) = quota(&state, guild) {
+        let (count, bytes) = match store.usage(guild, None).await {
+            Ok(usage) => usage,
+            Err(e) => return storage_failed("usage", e),
+        };
+        body["usage"] = serde_json::json!({
+            "emotes": count,
+            "bytes": bytes,
+            "max_emotes": quota.max_emotes,
+            "max_bytes": quota.max_bytes,
+        });
+    }
+    Json(body).into_response()
+}
+
+/// `PATCH /custom/:name`: `{"name": "새이름", "tags": [...], "owner": "..."}`
+pub async fn update_handler(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    headers: HeaderMap,
+    Json(mut update): Json<Update>,
+) -> Response {
+    let Some(store) = state.custom.clone() else {
+        return (StatusCode::NOT_FOUND, "custom emotes disabled").into_response();
+    };
+    let Some((guild, name)) = parse_path(&path) else {
+        return (StatusCode::NOT_FOUND, "emote not found").into_response();
+    };
+    if let Err(rejection) = authorize(&state, &headers, guild.as_deref()) {
+        return rejection.into_response();
+    }
+    if update.name.as_deref().is_some_and(|n| !valid_name(n)) {
+        return (StatusCode::BAD_REQUEST, "name must be 2-32 characters of [A-Za-z0-9_-]").into_response();
+    }
+    // 이름을 바꿔도 같은 네임스페이스 안에 머문다
+    let name = key(guild.as_deref(), &name);
+    update.name = update.name.map(|n| key(guild.as_deref(), &n));
+    if let Some(tags) = update.tags.take() {
+        let Some(tags) = normalize_tags(tags) else {
+            return (StatusCode::BAD_REQUEST, "tags must be up to 16 of [a-z0-9_-]{1,32}").into_response();
+        };
+        update.tags = Some(tags);
+    }
+    if update.owner.as_deref().is_some_and(|o| !valid_owner(o)) {
+        return (StatusCode::BAD_REQUEST, "owner must be at most 64 characters").into_response();
+    }
+    if update.private == Some(true) && state.config.custom_signing_key.is_none() {
+        return (StatusCode::BAD_REQUEST, "private emotes need CUSTOM_SIGNING_KEY").into_response();
+    }
+    let was_private = match store.get(&name).await {
+        Ok(entry) => entry.is_some_and(|e| e.private),
+        Err(e) => return storage_failed(&name, e),
+    };
+
+    match store.update(&name, update).await {
+        Ok(Updated::Done(new_name, entry)) => {
+            // 옛 이름의 URL은 더 이상 이 이모트가 아니다
+            if new_name != name {
+                admin::purge_everywhere(&state, &format!("custom/{name}")).await;
+                info!("custom emote renamed - {} → {}", name, new_name);
+            }
+            if entry.private && !was_private {
+                hide(&state, &store, &new_name, &entry).await;
+                info!("custom emote made private - {}", new_name);
+            }
+            Json(describe(&new_name, &entry)).into_response()
+        }
+        Ok(Updated::NotFound) => (StatusCode::NOT_FOUND, "emote not found").into_response(),
+        Ok(Updated::NameTaken) => (StatusCode::CONFLICT, "name already in use").into_response(),
+        Err(e) => {
+            error!("Failed to update custom emote {}: {:#}", name, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "storage failed").into_response()
+        }
+    }
+}
+
+pub async fn delete_handler(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap) -> Response {
+    let Some(store) = state.custom.clone() else {
+        return (StatusCode::NOT_FOUND, "custom emotes disabled").into_response();
+    };
+    let Some((guild, name)) = parse_path(&path) else {
+        return (StatusCode::NOT_FOUND, "emote not found").into_response();
+    };
+    if let Err(rejection) = authorize(&state, &headers, guild.as_deref()) {
+        return rejection.into_response();
+    }
+    let name = key(guild.as_deref(), &name);
+    match store.remove(&name).await {
+        Ok(Some(removed)) => {
+            admin::purge_everywhere(&state, &format!("custom/{name}")).await;
+            forget_unreferenced(&state, &store, &removed.hash).await;
+            info!("custom emote deleted - {}", name);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "emote not found").into_response(),
+        Err(e) => {
+            error!("Failed to delete custom emote {}: {:#}", name, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "storage failed").into_response()
+        }
+    }
+}
+
+/// `GET /admin/custom/review`: 검토 대기열 (제출 순)
+pub async fn review_queue_handler(State(state): State<AppState>) -> Response {
+    let Some(store) = state.custom.clone() else {
+        return (StatusCode::NOT_FOUND, "custom emotes disabled").into_response();
+    };
+    let queue = match store.review_queue().await {
+        Ok(queue) => queue,
+        Err(e) => return storage_failed("review queue", e),
+    };
+    let pending: Vec<_> = queue
+        .into_iter()
+        .map(|(key, p)| {
+            let (guild, name) = split_key(&key);
+            serde_json::json!({
+                "name": name,
+                "guild": guild,
+                "preview_url": format!("/admin/custom/review/{key}"),
+                "score": p.score,
+                "reason": p.reason,
+                "submitted_at": p.submitted_at,
+                "emote": p.entry,
+            })
+        })
+        .collect();
+    Json(serde_json::json!({ "pending": pending })).into_response()
+}
+
+/// `GET /admin/custom/review/*key`: 검토할 저장본 그대로 (캐시하지 않음)
+pub async fn review_image_handler(State(state): State<AppState>, Path(path): Path<String>) -> Response {
+    let Some(store) = state.custom.clone() else {
+        return (StatusCode::NOT_FOUND, "custom emotes disabled").into_response();
+    };
+    let Some((guild, name)) = parse_path(&path) else {
+        return (StatusCode::NOT_FOUND, "emote not found").into_response();
+    };
+    match store.read_pending(&key(guild.as_deref(), &name)).await {
+        Ok(Some(bytes)) => {
+            ([(header::CONTENT_TYPE, "image/webp"), (header::CACHE_CONTROL, "no-store")], bytes).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "no pending upload").into_response(),
+        Err(e) => {
+            error!("Failed to read pending custom emote {}: {:#}", path, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "storage failed").into_response()
+        }
+    }
+}
+
+/// `POST /admin/custom/review/*key` 본문: `{"action": "approve"}` 또는 `{"action": "reject"}`
+#[derive(Debug, Deserialize)]
+pub struct ReviewBody {
+    action: String,
+}
+
+pub async fn review_handler(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Json(body): Json<ReviewBody>,
+) -> Response {
+    let Some(store) = state.custom.clone() else {
+        return (StatusCode::NOT_FOUND, "custom emotes disabled").into_response();
+    };
+    let Some((guild, name)) = parse_path(&path) else {
+        return (StatusCode::NOT_FOUND, "emote not found").into_response();
+    };
+    let name = key(guild.as_deref(), &name);
+    let result = match body.action.as_str() {
+        "approve" => store.approve(&name).await.map(|stored| stored.map(Some)),
+        "reject" => store.reject(&name).await.map(|pending| pending.map(|_| None)),
+        _ => return (StatusCode::BAD_REQUEST, "action must be approve or reject").into_response(),
+    };
+    match result {
+        Ok(Some(Some(stored))) => {
+            if let Some(previous) = stored.previous.as_ref() {
+                admin::purge_everywhere(&state, &format!("custom/{name}")).await;
+                forget_unreferenced(&state, &store, &previous.hash).await;
+            }
+            ::metrics::counter!(metrics::CUSTOM_UPLOADS_TOTAL, "outcome" => "approved").increment(1);
+            info!("custom emote approved - {}", name);
+            Json(describe(&name, &stored.current)).into_response()
+        }
+        Ok(Some(None)) => {
+            ::metrics::counter!(metrics::CUSTOM_UPLOADS_TOTAL, "outcome" => "declined").increment(1);
+            info!("custom emote declined in review - {}", name);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "no pending upload").into_response(),
+        Err(e) => {
+            error!("Failed to review custom emote {}: {:#}", name, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "storage failed").into_response()
+        }
+    }
+}
+
+/// `POST /admin/custom/:name/restore` 본문(선택): `{"version": 2}`
+#[derive(Debug, Default, Deserialize)]
+pub struct RestoreBody {
+    version: Option<u32>,
+}
+
+/// 삭제를 되돌리고, 버전을 주면 그 내용을 새 버전으로 되살린다 (관리 토큰으로 보호됨)
+pub async fn restore_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    body: Option<Json<RestoreBody>>,
+) -> Response {
+    restore(state, name, body).await
+}
+
+/// `POST /admin/custom/:guild_id/:name/restore`
+pub async fn guild_restore_handler(
+    State(state): State<AppState>,
+    Path((guild, name)): Path<(String, String)>,
+    body: Option<Json<RestoreBody>>,
+) -> Response {
+    restore(state, key(Some(&guild), &name), body).await
+}
+
+async fn restore(state: AppState, name: String, body: Option<Json<RestoreBody>>) -> Response {
+    let Some(store) = state.custom.clone() else {
+        return (StatusCode::NOT_FOUND, "custom emotes disabled").into_response();
+    };
+    let version = body.and_then(|Json(b)| b.version);
+    match store.restore(&name, version).await {
+        Ok(Restored::Done(entry)) => {
+            admin::purge_everywhere(&state, &format!("custom/{name}")).await;
+            info!("custom emote restored - {} (now v{})", name, entry.version);
+            Json(describe(&name, &entry)).into_response()
+        }
+        Ok(Restored::NotFound) => (StatusCode::NOT_FOUND, "emote not found").into_response(),
+        Ok(Restored::NoSuchVersion) => (StatusCode::NOT_FOUND, "version not found").into_response(),
+        Err(e) => {
+            error!("Failed to restore custom emote {}: {:#}", name, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "storage failed").into_response()
+        }
+    }
+}
+
+/// `GET /admin/custom/deleted`: 복원할 수 있는 삭제된 이모트
+pub async fn deleted_handler(State(state): State<AppState>) -> Response {
+    let Some(store) = state.custom.clone() else {
+        return (StatusCode::NOT_FOUND, "custom emotes disabled").into_response();
+    };
+    let deleted = match store.deleted().await {
+        Ok(deleted) => deleted,
+        Err(e) => return storage_failed("deleted", e),
+    };
+    let emotes: Vec<_> = deleted.iter().map(|(name, entry)| describe(name, entry)).collect();
+    Json(serde_json::json!({ "emotes": emotes })).into_response()
+}
+
+/// 비공개 이모트 주소의 서명 (`?exp=&sig=`)
+#[derive(Debug, Default, Deserialize)]
+pub struct Signature {
+    exp: Option<u64>,
+    sig: Option<String>,
+}
+
+impl Signature {
+    /// 리다이렉트해도 서명이 따라가도록 `Location`에 다시 붙인다
+    fn carry(&self, mut res: Response) -> Response {
+        let (Some(exp), Some(sig)) = (self.exp, self.sig.as_deref()) else {
+            return res;
+        };
+        let location = res.headers().get(header::LOCATION).and_then(|v| v.to_str().ok());
+        if let Some(location) = location {
+            let separator = if location.contains('?') { '&' } else { '?' };
+            let signed = format!("{location}{separator}exp={exp}&sig={sig}");
+            if let Ok(value) = signed.parse() {
+                res.headers_mut().insert(header::LOCATION, value);
+            }
+        }
+        res
+    }
+}
+
+pub async fn handler(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Query(params): Query<EmojiParams>,
+    Query(signature): Query<Signature>,
+    deadline: Option<Extension<middleware::Deadline>>,
+    req: RequestInputs,
+) -> Response {
+    let Some(store) = state.custom.clone() else {
+        return (StatusCode::NOT_FOUND, "custom emotes disabled").into_response();
+    };
+    let Some((guild, name)) = parse_path(&path) else {
+        return (StatusCode::NOT_FOUND, "emote not found").into_response();
+    };
+    let requested = path;
+    // `이름@v2`는 예전 버전
+    let (name, version) = match name.rsplit_once("@v").and_then(|(n, v)| Some((n, v.parse::<u32>().ok()?))) {
+        Some((n, v)) => (key(guild.as_deref(), n), Some(v)),
+        None => (key(guild.as_deref(), &name), None),
+    };
+    let path = match version {
+        Some(v) => format!("/custom/{name}@v{v}.webp"),
+        None => format!("/custom/{name}.webp"),
+    };
+    let variant = match pick_variant(&state.config, &params, &path) {
+        Ok(v) => v,
+        Err(redirect) => return signature.carry(redirect.into_response()),
+    };
+    if state.blocklist.blocks_source("custom") {
+        warn!("Blocked custom emote requested: {}", name);
+        return (StatusCode::GONE, "emoji unavailable").into_response();
+    }
+
+    let entry = match store.get(&name).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return (StatusCode::NOT_FOUND, "emote not found").into_response(),
+        Err(e) => return storage_failed(&name, e),
+    };
+    // 비공개 이모트는 API 키나 서명이 없으면 없는 것처럼. 어느 쪽이든 공유 캐시에는 남기지 않는다.
+    let private_max_age = match (entry.private, signature.exp, signature.sig.as_deref()) {
+        (false, ..) => None,
+        (true, ..) if req.is_authenticated() => Some(state.config.emoji_cache.max_age),
+        (true, Some(exp), Some(sig)) => {
+            let Some(secret) = state.config.custom_signing_key.as_deref() else {
+                return (StatusCode::NOT_FOUND, "emote not found").into_response();
+            };
+            match signing::verify(secret, &name, exp, sig, now_secs()) {
+                Ok(()) => Some(exp.saturating_sub(now_secs())),
+                Err(signing::Rejected::Expired) => return (StatusCode::FORBIDDEN, "signature expired").into_response(),
+                Err(signing::Rejected::Invalid) => return (StatusCode::FORBIDDEN, "invalid signature").into_response(),
+            }
+        }
+        (true, ..) => return (StatusCode::NOT_FOUND, "emote not found").into_response(),
+    };
+    let (label, key_base, hash, animated) = match version {
+        // 버전 주소는 내용이 바뀌지 않으므로 해시 주소와 캐시를 함께 쓴다
+        Some(v) => {
+            let Some(found) = entry.find_version(v) else {
+                return (StatusCode::NOT_FOUND, "version not found").into_response();
+            };
+            (format!("custom/{name}@v{v}"), format!("c/{}", found.hash), found.hash, found.animated)
+        }
+        None => (format!("custom/{name}"), format!("custom/{name}"), entry.hash, entry.animated),
+    };
+    // 저장할 때 애니메이션 여부를 알고 있으므로 `.gif`도 응답을 만들기 전에 가른다
+    match path_extension(&requested, variant) {
+        PathExtension::Gif if animated => {}
+        PathExtension::Canonical => {}
+        _ => {
+            let stem = path.strip_suffix(".webp").unwrap_or(&path);
+            return signature.carry(Redirect::permanent(&canonical_url(&state.config, stem, variant)).into_response());
+        }
+    }
+    let origin = Origin {
+        tags: purge::tags(&key_base, "custom", variant.size()),
+        key_base,
+        url: path,
+        label,
+        fetch: Fetch::Stored(store, hash),
+    };
+    let mut res = serve_image(state, origin, variant, deadline.map(|Extension(d)| d.0), req, Mode::Interactive).await;
+    // 서명한 주소는 브라우저에도 만료 시각까지만
+    if let Some(max_age) = private_max_age {
+        if let Ok(value) = format!("private, max-age={max_age}").parse() {
+            res.headers_mut().insert(header::CACHE_CONTROL, value);
+        }
+    }
+    res
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SignParams {
+    /// 유효 기간(초). 없으면 `CUSTOM_SIGNED_URL_TTL_SECS`
+    ttl: Option<u64>,
+}
+
+/// `POST /custom/:name/sign?ttl=…`: 업로드 토큰으로 비공개 이모트를 잠시 여는 주소를 받는다 (최대 7일)
+pub async fn sign_handler(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Query(params): Query<SignParams>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(store) = state.custom.clone() else {
+        return (StatusCode::NOT_FOUND, "custom emotes disabled").into_response();
+    };
+    let Some((guild, name)) = path.strip_suffix("/sign").and_then(parse_path) else {
+        return (StatusCode::NOT_FOUND, "emote not found").into_response();
+    };
+    if let Err(rejection) = authorize(&state, &headers, guild.as_deref()) {
+        return rejection.into_response();
+    }
+    let Some(secret) = state.config.custom_signing_key.as_deref() else {
+        return (StatusCode::BAD_REQUEST, "signed URLs need CUSTOM_SIGNING_KEY").into_response();
+    };
+    let name = key(guild.as_deref(), &name);
+    match store.get(&name).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return (StatusCode::NOT_FOUND, "emote not found").into_response(),
+        Err(e) => return storage_failed(&name, e),
+    }
+    let ttl = params
+        .ttl
+        .map_or(state.config.custom_signed_ttl, Duration::from_secs)
+        .clamp(Duration::from_secs(1), MAX_SIGNED_TTL);
+    let exp = now_secs() + ttl.as_secs();
+    let sig = signing::sign(secret, &name, exp);
+    Json(serde_json::json!({
+        "url": format!("/custom/{name}.webp?exp={exp}&sig={sig}"),
+        "expires_at": exp,
+    }))
+    .into_response()
+}
+
+/// `/c/:hash.webp`: 내용 해시로 고정된 주소
+pub async fn hash_handler(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+    Query(params): Query<EmojiParams>,
+    deadline: Option<Extension<middleware::Deadline>>,
+    req: RequestInputs,
+) -> Response {
+    let Some(store) = state.custom.clone() else {
+        return (StatusCode::NOT_FOUND, "custom emotes disabled").into_response();
+    };
+    let requested = hash;
+    let hash = strip_extension(&requested).to_string();
+    if !valid_hash(&hash) {
+        return (StatusCode::NOT_FOUND, "emote not found").into_response();
+    }
+    match store.is_public(&hash).await {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::NOT_FOUND, "emote not found").into_response(),
+        Err(e) => return storage_failed(&hash, e),
+    }
+    let variant = match pick_variant(&state.config, &params, &format!("/c/{hash}.webp")) {
+        Ok(v) => v,
+        Err(redirect) => return redirect.into_response(),
+    };
+    let canonical = canonical_url(&state.config, &format!("/c/{hash}"), variant);
+    let extension = path_extension(&requested, variant);
+    if matches!(extension, PathExtension::Png | PathExtension::Other) {
+        return Redirect::permanent(&canonical).into_response();
+    }
+    if state.blocklist.blocks_source("custom") {
+        warn!("Blocked custom emote requested: {}", hash);
+        return (StatusCode::GONE, "emoji unavailable").into_response();
+    }
+
+    let label = format!("c/{hash}");
+    let origin = Origin {
+        tags: purge::tags(&label, "custom", variant.size()),
+        key_base: label.clone(),
+        url: format!("/c/{hash}.webp"),
+        label,
+        fetch: Fetch::Stored(store, hash),
+    };
+    let res = serve_image(state, origin, variant, deadline.map(|Extension(d)| d.0), req, Mode::Interactive).await;
+    match extension {
+        PathExtension::Gif => redirect_static_gif(res, &canonical).await,
+        _ => res,
+    }
+}
@@ -0,0 +1,53 @@
+//! libwebp 인코더 (`libwebp` 기능).
+//!
+//! libwebp의 기준 인코더인 `cwebp` 명령으로 정적 WebP를 만든다. `image` 크레이트의
+//! 인코더와 달리 압축 노력(`-m`), 목표 크기(`-size`), 손실 화질(`-q`), near-lossless(`-near_lossless`)를
+//! 고를 수 있다. 실패하면 호출하는 쪽이 내장 인코더로 되돌아간다.
+
+use anyhow::Context;
+use image::DynamicImage;
+use std::{io::Write, time::Instant};
+use tokio::process::Command;
+
+use crate::{
+    imaging::WebpOptions,
+    subprocess::{run_with_deadline, Scratch},
+};
+
+/// RGBA 그대로 넘기도록 PAM으로 써서 `cwebp`에 준다. 끝날 때까지 스레드를 붙잡으므로 블로킹 스레드
+/// (`spawn_blocking` 안이나 런타임 밖)에서 부를 것. `deadline`이 지나면 프로세스를 죽이고 실패로 돌려준다.
+pub fn encode(img: &DynamicImage, options: &WebpOptions, deadline: Option<Instant>) -> anyhow::Result<Vec<u8>> {
+    let rgba = img.to_rgba8();
+    let id = uuid::Uuid::new_v4();
+    let (src, dst) = (Scratch::new(format!("cwebp-{id}.pam")), Scratch::new(format!("cwebp-{id}.webp")));
+    let mut file = std::fs::File::create(src.path()).with_context(|| format!("creating {}", src.path().display()))?;
+    write!(
+        file,
+        "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+        rgba.width(),
+        rgba.height()
+    )?;
+    file.write_all(rgba.as_raw())?;
+    drop(file);
+
+    let mut command = Command::new(&options.binary);
+    command.arg("-quiet").arg("-m").arg(options.method.to_string());
+    if options.target_size > 0 {
+        // 목표 크기는 손실 압축에서만 쓸 수 있다
+        command.arg("-size").arg(options.target_size.to_string());
+    } else if let Some(quality) = options.quality {
+        command.arg("-q").arg(quality.to_string());
+    } else {
+        command.arg("-lossless");
+        if options.near_lossless < 100 {
+            command.arg("-near_lossless").arg(options.near_lossless.to_string());
+        }
+    }
+    command.arg(src.path()).arg("-o").arg(dst.path());
+    let run = run_with_deadline(command, deadline);
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => runtime.block_on(run)?,
+        Err(_) => tokio::runtime::Builder::new_current_thread().enable_all().build()?.block_on(run)?,
+    }
+    std::fs::read(dst.path()).with_context(|| format!("reading {}", dst.path().display()))
+}
@@ -0,0 +1,100 @@
+//! 과부하 시 성능 저하 모드.
+//!
+//! 처리 중인 인코딩 작업 수나 상주 메모리가 임계값을 넘으면, 캐시 미스 요청을 더
+//! 쌓아 타임아웃으로 몰아넣는 대신 보관 중인 만료 항목이나 Discord 원본을 그대로
+//! (짧은 Cache-Control로, 캐시하지 않고) 제공한다.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tracing::warn;
+
+use crate::{
+    allocator,
+    metrics::{DEGRADED, ENCODE_IN_FLIGHT},
+};
+
+pub struct Degradation {
+    in_flight: AtomicUsize,
+    /// 마지막으로 샘플링한 상주 메모리 (바이트)
+    resident: AtomicU64,
+    degraded: AtomicBool,
+    /// 이 수 이상 인코딩 중이면 저하 모드 (0이면 비활성)
+    max_in_flight: usize,
+    /// 상주 메모리가 이 바이트 이상이면 저하 모드 (0이면 비활성)
+    max_resident: u64,
+}
+
+/// 인코딩 작업 하나가 끝날 때(drop) 처리 중 수를 되돌린다
+pub struct EncodeGuard(Arc<Degradation>);
+
+impl Drop for EncodeGuard {
+    fn drop(&mut self) {
+        let n = self.0.in_flight.fetch_sub(1, Ordering::Relaxed) - 1;
+        metrics::gauge!(ENCODE_IN_FLIGHT).set(n as f64);
+    }
+}
+
+impl Degradation {
+    pub fn new(max_in_flight: usize, max_resident: u64) -> Arc<Self> {
+        Arc::new(Self {
+            in_flight: AtomicUsize::new(0),
+            resident: AtomicU64::new(0),
+            degraded: AtomicBool::new(false),
+            max_in_flight,
+            max_resident,
+        })
+    }
+
+    pub fn enter(self: &Arc<Self>) -> EncodeGuard {
+        let n = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        metrics::gauge!(ENCODE_IN_FLIGHT).set(n as f64);
+        EncodeGuard(self.clone())
+    }
+
+    /// 지금 저하 모드로 응답해야 하는지. 상태가 바뀌면 로그와 게이지를 갱신한다.
+    pub fn is_degraded(&self) -> bool {
+        let queue = self.max_in_flight > 0 && self.in_flight.load(Ordering::Relaxed) >= self.max_in_flight;
+        let memory = self.max_resident > 0 && self.resident.load(Ordering::Relaxed) >= self.max_resident;
+        let degraded = queue || memory;
+        if self.degraded.swap(degraded, Ordering::Relaxed) != degraded {
+            if degraded {
+                warn!(
+                    "entering degraded mode (encodes in flight: {}, resident: {} MB)",
+                    self.in_flight.load(Ordering::Relaxed),
+                    self.resident.load(Ordering::Relaxed) >> 20
+                );
+            } else {
+                warn!("leaving degraded mode");
+            }
+            metrics::gauge!(DEGRADED).set(u8::from(degraded) as f64);
+        }
+        degraded
+    }
+
+    /// 상주 메모리를 주기적으로 읽는다 (할당자 통계는 요청마다 읽기엔 비싸다)
+    pub fn spawn_sampler(self: &Arc<Self>) {
+        if self.max_resident == 0 {
+            return;
+        }
+        let this = self.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                tick.tick().await;
+                let resident = tokio::task::spawn_blocking(|| allocator::stats()["resident_bytes"].as_u64())
+                    .await
+                    .ok()
+                    .flatten();
+                if let Some(bytes) = resident {
+                    this.resident.store(bytes, Ordering::Relaxed);
+                }
+                this.is_degraded();
+            }
+        });
+    }
+}
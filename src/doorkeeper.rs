@@ -0,0 +1,30 @@
+//! 메모리 캐시 입장 제한 (`CACHE_ADMIT_AFTER`, `CACHE_ADMIT_WINDOW_SECS`).
+//!
+//! moka의 TinyLFU는 캐시가 가득 찼을 때만 빈도로 새 항목을 거르므로, 덜 찬 캐시에는 한 번 보고 마는 스크레이퍼
+//! 트래픽도 그대로 들어와 자리를 차지하다가 자주 쓰이는 항목과 함께 밀려난다. 같은 키를 창 안에서 정한 횟수만큼
+//! 본 뒤에야 메모리에 넣고, 그 전에는 2차 계층(있으면)과 검증자에만 남긴다. 창은 마지막으로 본 때부터 센다.
+
+use moka::sync::Cache;
+use std::time::Duration;
+
+pub struct Doorkeeper {
+    after: u32,
+    seen: Cache<String, u32>,
+}
+
+impl Doorkeeper {
+    /// `after`번째로 본 키부터 들인다. 1 이하면 모두 들이므로 `None`.
+    pub fn new(after: u32, window: Duration, capacity: u64) -> Option<Self> {
+        (after > 1).then(|| Self { after, seen: Cache::builder().max_capacity(capacity).time_to_live(window).build() })
+    }
+
+    /// `key`를 한 번 더 봤다. 메모리 캐시에 넣을 만큼 봤으면 `true`.
+    pub fn admit(&self, key: &str) -> bool {
+        let seen = self
+            .seen
+            .entry(key.to_string())
+            .and_upsert_with(|entry| entry.map_or(1, |e| e.into_value().saturating_add(1)))
+            .into_value();
+        seen >= self.after
+    }
+}
@@ -0,0 +1,361 @@
+//! 주소로 고르는 모양 옵션 (`/e/:name?trim=1&stroke=2,ffffff&shadow=2,2,4,000000&pad_px=8`).
+//!
+//! 옵션은 리사이즈 결과(WebP·PNG·GIF·AVIF)에만 적용하고 패스스루/원본은 손대지 않는다. 켠 옵션은 캐시 키에
+//! `~fx-…` 꼬리표로 붙어 옵션 없는 결과와 섞이지 않는다. 조합이 많아 하나씩 꼽을 수 없으므로 이런 결과는
+//! 2차 캐시 계층에 두지 않고, purge할 때는 메모리 캐시를 훑어 바탕 키가 같은 것을 지운다.
+//!
+//! - `trim`: 리사이즈 전에 완전히 투명한 가장자리를 잘라 낸다. 여백이 큰 이모트도 출력 박스를 꽉 채운다.
+//!   애니메이션은 모든 프레임을 합친 영역으로 잘라 프레임끼리 어긋나지 않게 한다.
+//! - `stroke`: 리사이즈한 결과의 알파 윤곽을 따라 폭 `W`의 색 테두리를 그린다. 테두리가 잘리지 않도록 캔버스를
+//!   사방으로 `W`씩 키운다. 알파를 원판 모양으로 넓힌(dilate) 것을 테두리로 깔고 원래 픽셀을 위에 얹는다.
+//! - `shadow`: `x,y`만큼 옮기고 `blur`만큼 번진 그림자를 이모트 밑에 깐다 (테두리가 있으면 테두리까지 포함한 윤곽).
+//!   번짐은 상자 흐림 세 번으로 가우시안을 흉내 내고, 캔버스는 옮긴 거리와 번짐이 닿는 만큼 사방으로 똑같이 키워
+//!   이모트가 가운데에 남게 한다.
+//! - `pad_px`: 리사이즈한 결과 둘레에 투명한 여백을 N픽셀씩 더한다 (출력은 가로·세로가 `2N`씩 커진다).
+//!   격자 UI에서 이모트끼리 붙지 않게 할 때 쓴다.
+
+use image::{imageops, RgbaImage};
+
+use crate::{error::Error, EmojiParams};
+
+/// 캐시 키에서 모양 옵션 꼬리표가 시작되는 곳
+const KEY_TAG: &str = "~fx-";
+/// `pad_px` 상한 (가장 큰 출력 박스에 더해도 지나치게 커지지 않도록)
+pub const MAX_PAD_PX: u32 = 64;
+/// `stroke` 폭 상한
+pub const MAX_STROKE_PX: u32 = 16;
+/// `shadow` 오프셋(절댓값)과 번짐 상한
+pub const MAX_SHADOW_PX: u32 = 32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Effects {
+    /// 완전히 투명한 가장자리를 잘라 내고 리사이즈
+    pub trim: bool,
+    /// 알파 윤곽을 따라 그릴 테두리
+    pub stroke: Option<Stroke>,
+    /// 이모트 밑에 깔 그림자
+    pub shadow: Option<Shadow>,
+    /// 리사이즈한 결과 둘레에 더할 투명한 여백 (픽셀)
+    pub pad_px: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stroke {
+    /// 폭 (픽셀, 1~[`MAX_STROKE_PX`])
+    pub width: u32,
+    pub color: [u8; 3],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shadow {
+    pub x: i32,
+    pub y: i32,
+    /// 번짐 반경 (0이면 또렷한 그림자)
+    pub blur: u32,
+    pub color: [u8; 4],
+}
+
+impl Effects {
+    pub const NONE: Effects = Effects { trim: false, stroke: None, shadow: None, pad_px: 0 };
+
+    pub(crate) fn from_params(params: &EmojiParams) -> Result<Self, Error> {
+        let trim = match params.trim.as_deref() {
+            None | Some("0" | "false" | "no") => false,
+            Some("1" | "true" | "yes") => true,
+            Some(_) => return Err(Error::BadRequest("trim must be 1 or 0")),
+        };
+        let pad_px = match params.pad_px.as_deref() {
+            None => 0,
+            Some(value) => match value.parse::<u32>() {
+                Ok(pad_px) if pad_px <= MAX_PAD_PX => pad_px,
+                Ok(_) => return Err(Error::BadRequest("pad_px must be at most 64")),
+                Err(_) => return Err(Error::BadRequest("pad_px must be a number of pixels")),
+            },
+        };
+        let stroke = params.stroke.as_deref().map(Stroke::parse).transpose()?.flatten();
+        let shadow = params.shadow.as_deref().map(Shadow::parse).transpose()?;
+        Ok(Self { trim, stroke, shadow, pad_px })
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::NONE
+    }
+
+    /// 정규 형태의 쿼리 (`trim=1&pad_px=8`). 옵션이 없으면 빈 문자열
+    pub fn query(&self) -> String {
+        let mut pairs = Vec::new();
+        if self.trim {
+            pairs.push("trim=1".to_string());
+        }
+        if let Some(stroke) = self.stroke {
+            pairs.push(format!("stroke={},{}", stroke.width, hex(&stroke.color)));
+        }
+        if let Some(Shadow { x, y, blur, color }) = self.shadow {
+            pairs.push(format!("shadow={x},{y},{blur},{}", hex(&color)));
+        }
+        if self.pad_px > 0 {
+            pairs.push(format!("pad_px={}", self.pad_px));
+        }
+        pairs.join("&")
+    }
+
+    /// 리다이렉트할 주소에 옵션을 이어 붙인다
+    pub fn carry(&self, url: &str) -> String {
+        match self.query() {
+            query if query.is_empty() => url.to_string(),
+            query if url.contains('?') => format!("{url}&{query}"),
+            query => format!("{url}?{query}"),
+        }
+    }
+
+    /// 옵션을 켠 결과의 캐시 키 (`123@64~fx-trim-stroke2.ffffff-shadow2.2.4.00000080-pad8`)
+    pub fn cache_key(&self, key: &str) -> String {
+        let mut tags = Vec::new();
+        if self.trim {
+            tags.push("trim".to_string());
+        }
+        if let Some(stroke) = self.stroke {
+            tags.push(format!("stroke{}.{}", stroke.width, hex(&stroke.color)));
+        }
+        if let Some(Shadow { x, y, blur, color }) = self.shadow {
+            tags.push(format!("shadow{x}.{y}.{blur}.{}", hex(&color)));
+        }
+        if self.pad_px > 0 {
+            tags.push(format!("pad{}", self.pad_px));
+        }
+        if tags.is_empty() {
+            return key.to_string();
+        }
+        format!("{key}{KEY_TAG}{}", tags.join("-"))
+    }
+
+    /// 리사이즈 전에 프레임들에 적용한다 (애니메이션은 모든 프레임을 같은 영역으로)
+    pub fn before_resize(&self, frames: &mut [RgbaImage]) {
+        if !self.trim {
+            return;
+        }
+        let Some((x, y, width, height)) = opaque_bounds(frames.iter()) else {
+            return;
+        };
+        // 영역이 프레임 전체면 그대로 둔다
+        for frame in frames.iter_mut().filter(|f| f.dimensions() != (width, height)) {
+            *frame = imageops::crop_imm(frame, x, y, width, height).to_image();
+        }
+    }
+
+    /// 리사이즈한 한 프레임에 적용한다 (테두리 → 그림자 → 여백)
+    pub fn after_resize(&self, mut frame: RgbaImage) -> RgbaImage {
+        if let Some(stroke) = self.stroke {
+            frame = stroke.apply(&frame);
+        }
+        if let Some(shadow) = self.shadow {
+            frame = shadow.apply(&frame);
+        }
+        if self.pad_px > 0 {
+            frame = extend(&frame, self.pad_px);
+        }
+        frame
+    }
+
+    /// 리사이즈한 크기에 [`Self::after_resize`]를 적용한 뒤의 출력 크기
+    pub fn output_dimensions(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        let grow = 2 * (self.pad_px + self.stroke.map_or(0, |s| s.width) + self.shadow.map_or(0, |s| s.margin()));
+        (width + grow, height + grow)
+    }
+}
+
+impl Stroke {
+    /// `2,ffffff` (색을 빼면 흰색). 폭 0은 테두리 없음.
+    fn parse(value: &str) -> Result<Option<Self>, Error> {
+        let (width, color) = match value.split_once(',') {
+            Some((width, color)) => (width, parse_color(color)),
+            None => (value, Some([0xFF; 4])),
+        };
+        let (Ok(width), Some([r, g, b, 0xFF])) = (width.parse::<u32>(), color) else {
+            return Err(Error::BadRequest("stroke must look like 2,ffffff"));
+        };
+        if width > MAX_STROKE_PX {
+            return Err(Error::BadRequest("stroke width must be at most 16"));
+        }
+        Ok((width > 0).then_some(Self { width, color: [r, g, b] }))
+    }
+
+    /// 캔버스를 폭만큼 키우고, 넓힌 알파에 테두리 색을 칠해 깐 위에 원래 픽셀을 얹는다
+    fn apply(&self, frame: &RgbaImage) -> RgbaImage {
+        let mut canvas = extend(frame, self.width);
+        let alpha = dilate(&canvas, self.width);
+        underlay(&mut canvas, self.color, &alpha);
+        canvas
+    }
+}
+
+impl Shadow {
+    /// `x,y,blur[,RRGGBB[AA]]` (색을 빼면 반투명 검정 `00000080`)
+    fn parse(value: &str) -> Result<Self, Error> {
+        let invalid = || Error::BadRequest("shadow must look like 2,2,4,000000");
+        let mut parts = value.split(',');
+        let mut number = || parts.next().and_then(|v| v.trim().parse::<i32>().ok()).ok_or_else(invalid);
+        let (x, y, blur) = (number()?, number()?, number()?);
+        let color = match parts.next() {
+            Some(color) => parse_color(color.trim()).ok_or_else(invalid)?,
+            None => [0, 0, 0, 0x80],
+        };
+        if parts.next().is_some() || blur < 0 {
+            return Err(invalid());
+        }
+        if x.unsigned_abs().max(y.unsigned_abs()).max(blur as u32) > MAX_SHADOW_PX {
+            return Err(Error::BadRequest("shadow offset and blur must be at most 32"));
+        }
+        Ok(Self { x, y, blur: blur as u32, color })
+    }
+
+    /// 상자 흐림 한 번의 반폭. 세 번 겹친 분산이 `(blur / 2)²`에 가깝도록 고른다.
+    fn box_radius(&self) -> u32 {
+        (((self.blur * self.blur + 1) as f64).sqrt() / 2.0 - 0.5).round() as u32
+    }
+
+    /// 그림자가 잘리지 않도록 사방으로 키울 폭
+    fn margin(&self) -> u32 {
+        self.x.unsigned_abs().max(self.y.unsigned_abs()) + 3 * self.box_radius()
+    }
+
+    fn apply(&self, frame: &RgbaImage) -> RgbaImage {
+        let mut canvas = extend(frame, self.margin());
+        let (width, height) = (canvas.width() as usize, canvas.height() as usize);
+        let mut plane: Vec<f32> = canvas.pixels().map(|p| p[3] as f32).collect();
+        let radius = self.box_radius() as usize;
+        if radius > 0 {
+            for _ in 0..3 {
+                box_blur(&mut plane, width, height, radius, 1);
+                box_blur(&mut plane, width, height, radius, width);
+            }
+        }
+        let [r, g, b, a] = self.color;
+        let mut alpha = vec![0u8; width * height];
+        for y in 0..height {
+            let Some(sy) = y.checked_add_signed(-self.y as isize).filter(|&sy| sy < height) else {
+                continue;
+            };
+            for x in 0..width {
+                if let Some(sx) = x.checked_add_signed(-self.x as isize).filter(|&sx| sx < width) {
+                    alpha[y * width + x] = (plane[sy * width + sx] * a as f32 / 255.0).round() as u8;
+                }
+            }
+        }
+        underlay(&mut canvas, [r, g, b], &alpha);
+        canvas
+    }
+}
+
+/// `plane`을 `step` 간격(가로 1, 세로 한 줄 폭)으로 반폭 `radius`의 상자 평균으로 흐린다. 밖은 0으로 본다.
+fn box_blur(plane: &mut [f32], width: usize, height: usize, radius: usize, step: usize) {
+    let (lines, len, line_step) = if step == 1 { (height, width, width) } else { (width, height, 1) };
+    let scale = 1.0 / (2 * radius + 1) as f32;
+    let mut line = vec![0f32; len];
+    for l in 0..lines {
+        let at = |i: usize| l * line_step + i * step;
+        for (i, value) in line.iter_mut().enumerate() {
+            *value = plane[at(i)];
+        }
+        let mut sum: f32 = line.iter().take(radius).sum();
+        for i in 0..len {
+            if i + radius < len {
+                sum += line[i + radius];
+            }
+            if i > radius {
+                sum -= line[i - radius - 1];
+            }
+            plane[at(i)] = sum * scale;
+        }
+    }
+}
+
+/// `color`를 픽셀별 `alpha`로 칠한 층을 `canvas` 밑에 깐다 (스트레이트 알파 over 합성)
+fn underlay(canvas: &mut RgbaImage, color: [u8; 3], alpha: &[u8]) {
+    for (pixel, &under) in canvas.pixels_mut().zip(alpha) {
+        let top = pixel[3] as u32;
+        let out = top * 255 + under as u32 * (255 - top);
+        if out == 0 {
+            continue;
+        }
+        for (c, &layer) in color.iter().enumerate() {
+            let value = pixel[c] as u32 * top * 255 + layer as u32 * under as u32 * (255 - top);
+            pixel[c] = ((value + out / 2) / out) as u8;
+        }
+        pixel[3] = ((out + 127) / 255) as u8;
+    }
+}
+
+/// 사방으로 `by`픽셀씩 투명하게 넓힌 캔버스
+fn extend(frame: &RgbaImage, by: u32) -> RgbaImage {
+    let mut canvas = RgbaImage::new(frame.width() + 2 * by, frame.height() + 2 * by);
+    imageops::replace(&mut canvas, frame, by as i64, by as i64);
+    canvas
+}
+
+/// 반지름 `radius`인 원판 안의 최대 알파. 줄마다 가로 반폭 `h`의 최댓값을 `h-1`에서 한 칸씩 넓혀 구하고,
+/// 세로로 `dy`만큼 떨어진 줄에서는 원판의 그 높이 반폭을 가져다 쓴다.
+fn dilate(image: &RgbaImage, radius: u32) -> Vec<u8> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let r = radius as usize;
+    // spans[h][y * width + x] = 같은 줄 x-h..=x+h의 최대 알파
+    let mut spans: Vec<Vec<u8>> = Vec::with_capacity(r + 1);
+    spans.push(image.pixels().map(|p| p[3]).collect());
+    for h in 1..=r {
+        let prev = &spans[h - 1];
+        let next: Vec<u8> = (0..width * height)
+            .map(|i| {
+                let x = i % width;
+                let left = if x > 0 { prev[i - 1] } else { 0 };
+                let right = if x + 1 < width { prev[i + 1] } else { 0 };
+                prev[i].max(left).max(right)
+            })
+            .collect();
+        spans.push(next);
+    }
+    let half_widths: Vec<usize> = (0..=r).map(|dy| ((r * r - dy * dy) as f64).sqrt().round() as usize).collect();
+    let mut out = vec![0u8; width * height];
+    for y in 0..height {
+        for (dy, &h) in half_widths.iter().enumerate() {
+            for source in [y.checked_sub(dy), (y + dy < height).then_some(y + dy)].into_iter().flatten() {
+                let (row, src) = (&mut out[y * width..(y + 1) * width], &spans[h][source * width..(source + 1) * width]);
+                for (o, &s) in row.iter_mut().zip(src) {
+                    *o = (*o).max(s);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// `RRGGBB` 또는 `RRGGBBAA` (알파를 빼면 불투명)
+fn parse_color(hex: &str) -> Option<[u8; 4]> {
+    if !matches!(hex.len(), 6 | 8) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2).unwrap_or("ff"), 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?, channel(6)?])
+}
+
+fn hex(channels: &[u8]) -> String {
+    channels.iter().map(|c| format!("{c:02x}")).collect()
+}
+
+/// 모양 옵션을 켜고 만든 결과의 키인지 (2차 계층에 두지 않는다)
+pub fn is_effect_key(key: &str) -> bool {
+    key.contains(KEY_TAG)
+}
+
+/// 완전히 투명하지 않은 픽셀을 모두 담는 가장 작은 영역 `(x, y, 가로, 세로)`. 모두 투명하면 `None`.
+pub fn opaque_bounds<'a>(frames: impl Iterator<Item = &'a RgbaImage>) -> Option<(u32, u32, u32, u32)> {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for frame in frames {
+        for (x, y, _) in frame.enumerate_pixels().filter(|(_, _, p)| p[3] != 0) {
+            bounds = Some(match bounds {
+                None => (x, y, x, y),
+                Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+            });
+        }
+    }
+    bounds.map(|(left, top, right, bottom)| (left, top, right - left + 1, bottom - top + 1))
+}
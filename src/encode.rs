@@ -0,0 +1,58 @@
+//! WebP를 못 읽는 클라이언트용 PNG·GIF 인코딩 (`/e/:id.png`, `/e/:id.gif`).
+//!
+//! - PNG: 8비트 RGBA를 `image`의 `PngEncoder`로 (최고 압축, 줄마다 적응 필터).
+//! - GIF: `image`의 `GifEncoder`로. 프레임마다 NeuQuant로 뽑은 지역 팔레트를 쓴다. GIF 알파는 켜고 끄기뿐이라
+//!   알파 128 미만은 투명하게, 나머지는 불투명하게 미리 고른다. 프레임은 캔버스 전체를 덮고 배경으로 폐기되므로
+//!   투명한 곳에 앞 프레임이 남지 않는다.
+
+use image::{
+    codecs::{
+        gif::{GifEncoder, Repeat},
+        png::{CompressionType, FilterType, PngEncoder},
+    },
+    Delay, ExtendedColorType, Frame, ImageEncoder, Rgba, RgbaImage,
+};
+
+use crate::imaging::ImagingError;
+
+/// NeuQuant 표본 간격 (1이 가장 느리고 정확하다). `gif` 크레이트가 권하는 기본값.
+const GIF_SPEED: i32 = 10;
+
+pub fn png(image: &RgbaImage) -> Result<Vec<u8>, ImagingError> {
+    let mut out = Vec::new();
+    PngEncoder::new_with_quality(&mut out, CompressionType::Best, FilterType::Adaptive)
+        .write_image(image.as_raw(), image.width(), image.height(), ExtendedColorType::Rgba8)
+        .map_err(ImagingError::Encode)?;
+    Ok(out)
+}
+
+/// 같은 크기의 프레임들(지연 ms)을 GIF로. `loop_count`는 WebP `ANIM`과 같은 뜻(0이면 무한)이고, 프레임이
+/// 하나면 정지 GIF가 된다.
+pub fn gif(width: u16, height: u16, loop_count: u16, frames: &[(RgbaImage, u32)]) -> Result<Vec<u8>, ImagingError> {
+    debug_assert!(frames.iter().all(|(image, _)| image.dimensions() == (width as u32, height as u32)));
+    let mut out = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut out, GIF_SPEED);
+        // 한 번 재생이면 확장을 빼고, 그 밖에는 처음 재생 뒤 더 돌 횟수를 적는다
+        let repeat = match loop_count {
+            _ if frames.len() < 2 => None,
+            0 => Some(Repeat::Infinite),
+            1 => None,
+            n => Some(Repeat::Finite(n - 1)),
+        };
+        if let Some(repeat) = repeat {
+            encoder.set_repeat(repeat).map_err(ImagingError::Encode)?;
+        }
+        for (image, delay_ms) in frames {
+            // 1cs 이하는 브라우저가 100ms로 늘리므로 2cs(20ms)보다 짧게 적지 않는다
+            let delay_cs = if frames.len() < 2 { 0 } else { delay_ms.div_ceil(10).clamp(2, u16::MAX as u32) };
+            let mut image = image.clone();
+            for pixel in image.pixels_mut() {
+                *pixel = if pixel[3] < 128 { Rgba([0, 0, 0, 0]) } else { Rgba([pixel[0], pixel[1], pixel[2], 255]) };
+            }
+            let frame = Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(delay_cs * 10, 1));
+            encoder.encode_frame(frame).map_err(ImagingError::Encode)?;
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,237 @@
+//! 이미지 요청 처리 중의 실패를 한곳에 모은 에러 타입.
+//!
+//! 처리 경로는 상태 코드와 메시지를 그 자리에서 만들지 않고 [`Error`]를 돌려준다. 어느 업스트림의
+//! 어떤 응답 때문인지와 다시 시도할 만한지를 함께 실어 두어서, 응답 형식·메트릭 라벨·재시도를
+//! 한 군데에서 정할 수 있게 한다. 응답 본문은 지금까지와 같은 짧은 문구이고, 응답 확장(extensions)에
+//! 에러 자체를 넣어 미들웨어가 읽을 수 있게 한다.
+//!
+//! 봇이 "지워진 이모지"와 "잠깐의 Discord 장애"를 코드로 구분할 수 있도록 모든 에러 응답에
+//! [`X_ERROR_CODE`] 헤더로 [`Error::code`]를 싣고, JSON을 원하는 클라이언트에게는
+//! [`crate::middleware::problem_json`]이 본문을 `application/problem+json`(RFC 9457)으로 바꾼다.
+
+use axum::{
+    http::{HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::fmt;
+
+use crate::upstream::Source;
+
+/// 에러 응답에 싣는 [`Error::code`]
+pub const X_ERROR_CODE: HeaderName = HeaderName::from_static("x-error-code");
+
+/// problem+json `type`의 접두어 (뒤에 [`Error::code`]가 붙는다)
+pub const PROBLEM_TYPE_PREFIX: &str = "urn:emoji-resizer:error:";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// 경로나 쿼리 인자가 잘못됐다 (문구가 곧 응답 본문)
+    BadRequest(&'static str),
+    /// 차단 목록에 걸린 이모지
+    Blocked,
+    /// 업스트림이나 업로드 저장소에 없는 이모지
+    NotFound,
+    /// 업스트림 요청이 타임아웃으로 끝났다
+    UpstreamTimeout { source: Source },
+    /// 연결이나 요청 자체가 실패했다
+    UpstreamUnreachable { source: Source },
+    /// 업스트림이 404가 아닌 실패 상태로 응답했다
+    UpstreamStatus { source: Source, status: StatusCode },
+    /// 본문을 끝까지 읽지 못했다
+    UpstreamRead { source: Source },
+    /// 원본 크기 한도(`ORIGINAL_MAX_BYTES`)를 넘었다
+    UpstreamTooLarge { source: Source },
+    /// 리다이렉트 정책(`UPSTREAM_<소스>_MAX_REDIRECTS`, `UPSTREAM_<소스>_REDIRECT_HOSTS`)에 걸렸다
+    UpstreamRedirect { source: Source },
+    /// 이미지가 아닌 본문 (`reason`은 검증에 실패한 이유)
+    UpstreamInvalid { source: Source, reason: &'static str },
+    /// 업로드 저장소를 읽지 못했거나 저장본이 이미지가 아니다
+    Storage,
+    /// 요청 마감 시각을 넘겼다
+    DeadlineExceeded,
+    /// 원본을 이미지로 풀지 못했다
+    Decode,
+    /// 리사이즈/인코딩 작업이 실패했다
+    Processing,
+    /// 인코딩 대기열이 쌓여 새 요청을 잠시 받지 않는다 (`ADMISSION_QUEUE_SOFT`)
+    Throttled,
+    /// 대기열이 한계에 가까워 거의 모든 요청을 돌려보낸다 (`ADMISSION_QUEUE_HARD`)
+    Overloaded,
+}
+
+impl Error {
+    pub fn status(self) -> StatusCode {
+        match self {
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Error::Blocked => StatusCode::GONE,
+            Error::NotFound => StatusCode::NOT_FOUND,
+            Error::UpstreamTimeout { .. } | Error::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+            Error::UpstreamUnreachable { .. }
+            | Error::UpstreamStatus { .. }
+            | Error::UpstreamRead { .. }
+            | Error::UpstreamTooLarge { .. }
+            | Error::UpstreamRedirect { .. }
+            | Error::UpstreamInvalid { .. } => StatusCode::BAD_GATEWAY,
+            Error::Storage | Error::Processing => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::Decode => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::Throttled => StatusCode::TOO_MANY_REQUESTS,
+            Error::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// 응답 본문에 싣는 문구
+    pub fn message(self) -> &'static str {
+        match self {
+            Error::BadRequest(message) => message,
+            Error::Blocked => "emoji unavailable",
+            Error::NotFound => "emoji not found",
+            Error::UpstreamTimeout { .. } => "upstream timed out",
+            Error::UpstreamUnreachable { .. } => "upstream fetch failed",
+            Error::UpstreamStatus { .. } => "upstream error",
+            Error::UpstreamRead { .. } => "upstream read failed",
+            Error::UpstreamTooLarge { .. } => "upstream payload too large",
+            Error::UpstreamRedirect { .. } => "upstream redirect refused",
+            Error::UpstreamInvalid { .. } => "upstream returned non-image",
+            Error::Storage => "storage read failed",
+            Error::DeadlineExceeded => "request deadline exceeded",
+            Error::Decode => "decode failed",
+            Error::Processing => "processing failed",
+            Error::Throttled => "too many requests, retry later",
+            Error::Overloaded => "server overloaded, retry later",
+        }
+    }
+
+    /// 메트릭 라벨용 짧은 이름
+    pub fn kind(self) -> &'static str {
+        match self {
+            Error::BadRequest(_) => "bad_request",
+            Error::Blocked => "blocked",
+            Error::NotFound => "not_found",
+            Error::UpstreamTimeout { .. } => "upstream_timeout",
+            Error::UpstreamUnreachable { .. } => "upstream_unreachable",
+            Error::UpstreamStatus { .. } => "upstream_status",
+            Error::UpstreamRead { .. } => "upstream_read",
+            Error::UpstreamTooLarge { .. } => "upstream_too_large",
+            Error::UpstreamRedirect { .. } => "upstream_redirect",
+            Error::UpstreamInvalid { .. } => "upstream_invalid",
+            Error::Storage => "storage",
+            Error::DeadlineExceeded => "deadline",
+            Error::Decode => "decode",
+            Error::Processing => "processing",
+            Error::Throttled => "throttled",
+            Error::Overloaded => "overloaded",
+        }
+    }
+
+    /// 클라이언트에게 알리는 안정된 에러 코드. [`Error::kind`]와 같되, 업스트림 실패 상태는 원인별로 나눈다:
+    /// 403은 만료된 첨부 URL이나 접근할 수 없게 된 이모지, 415는 업스트림이 거부한 형식, 429는 업스트림
+    /// 스로틀링, 5xx는 일시적인 업스트림 장애.
+    pub fn code(self) -> &'static str {
+        match self {
+            Error::UpstreamStatus { status, .. } => match status {
+                StatusCode::FORBIDDEN => "upstream_forbidden",
+                StatusCode::UNSUPPORTED_MEDIA_TYPE => "upstream_unsupported_media_type",
+                StatusCode::TOO_MANY_REQUESTS => "upstream_rate_limited",
+                s if s.is_server_error() => "upstream_unavailable",
+                _ => "upstream_status",
+            },
+            _ => self.kind(),
+        }
+    }
+
+    /// 클라이언트가 할 수 있는 일을 알려 주는 설명 (problem+json `detail`)
+    pub fn hint(self) -> Option<&'static str> {
+        match self {
+            Error::NotFound => Some("the emoji does not exist or was deleted; retrying will not help"),
+            Error::Blocked => Some("the emoji was removed from this service"),
+            Error::UpstreamStatus { status, .. } => match status {
+                StatusCode::FORBIDDEN => Some("the attachment URL expired or the emoji is no longer public; fetch a fresh URL"),
+                StatusCode::UNSUPPORTED_MEDIA_TYPE => Some("the upstream does not serve this emoji in the requested format"),
+                StatusCode::TOO_MANY_REQUESTS => Some("the upstream is rate limiting this service; retry later"),
+                s if s.is_server_error() => Some("temporary upstream outage; retry later"),
+                _ => None,
+            },
+            Error::UpstreamTimeout { .. } | Error::UpstreamUnreachable { .. } => Some("temporary upstream outage; retry later"),
+            _ => None,
+        }
+    }
+
+    /// RFC 9457 problem details 본문
+    pub fn problem(self) -> serde_json::Value {
+        let code = self.code();
+        serde_json::json!({
+            "type": format!("{PROBLEM_TYPE_PREFIX}{code}"),
+            "title": self.message(),
+            "status": self.status().as_u16(),
+            "detail": self.hint().map_or_else(|| self.to_string(), str::to_owned),
+            "code": code,
+            "retryable": self.retryable(),
+            "upstream": self.source().map(|s| s.name()),
+            "upstream_status": self.upstream_status().map(|s| s.as_u16()),
+        })
+    }
+
+    /// 실패의 원인이 된 업스트림 (업스트림과 무관하면 `None`)
+    pub fn source(self) -> Option<Source> {
+        match self {
+            Error::UpstreamTimeout { source }
+            | Error::UpstreamUnreachable { source }
+            | Error::UpstreamStatus { source, .. }
+            | Error::UpstreamRead { source }
+            | Error::UpstreamTooLarge { source }
+            | Error::UpstreamRedirect { source }
+            | Error::UpstreamInvalid { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+
+    /// 업스트림이 실제로 돌려준 상태 코드
+    pub fn upstream_status(self) -> Option<StatusCode> {
+        match self {
+            Error::UpstreamStatus { status, .. } => Some(status),
+            _ => None,
+        }
+    }
+
+    /// 같은 요청을 나중에 다시 보내면 성공할 수도 있는지. 일시적인 네트워크 문제, 업스트림의 5xx/429,
+    /// 마감 초과, 저장소 읽기 실패는 그렇고, 결과가 바뀌지 않는 차단·없음·잘못된 원본은 아니다.
+    pub fn retryable(self) -> bool {
+        match self {
+            Error::UpstreamTimeout { .. }
+            | Error::UpstreamUnreachable { .. }
+            | Error::UpstreamRead { .. }
+            | Error::Storage
+            | Error::DeadlineExceeded
+            | Error::Throttled
+            | Error::Overloaded => true,
+            Error::UpstreamStatus { status, .. } => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+            Error::BadRequest(_)
+            | Error::Blocked
+            | Error::NotFound
+            | Error::UpstreamTooLarge { .. }
+            | Error::UpstreamRedirect { .. }
+            | Error::UpstreamInvalid { .. }
+            | Error::Decode
+            | Error::Processing => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.source(), self.upstream_status()) {
+            (Some(source), Some(status)) => write!(f, "{} ({} upstream, status {})", self.message(), source.name(), status),
+            (Some(source), None) => write!(f, "{} ({} upstream)", self.message(), source.name()),
+            _ => f.write_str(self.message()),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let mut res = (self.status(), self.message()).into_response();
+        res.headers_mut().insert(X_ERROR_CODE, HeaderValue::from_static(self.code()));
+        res.extensions_mut().insert(self);
+        res
+    }
+}
@@ -0,0 +1,42 @@
+//! 정적 WebP 인코더 설정 실험 (`ENCODER_EXPERIMENT`).
+//!
+//! 캐시 미스 중 `ENCODER_EXPERIMENT_PERCENT`만큼을 실험 설정(`ENCODER_EXPERIMENT_WEBP_*`)으로 인코딩하고 나머지는
+//! 기본 설정(`control`)으로 인코딩한다. 새로 인코딩한 응답에는 어느 쪽으로 만들었는지 `X-Encoder-Profile`로 싣고,
+//! 두 쪽의 출력 크기와 인코딩 시간을 같은 메트릭에 `profile` 라벨로 나눠 남겨서 기본값을 바꾸기 전에 실제
+//! 트래픽으로 비교할 수 있게 한다. 실험으로 만든 결과도 그대로 캐시에 들어가므로, 비율은 요청이 아니라 새로
+//! 만드는 캐시 항목에 대한 것이다.
+
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::{
+    imaging::WebpOptions,
+    metrics::{ENCODER_PROFILE_BYTES, ENCODER_PROFILE_SECONDS, ENCODER_PROFILE_TOTAL},
+};
+
+/// 실험하지 않는 쪽의 프로필 이름
+pub const CONTROL: &str = "control";
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Experiment {
+    /// 헤더와 메트릭에 쓰는 프로필 이름
+    pub name: String,
+    /// 실험 설정으로 인코딩할 미스 비율 (0~100)
+    pub percent: f64,
+    pub webp: WebpOptions,
+}
+
+impl Experiment {
+    /// 이번 미스를 실험 설정으로 인코딩할지 고른다
+    pub fn pick(&self) -> bool {
+        let roll = (uuid::Uuid::new_v4().as_u128() % 10_000) as f64;
+        roll < self.percent * 100.0
+    }
+}
+
+/// 프로필별 인코딩 결과를 기록한다
+pub fn record(profile: &str, bytes: usize, encode: Duration) {
+    ::metrics::counter!(ENCODER_PROFILE_TOTAL, "profile" => profile.to_owned()).increment(1);
+    ::metrics::histogram!(ENCODER_PROFILE_BYTES, "profile" => profile.to_owned()).record(bytes as f64);
+    ::metrics::histogram!(ENCODER_PROFILE_SECONDS, "profile" => profile.to_owned()).record(encode.as_secs_f64());
+}
@@ -0,0 +1,163 @@
+//! Mastodon/Misskey 커스텀 이모지 소스 (`/fedi/:instance/:shortcode`).
+//!
+//! 허용 목록(`FEDI_INSTANCES`)에 있는 인스턴스만 조회한다. 인스턴스의 이모지 목록을
+//! Mastodon API(`/api/v1/custom_emojis`)로 먼저 받아 보고, 없으면 Misskey API(`/api/emojis`)를
+//! 쓴다. 목록은 `FEDI_EMOJI_LIST_TTL_SECS` 동안 재사용한다.
+
+use anyhow::Context;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use moka::future::Cache;
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tracing::{error, info, warn};
+
+use crate::{
+    middleware, pick_variant, purge, serve_image, upstream::Upstream, AppState, EmojiParams, Fetch, Mode, Origin,
+    RequestInputs,
+};
+
+pub struct Fedi {
+    /// `upstream::Source::Fedi` 클라이언트 (임의의 인스턴스 서버를 상대하므로 기본은 협상)
+    http: Upstream,
+    /// `host[:port]` → API 바탕 주소 (`https://host`)
+    instances: HashMap<String, String>,
+    /// 인스턴스별 shortcode → 이미지 URL
+    lists: Cache<String, Arc<HashMap<String, String>>>,
+}
+
+#[derive(Deserialize)]
+struct MastodonEmoji {
+    shortcode: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct MisskeyEmojis {
+    emojis: Vec<MisskeyEmoji>,
+}
+
+#[derive(Deserialize)]
+struct MisskeyEmoji {
+    name: String,
+    url: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+impl Fedi {
+    /// 허용 목록이 비어 있으면 `None` (라우트를 붙이지 않는다).
+    /// 항목은 `mastodon.social`처럼 호스트만 적거나 `http://127.0.0.1:3000`처럼 주소 전체를 적는다.
+    pub fn new(allowlist: &[String], list_ttl: Duration, http: Upstream) -> anyhow::Result<Option<Self>> {
+        if allowlist.is_empty() {
+            return Ok(None);
+        }
+        let instances = allowlist
+            .iter()
+            .map(|entry| {
+                let entry = entry.trim_end_matches('/').to_ascii_lowercase();
+                match entry.split_once("://") {
+                    Some((_, host)) => (host.to_string(), entry.clone()),
+                    None => (entry.clone(), format!("https://{entry}")),
+                }
+            })
+            .collect();
+        Ok(Some(Self {
+            http,
+            instances,
+            lists: Cache::builder().max_capacity(256).time_to_live(list_ttl).build(),
+        }))
+    }
+
+    /// shortcode를 이미지 URL로. 허용되지 않은 인스턴스나 모르는 shortcode면 `None`.
+    async fn resolve(&self, instance: &str, shortcode: &str) -> anyhow::Result<Option<String>> {
+        let instance = instance.to_ascii_lowercase();
+        let Some(base) = self.instances.get(&instance) else {
+            return Ok(None);
+        };
+        let list = self
+            .lists
+            .try_get_with(instance, self.fetch_list(base))
+            .await
+            .map_err(|e| anyhow::anyhow!("{e:#}"))?;
+        Ok(list.get(shortcode).cloned())
+    }
+
+    async fn fetch_list(&self, base: &str) -> anyhow::Result<Arc<HashMap<String, String>>> {
+        let res = self
+            .http
+            .send(|c| c.get(format!("{base}/api/v1/custom_emojis")))
+            .await
+            .context("custom_emojis request")?;
+        // Mastodon API가 없으면 Misskey로 (Misskey는 이 경로에 404를 준다)
+        if res.status() != reqwest::StatusCode::NOT_FOUND {
+            let emojis: Vec<MastodonEmoji> = res.error_for_status()?.json().await.context("custom_emojis response")?;
+            info!("fetched {} custom emojis from {} (mastodon)", emojis.len(), base);
+            return Ok(Arc::new(emojis.into_iter().map(|e| (e.shortcode, e.url)).collect()));
+        }
+
+        let list: MisskeyEmojis = self
+            .http
+            .send(|c| c.get(format!("{base}/api/emojis")))
+            .await
+            .context("misskey emojis request")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("misskey emojis response")?;
+        info!("fetched {} custom emojis from {} (misskey)", list.emojis.len(), base);
+        let mut map = HashMap::new();
+        for emoji in list.emojis {
+            // 별칭은 같은 이름의 실제 이모지를 덮어쓰지 않는다
+            for alias in emoji.aliases.into_iter().filter(|a| !a.is_empty()) {
+                map.entry(alias).or_insert_with(|| emoji.url.clone());
+            }
+            map.insert(emoji.name, emoji.url);
+        }
+        Ok(Arc::new(map))
+    }
+}
+
+pub async fn handler(
+    State(state): State<AppState>,
+    Path((instance, shortcode)): Path<(String, String)>,
+    Query(params): Query<EmojiParams>,
+    deadline: Option<Extension<middleware::Deadline>>,
+    req: RequestInputs,
+) -> Response {
+    let Some(fedi) = state.fedi.clone() else {
+        return (StatusCode::NOT_FOUND, "fediverse source disabled").into_response();
+    };
+    let shortcode = shortcode.split('.').next().unwrap_or(&shortcode).trim_matches(':').to_string();
+    let variant = match pick_variant(&state.config, &params, &format!("/fedi/{instance}/{shortcode}")) {
+        Ok(v) => v,
+        Err(redirect) => return redirect.into_response(),
+    };
+    if state.blocklist.blocks_source("fedi") {
+        warn!("Blocked fediverse emoji requested: {}/{}", instance, shortcode);
+        return (StatusCode::GONE, "emoji unavailable").into_response();
+    }
+
+    let url = match fedi.resolve(&instance, &shortcode).await {
+        Ok(Some(url)) => url,
+        Ok(None) => return (StatusCode::NOT_FOUND, "emoji not found").into_response(),
+        Err(e) => {
+            error!("Fediverse emoji lookup failed for {}/{}: {:#}", instance, shortcode, e);
+            return (StatusCode::BAD_GATEWAY, "instance lookup failed").into_response();
+        }
+    };
+
+    let label = format!("fedi/{}/{shortcode}", instance.to_ascii_lowercase());
+    let origin = Origin {
+        tags: purge::tags(&label, "fedi", variant.size()),
+        key_base: label.clone(),
+        url,
+        label,
+        fetch: Fetch::Http(fedi.http.clone()),
+    };
+    serve_image(state, origin, variant, deadline.map(|Extension(d)| d.0), req, Mode::Interactive).await
+}
@@ -0,0 +1,82 @@
+//! 같은 인스턴스 안의 캐시 미스 묶기 (singleflight).
+//!
+//! 인기 이모지가 캐시에 없을 때 동시에 들어온 요청이 저마다 Discord에서 받아 인코딩하지 않도록, 키마다 먼저 온
+//! 요청 하나(리더)만 만들고 나머지는 그 결과를 기다렸다가 같은 바이트를 받는다. 리더가 결과 없이 끝나면(에러, 취소)
+//! 기다리던 요청은 각자 처리로 넘어간다. 인스턴스 사이의 조정은 2차 계층 임대([`crate::cache::Tiers::claim`])가 맡는다.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+use tokio::sync::watch;
+
+/// 키 → (리더 번호, 결과를 보낼 채널)
+type InFlight<T> = HashMap<String, (u64, watch::Sender<Option<T>>)>;
+
+pub struct Flights<T> {
+    inflight: Mutex<InFlight<T>>,
+    next_id: AtomicU64,
+}
+
+pub enum Flight<T> {
+    /// 이 요청이 만든다. 쥐고 있는 동안 같은 키의 요청은 기다린다.
+    Leader(FlightGuard<T>),
+    /// 먼저 온 요청이 만드는 중
+    Follower(watch::Receiver<Option<T>>),
+}
+
+/// 리더가 끝날 때(결과를 냈든 못 냈든) 자리를 비운다
+pub struct FlightGuard<T> {
+    flights: Arc<Flights<T>>,
+    key: String,
+    id: u64,
+}
+
+impl<T: Clone> Flights<T> {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self { inflight: Mutex::new(HashMap::new()), next_id: AtomicU64::new(0) })
+    }
+
+    pub fn join(self: &Arc<Self>, key: &str) -> Flight<T> {
+        let mut inflight = self.inflight.lock().unwrap();
+        if let Some((_, tx)) = inflight.get(key) {
+            return Flight::Follower(tx.subscribe());
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        inflight.insert(key.to_string(), (id, watch::channel(None).0));
+        Flight::Leader(FlightGuard { flights: self.clone(), key: key.to_string(), id })
+    }
+
+    /// 만든 결과를 기다리는 요청에 넘기고 자리를 비운다. 이 키를 만드는 중이 아니면 아무것도 하지 않는다.
+    pub fn publish(&self, key: &str, value: &T) {
+        if let Some((_, tx)) = self.inflight.lock().unwrap().remove(key) {
+            tx.send_replace(Some(value.clone()));
+        }
+    }
+}
+
+/// 리더의 결과를 `deadline`까지 기다린다. 리더가 결과 없이 끝났거나 기한이 지나면 `None`.
+pub async fn wait<T: Clone>(mut rx: watch::Receiver<Option<T>>, deadline: Option<Instant>) -> Option<T> {
+    let wait = async {
+        let value = rx.wait_for(Option::is_some).await.ok()?;
+        value.clone()
+    };
+    match deadline {
+        Some(at) => tokio::time::timeout_at(at.into(), wait).await.ok().flatten(),
+        None => wait.await,
+    }
+}
+
+impl<T> Drop for FlightGuard<T> {
+    fn drop(&mut self) {
+        let mut inflight = self.flights.inflight.lock().unwrap();
+        // 결과를 냈으면 이미 비었고, 그 뒤 다른 리더가 같은 키를 잡았을 수도 있다
+        if inflight.get(&self.key).is_some_and(|(id, _)| *id == self.id) {
+            inflight.remove(&self.key);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! 업스트림 호스트별 요청 속도·동시 요청 제한 (`UPSTREAM_<소스>_RATE_LIMIT`, `_BURST`, `_MAX_CONCURRENCY`).
+//!
+//! 업스트림 요청은 모두 [`crate::upstream::Upstream`]을 거치므로 여기서 호스트마다 토큰 버킷과 동시 요청 수
+//! 상한을 건다. 캐시를 비운 뒤나 워밍 작업이 한꺼번에 받으러 가도 업스트림(특히 Discord)이 스로틀링하기 전에
+//! 이쪽에서 줄을 세운다. 백그라운드 요청(워밍·선제 갱신·보관)은 버킷과 동시 요청 상한의 절반까지만 쓰므로
+//! 나머지는 언제나 사용자 요청 몫이다. 업스트림이 `429`로 답하면 `Retry-After`(최대 1분) 동안 그 호스트로
+//! 새 요청을 보내지 않는다. 이 일시 정지는 제한을 설정하지 않은 소스에도 적용된다.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{metrics, processing::Priority, upstream::ClientSettings};
+
+/// 백그라운드 요청이 쓸 수 있는 몫
+const BACKGROUND_SHARE: f64 = 0.5;
+
+/// `Retry-After`를 따르는 최대 시간
+const MAX_PAUSE: Duration = Duration::from_secs(60);
+
+/// 소스 하나의 호스트별 제한
+pub struct HostLimits {
+    source: &'static str,
+    rate: Option<f64>,
+    burst: f64,
+    max_concurrency: Option<usize>,
+    hosts: Mutex<HashMap<String, Arc<Host>>>,
+}
+
+struct Host {
+    bucket: Mutex<Bucket>,
+    slots: Option<Arc<Semaphore>>,
+    /// 백그라운드 요청이 먼저 잡아야 하는 몫
+    background: Option<Arc<Semaphore>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+    /// `429`를 받고 쉬는 중이면 다시 보내도 되는 시각
+    paused_until: Option<Instant>,
+}
+
+/// 요청을 보내는 동안 들고 있는 동시 요청 자리
+pub struct Permit {
+    _slot: Option<OwnedSemaphorePermit>,
+    _background: Option<OwnedSemaphorePermit>,
+}
+
+impl HostLimits {
+    pub fn new(source: &'static str, settings: &ClientSettings) -> Self {
+        Self {
+            source,
+            rate: settings.rate_limit,
+            burst: f64::from(settings.burst.max(1)),
+            max_concurrency: settings.max_concurrency,
+            hosts: Mutex::default(),
+        }
+    }
+
+    /// `host`로 요청을 보내도 될 때까지 기다린다
+    pub async fn acquire(&self, host: &str, priority: Priority) -> Permit {
+        let host = self.host(host);
+        let started = Instant::now();
+        let background = match (&host.background, priority) {
+            (Some(share), Priority::Low) => share.clone().acquire_owned().await.ok(),
+            _ => None,
+        };
+        let slot = match &host.slots {
+            Some(slots) => slots.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        // 백그라운드 요청은 버킷의 일부를 남겨 둔다 (버킷이 1이면 남길 수 없다)
+        let reserve = match priority {
+            Priority::High => 0.0,
+            Priority::Low => (self.burst * (1.0 - BACKGROUND_SHARE)).floor().min(self.burst - 1.0),
+        };
+        loop {
+            let wait = host.bucket.lock().unwrap().take(self.rate, self.burst, reserve);
+            match wait {
+                Some(wait) => tokio::time::sleep(wait).await,
+                None => break,
+            }
+        }
+        let waited = started.elapsed();
+        if waited >= Duration::from_millis(1) {
+            ::metrics::histogram!(metrics::UPSTREAM_QUEUE_SECONDS, "source" => self.source, "priority" => priority.name())
+                .record(waited.as_secs_f64());
+        }
+        Permit { _slot: slot, _background: background }
+    }
+
+    /// 업스트림이 `429`로 답했다. `retry_after` 동안 이 호스트로 보내지 않는다.
+    pub fn pause(&self, host: &str, retry_after: Duration) {
+        let until = Instant::now() + retry_after.min(MAX_PAUSE);
+        let host = self.host(host);
+        let mut bucket = host.bucket.lock().unwrap();
+        bucket.paused_until = bucket.paused_until.max(Some(until));
+        ::metrics::counter!(metrics::UPSTREAM_THROTTLED_TOTAL, "source" => self.source).increment(1);
+    }
+
+    fn host(&self, name: &str) -> Arc<Host> {
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(host) = hosts.get(name) {
+            return host.clone();
+        }
+        let host = Arc::new(Host {
+            bucket: Mutex::new(Bucket { tokens: self.burst, refilled: Instant::now(), paused_until: None }),
+            slots: self.max_concurrency.map(|n| Arc::new(Semaphore::new(n))),
+            background: self
+                .max_concurrency
+                .map(|n| Arc::new(Semaphore::new(((n as f64 * BACKGROUND_SHARE).floor() as usize).max(1)))),
+        });
+        hosts.insert(name.to_string(), host.clone());
+        host
+    }
+}
+
+impl Bucket {
+    /// 토큰을 하나 쓴다. `reserve`개는 남겨야 하며, 모자라면 다시 해 볼 때까지 기다릴 시간을 돌려준다.
+    fn take(&mut self, rate: Option<f64>, burst: f64, reserve: f64) -> Option<Duration> {
+        let now = Instant::now();
+        if let Some(until) = self.paused_until.filter(|until| *until > now) {
+            return Some(until - now);
+        }
+        let rate = rate?;
+        self.tokens = (self.tokens + now.duration_since(self.refilled).as_secs_f64() * rate).min(burst);
+        self.refilled = now;
+        if self.tokens >= 1.0 + reserve {
+            self.tokens -= 1.0;
+            return None;
+        }
+        Some(Duration::from_secs_f64((1.0 + reserve - self.tokens) / rate))
+    }
+}
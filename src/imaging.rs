@@ -0,0 +1,708 @@
+use fast_image_resize::{self as fr, IntoImageView, ResizeAlg, ResizeOptions, Resizer};
+use image::{
+    codecs::webp::WebPDecoder,
+    error::{DecodingError, EncodingError, ImageFormatHint},
+    imageops::FilterType,
+    metadata::LoopCount,
+    AnimationDecoder, DynamicImage, GenericImageView, ImageError, ImageFormat, RgbaImage,
+};
+use rayon::{prelude::*, ThreadPool};
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    fmt,
+    io::Cursor,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::effects::Effects;
+
+/// 출력 박스 한 변의 기본 크기 (종횡비 유지). `DEFAULT_SIZE`가 없을 때 쓰고, 이 크기의 캐시 키는 이모지 ID만이다
+pub const TARGET_SIZE: u32 = 160;
+
+#[derive(Debug)]
+pub enum ImagingError {
+    Decode(ImageError),
+    Encode(ImageError),
+    /// 요청 마감 시각이 지났거나 클라이언트가 떠나 작업을 중단함
+    Cancelled,
+}
+
+impl fmt::Display for ImagingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImagingError::Decode(e) => write!(f, "decode failed: {e}"),
+            ImagingError::Encode(e) => write!(f, "encode failed: {e}"),
+            ImagingError::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for ImagingError {}
+
+/// 오래 걸리는 처리를 중간에 멈출 조건. 요청 마감 시각이 지났거나, 응답을 기다리던 클라이언트가 떠나
+/// 요청 future가 버려졌을 때(`CLIENT_DISCONNECT=cancel`) 남은 작업을 하지 않는다.
+#[derive(Clone, Debug, Default)]
+pub struct Cancel {
+    pub deadline: Option<Instant>,
+    /// 요청 future가 버려지면 세워지는 표시
+    pub abandoned: Option<Arc<AtomicBool>>,
+}
+
+impl Cancel {
+    pub fn at(deadline: Option<Instant>) -> Self {
+        Self { deadline, abandoned: None }
+    }
+
+    pub fn check(&self) -> Result<(), ImagingError> {
+        let expired = self.deadline.is_some_and(|d| Instant::now() >= d);
+        let abandoned = self.abandoned.as_ref().is_some_and(|a| a.load(Ordering::Relaxed));
+        if expired || abandoned {
+            return Err(ImagingError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// 응답 본문의 실제 포맷. 리사이즈 결과는 WebP(확장자로 고르면 PNG·GIF)이고, 패스스루 원본과
+/// 재인코딩 실패 시 넘겨주는 원본만 다른 포맷일 수 있다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentType {
+    Webp,
+    Gif,
+    Png,
+    Jpeg,
+    Avif,
+}
+
+impl ContentType {
+    pub fn from_format(format: ImageFormat) -> Option<Self> {
+        match format {
+            ImageFormat::WebP => Some(ContentType::Webp),
+            ImageFormat::Gif => Some(ContentType::Gif),
+            ImageFormat::Png => Some(ContentType::Png),
+            ImageFormat::Jpeg => Some(ContentType::Jpeg),
+            ImageFormat::Avif => Some(ContentType::Avif),
+            _ => None,
+        }
+    }
+
+    /// 매직 바이트로 판별. 캐시에는 이미지만 들어가므로 알 수 없으면 WebP로 본다.
+    pub fn sniff(bytes: &[u8]) -> Self {
+        if is_avif_sequence(bytes) {
+            return ContentType::Avif;
+        }
+        image::guess_format(bytes)
+            .ok()
+            .and_then(Self::from_format)
+            .unwrap_or(ContentType::Webp)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContentType::Webp => "image/webp",
+            ContentType::Gif => "image/gif",
+            ContentType::Png => "image/png",
+            ContentType::Jpeg => "image/jpeg",
+            ContentType::Avif => "image/avif",
+        }
+    }
+}
+
+impl fmt::Display for ContentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 업스트림 본문이 실제로 이미지인지 확인한다 (Content-Type + 매직 바이트).
+/// 중간 프록시의 HTML 에러 페이지 같은 것이 캐시되지 않도록 캐시 전에 호출한다.
+/// 실패하면 거부 사유(`content_type` / `magic`)를 돌려준다.
+pub fn validate_upstream(content_type: Option<&str>, body: &[u8]) -> Result<ContentType, &'static str> {
+    if let Some(ct) = content_type {
+        let mime = ct.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        if !mime.starts_with("image/") && mime != "application/octet-stream" {
+            return Err("content_type");
+        }
+    }
+    image::guess_format(body)
+        .ok()
+        .and_then(ContentType::from_format)
+        .ok_or("magic")
+}
+
+/// 정적 이미지 리사이즈 결과
+pub struct Resized {
+    pub bytes: Vec<u8>,
+    pub original: (u32, u32),
+    pub resized: (u32, u32),
+    pub timings: StageTimings,
+    /// 애니메이션 크기 예산을 적용했을 때 고른 수준
+    pub budget: Option<BudgetOutcome>,
+}
+
+/// 단계별 소요 시간 (`Server-Timing` 헤더용)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StageTimings {
+    pub decode: Duration,
+    /// 애니메이션은 프레임별 리사이즈와 인코드를 병렬로 섞어 하므로 따로 재지 않는다
+    pub resize: Option<Duration>,
+    pub encode: Duration,
+}
+
+/// 정적 WebP 인코더 설정. `libwebp` 기능으로 빌드했을 때만 `cwebp`로 적용되고,
+/// 아니면 내장 무손실 인코더를 쓴다.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct WebpOptions {
+    /// 압축 노력 0(빠름)~6(작음)
+    pub method: u8,
+    /// 목표 바이트 수. 0이 아니면 손실 압축으로 이 크기에 맞춘다.
+    pub target_size: u64,
+    /// 0~100, 100이면 완전 무손실. 낮을수록 픽셀을 더 손봐서 작게 만든다.
+    pub near_lossless: u8,
+    /// 손실 압축 화질 0~100. 정하면 무손실 대신 쓴다 (`target_size`가 우선). 설정의 화질 표에서 채운다.
+    pub quality: Option<u8>,
+    /// `cwebp` 실행 파일
+    pub binary: String,
+}
+
+impl Default for WebpOptions {
+    fn default() -> Self {
+        Self { method: 4, target_size: 0, near_lossless: 100, quality: None, binary: "cwebp".into() }
+    }
+}
+
+/// 정적 이미지 처리: 디코드 → (모양 옵션) → 종횡비 유지하며 리사이즈 → (모양 옵션) → WebP 인코드.
+/// `cwebp`를 기다릴 수 있으므로 async 컨텍스트에서는 `spawn_blocking` 안에서 호출할 것.
+/// 인코드 전에 `cancel` 조건이 되면 `Cancelled`를, `cwebp`는 `cancel.deadline`이 지나면 죽인다.
+pub fn resize_static(body: &[u8], size: u32, webp: &WebpOptions, effects: &Effects, cancel: &Cancel) -> Result<Resized, ImagingError> {
+    let start = Instant::now();
+    let img: DynamicImage = image::load_from_memory(body).map_err(ImagingError::Decode)?;
+    let original = img.dimensions();
+    let img = apply_before_resize(img, effects);
+    let decoded = Instant::now();
+
+    // 종횡비를 유지하면서 size x size 박스 안에 맞는 최대 크기로 리사이즈
+    let resized = apply_after_resize(resize_fit(&img, size, size, FilterType::Lanczos3), effects);
+    let resized_at = Instant::now();
+
+    cancel.check()?;
+    let bytes = encode_still(&resized, webp, cancel.deadline)?;
+    Ok(Resized {
+        bytes,
+        original,
+        resized: resized.dimensions(),
+        timings: StageTimings {
+            decode: decoded - start,
+            resize: Some(resized_at - decoded),
+            encode: resized_at.elapsed(),
+        },
+        budget: None,
+    })
+}
+
+/// 애니메이션 출력 크기 예산. 첫 인코드가 넘치면 [`BUDGET_LADDER`] 순서로 낮춰 다시 만든다.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Budget {
+    pub max_bytes: usize,
+    /// 프레임을 솎아낼 때 이보다 낮은 fps로는 내려가지 않는다
+    pub min_fps: f64,
+}
+
+/// 예산에 맞추려고 고른 인코딩 수준. WebP 인코더가 무손실이라 품질 대신 채널당
+/// 비트 수를 줄여(포스터라이즈) 압축률을 높이고, `frame_step`개마다 한 프레임만 남긴다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodeStep {
+    pub bits: u8,
+    pub frame_step: usize,
+}
+
+impl EncodeStep {
+    const FULL: EncodeStep = EncodeStep { bits: 8, frame_step: 1 };
+}
+
+impl BudgetOutcome {
+    /// 첫 인코드가 예산 안에 들어온 경우
+    pub const UNCHANGED: BudgetOutcome = BudgetOutcome { step: EncodeStep::FULL, fits: true };
+}
+
+pub const BUDGET_LADDER: [EncodeStep; 6] = [
+    EncodeStep { bits: 8, frame_step: 2 },
+    EncodeStep { bits: 6, frame_step: 1 },
+    EncodeStep { bits: 6, frame_step: 2 },
+    EncodeStep { bits: 5, frame_step: 2 },
+    EncodeStep { bits: 5, frame_step: 3 },
+    EncodeStep { bits: 4, frame_step: 4 },
+];
+
+/// 예산을 적용한 결과 (`x-animation-encode` 헤더용)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BudgetOutcome {
+    pub step: EncodeStep,
+    /// 바닥 수준까지 내려도 예산을 넘으면 `false` (가장 작은 결과를 쓴다)
+    pub fits: bool,
+}
+
+impl fmt::Display for BudgetOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bits={}; frame-step={}; fits={}",
+            self.step.bits,
+            self.step.frame_step,
+            u8::from(self.fits)
+        )
+    }
+}
+
+/// 합성이 끝난 전체 프레임과 지연(ms), 반복 횟수. WebP는 `image`가, GIF·APNG는 [`crate::animation`]이
+/// 폐기 방식과 블렌딩을 적용해 캔버스 전체를 돌려준다.
+fn decode_frames(body: &[u8]) -> Result<(u16, Vec<(RgbaImage, u32)>), ImagingError> {
+    if !is_animated_webp(body) && crate::animation::is_animated(body) {
+        let animation = crate::animation::decode(body).map_err(ImagingError::Decode)?;
+        let frames = animation.frames.into_iter().map(|f| (f.image, f.delay_ms)).collect();
+        return Ok((animation.loop_count, frames));
+    }
+    let decoder = WebPDecoder::new(Cursor::new(body)).map_err(ImagingError::Decode)?;
+    let loop_count = match decoder.loop_count() {
+        LoopCount::Infinite => 0,
+        LoopCount::Finite(n) => n.get().min(u16::MAX as u32) as u16,
+    };
+    let frames = decoder
+        .into_frames()
+        .collect_frames()
+        .map_err(ImagingError::Decode)?
+        .into_iter()
+        .map(|frame| {
+            let (num, den) = frame.delay().numer_denom_ms();
+            (frame.into_buffer(), num / den.max(1))
+        })
+        .collect();
+    Ok((loop_count, frames))
+}
+
+/// 애니메이션 처리 (입력은 WebP·GIF·APNG, 출력은 애니메이션 WebP): 전체 프레임 디코드 → 프레임별 리사이즈+인코드를 `pool`에서
+/// 병렬로 수행(순서 보존) → 애니메이션 WebP로 재조립. 프레임 지연과 반복 횟수는 유지한다.
+/// `budget`이 있고 결과가 넘치면 프레임을 솎거나 색 깊이를 줄여 다시 조립한다.
+/// CPU를 오래 쓰므로 async 컨텍스트에서는 `spawn_blocking` 안에서 호출할 것.
+/// `cancel` 조건이 되면 남은 프레임을 처리하지 않고 `Cancelled`를 돌려준다.
+pub fn resize_animated_webp(
+    body: &[u8],
+    size: u32,
+    effects: &Effects,
+    pool: &ThreadPool,
+    cancel: &Cancel,
+    budget: Option<Budget>,
+) -> Result<Resized, ImagingError> {
+    let start = Instant::now();
+    let (loop_count, mut frames) = decode_frames(body)?;
+    let Some((first, _)) = frames.first() else {
+        return Err(no_frames());
+    };
+    let original = first.dimensions();
+    let cropped = apply_before_resize_frames(&mut frames, effects);
+    let fitted = fit_dimensions(cropped.0, cropped.1, size, size);
+    let resized = effects.output_dimensions(fitted);
+    let decoded = Instant::now();
+
+    // 예산 재시도 때 다시 줄이지 않도록 리사이즈한 프레임을 들고 있는다
+    let scaled = pool.install(|| {
+        frames
+            .par_iter()
+            .map(|(buffer, delay_ms)| {
+                cancel.check()?;
+                let img = DynamicImage::ImageRgba8(buffer.clone());
+                let img = apply_after_resize(resize_fit(&img, fitted.0, fitted.1, FilterType::Lanczos3), effects);
+                let webp = encode_webp(&img)?;
+                Ok((img, *delay_ms, webp))
+            })
+            .collect::<Result<Vec<_>, ImagingError>>()
+    })?;
+    let full: Vec<AnimFrame> = scaled
+        .iter()
+        .map(|(_, delay_ms, webp)| AnimFrame { webp: webp.clone(), delay_ms: *delay_ms })
+        .collect();
+    let mut bytes = mux_animated_webp(resized.0, resized.1, loop_count, &full)
+        .ok_or_else(|| mux_error("frame bitstream missing"))?;
+
+    let mut outcome = None;
+    if let Some(budget) = budget {
+        let mut best = BudgetOutcome { step: EncodeStep::FULL, fits: bytes.len() <= budget.max_bytes };
+        let total_ms: u32 = scaled.iter().map(|(_, d, _)| *d).sum();
+        for step in BUDGET_LADDER {
+            if best.fits {
+                break;
+            }
+            let kept = scaled.len().div_ceil(step.frame_step);
+            let fps = kept as f64 * 1000.0 / total_ms.max(1) as f64;
+            if step.frame_step > 1 && (kept < 2 || fps < budget.min_fps) {
+                continue;
+            }
+            cancel.check()?;
+            let frames = pool.install(|| {
+                scaled
+                    .par_chunks(step.frame_step)
+                    .map(|chunk| {
+                        let (img, _, webp) = &chunk[0];
+                        let webp = match step.bits {
+                            8 => webp.clone(),
+                            bits => encode_webp(&posterize(img, bits))?,
+                        };
+                        // 솎아낸 프레임의 지연은 남긴 프레임에 합친다
+                        Ok(AnimFrame { webp, delay_ms: chunk.iter().map(|(_, d, _)| *d).sum() })
+                    })
+                    .collect::<Result<Vec<_>, ImagingError>>()
+            })?;
+            let candidate = mux_animated_webp(resized.0, resized.1, loop_count, &frames)
+                .ok_or_else(|| mux_error("frame bitstream missing"))?;
+            if candidate.len() < bytes.len() {
+                bytes = candidate;
+                best = BudgetOutcome { step, fits: bytes.len() <= budget.max_bytes };
+            }
+        }
+        outcome = Some(best);
+    }
+
+    Ok(Resized {
+        bytes,
+        original,
+        resized,
+        timings: StageTimings {
+            decode: decoded - start,
+            resize: None,
+            encode: decoded.elapsed(),
+        },
+        budget: outcome,
+    })
+}
+
+/// PNG 출력: 정적 처리와 같이 리사이즈해 PNG로 (애니메이션은 첫 프레임)
+pub fn resize_png(body: &[u8], size: u32, effects: &Effects) -> Result<Resized, ImagingError> {
+    let start = Instant::now();
+    let img = decode_still(body)?;
+    let original = img.dimensions();
+    let img = apply_before_resize(img, effects);
+    let decoded = Instant::now();
+
+    let resized = effects.after_resize(resize_fit(&img, size, size, FilterType::Lanczos3).to_rgba8());
+    let resized_at = Instant::now();
+
+    let bytes = crate::encode::png(&resized)?;
+    Ok(Resized {
+        bytes,
+        original,
+        resized: resized.dimensions(),
+        timings: StageTimings {
+            decode: decoded - start,
+            resize: Some(resized_at - decoded),
+            encode: resized_at.elapsed(),
+        },
+        budget: None,
+    })
+}
+
+/// GIF 출력: 애니메이션은 프레임별로 `pool`에서 리사이즈해 다시 묶고(지연·반복 횟수 유지), 정적이면 한 장짜리 GIF.
+/// 256색 팔레트에 1비트 알파라 WebP보다 거칠다. `spawn_blocking` 안에서 호출할 것.
+pub fn resize_gif(body: &[u8], size: u32, effects: &Effects, pool: &ThreadPool, cancel: &Cancel) -> Result<Resized, ImagingError> {
+    let start = Instant::now();
+    let (loop_count, mut frames) = if is_animated(body) {
+        decode_frames(body)?
+    } else {
+        (0, vec![(decode_still(body)?.to_rgba8(), 0)])
+    };
+    let Some((first, _)) = frames.first() else {
+        return Err(no_frames());
+    };
+    let original = first.dimensions();
+    let cropped = apply_before_resize_frames(&mut frames, effects);
+    let fitted = fit_dimensions(cropped.0, cropped.1, size, size);
+    let resized = effects.output_dimensions(fitted);
+    let decoded = Instant::now();
+
+    let scaled = pool.install(|| {
+        frames
+            .par_iter()
+            .map(|(buffer, delay_ms)| {
+                cancel.check()?;
+                let img = resize_fit(&DynamicImage::ImageRgba8(buffer.clone()), fitted.0, fitted.1, FilterType::Lanczos3);
+                Ok((effects.after_resize(img.to_rgba8()), *delay_ms))
+            })
+            .collect::<Result<Vec<_>, ImagingError>>()
+    })?;
+    let resized_at = Instant::now();
+    cancel.check()?;
+
+    let bytes = crate::encode::gif(resized.0 as u16, resized.1 as u16, loop_count, &scaled)?;
+    Ok(Resized {
+        bytes,
+        original,
+        resized,
+        timings: StageTimings {
+            decode: decoded - start,
+            resize: Some(resized_at - decoded),
+            encode: resized_at.elapsed(),
+        },
+        budget: None,
+    })
+}
+
+/// 리사이즈 전 모양 옵션을 정지 이미지에. 옵션이 없으면 픽셀 형식을 바꾸지 않는다.
+fn apply_before_resize(img: DynamicImage, effects: &Effects) -> DynamicImage {
+    if effects.is_none() {
+        return img;
+    }
+    let mut frames = [img.into_rgba8()];
+    effects.before_resize(&mut frames);
+    let [img] = frames;
+    DynamicImage::ImageRgba8(img)
+}
+
+/// 리사이즈 뒤 모양 옵션을 한 장에. 옵션이 없으면 픽셀 형식을 바꾸지 않는다.
+fn apply_after_resize(img: DynamicImage, effects: &Effects) -> DynamicImage {
+    if effects.is_none() {
+        return img;
+    }
+    DynamicImage::ImageRgba8(effects.after_resize(img.into_rgba8()))
+}
+
+/// 리사이즈 전 모양 옵션을 애니메이션 프레임 전체에. 적용한 뒤의 프레임 크기를 돌려준다.
+fn apply_before_resize_frames(frames: &mut [(RgbaImage, u32)], effects: &Effects) -> (u32, u32) {
+    if !effects.is_none() {
+        let mut images: Vec<RgbaImage> = frames.iter_mut().map(|(image, _)| std::mem::take(image)).collect();
+        effects.before_resize(&mut images);
+        for ((image, _), cropped) in frames.iter_mut().zip(images) {
+            *image = cropped;
+        }
+    }
+    frames.first().map_or((0, 0), |(image, _)| image.dimensions())
+}
+
+/// 한 장으로 디코드한다. 내장 디코더가 모르는 애니메이션 GIF·APNG는 합성한 첫 프레임을 쓴다.
+fn decode_still(body: &[u8]) -> Result<DynamicImage, ImagingError> {
+    if !is_animated_webp(body) && crate::animation::is_animated(body) {
+        let (_, frames) = decode_frames(body)?;
+        let (first, _) = frames.into_iter().next().ok_or_else(no_frames)?;
+        return Ok(DynamicImage::ImageRgba8(first));
+    }
+    image::load_from_memory(body).map_err(ImagingError::Decode)
+}
+
+/// 채널당 `bits`비트만 남긴다 (무손실 인코더의 압축률을 높이는 대신 색 단계가 줄어듦)
+fn posterize(img: &DynamicImage, bits: u8) -> DynamicImage {
+    let mask = !(0xFFu8 >> bits);
+    let mut rgba = img.to_rgba8();
+    for px in rgba.pixels_mut() {
+        for c in &mut px.0[..3] {
+            *c &= mask;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// 프레임이 하나도 없는 애니메이션은 깨진 입력이다
+fn no_frames() -> ImagingError {
+    ImagingError::Decode(ImageError::Decoding(DecodingError::new(ImageFormatHint::Unknown, "animation has no frames")))
+}
+
+fn mux_error(msg: &str) -> ImagingError {
+    ImagingError::Encode(ImageError::Encoding(EncodingError::new(
+        ImageFormatHint::Exact(ImageFormat::WebP),
+        msg.to_string(),
+    )))
+}
+
+thread_local! {
+    // Resizer는 내부 버퍼를 재사용하므로 (blocking) 스레드마다 하나씩 둔다
+    static RESIZER: RefCell<Resizer> = RefCell::new(Resizer::new());
+}
+
+/// image 크레이트의 FilterType을 fast_image_resize 알고리즘으로 매핑
+fn resize_alg(filter: FilterType) -> ResizeAlg {
+    match filter {
+        FilterType::Nearest => ResizeAlg::Nearest,
+        FilterType::Triangle => ResizeAlg::Convolution(fr::FilterType::Bilinear),
+        FilterType::CatmullRom => ResizeAlg::Convolution(fr::FilterType::CatmullRom),
+        FilterType::Gaussian => ResizeAlg::Convolution(fr::FilterType::Gaussian),
+        FilterType::Lanczos3 => ResizeAlg::Convolution(fr::FilterType::Lanczos3),
+    }
+}
+
+/// 종횡비를 유지하면서 max_w x max_h 박스에 들어가는 최대 크기 (`DynamicImage::resize`와 동일한 반올림)
+pub fn fit_dimensions(width: u32, height: u32, max_w: u32, max_h: u32) -> (u32, u32) {
+    let ratio = f64::min(max_w as f64 / width as f64, max_h as f64 / height as f64);
+    let w = ((width as f64 * ratio).round() as u32).max(1);
+    let h = ((height as f64 * ratio).round() as u32).max(1);
+    (w, h)
+}
+
+/// SIMD(SSE4.1/AVX2/NEON) 가속 리사이즈. fast_image_resize가 다루지 못하는 픽셀 형식은
+/// image 크레이트 구현으로 대체한다.
+pub fn resize_fit(img: &DynamicImage, max_w: u32, max_h: u32, filter: FilterType) -> DynamicImage {
+    let (w, h) = fit_dimensions(img.width(), img.height(), max_w, max_h);
+    if (w, h) == img.dimensions() {
+        return img.clone();
+    }
+    if img.pixel_type().is_none() {
+        return img.resize_exact(w, h, filter);
+    }
+
+    let mut dst = DynamicImage::new(w, h, img.color());
+    let options = ResizeOptions::new().resize_alg(resize_alg(filter));
+    match RESIZER.with(|r| r.borrow_mut().resize(img, &mut dst, &options)) {
+        Ok(()) => dst,
+        Err(_) => img.resize_exact(w, h, filter),
+    }
+}
+
+pub fn encode_webp(img: &DynamicImage) -> Result<Vec<u8>, ImagingError> {
+    let mut out = Vec::new();
+    img.write_to(&mut Cursor::new(&mut out), ImageFormat::WebP)
+        .map_err(ImagingError::Encode)?;
+    Ok(out)
+}
+
+/// 정적 출력 인코드. `libwebp` 기능이면 `cwebp`로, 실패하면(마감 시각을 넘겨도) 내장 인코더로 되돌아간다.
+#[cfg_attr(not(feature = "libwebp"), allow(unused_variables))]
+fn encode_still(img: &DynamicImage, options: &WebpOptions, deadline: Option<Instant>) -> Result<Vec<u8>, ImagingError> {
+    #[cfg(feature = "libwebp")]
+    match crate::cwebp::encode(img, options, deadline) {
+        Ok(bytes) => return Ok(bytes),
+        Err(e) => tracing::warn!("cwebp encode failed, using the built-in encoder: {:#}", e),
+    }
+    encode_webp(img)
+}
+
+/// `image`는 정지 AVIF(`ftypavif`)만 알아보므로 이미지 시퀀스(`ftypavis`)는 따로 본다
+pub fn is_avif_sequence(data: &[u8]) -> bool {
+    data.get(4..12) == Some(b"ftypavis")
+}
+
+/// 내장 애니메이션 경로([`resize_animated_webp`])로 처리할 입력인지 (애니메이션 WebP, 프레임이 둘 이상인 GIF·APNG)
+pub fn is_animated(data: &[u8]) -> bool {
+    is_animated_webp(data) || crate::animation::is_animated(data)
+}
+
+pub fn is_animated_webp(data: &[u8]) -> bool {
+    // WebP 파일 시그니처 확인: "RIFF????WEBP"
+    if data.len() < 12 {
+        return false;
+    }
+
+    if &data[0..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return false;
+    }
+
+    // VP8X 청크가 있는지 확인 (확장 기능을 나타냄)
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let chunk_type = &data[pos..pos + 4];
+        let chunk_size = u32::from_le_bytes([
+            data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]
+        ]) as usize;
+
+        if chunk_type == b"VP8X" {
+            // VP8X 플래그에서 애니메이션 비트(bit 1) 확인
+            if pos + 8 < data.len() {
+                let flags = data[pos + 8];
+                return (flags & 0x02) != 0; // 애니메이션 플래그
+            }
+            return false;
+        }
+
+        // ANIM 청크가 있으면 애니메이션
+        if chunk_type == b"ANIM" {
+            return true;
+        }
+
+        pos += 8 + chunk_size;
+        // 홀수 크기인 경우 패딩 바이트 추가
+        if chunk_size % 2 == 1 {
+            pos += 1;
+        }
+    }
+
+    false
+}
+
+/// 단일 프레임 WebP 파일에서 비트스트림 청크(VP8/VP8L, 헤더 포함)를 꺼낸다
+fn bitstream_chunk(webp: &[u8]) -> Option<&[u8]> {
+    let mut pos = 12;
+    while pos + 8 <= webp.len() {
+        let chunk_type = &webp[pos..pos + 4];
+        let chunk_size = u32::from_le_bytes([
+            webp[pos + 4], webp[pos + 5], webp[pos + 6], webp[pos + 7]
+        ]) as usize;
+        let end = (pos + 8 + chunk_size + (chunk_size & 1)).min(webp.len());
+        if chunk_type == b"VP8 " || chunk_type == b"VP8L" {
+            return Some(&webp[pos..end]);
+        }
+        pos = end;
+    }
+    None
+}
+
+fn push_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], payload: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+fn u24(v: u32) -> [u8; 3] {
+    let b = v.to_le_bytes();
+    [b[0], b[1], b[2]]
+}
+
+/// 애니메이션 WebP 한 프레임 (캔버스 전체를 덮는 프레임)
+pub struct AnimFrame {
+    /// 단일 프레임 WebP 파일 바이트
+    pub webp: Vec<u8>,
+    pub delay_ms: u32,
+}
+
+/// 프레임별 WebP 인코딩 결과를 애니메이션 WebP 컨테이너(VP8X + ANIM + ANMF)로 묶는다
+pub fn mux_animated_webp(
+    width: u32,
+    height: u32,
+    loop_count: u16,
+    frames: &[AnimFrame],
+) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+
+    // VP8X: 애니메이션(0x02) + 알파(0x10) 플래그, 캔버스 크기
+    let mut vp8x = vec![0x02 | 0x10, 0, 0, 0];
+    vp8x.extend_from_slice(&u24(width - 1));
+    vp8x.extend_from_slice(&u24(height - 1));
+    push_chunk(&mut body, b"VP8X", &vp8x);
+
+    // ANIM: 배경색(투명) + 반복 횟수
+    let mut anim = vec![0, 0, 0, 0];
+    anim.extend_from_slice(&loop_count.to_le_bytes());
+    push_chunk(&mut body, b"ANIM", &anim);
+
+    for frame in frames {
+        let bitstream = bitstream_chunk(&frame.webp)?;
+        let mut anmf = Vec::with_capacity(16 + bitstream.len());
+        anmf.extend_from_slice(&u24(0)); // x / 2
+        anmf.extend_from_slice(&u24(0)); // y / 2
+        anmf.extend_from_slice(&u24(width - 1));
+        anmf.extend_from_slice(&u24(height - 1));
+        anmf.extend_from_slice(&u24(frame.delay_ms.min(0xFF_FFFF)));
+        anmf.push(0x02); // 블렌딩 없음, 폐기 없음
+        anmf.extend_from_slice(bitstream);
+        push_chunk(&mut body, b"ANMF", &anmf);
+    }
+
+    let mut out = Vec::with_capacity(12 + body.len());
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&((body.len() + 4) as u32).to_le_bytes());
+    out.extend_from_slice(b"WEBP");
+    out.extend_from_slice(&body);
+    Some(out)
+}
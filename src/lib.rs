@@ -0,0 +1,1915 @@
+//! 이모지 리사이저 서버 본체: 상태 구성과 라우터.
+//!
+//! 바이너리(`main.rs`)는 설정/로그 초기화와 서버 실행만 하고, 통합 테스트는
+//! [`build_state`] + [`build_router`]로 같은 앱을 띄운다.
+
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Extension, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Router,
+};
+use metrics_exporter_prometheus::PrometheusHandle;
+use moka::future::Cache;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    compression::CompressionLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+};
+use tracing::{error, info, warn};
+
+mod admin;
+mod allocator;
+pub mod animation;
+mod archive;
+mod auth;
+pub mod bench;
+mod blocklist;
+mod cache;
+pub mod config;
+mod custom;
+mod degrade;
+mod doorkeeper;
+pub mod effects;
+mod encode;
+pub mod error;
+pub mod experiment;
+mod fedi;
+mod flight;
+mod hostlimit;
+pub mod imaging;
+pub mod logging;
+mod metrics;
+mod middleware;
+mod mirror;
+mod moderation;
+mod notify;
+mod placeholder;
+mod popularity;
+mod privacy;
+pub mod processing;
+mod purge;
+mod recent_errors;
+mod report;
+mod request;
+pub mod response;
+pub mod s3;
+mod search;
+mod selftest;
+mod shadow;
+mod slo;
+pub mod signing;
+mod slack;
+#[cfg(any(feature = "avif", feature = "libwebp", feature = "vips"))]
+mod subprocess;
+mod tasks;
+pub mod tenant;
+mod test_page;
+mod twitch;
+pub mod upstream;
+#[cfg(feature = "avif")]
+mod avif;
+#[cfg(feature = "libwebp")]
+mod cwebp;
+#[cfg(feature = "vips")]
+mod vips;
+pub mod warm;
+
+use config::Config;
+use effects::Effects;
+use error::Error;
+use request::EmoteRequest;
+use response::{ImageResponse, RequestInputs, ServerTiming, VaryInput};
+use imaging::{is_animated_webp, ContentType, ImagingError, TARGET_SIZE};
+
+#[derive(Clone)]
+pub struct AppState {
+    upstreams: upstream::Upstreams,     // 소스별 업스트림 클라이언트
+    cache: Cache<String, Cached>,       // 응답 바이트: WebP·AVIF·PNG·GIF 변환 결과, 패스스루·원본 (키의 꼬리표로 구분)
+    stale: Cache<String, Cached>,       // stale-if-error용: 같은 바이트를 더 오래 보관
+    validators: Cache<String, Validator>, // 바이트 없이 남기는 ETag/형식/길이 (조건부 요청·HEAD용)
+    placeholders: Cache<String, Arc<placeholder::Placeholder>>, // 원본 ETag → 흐린 자리 표시와 ThumbHash
+    ready: Arc<AtomicBool>,             // 부팅 자체 점검 통과 여부
+    metrics: PrometheusHandle,
+    frame_pool: Arc<rayon::ThreadPool>, // 애니메이션 프레임 병렬 처리용
+    config: Arc<Config>,
+    purger: Option<purge::CdnPurger>,   // 관리자 purge를 엣지 캐시에 전파
+    blocklist: Arc<blocklist::Blocklist>,
+    reports: Arc<report::Reports>,
+    privacy: Arc<privacy::Privacy>,
+    tiers: Arc<cache::Tiers>,           // 메모리 캐시 뒤의 2차 계층 (디스크 등)
+    flights: Arc<flight::Flights<Cached>>, // 같은 키를 만드는 중인 요청 (동시 캐시 미스 묶기)
+    doorkeeper: Option<Arc<doorkeeper::Doorkeeper>>, // 처음 본 키를 메모리에 바로 넣지 않기 (`CACHE_ADMIT_AFTER`가 2 이상일 때만)
+    popularity: Arc<popularity::Popularity>,
+    tasks: tasks::Tasks,                // 응답과 무관한 백그라운드 작업 큐
+    degradation: Arc<degrade::Degradation>, // 과부하 시 저하 모드 판단
+    processing: Arc<processing::Processing>, // 우선순위가 있는 인코딩 대기열
+    upstream_stats: Arc<notify::UpstreamStats>, // 웹훅 알림용 업스트림 성공/실패 누계
+    slack: Option<Arc<slack::Slack>>,   // `/slack` 소스 (토큰이 있을 때만)
+    fedi: Option<Arc<fedi::Fedi>>,      // `/fedi` 소스 (허용 인스턴스가 있을 때만)
+    twitch: Option<Arc<twitch::Twitch>>, // `/twitch` 소스 (자격 증명이 있을 때만)
+    custom: Option<Arc<custom::Store>>, // `/custom` 업로드 저장소 (`CUSTOM_DIR`이 있을 때만)
+    guilds: Option<Arc<search::Guilds>>, // 검색·보관 대상 길드 이모지 (봇 토큰이 있을 때만)
+    moderation: Option<Arc<moderation::Moderator>>, // 커스텀 업로드 검사 (설정했을 때만)
+    mirror: Option<Arc<mirror::Mirror>>, // 업스트림 원본 보관 (`MIRROR_DIR`이 있을 때만)
+    anon_limiter: Option<Arc<report::RateLimiter>>, // API 키 없는 요청의 IP당 제한 (`ANON_RATE_LIMIT`이 있을 때만)
+    warmup: Arc<warm::Warmup>,          // 부팅 시드 워밍 진행 상황 (`/readyz`가 기다린다)
+    shadow: Option<Arc<shadow::Shadow>>, // 스테이징으로 요청 따라 보내기 (`SHADOW_URL`이 있을 때만)
+    recent_errors: Arc<recent_errors::RecentErrors>, // `/admin/errors`용 최근 실패
+    slo: Arc<slo::Slo>,                 // 소스별 캐시 미스 지연과 SLO 소진율
+}
+
+/// 메모리 캐시 항목. ETag와 포맷은 저장할 때 한 번만 계산해 두므로 조건부 요청(304)은
+/// 본문을 다시 읽지 않고, 저장 시각으로 `Age`를 계산한다. 2차 계층에서 올라온 항목은
+/// 그 계층에 저장한 시각을 이어받아, 재시작이나 메모리 축출 뒤에도 `Age`가 0으로 돌아가지 않는다.
+#[derive(Clone)]
+struct Cached {
+    bytes: Arc<Vec<u8>>,
+    etag: Arc<str>,
+    content_type: ContentType,
+    stored: SystemTime,
+}
+
+impl Cached {
+    fn new(bytes: Arc<Vec<u8>>) -> Self {
+        Self::stored_at(bytes, SystemTime::now())
+    }
+
+    fn stored_at(bytes: Arc<Vec<u8>>, stored: SystemTime) -> Self {
+        Self {
+            etag: response::make_etag(&bytes).into(),
+            content_type: ContentType::sniff(&bytes),
+            stored,
+            bytes,
+        }
+    }
+
+    /// 캐시에 들어온 뒤 지난 시간
+    fn age(&self) -> Duration {
+        self.stored.elapsed().unwrap_or_default()
+    }
+
+    fn response(&self) -> ImageResponse<'static> {
+        ImageResponse::new(self.bytes.clone(), self.content_type).etag(self.etag.clone()).age(self.age())
+    }
+
+    fn validator(&self) -> Validator {
+        Validator {
+            etag: self.etag.clone(),
+            content_type: self.content_type,
+            length: self.bytes.len(),
+            stored: self.stored,
+        }
+    }
+}
+
+/// 캐시 항목에서 바이트만 뺀 것. 항목 하나가 100바이트 남짓이라 본 캐시보다 훨씬 많이 들고 있을 수 있어서,
+/// 바이트가 밀려난 뒤에도 조건부 요청(304)과 HEAD에는 다시 가져오지 않고 답한다.
+#[derive(Clone)]
+struct Validator {
+    etag: Arc<str>,
+    content_type: ContentType,
+    length: usize,
+    stored: SystemTime,
+}
+
+/// 장애 중 제공하는 만료 응답은 엣지에 오래 남지 않도록 짧게
+const STALE_CACHE_CONTROL: &str = "public, max-age=60";
+
+static USER_AGENT: Lazy<String> =
+    Lazy::new(|| "emoji-resizer/0.1 (+https://example.local) reqwest/0.12".to_string());
+
+
+/// 설정으로 앱 상태를 만들고 부팅 자체 점검과 주기 작업을 시작한다.
+/// 전역 메트릭 레코더를 설치하므로 tokio 런타임 안에서 호출할 것.
+pub async fn build_state(config: Config) -> anyhow::Result<AppState> {
+    let metrics = metrics::install()?;
+    // 설정 실수를 소스를 읽지 않고 찾을 수 있도록 (비밀 값은 가려서). `GET /admin/config`와 같은 내용
+    info!(target: "config", "effective configuration: {}", config.summary());
+
+    let upstreams = upstream::Upstreams::new(&config.upstreams)?;
+    if config.upstreams.ip_family != upstream::IpFamily::Auto {
+        info!("upstream connections use IP family {:?}", config.upstreams.ip_family);
+    }
+
+    let (cache_capacity, cache_ttl) = (config.cache_capacity, config.cache_ttl);
+    let cache = Cache::builder()
+        .max_capacity(config.cache_capacity)
+        .time_to_live(config.cache_ttl)
+        .eviction_policy(config.cache_eviction.policy())
+        .build();
+    let doorkeeper = doorkeeper::Doorkeeper::new(config.cache_admit_after, config.cache_admit_window, config.cache_capacity)
+        .map(Arc::new);
+
+    // 본 캐시와 Arc를 공유하므로 추가 메모리는 항목 메타데이터 정도
+    let stale = Cache::builder()
+        .max_capacity(config.stale_capacity)
+        .time_to_live(config.stale_ttl)
+        .build();
+
+    // 본 캐시와 같은 수명: 이 기간 안에는 같은 키가 같은 내용이라고 본다
+    let validators = Cache::builder()
+        .max_capacity(config.validator_capacity)
+        .time_to_live(config.cache_ttl)
+        .build();
+    let placeholders = Cache::builder()
+        .max_capacity(placeholder::CAPACITY)
+        .time_to_live(config.cache_ttl)
+        .build();
+
+    let frame_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config.animation_parallelism)
+        .thread_name(|i| format!("frame-worker-{i}"))
+        .build()?;
+
+    let purger = config.cdn_purge.clone().map(purge::CdnPurger::new).transpose()?;
+    if let Some(p) = &purger {
+        info!("CDN purge propagation enabled: {}", p.provider().name());
+    }
+
+    let blocklist = blocklist::Blocklist::load(config.blocklist.clone(), config.blocklist_file.clone())?;
+
+    let reports = report::Reports {
+        store: report::ReportStore::default(),
+        limiter: report::RateLimiter::new(config.report_rate_limit, config.report_rate_window),
+        captcha: config.hcaptcha_secret.clone().map(report::Captcha::new).transpose()?,
+        trust_forwarded_for: config.trust_forwarded_for,
+    };
+
+    let privacy = privacy::Privacy::new(config.ip_mode, config.ip_salt.clone());
+    let seed = config.warm_seed_file.as_deref().map(warm::read_seed).transpose()?.unwrap_or_default();
+    let warmup = Arc::new(warm::Warmup::new(seed.len(), config.ready_warm_fraction));
+    let anon_limiter = (config.anon_rate_limit > 0)
+        .then(|| Arc::new(report::RateLimiter::new(config.anon_rate_limit, config.anon_rate_window)));
+    let shadow = match &config.shadow_url {
+        Some(url) => {
+            info!("shadowing {}% of image requests to {}", config.shadow_percent, url);
+            Some(Arc::new(shadow::Shadow::new(url, config.shadow_percent, config.shadow_concurrency, config.shadow_timeout)?))
+        }
+        None => None,
+    };
+    let recent_errors = Arc::new(recent_errors::RecentErrors::new(config.recent_errors));
+    let slo = Arc::new(slo::Slo::new(config.slo_miss_target, config.slo_miss_objective, config.slo_window));
+
+    let mut backends: Vec<Box<dyn cache::Tier>> = Vec::new();
+    if let Some(dir) = &config.cache_dir {
+        backends.push(Box::new(cache::DiskTier::new(
+            dir.clone(),
+            config.cache_disk_ttl,
+            config.cache_disk_zstd,
+        )?));
+        info!("disk cache tier enabled at {}", dir.display());
+    }
+    if let Some(s3) = &config.cache_s3 {
+        backends.push(Box::new(s3::S3Tier::new(s3.clone())?));
+        info!("s3 cache tier enabled at {}/{}/{}", s3.endpoint, s3.bucket, s3.prefix);
+    }
+    let tiers = cache::Tiers::new(backends, config.cache_backend_timeout).generation(
+        config.cache_generation.clone(),
+        config.cache_generation_previous.clone(),
+        config.cache_dual_write,
+    );
+    if let Some(previous) = config.cache_generation_previous.as_ref().filter(|_| !tiers.is_empty()) {
+        info!(
+            "cache generation {:?}: also writing generation {:?} for {:?}",
+            config.cache_generation, previous, config.cache_dual_write
+        );
+    }
+    match config.cache_spill_bytes {
+        Some(_) if tiers.is_empty() => warn!("CACHE_SPILL_KB needs a second cache tier (CACHE_DIR), keeping large entries in memory"),
+        Some(bytes) => info!("cache entries over {} KiB live only in the second tier", bytes / 1024),
+        None => {}
+    }
+    let mirror = config
+        .mirror_dir
+        .clone()
+        .map(|dir| mirror::Mirror::new(dir, config.mirror_retention))
+        .transpose()?
+        .map(Arc::new);
+    if let Some(dir) = &config.mirror_dir {
+        info!("origin mirror enabled at {}", dir.display());
+    }
+
+    let slack = slack::Slack::new(
+        config.slack_tokens.clone(),
+        config.slack_api_base.clone(),
+        config.slack_list_ttl,
+        upstreams.client(upstream::Source::Slack).clone(),
+    )?
+    .map(Arc::new);
+    let fedi = fedi::Fedi::new(
+        &config.fedi_instances,
+        config.fedi_list_ttl,
+        upstreams.client(upstream::Source::Fedi).clone(),
+    )?.map(Arc::new);
+    let twitch = twitch::Twitch::new(&config, upstreams.client(upstream::Source::Twitch).clone())?.map(Arc::new);
+    let custom = match config.custom_dir.clone() {
+        Some(dir) => Some(Arc::new(custom::Store::open(dir, config.custom_max_versions, config.custom_deleted_retention).await?)),
+        None => None,
+    };
+    let guilds = search::Guilds::new(&config)?.map(Arc::new);
+    if !config.vips_operations.is_empty() {
+        if cfg!(feature = "vips") {
+            info!("libvips backend enabled for {:?} via {}", config.vips_operations, config.vips_binary);
+        } else {
+            warn!("VIPS_OPERATIONS is set but this build lacks the vips feature, using the built-in pipeline");
+        }
+    }
+    if config.avif_animated {
+        if cfg!(feature = "avif") {
+            info!("animated AVIF enabled via {} (speed {}, quality {})", config.avif_binary, config.avif_speed, config.avif_quality);
+        } else {
+            warn!("AVIF_ANIMATED is set but this build lacks the avif feature, serving WebP");
+        }
+    }
+    if config.webp != imaging::WebpOptions::default() {
+        if cfg!(feature = "libwebp") {
+            info!("libwebp encoder enabled via {} ({:?})", config.webp.binary, config.webp);
+        } else {
+            warn!("WEBP_* encoder settings are set but this build lacks the libwebp feature, using the built-in encoder");
+        }
+    }
+    if let Some(experiment) = &config.encoder_experiment {
+        info!("encoder experiment {:?} on {}% of static encodes ({:?})", experiment.name, experiment.percent, experiment.webp);
+        if !cfg!(feature = "libwebp") {
+            warn!("ENCODER_EXPERIMENT is set but this build lacks the libwebp feature, both profiles use the built-in encoder");
+        }
+    }
+    let moderation = moderation::Moderator::new(&config)?.map(Arc::new);
+
+    let tasks = tasks::Tasks::new(config.task_concurrency, config.task_queue_capacity);
+
+    let degradation = degrade::Degradation::new(config.degrade_in_flight, config.degrade_memory_bytes);
+    let processing = processing::Processing::new(config.processing_concurrency, config.processing_background_max);
+    degradation.spawn_sampler();
+
+    let state = AppState {
+        upstreams,
+        cache,
+        stale,
+        validators,
+        placeholders,
+        ready: Arc::new(AtomicBool::new(false)),
+        metrics,
+        frame_pool: Arc::new(frame_pool),
+        config: Arc::new(config),
+        purger,
+        blocklist: Arc::new(blocklist),
+        reports: Arc::new(reports),
+        privacy: Arc::new(privacy),
+        tiers: Arc::new(tiers),
+        flights: flight::Flights::new(),
+        doorkeeper,
+        slo,
+        popularity: Arc::new(popularity::Popularity::new(cache_capacity, cache_ttl)),
+        tasks,
+        degradation,
+        processing,
+        upstream_stats: Arc::default(),
+        slack,
+        fedi,
+        twitch,
+        custom,
+        guilds,
+        moderation,
+        mirror,
+        anon_limiter,
+        shadow,
+        warmup,
+        recent_errors,
+    };
+
+    // 트래픽을 받기 전에 이미징 파이프라인이 정상인지 점검
+    let pool = state.frame_pool.clone();
+    let webp = state.config.webp.clone();
+    match tokio::task::spawn_blocking(move || selftest::run(&pool, &webp)).await? {
+        Ok(()) => {
+            info!("imaging self-test passed");
+            state.ready.store(true, Ordering::Release);
+        }
+        Err(e) => error!("imaging self-test failed, staying unready: {e:#}"),
+    }
+    if !seed.is_empty() {
+        tokio::spawn(warm::seed(state.clone(), seed, state.warmup.clone(), state.config.ready_warm_timeout));
+    }
+
+    // 보관 기간이 지난 신고 기록을 주기적으로 정리
+    let reports = state.reports.clone();
+    let retention = state.config.retention;
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(Duration::from_secs(3600));
+        loop {
+            tick.tick().await;
+            let pruned = reports.store.prune(retention);
+            if pruned > 0 {
+                info!("pruned {pruned} report records past retention");
+            }
+        }
+    });
+
+    // 디스크 계층의 만료 항목과 고아 파일을 주기적으로 정리 (부팅 직후는 건너뜀)
+    let interval = state.config.cache_maintenance_interval;
+    if !state.tiers.is_empty() && !interval.is_zero() {
+        let tiers = state.tiers.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                tick.tick().await;
+                if tiers.maintain().await.is_none() {
+                    info!("cache maintenance already running, skipping scheduled pass");
+                }
+            }
+        });
+    }
+
+    // 보관 기간이 지난 원본 사본을 주기적으로 정리 (디스크 계층과 같은 주기)
+    let interval = state.config.cache_maintenance_interval;
+    if let Some(mirror) = state.mirror.clone().filter(|_| !interval.is_zero()) {
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                tick.tick().await;
+                match mirror.maintain().await {
+                    Ok(Some(report)) if report.expired > 0 => {
+                        info!("origin mirror dropped {} copies past retention", report.expired)
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("origin mirror maintenance failed: {e:#}"),
+                }
+            }
+        });
+    }
+
+    // 어느 이름도 가리키지 않게 된 커스텀 이모트 블롭을 주기적으로 정리
+    let interval = state.config.custom_gc_interval;
+    if let Some(store) = state.custom.clone().filter(|_| !interval.is_zero()) {
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                tick.tick().await;
+                custom::run_gc(&store).await;
+            }
+        });
+    }
+
+    // 인기 이모지는 메모리 캐시에서 만료되기 전에 미리 갱신
+    if state.config.prewarm_top > 0 {
+        tokio::spawn(prewarm_loop(state.clone()));
+    }
+
+    // 저하 모드, 업스트림 에러율, 디스크 사용량을 Discord 웹훅으로 알림
+    if let Some(url) = &state.config.webhook_url {
+        let notifier = notify::Notifier::new(url.clone(), state.config.notify_cooldown)?;
+        info!("webhook notifications enabled");
+        tokio::spawn(notify::monitor(state.clone(), notifier));
+    }
+
+    Ok(state)
+}
+
+/// 라우트와 공통 미들웨어를 붙인 라우터. 접근 로그가 클라이언트 주소를 읽으므로
+/// `into_make_service_with_connect_info::<SocketAddr>()`로 띄울 것.
+pub fn build_router(state: AppState) -> Router {
+    let mut app = Router::new()
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/readyz", get(readyz_handler))
+        .route("/metrics", get(metrics::handler))
+        // 예: GET /e/123456789012345678.webp
+        .route("/e/:name", get(resize_handler))
+        .route("/e/:name/original", get(original_handler))
+        .route("/e/:name/variants", get(variants_handler))
+        .route("/e/:name/test", get(test_page::handler))
+        .route("/e/:name/placeholder", get(placeholder::handler))
+        .route("/e/:name/info", get(info_handler))
+        // 예: GET /e/123456789012345678/64.png → /e/123456789012345678.png?size=64
+        .route("/e/:name/:file", get(sized_path_handler))
+        .route("/report/:emoji_id", post(report::submit));
+
+    if state.slack.is_some() {
+        app = app.route("/slack/:workspace/:name", get(slack::handler));
+    }
+    if state.fedi.is_some() {
+        app = app.route("/fedi/:instance/:shortcode", get(fedi::handler));
+    }
+    if state.twitch.is_some() {
+        app = app
+            .route("/twitch/badges/:channel/:set/:version", get(twitch::badge_handler))
+            .route("/twitch/cheermotes/:channel/:prefix/:bits", get(twitch::cheermote_handler));
+    }
+    if state.custom.is_some() {
+        // 예: PUT /custom/blobheart (본문: WebP), GET /custom/blobheart.webp, GET /custom/blobheart@v2.webp,
+        //     GET /custom/<길드 ID>/blobheart.webp (길드별 네임스페이스), GET /c/<sha1>.webp,
+        //     POST /custom/blobheart/sign (비공개 이모트용 서명한 주소)
+        app = app
+            .route(
+                "/custom/*path",
+                get(custom::handler)
+                    .put(custom::upload_handler)
+                    .patch(custom::update_handler)
+                    .delete(custom::delete_handler)
+                    .post(custom::sign_handler)
+                    .layer(DefaultBodyLimit::max(state.config.custom_max_upload_bytes)),
+            )
+            .route("/custom", get(custom::list_handler))
+            .route("/c/:hash", get(custom::hash_handler));
+    }
+    if state.custom.is_some() || (state.guilds.is_some() && !state.config.discord_guild_ids.is_empty()) {
+        // 예: GET /search?q=blob&limit=10
+        app = app.route("/search", get(search::handler));
+    }
+
+    match &state.config.admin_token {
+        Some(token) => app = app.nest("/admin", admin::router(token.as_str().into())),
+        None => info!("ADMIN_TOKEN not set, admin API disabled"),
+    }
+
+    if state.config.response_compression {
+        // 기본 조건이 `image/*`(SVG 제외)와 32바이트 미만 응답은 건너뛴다. 이미지는 이미 압축돼 있다.
+        app = app.layer(CompressionLayer::new());
+    }
+
+    let app: Router = app
+        // 패닉은 요청 ID가 담긴 500으로 응답 (바깥 레이어가 먼저 실행됨)
+        .layer(CatchPanicLayer::custom(middleware::handle_panic))
+        .layer(axum::middleware::from_fn(middleware::panic_response))
+        .layer(axum::middleware::from_fn(middleware::deadline))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), middleware::admission))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), middleware::auth))
+        .layer(axum::middleware::from_fn(middleware::problem_json))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), middleware::shadow))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), middleware::access_log))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .with_state(state);
+
+    // `Router::layer`는 라우팅 뒤에 실행되므로 경로 정규화는 바깥 라우터의 폴백으로 감싸서 먼저 한다
+    Router::new()
+        .fallback_service(app)
+        .layer(axum::middleware::from_fn(middleware::normalize_path))
+}
+
+async fn readyz_handler(State(state): State<AppState>) -> axum::response::Response {
+    if !state.ready.load(Ordering::Acquire) {
+        return (StatusCode::SERVICE_UNAVAILABLE, "not ready").into_response();
+    }
+    if !state.warmup.ready() {
+        let (cached, required) = state.warmup.progress();
+        return (StatusCode::SERVICE_UNAVAILABLE, format!("warming up ({cached}/{required})")).into_response();
+    }
+    (StatusCode::OK, "ready").into_response()
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct EmojiParams {
+    /// `1`/`true`면 재인코딩 없이 Discord 원본 바이트를 그대로 제공
+    passthrough: Option<String>,
+    /// 출력 크기. `OUTPUT_SIZES` 중 가장 가까운 값으로 리다이렉트한다
+    size: Option<u32>,
+    /// `1`이면 투명한 가장자리를 잘라 내고 리사이즈 ([`effects::Effects`])
+    trim: Option<String>,
+    /// 알파 윤곽을 따라 그릴 테두리 `폭,RRGGBB` ([`effects::Effects`])
+    stroke: Option<String>,
+    /// 이모트 밑에 깔 그림자 `x,y,번짐,RRGGBB[AA]` ([`effects::Effects`])
+    shadow: Option<String>,
+    /// 리사이즈한 결과 둘레에 더할 투명한 여백 픽셀 ([`effects::Effects`])
+    pad_px: Option<String>,
+}
+
+/// 같은 이모지의 응답 종류. 종류마다 캐시 키가 다르다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Variant {
+    /// 주어진 크기로 리사이즈한 WebP
+    Resized(u32),
+    /// `Resized`와 같되 애니메이션은 AVIF로 (`Accept: image/avif`, `AVIF_ANIMATED`)
+    Avif(u32),
+    /// 주어진 크기로 리사이즈한 PNG (`.png`, 애니메이션은 첫 프레임)
+    Png(u32),
+    /// 주어진 크기로 리사이즈한 GIF (`.gif`, 애니메이션은 그대로)
+    Gif(u32),
+    /// 160px로 요청한 Discord 응답을 재인코딩 없이 (`?passthrough`)
+    Passthrough,
+    /// 크기 지정 없이 요청한 Discord 원본 (`/e/:id/original`)
+    Original,
+}
+
+impl Variant {
+    /// 설정상 존재할 수 있는 모든 종류 (purge용)
+    fn all(config: &Config) -> Vec<Variant> {
+        config
+            .output_sizes
+            .iter()
+            .flat_map(|&size| [Variant::Resized(size), Variant::Avif(size), Variant::Png(size), Variant::Gif(size)])
+            .chain([Variant::Passthrough, Variant::Original])
+            .collect()
+    }
+
+    /// 이 이모지가 캐시에 가질 수 있는 모든 키. 기본값과 다른 테넌트 인코더 설정으로 만든 리사이즈 결과 키도 넣는다.
+    fn keys(config: &Config, emoji_id: &str) -> Vec<(Variant, String)> {
+        let mut profiles: Vec<&imaging::WebpOptions> = Vec::new();
+        for webp in config.tenants.iter().map(|t| &t.webp).filter(|w| **w != config.webp) {
+            if !profiles.contains(&webp) {
+                profiles.push(webp);
+            }
+        }
+        let mut keys = Vec::new();
+        for variant in Variant::all(config) {
+            let key = variant.key(emoji_id);
+            let tenant_keys: Vec<_> = match variant {
+                Variant::Resized(_) | Variant::Avif(_) => profiles.iter().map(|webp| tenant::cache_key(&key, webp)).collect(),
+                _ => Vec::new(),
+            };
+            keys.push((variant, key));
+            keys.extend(tenant_keys.into_iter().map(|key| (variant, key)));
+        }
+        keys
+    }
+
+    /// 기본 크기(160px)는 예전처럼 이모지 ID만 키로 쓴다
+    fn key(self, emoji_id: &str) -> String {
+        match self {
+            Variant::Resized(TARGET_SIZE) => emoji_id.to_string(),
+            Variant::Resized(size) => format!("{emoji_id}@{size}"),
+            Variant::Avif(size) => format!("{}:avif", Variant::Resized(size).key(emoji_id)),
+            Variant::Png(size) => format!("{}:png", Variant::Resized(size).key(emoji_id)),
+            Variant::Gif(size) => format!("{}:gif", Variant::Resized(size).key(emoji_id)),
+            Variant::Passthrough => format!("{emoji_id}:original"),
+            Variant::Original => format!("{emoji_id}:source"),
+        }
+    }
+
+    /// 리사이즈 결과를 확장자가 고른 포맷으로 (`/e/:id.png`, `/e/:id.gif`)
+    fn with_extension(self, extension: PathExtension) -> Variant {
+        match (self, extension) {
+            (Variant::Resized(size), PathExtension::Png) => Variant::Png(size),
+            (Variant::Resized(size), PathExtension::Gif) => Variant::Gif(size),
+            (variant, _) => variant,
+        }
+    }
+
+    /// 출력 크기 (패스스루/원본은 캐시 태그용으로 기본 크기)
+    fn size(self) -> u32 {
+        match self {
+            Variant::Resized(size) | Variant::Avif(size) | Variant::Png(size) | Variant::Gif(size) => size,
+            _ => TARGET_SIZE,
+        }
+    }
+
+    /// 캐시 키에서 이모지 ID와 종류를 되찾는다 (선제 갱신용)
+    fn from_key(key: &str) -> (String, Variant) {
+        let key = tenant::base_key(key);
+        if let Some(rest) = key.strip_suffix(":avif") {
+            let (id, variant) = Variant::from_key(rest);
+            (id, Variant::Avif(variant.size()))
+        } else if let Some(rest) = key.strip_suffix(":png") {
+            let (id, variant) = Variant::from_key(rest);
+            (id, Variant::Png(variant.size()))
+        } else if let Some(rest) = key.strip_suffix(":gif") {
+            let (id, variant) = Variant::from_key(rest);
+            (id, Variant::Gif(variant.size()))
+        } else if let Some(id) = key.strip_suffix(":original") {
+            (id.to_string(), Variant::Passthrough)
+        } else if let Some(id) = key.strip_suffix(":source") {
+            (id.to_string(), Variant::Original)
+        } else if let Some((id, size)) = key.split_once('@').and_then(|(id, s)| Some((id, s.parse().ok()?))) {
+            (id.to_string(), Variant::Resized(size))
+        } else {
+            (key.to_string(), Variant::Resized(TARGET_SIZE))
+        }
+    }
+}
+
+/// `TTL - PREWARM_LEAD_SECS`가 지난 인기 키를 캐시를 거치지 않고 다시 만들어 넣는다
+async fn prewarm_loop(state: AppState) {
+    let config = state.config.clone();
+    let mut tick = tokio::time::interval(config.prewarm_interval);
+    loop {
+        tick.tick().await;
+        // 과부하 중에는 선제 갱신으로 부담을 더하지 않는다
+        if state.degradation.is_degraded() {
+            continue;
+        }
+        let due = state.popularity.due(config.prewarm_top, config.cache_ttl, config.prewarm_lead);
+        if due.is_empty() {
+            continue;
+        }
+        let total = due.len();
+        let mut queued = 0;
+        // Discord 외 소스(`slack/…`, `fedi/…`, `twitch/…`, `custom/…`, `c/…`)는 이름 해석이 따로 필요해서,
+        // 테넌트 설정으로 만든 항목(`…~webp-…`)은 갱신 경로에 테넌트가 없어서 선제 갱신하지 않는다
+        for key in due.into_iter().filter(|k| !k.contains('/') && !k.contains('~')) {
+            let state = state.clone();
+            queued += state.tasks.clone().submit("prewarm", 1, move || {
+                let (state, key) = (state.clone(), key.clone());
+                async move {
+                    let (id, variant) = Variant::from_key(&key);
+                    let res = serve_emoji(state, id, variant, None, RequestInputs::default(), Mode::Refresh).await;
+                    anyhow::ensure!(res.status().is_success(), "refresh {key}: status {}", res.status());
+                    Ok(())
+                }
+            }) as usize;
+        }
+        info!("prewarm queued {queued}/{total} popular emojis");
+    }
+}
+
+async fn resize_handler(
+    State(state): State<AppState>,
+    emote: EmoteRequest,
+    deadline: Option<Extension<middleware::Deadline>>,
+    req: RequestInputs,
+) -> axum::response::Response {
+    let emoji_id = emote.stem.clone();
+    let variant = match emote.variant(&state.config, "/e") {
+        Ok(v) => v,
+        Err(redirect) => return redirect.into_response(),
+    };
+    let extension = path_extension(&emote.name, variant);
+    if extension == PathExtension::Other {
+        let canonical = canonical_url(&state.config, &format!("/e/{emoji_id}"), variant);
+        return Redirect::permanent(&emote.effects.carry(&canonical)).into_response();
+    }
+    // 여기서는 확장자가 출력 포맷을 고른다 (WebP를 못 읽는 클라이언트용 `.png`/`.gif`)
+    let variant = variant.with_extension(extension);
+    let deadline = deadline.map(|Extension(d)| d.0);
+    let req = req.with_effects(emote.effects);
+    let res = serve_emoji(state.clone(), emoji_id.clone(), variant, deadline, req, Mode::Interactive).await;
+    // 모양 옵션을 켠 요청은 옵션 없는 다른 크기를 데울 이유가 없다
+    if let (Variant::Resized(size), true) = (variant, emote.effects.is_none()) {
+        if res.status() == StatusCode::OK && res.headers().get(response::X_CACHE).is_some_and(|v| v == "MISS") {
+            warm_siblings(&state, &emoji_id, size);
+        }
+    }
+    res
+}
+
+/// 크기를 경로에 적은 주소(`/e/:id/64.webp`, `64.png`, `64.gif`)는 같은 결과의 `?size` 정규 주소로 보낸다.
+/// 캐시와 CDN에는 정규 주소 하나만 남는다.
+async fn sized_path_handler(
+    State(state): State<AppState>,
+    axum::extract::Path((name, file)): axum::extract::Path<(String, String)>,
+) -> axum::response::Response {
+    let requested = file.split_once('.').map_or(file.as_str(), |(size, _)| size);
+    let Ok(requested) = requested.parse::<u32>() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if requested == 0 {
+        return Error::BadRequest("size must be a positive integer").into_response();
+    }
+    let stem = name.split('.').next().unwrap_or(&name);
+    let variant = Variant::Resized(state.config.snap_size(requested));
+    let variant = variant.with_extension(path_extension(&file, variant));
+    Redirect::permanent(&canonical_url(&state.config, &format!("/e/{stem}"), variant)).into_response()
+}
+
+/// UI는 보통 목록용 작은 크기와 호버용 큰 크기를 잇달아 요청하므로, 한 크기를 새로 만들었으면
+/// `SIBLING_WARM_SIZES`의 나머지 크기도 백그라운드에서 미리 만들어 둔다. 이미 캐시된 크기는 건너뛴다.
+fn warm_siblings(state: &AppState, emoji_id: &str, requested: u32) {
+    for &size in state.config.sibling_warm_sizes.iter().filter(|&&s| s != requested) {
+        let key = Variant::Resized(size).key(emoji_id);
+        // 디스크에만 둔 큰 항목은 검증자로 확인한다
+        if state.cache.contains_key(&key) || (state.config.cache_spill_bytes.is_some() && state.validators.contains_key(&key)) {
+            continue;
+        }
+        let (state, id) = (state.clone(), emoji_id.to_string());
+        state.tasks.clone().submit("sibling_warm", 0, move || {
+            let (state, id) = (state.clone(), id.clone());
+            async move {
+                // 인기도 집계를 부풀리지 않도록 갱신 경로로 만든다
+                let res = serve_emoji(state, id.clone(), Variant::Resized(size), None, RequestInputs::default(), Mode::Refresh).await;
+                anyhow::ensure!(res.status().is_success(), "warm {id}@{size}: status {}", res.status());
+                Ok(())
+            }
+        });
+    }
+}
+
+/// 요청 주소의 확장자. 리사이즈 결과는 WebP(협상하면 AVIF)라 `.webp`나 확장자 없음이 정규 주소다.
+/// `/e`는 `.png`/`.gif`로 그 포맷을 만들고, `/custom`·`/c`는 Discord처럼 애니메이션을 `.gif`로 부르는 것까지만
+/// 받아 준다. 패스스루/원본은 원본 포맷 그대로라 따지지 않는다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PathExtension {
+    Canonical,
+    Png,
+    /// `/custom`·`/c`에서는 애니메이션일 때만 맞는 별칭
+    Gif,
+    /// 만들지 않는 포맷 (`.jpg` 등)
+    Other,
+}
+
+fn path_extension(name: &str, variant: Variant) -> PathExtension {
+    if !matches!(variant, Variant::Resized(_)) {
+        return PathExtension::Canonical;
+    }
+    match name.split_once('.').map(|(_, ext)| ext) {
+        None | Some("webp") => PathExtension::Canonical,
+        Some("png") => PathExtension::Png,
+        Some("gif") => PathExtension::Gif,
+        Some(_) => PathExtension::Other,
+    }
+}
+
+/// 확장자 없는 경로(`/e/123`)의 정규 주소. `?size`는 기본 크기가 아닐 때만 붙인다.
+fn canonical_url(config: &Config, stem: &str, variant: Variant) -> String {
+    let extension = match variant {
+        Variant::Png(_) => "png",
+        Variant::Gif(_) => "gif",
+        _ => "webp",
+    };
+    match variant.size() {
+        size if size == config.default_size => format!("{stem}.{extension}"),
+        size => format!("{stem}.{extension}?size={size}"),
+    }
+}
+
+/// `.gif`로 요청했는데 정적 WebP가 나왔으면 엉뚱한 확장자로 캐시되지 않도록 정규 주소로 보낸다
+async fn redirect_static_gif(res: axum::response::Response, canonical: &str) -> axum::response::Response {
+    if res.status() != StatusCode::OK {
+        return res;
+    }
+    let (parts, body) = res.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Reading response body failed: {}", e);
+            return Error::Processing.into_response();
+        }
+    };
+    if ContentType::sniff(&bytes) == ContentType::Webp && !is_animated_webp(&bytes) {
+        return Redirect::permanent(canonical).into_response();
+    }
+    axum::response::Response::from_parts(parts, axum::body::Body::from(bytes))
+}
+
+/// `?passthrough`/`?size`로 응답 종류를 고른다. 허용 목록 밖의 크기는 가까운 크기의
+/// 정규 URL(`path?size=N`)로 리다이렉트해서 캐시/CDN 변형 수를 묶어 둔다.
+fn pick_variant(config: &Config, params: &EmojiParams, path: &str) -> Result<Variant, Redirect> {
+    let passthrough = params
+        .passthrough
+        .as_deref()
+        .map_or(config.passthrough, |v| matches!(v, "1" | "true" | "yes"));
+    if passthrough {
+        return Ok(Variant::Passthrough);
+    }
+    let size = match params.size {
+        Some(requested) => {
+            let size = config.snap_size(requested);
+            if size != requested {
+                return Err(Redirect::permanent(&format!("{path}?size={size}")));
+            }
+            size
+        }
+        None => config.default_size,
+    };
+    Ok(Variant::Resized(size))
+}
+
+/// 백업 봇처럼 화질 손실 없는 원본이 필요한 도구용: 크기 지정 없이 받은 Discord 원본을 그대로
+async fn original_handler(
+    State(state): State<AppState>,
+    emote: EmoteRequest,
+    deadline: Option<Extension<middleware::Deadline>>,
+    req: RequestInputs,
+) -> axum::response::Response {
+    serve_emoji(state, emote.stem, Variant::Original, deadline.map(|Extension(d)| d.0), req, Mode::Interactive).await
+}
+
+/// 캐시 디버깅·purge 도구용: 이 이모지의 변형 중 어느 계층에든 남아 있는 것의 크기·형식·바이트 수·ETag.
+/// `layers`는 `memory`(본 캐시), `stale`(만료 보관분), 2차 계층 이름(`disk` 등), `validator`(검증자만) 중 가진 곳.
+async fn variants_handler(State(state): State<AppState>, emote: EmoteRequest) -> axum::response::Response {
+    let emoji_id = emote.stem;
+    if state.blocklist.blocks_id(&emoji_id) {
+        return Error::Blocked.into_response();
+    }
+    let mut variants = Vec::new();
+    for (variant, key) in Variant::keys(&state.config, &emoji_id) {
+        let mut layers = Vec::new();
+        let mut found = None;
+        if let Some(entry) = state.cache.get(&key).await {
+            layers.push("memory");
+            found = Some(entry.validator());
+        }
+        if let Some(entry) = state.stale.get(&key).await {
+            layers.push("stale");
+            found = found.or(Some(entry.validator()));
+        }
+        if let Some((stored, tier)) = state.tiers.get(&key).await {
+            layers.push(tier);
+            found = found.or_else(|| Some(Cached::stored_at(Arc::new(stored.bytes), stored.stored).validator()));
+        }
+        if let Some(validator) = state.validators.get(&key).await {
+            layers.push("validator");
+            found = found.or(Some(validator));
+        }
+        let Some(found) = found else {
+            continue;
+        };
+        let (kind, size) = match variant {
+            Variant::Resized(size) => ("resized", Some(size)),
+            Variant::Avif(size) => ("avif", Some(size)),
+            Variant::Png(size) => ("png", Some(size)),
+            Variant::Gif(size) => ("gif", Some(size)),
+            Variant::Passthrough => ("passthrough", None),
+            Variant::Original => ("original", None),
+        };
+        variants.push(serde_json::json!({
+            "key": key,
+            "kind": kind,
+            "size": size,
+            "content_type": found.content_type.as_str(),
+            "bytes": found.length,
+            "etag": &*found.etag,
+            "age_secs": found.stored.elapsed().unwrap_or_default().as_secs(),
+            "layers": layers,
+        }));
+    }
+    axum::Json(serde_json::json!({ "emoji_id": emoji_id, "variants": variants })).into_response()
+}
+
+/// 웹 클라이언트용: 리사이즈 결과(`?size`, DPR 접미사 반영)의 형식·크기·애니메이션 여부와 ThumbHash·대표 색을 JSON으로.
+/// 결과는 이미지 요청과 같은 경로로 만들고 캐시하므로, 이어서 받는 이미지는 HIT이다.
+async fn info_handler(
+    State(state): State<AppState>,
+    emote: EmoteRequest,
+    deadline: Option<Extension<middleware::Deadline>>,
+) -> axum::response::Response {
+    let requested = emote.params.size.unwrap_or(state.config.default_size);
+    let variant = Variant::Resized(state.config.snap_size((requested as f32 * emote.dpr).round() as u32));
+    let deadline = deadline.map(|Extension(d)| d.0);
+    let res = serve_emoji(state.clone(), emote.stem.clone(), variant, deadline, RequestInputs::default(), Mode::Interactive).await;
+    if res.status() != StatusCode::OK {
+        return res;
+    }
+    let (parts, body) = res.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Reading response body failed: {}", e);
+            return Error::Processing.into_response();
+        }
+    };
+    let value = |name: header::HeaderName| parts.headers.get(name).and_then(|v| v.to_str().ok());
+    let dimensions = image::ImageReader::new(std::io::Cursor::new(&bytes))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok());
+    let mut res = axum::Json(serde_json::json!({
+        "emoji_id": emote.stem,
+        "url": format!("/e/{}", canonical_url(&state.config, &emote.stem, variant)),
+        "size": variant.size(),
+        "content_type": value(header::CONTENT_TYPE),
+        "bytes": bytes.len(),
+        "width": dimensions.map(|(w, _)| w),
+        "height": dimensions.map(|(_, h)| h),
+        "animated": is_animated_webp(&bytes),
+        "etag": value(header::ETAG),
+        "thumbhash": value(placeholder::X_THUMBHASH),
+        "dominant_color": value(placeholder::X_DOMINANT_COLOR),
+        "placeholder": format!("/e/{}/placeholder", emote.stem),
+    }))
+    .into_response();
+    for name in placeholder::COPIED {
+        if let Some(value) = parts.headers.get(&name) {
+            res.headers_mut().insert(name, value.clone());
+        }
+    }
+    res
+}
+
+/// 요청이 어디서 왔는지. 캐시를 조회할지와 인코딩 대기열의 우선순위를 정한다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// 클라이언트가 기다리는 요청 (높은 우선순위)
+    Interactive,
+    /// 캐시 채우기. 캐시는 조회하되 낮은 우선순위로 만든다
+    Warm,
+    /// 캐시를 조회하지 않고 업스트림에서 다시 만들어 덮어쓴다 (선제 갱신·형제 크기 준비, 낮은 우선순위)
+    Refresh,
+}
+
+impl Mode {
+    fn priority(self) -> processing::Priority {
+        match self {
+            Mode::Interactive => processing::Priority::High,
+            Mode::Warm | Mode::Refresh => processing::Priority::Low,
+        }
+    }
+}
+
+async fn serve_emoji(
+    state: AppState,
+    name: String,
+    variant: Variant,
+    deadline: Option<Instant>,
+    // 협상 입력은 이걸 통해 읽어야 응답의 `Vary`에 실린다
+    req: RequestInputs,
+    mode: Mode,
+) -> axum::response::Response {
+    info!("Request received - Emoji ID: {}", name);
+
+    // 확장자 제거 (.webp, .gif, .png 등)
+    let emoji_id = name
+        .split('.')
+        .next()
+        .unwrap_or(&name)
+        .to_string();
+    
+    info!("Processed Emoji ID: {}", emoji_id);
+
+    if state.blocklist.blocks_id(&emoji_id) || state.blocklist.blocks_source("discord") {
+        warn!("Blocked emoji requested: {}", emoji_id);
+        return Error::Blocked.into_response();
+    }
+
+    let size = variant.size();
+    let origin = Origin {
+        tags: purge::tags(&emoji_id, "discord", size),
+        // 캐시 키: 이모지 ID만 사용 (기본 크기 160x160, WebP 포맷). 패스스루/원본은 따로 보관
+        key_base: emoji_id.clone(),
+        url: format_src(&state.config, &emoji_id, variant),
+        label: emoji_id,
+        fetch: Fetch::Http(state.upstreams.client(upstream::Source::Discord).clone()),
+    };
+    serve_image(state, origin, variant, deadline, req, mode).await
+}
+
+/// 이모지 하나를 어디서 어떻게 가져올지. 소스마다 캐시 키 공간과 HTTP 클라이언트가 다르다.
+struct Origin {
+    /// 로그에 남길 이름 (Discord는 이모지 ID)
+    label: String,
+    /// [`Variant::key`]에 넘길 캐시 키 바탕. Discord 외 소스는 `소스/…` 형태로 겹치지 않게 한다.
+    key_base: String,
+    url: String,
+    tags: Vec<String>,
+    fetch: Fetch,
+}
+
+/// 원본을 읽어 올 곳
+enum Fetch {
+    /// `Origin::url`을 이 클라이언트로 받는다
+    Http(upstream::Upstream),
+    /// 업로드 저장소의 이 내용 해시
+    Stored(Arc<custom::Store>, String),
+}
+
+/// 캐시 조회 → 업스트림 fetch/검증 → 리사이즈 → 캐시 저장. 소스와 무관한 공통 경로.
+/// 에러로 끝난 응답은 `/admin/errors`에서 볼 수 있게 남기고, 리사이즈한 WebP 응답에는 ThumbHash를 싣는다.
+async fn serve_image(
+    state: AppState,
+    origin: Origin,
+    variant: Variant,
+    deadline: Option<Instant>,
+    req: RequestInputs,
+    mode: Mode,
+) -> axum::response::Response {
+    let (label, request_id) = (origin.label.clone(), req.request_id().map(str::to_owned));
+    let source = match &origin.fetch {
+        Fetch::Http(http) => http.source().name(),
+        Fetch::Stored(..) => "custom",
+    };
+    let started = Instant::now();
+    let res = render(state.clone(), origin, variant, deadline, req, mode).await;
+    // 사용자가 기다린 미스만 SLO에 넣는다 (워밍·갱신은 빼고, 304도 새로 만든 것이면 넣는다)
+    if mode == Mode::Interactive && res.headers().get(response::X_CACHE).is_some_and(|v| v == "MISS") {
+        state.slo.record(source, started.elapsed());
+    }
+    if let Some(error) = res.extensions().get::<Error>() {
+        state.recent_errors.record(&label, *error, request_id.as_deref());
+        return res;
+    }
+    match (variant, mode) {
+        (Variant::Resized(_), Mode::Interactive) => placeholder::attach(&state, &label, res).await,
+        _ => res,
+    }
+}
+
+/// 클라이언트가 응답 전에 연결을 끊으면 요청 future가 버려진다. `CLIENT_DISCONNECT=cancel`이면 그때 진행 중인
+/// 업스트림 요청과 외부 인코더가 함께 버려지고 블로킹 스레드의 프레임 처리도 다음 프레임에서 멈춘다.
+/// `finish`면 작업을 따로 띄워 두어 끝까지 만들고 캐시에 넣는다 (다음 요청이 HIT이 되도록).
+async fn render(
+    state: AppState,
+    origin: Origin,
+    variant: Variant,
+    deadline: Option<Instant>,
+    req: RequestInputs,
+    mode: Mode,
+) -> axum::response::Response {
+    if mode != Mode::Interactive {
+        return render_image(state, origin, variant, deadline, None, req, mode).await;
+    }
+    let watch = DisconnectWatch::new(state.config.client_disconnect, deadline);
+    let res = match state.config.client_disconnect {
+        config::ClientDisconnect::Cancel => {
+            render_image(state, origin, variant, deadline, Some(watch.abandoned.clone()), req, mode).await
+        }
+        config::ClientDisconnect::Finish => {
+            match tokio::spawn(render_image(state, origin, variant, deadline, None, req, mode)).await {
+                Ok(res) => res,
+                // 패닉은 그대로 다시 일으켜 `CatchPanicLayer`가 처리하게 한다
+                Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+                Err(e) => {
+                    error!("Detached render task failed: {}", e);
+                    Error::Processing.into_response()
+                }
+            }
+        }
+    };
+    watch.done();
+    res
+}
+
+/// 응답을 돌려주기 전에 버려지면(클라이언트가 끊으면) 표시를 세우고 세어 둔다
+struct DisconnectWatch {
+    policy: config::ClientDisconnect,
+    deadline: Option<Instant>,
+    abandoned: Arc<AtomicBool>,
+    done: bool,
+}
+
+impl DisconnectWatch {
+    fn new(policy: config::ClientDisconnect, deadline: Option<Instant>) -> Self {
+        Self { policy, deadline, abandoned: Arc::new(AtomicBool::new(false)), done: false }
+    }
+
+    fn done(mut self) {
+        self.done = true;
+    }
+}
+
+impl Drop for DisconnectWatch {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        self.abandoned.store(true, Ordering::Relaxed);
+        // 마감 시각 초과로 버려진 것은 `deadline_exceeded`가 따로 센다
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            return;
+        }
+        let outcome = match self.policy {
+            config::ClientDisconnect::Cancel => "cancelled",
+            config::ClientDisconnect::Finish => "finished",
+        };
+        ::metrics::counter!(metrics::CLIENT_DISCONNECTS_TOTAL, "outcome" => outcome).increment(1);
+        info!("Client disconnected before the response, work {}", outcome);
+    }
+}
+
+async fn render_image(
+    state: AppState,
+    origin: Origin,
+    variant: Variant,
+    deadline: Option<Instant>,
+    abandoned: Option<Arc<AtomicBool>>,
+    req: RequestInputs,
+    mode: Mode,
+) -> axum::response::Response {
+    let refresh = mode == Mode::Refresh;
+    // 인증된 요청도 본문은 같으므로 `Vary`를 늘리지 않고 수명만 다르게 준다
+    let cache_control = if req.is_authenticated() {
+        state.config.emoji_cache_auth.header_value()
+    } else {
+        state.config.emoji_cache.header_value()
+    };
+    let variant = negotiate(&state.config, variant, &req);
+    let size = variant.size();
+    let Origin { label, key_base, url: src, tags, fetch } = origin;
+    // 인코더 설정이 기본값과 다른 테넌트의 리사이즈 결과만 키를 나눈다 (같은 설정끼리는 공유)
+    let tenant_webp = match variant {
+        Variant::Resized(_) | Variant::Avif(_) if !state.config.tenants.is_empty() => {
+            req.tenant().map(|t| t.webp.clone()).filter(|w| *w != state.config.webp)
+        }
+        _ => None,
+    };
+    let key = match &tenant_webp {
+        Some(webp) => tenant::cache_key(&variant.key(&key_base), webp),
+        None => variant.key(&key_base),
+    };
+    // 모양 옵션은 리사이즈 결과에만 (패스스루/원본은 원본 그대로)
+    let effects = match variant {
+        Variant::Passthrough | Variant::Original => Effects::NONE,
+        _ => req.effects(),
+    };
+    let key = effects.cache_key(&key);
+
+    let mut timing = ServerTiming::default();
+    let started = Instant::now();
+    if !refresh {
+        state.popularity.record(&key);
+    }
+    let mut hit = if refresh { None } else { state.cache.get(&key).await };
+    if hit.is_none() && !refresh {
+        // 바이트는 밀려났어도 검증자가 남아 있으면 304/HEAD는 다시 만들지 않고 답한다
+        if let Some(validator) = state.validators.get(&key).await {
+            if req.is_head() || req.none_match(&validator.etag) {
+                info!("Validator hit for emoji: {}", label);
+                timing.push("cache", started.elapsed());
+                return ImageResponse::validated(validator.etag, validator.content_type, validator.length)
+                    .age(validator.stored.elapsed().unwrap_or_default())
+                    .cache_control(&cache_control)
+                    .tags(&tags)
+                    .source(src.clone())
+                    .x_cache("VALIDATED")
+                    .server_timing(&timing)
+                    .respond(&req);
+            }
+        }
+    }
+
+    if hit.is_none() && !refresh && effects.is_none() {
+        // 2차 계층은 타임아웃이 걸려 있어 느리면 업스트림으로 넘어간다
+        if let Some((stored, tier)) = state.tiers.get(&key).await {
+            info!("{} cache hit for emoji: {}", tier, label);
+            hit = Some(promote(&state, &key, stored).await);
+        }
+    }
+
+    timing.push("cache", started.elapsed());
+
+    if let Some(entry) = hit {
+        info!("Cache hit for emoji: {}", label);
+        return entry
+            .response()
+            .cache_control(&cache_control)
+            .tags(&tags)
+            .source(src.clone())
+            .x_cache("HIT")
+            .server_timing(&timing)
+            .respond(&req);
+    }
+
+    info!("Cache miss - fetching emoji: {}", label);
+
+    // 과부하 중이면 새로 인코딩하지 않고 보관 중인 만료 항목부터 제공
+    let degraded = !refresh && state.degradation.is_degraded();
+    if degraded {
+        if let Some(res) = stale_response(&state, &key, &label, &src, &tags, &req).await {
+            ::metrics::counter!(metrics::DEGRADED_RESPONSES_TOTAL, "action" => "stale").increment(1);
+            return res;
+        }
+    }
+
+    // 같은 인스턴스의 다른 요청이 이 키를 만드는 중이면 그 결과를 같이 쓴다 (선제 갱신은 늘 새로 만든다)
+    let flight = if refresh { None } else { Some(state.flights.join(&key)) };
+    let _flight = match flight {
+        None => None,
+        Some(flight::Flight::Leader(guard)) => {
+            // 위에서 캐시를 본 뒤 자리를 잡기 전에 앞선 리더가 끝났을 수 있으니 한 번 더 본다
+            if let Some(entry) = state.cache.get(&key).await {
+                info!("Cache hit after joining flight for emoji: {}", label);
+                state.flights.publish(&key, &entry);
+                return entry
+                    .response()
+                    .cache_control(&cache_control)
+                    .tags(&tags)
+                    .source(src.clone())
+                    .x_cache("HIT")
+                    .server_timing(&timing)
+                    .respond(&req);
+            }
+            Some(guard)
+        }
+        Some(flight::Flight::Follower(rx)) => {
+            let waited = Instant::now();
+            match flight::wait(rx, deadline).await {
+                Some(entry) => {
+                    info!("Coalesced cache miss for emoji: {}", label);
+                    ::metrics::counter!(metrics::REQUESTS_COALESCED_TOTAL, "outcome" => "shared").increment(1);
+                    timing.push("coalesce", waited.elapsed());
+                    return entry
+                        .response()
+                        .cache_control(&cache_control)
+                        .tags(&tags)
+                        .source(src.clone())
+                        .x_cache("HIT")
+                        .server_timing(&timing)
+                        .respond(&req);
+                }
+                // 리더가 결과 없이 끝났으면 각자 처리 (실패한 키를 한 줄로 세워 차례로 다시 받지 않도록)
+                None => {
+                    ::metrics::counter!(metrics::REQUESTS_COALESCED_TOTAL, "outcome" => "alone").increment(1);
+                    None
+                }
+            }
+        }
+    };
+
+    // 다른 인스턴스가 같은 키를 만드는 중이면 그 결과를 기다린다
+    let lease_enabled = !refresh && !state.tiers.is_empty() && !state.config.cache_lease.is_zero() && effects.is_none();
+    let _lease = if lease_enabled {
+        let waited = Instant::now();
+        match state.tiers.claim(&key, state.config.cache_lease, deadline).await {
+            cache::Claim::Leader(lease) => Some(lease),
+            cache::Claim::Follower(stored, tier) => {
+                info!("{} cache hit after waiting on lease for emoji: {}", tier, label);
+                timing.push("lease", waited.elapsed());
+                return promote(&state, &key, stored)
+                    .await
+                    .response()
+                    .cache_control(&cache_control)
+                    .tags(&tags)
+                    .source(src.clone())
+                    .x_cache("HIT")
+                    .server_timing(&timing)
+                    .respond(&req);
+            }
+            cache::Claim::Expired => {
+                warn!("Lease wait expired, processing without coordination - emoji: {}", label);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // 원본 fetch
+    let fetch_start = Instant::now();
+    let source = match &fetch {
+        Fetch::Http(http) => Some(http.source()),
+        Fetch::Stored(..) => None,
+    };
+    // 업스트림이 지운 이모지를 보관 사본으로 내보내는 중인지 (그 사본을 다시 보관하지 않도록)
+    let mut mirrored = false;
+    let (upstream_type, body) = match fetch {
+        Fetch::Http(http) => 'fetch: {
+            let source = http.source();
+            let mut resp = match http.send_as(mode.priority(), |c| c.get(&src).header(header::ACCEPT, "image/webp,image/*")).await {
+                Ok(r) => r,
+                Err(e) => {
+                    error!("Fetch error for emoji {}: {}", label, e);
+                    state.upstream_stats.record(false);
+                    if let Some(res) = stale_response(&state, &key, &label, &src, &tags, &req).await {
+                        return res;
+                    }
+                    if e.is_timeout() {
+                        return Error::UpstreamTimeout { source }.into_response();
+                    }
+                    if e.is_redirect() {
+                        return Error::UpstreamRedirect { source }.into_response();
+                    }
+                    return Error::UpstreamUnreachable { source }.into_response();
+                }
+            };
+
+            if resp.status() == StatusCode::NOT_FOUND {
+                if let Some(mirror) = &state.mirror {
+                    // 리사이즈할 때도 큰 원본이 있으면 그쪽부터
+                    let mut keys = vec![Variant::Original.key(&key_base)];
+                    if variant != Variant::Original {
+                        keys.push(key_base.clone());
+                    }
+                    if let Some(bytes) = mirror.load(&keys).await {
+                        warn!("Upstream no longer has emoji {}, serving mirrored original", label);
+                        mirrored = true;
+                        break 'fetch (None, Bytes::from(bytes));
+                    }
+                }
+                warn!("Emoji not found: {}", label);
+                return Error::NotFound.into_response();
+            }
+            if !resp.status().is_success() {
+                error!("Upstream error for emoji {}: status {}", label, resp.status());
+                state.upstream_stats.record(false);
+                if resp.status().is_server_error() {
+                    if let Some(res) = stale_response(&state, &key, &label, &src, &tags, &req).await {
+                        return res;
+                    }
+                }
+                return Error::UpstreamStatus { source, status: resp.status() }.into_response();
+            }
+
+            let upstream_type = resp
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned);
+            let max_bytes = match variant {
+                Variant::Original => state.config.original_max_bytes,
+                _ => state.config.upstream_max_bytes,
+            };
+            let too_large = |len: u64| max_bytes > 0 && len > max_bytes;
+            if resp.content_length().is_some_and(too_large) {
+                warn!("Upstream body too large for emoji {}: {:?} bytes", label, resp.content_length());
+                ::metrics::counter!(metrics::UPSTREAM_INVALID_TOTAL, "reason" => "too large").increment(1);
+                return Error::UpstreamTooLarge { source }.into_response();
+            }
+            // Content-Length가 없거나 틀린 응답 대비: 읽으면서 세다가 한도를 넘으면 바로 끊는다
+            let mut body = Vec::new();
+            loop {
+                match resp.chunk().await {
+                    Ok(Some(chunk)) => {
+                        if too_large((body.len() + chunk.len()) as u64) {
+                            warn!("Upstream body too large for emoji {}: over {} bytes", label, max_bytes);
+                            state.upstream_stats.record(true);
+                            ::metrics::counter!(metrics::UPSTREAM_INVALID_TOTAL, "reason" => "too large").increment(1);
+                            return Error::UpstreamTooLarge { source }.into_response();
+                        }
+                        body.extend_from_slice(&chunk);
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        error!("Read body error for emoji {}: {}", label, e);
+                        state.upstream_stats.record(false);
+                        if let Some(res) = stale_response(&state, &key, &label, &src, &tags, &req).await {
+                            return res;
+                        }
+                        return Error::UpstreamRead { source }.into_response();
+                    }
+                }
+            }
+            state.upstream_stats.record(true);
+            let body = Bytes::from(body);
+            (upstream_type, body)
+        }
+        // 직접 올린 이모트는 업로드 때 검증/정규화한 저장본을 읽는다
+        Fetch::Stored(store, hash) => match store.read(&hash).await {
+            Ok(Some(bytes)) => (None, Bytes::from(bytes)),
+            Ok(None) => {
+                warn!("Emoji not found: {}", label);
+                return Error::NotFound.into_response();
+            }
+            Err(e) => {
+                error!("Stored read error for emoji {}: {:#}", label, e);
+                return Error::Storage.into_response();
+            }
+        },
+    };
+    timing.push("fetch", fetch_start.elapsed());
+
+    let upstream_format = match imaging::validate_upstream(upstream_type.as_deref(), &body) {
+        Ok(f) => f,
+        Err(reason) => {
+            error!(
+                "Upstream returned non-image body for emoji {} ({}: {:?}, {} bytes)",
+                label, reason, upstream_type, body.len()
+            );
+            ::metrics::counter!(metrics::UPSTREAM_INVALID_TOTAL, "reason" => reason).increment(1);
+            if let Some(res) = stale_response(&state, &key, &label, &src, &tags, &req).await {
+                return res;
+            }
+            return match source {
+                Some(source) => Error::UpstreamInvalid { source, reason }.into_response(),
+                None => Error::Storage.into_response(),
+            };
+        }
+    };
+
+    if state.blocklist.blocks_content(&body) {
+        warn!("Blocked content hash for emoji {}", label);
+        return Error::Blocked.into_response();
+    }
+
+    // 검증과 차단 확인을 통과한 업스트림 원본만 보관한다 (업로드 저장본은 이미 디스크에 있음)
+    if let Some(mirror) = state.mirror.clone().filter(|_| source.is_some() && !mirrored) {
+        let key = if variant == Variant::Original { key.clone() } else { key_base.clone() };
+        let bytes = body.clone();
+        state.tasks.submit("mirror_put", 1, move || {
+            let (mirror, key, bytes) = (mirror.clone(), key.clone(), bytes.clone());
+            async move { mirror.store(&key, &bytes).await }
+        });
+    }
+
+    if matches!(variant, Variant::Passthrough | Variant::Original) {
+        let bytes = Arc::new(body.to_vec());
+        cache_insert(&state, key, bytes.clone()).await;
+        info!(
+            "{:?} emoji served - emoji: {}, {}, size: {} bytes",
+            variant, label, upstream_format, bytes.len()
+        );
+
+        return ImageResponse::new(bytes, upstream_format)
+            .cache_control(&cache_control)
+            .tags(&tags)
+            .source(src.clone())
+            .server_timing(&timing)
+            .respond(&req);
+    }
+
+    // 만료 항목도 없으면 원본을 그대로 제공. 저하된 응답이 오래 남지 않도록 캐시하지 않는다
+    if degraded {
+        warn!("Degraded mode - serving original for emoji {}", label);
+        ::metrics::counter!(metrics::DEGRADED_RESPONSES_TOTAL, "action" => "original").increment(1);
+        return ImageResponse::new(Arc::new(body.to_vec()), upstream_format)
+            .cache_control(STALE_CACHE_CONTROL)
+            .tags(&tags)
+            .source(src.clone())
+            .x_cache("DEGRADED")
+            .server_timing(&timing)
+            .respond(&req);
+    }
+
+    let _encoding = state.degradation.enter();
+    let queued = Instant::now();
+    let acquire = state.processing.acquire(mode.priority());
+    let _permit = match deadline {
+        Some(at) => match tokio::time::timeout_at(at.into(), acquire).await {
+            Ok(permit) => permit,
+            Err(_) => {
+                warn!("Deadline passed while queued for processing - emoji: {}", label);
+                return Error::DeadlineExceeded.into_response();
+            }
+        },
+        None => acquire.await,
+    };
+    timing.push("queue", queued.elapsed());
+
+    // WebP를 못 읽는 클라이언트용 PNG/GIF는 libvips와 WebP 크기 예산을 거치지 않고 내장 인코더로
+    if let Variant::Png(_) | Variant::Gif(_) = variant {
+        let content_type = match variant {
+            Variant::Gif(_) => ContentType::Gif,
+            _ => ContentType::Png,
+        };
+        let pool = state.frame_pool.clone();
+        let input = body.clone();
+        let cancel = imaging::Cancel { deadline, abandoned };
+        let result = tokio::task::spawn_blocking(move || match content_type {
+            ContentType::Gif => imaging::resize_gif(&input, size, &effects, &pool, &cancel),
+            _ => imaging::resize_png(&input, size, &effects),
+        })
+        .await;
+        let bytes = match result {
+            Ok(Ok(out)) => {
+                info!("{} processed - emoji: {}, {}x{} → {}x{}, size: {} bytes",
+                      content_type, label, out.original.0, out.original.1,
+                      out.resized.0, out.resized.1, out.bytes.len());
+                timing.stages(&out.timings);
+                Arc::new(out.bytes)
+            }
+            Ok(Err(ImagingError::Decode(e))) => {
+                error!("Decode error for emoji {}: {}", label, e);
+                return Error::Decode.into_response();
+            }
+            Ok(Err(ImagingError::Cancelled)) => {
+                warn!("{} processing cancelled - emoji: {}", content_type, label);
+                return Error::DeadlineExceeded.into_response();
+            }
+            // 요청한 포맷이 아닌 것을 `:png`/`:gif` 키에 캐시하지 않는다
+            Ok(Err(ImagingError::Encode(e))) => {
+                error!("{} encode failed for emoji {} (input {}): {}", content_type, label, blocklist::content_hash(&body), e);
+                return Error::Processing.into_response();
+            }
+            Err(e) => {
+                error!("{} processing task failed for emoji {}: {}", content_type, label, e);
+                return Error::Processing.into_response();
+            }
+        };
+        if state.blocklist.blocks_content(&bytes) {
+            warn!("Blocked output hash for emoji {}", label);
+            return Error::Blocked.into_response();
+        }
+        cache_insert(&state, key, bytes.clone()).await;
+
+        return ImageResponse::new(bytes, content_type)
+            .cache_control(&cache_control)
+            .tags(&tags)
+            .source(src.clone())
+            .server_timing(&timing)
+            .respond(&req);
+    }
+
+    // 파일 헤더 분석으로 애니메이션 여부 확인 (애니메이션 WebP, 프레임이 둘 이상인 GIF·APNG)
+    let is_animated = imaging::is_animated(&body);
+
+    // 고른 작업은 libvips에 먼저 맡기고, 실패하면 아래 내장 경로로 처리 (모양 옵션은 내장 경로에서만)
+    #[cfg(feature = "vips")]
+    if effects.is_none() {
+        let operation = if upstream_format != ContentType::Webp {
+            config::VipsOperation::Foreign
+        } else if is_animated {
+            config::VipsOperation::Animated
+        } else {
+            config::VipsOperation::Static
+        };
+        if state.config.vips_operations.contains(&operation) {
+            let started = Instant::now();
+            let op = format!("{operation:?}").to_ascii_lowercase();
+            match vips::thumbnail(&state.config.vips_binary, &body, size, deadline).await {
+                Ok(out) => {
+                    ::metrics::counter!(metrics::VIPS_RUNS_TOTAL, "operation" => op, "outcome" => "ok").increment(1);
+                    timing.push("vips", started.elapsed());
+                    let bytes = Arc::new(out);
+                    if state.blocklist.blocks_content(&bytes) {
+                        warn!("Blocked output hash for emoji {}", label);
+                        return Error::Blocked.into_response();
+                    }
+                    cache_insert(&state, key, bytes.clone()).await;
+                    info!("vips processed - emoji: {}, {:?}, size: {} bytes", label, operation, bytes.len());
+                    return ImageResponse::new(bytes, ContentType::Webp)
+                        .cache_control(&cache_control)
+                        .tags(&tags)
+                        .source(src.clone())
+                        .server_timing(&timing)
+                        .respond(&req);
+                }
+                Err(e) => {
+                    ::metrics::counter!(metrics::VIPS_RUNS_TOTAL, "operation" => op, "outcome" => "error").increment(1);
+                    warn!("vips processing failed for emoji {}, using built-in pipeline: {:#}", label, e);
+                }
+            }
+        }
+    }
+
+
+    if is_animated {
+        info!("Processing animated emoji: {} ({})", label, upstream_format);
+        // 프레임별 리사이즈/인코드는 블로킹 스레드에서 프레임 풀로 병렬 처리
+        let pool = state.frame_pool.clone();
+        let input = body.clone();
+        let budget = state.config.animated_budget;
+        let cancel = imaging::Cancel { deadline, abandoned };
+        let task_cancel = cancel.clone();
+        let result =
+            tokio::task::spawn_blocking(move || {
+                imaging::resize_animated_webp(&input, size, &effects, &pool, &task_cancel, budget)
+            })
+            .await;
+
+        let mut encode_params = None;
+        let (bytes, content_type) = match result {
+            Ok(Ok(out)) => {
+                info!("Animated WebP processed - emoji: {}, {}x{} → {}x{}, size: {} bytes",
+                      label, out.original.0, out.original.1,
+                      out.resized.0, out.resized.1, out.bytes.len());
+                if let Some(outcome) = out.budget.filter(|o| *o != imaging::BudgetOutcome::UNCHANGED) {
+                    info!("Animated budget applied - emoji: {}, {}", label, outcome);
+                }
+                encode_params = out.budget.map(|o| o.to_string());
+                timing.stages(&out.timings);
+                (Arc::new(out.bytes), ContentType::Webp)
+            }
+            Ok(Err(ImagingError::Cancelled)) => {
+                warn!("Animated processing cancelled - emoji: {}", label);
+                return Error::DeadlineExceeded.into_response();
+            }
+            // 재인코딩에 실패하면 첫 프레임만 정적 WebP로, 그것도 안 되면 원본을 그대로 제공
+            Ok(Err(e)) => {
+                let fingerprint = blocklist::content_hash(&body);
+                let webp = state.config.quality.webp_options(size, tenant_webp.as_ref().unwrap_or(&state.config.webp)).into_owned();
+                let input = body.clone();
+                let fallback =
+                    tokio::task::spawn_blocking(move || imaging::resize_static(&input, size, &webp, &effects, &cancel)).await;
+                match fallback.map_err(|e| e.to_string()).and_then(|r| r.map_err(|e| e.to_string())) {
+                    Ok(out) => {
+                        warn!("Animated re-encode failed for emoji {} (input {}), falling back to static WebP: {}", label, fingerprint, e);
+                        ::metrics::counter!(metrics::ENCODE_FALLBACKS_TOTAL, "from" => "animated", "to" => "static").increment(1);
+                        timing.stages(&out.timings);
+                        (Arc::new(out.bytes), ContentType::Webp)
+                    }
+                    Err(static_err) => {
+                        warn!("Animated re-encode failed for emoji {} (input {}), passing through original: {} (static fallback: {})",
+                              label, fingerprint, e, static_err);
+                        ::metrics::counter!(metrics::ENCODE_FALLBACKS_TOTAL, "from" => "animated", "to" => "original").increment(1);
+                        (Arc::new(body.to_vec()), upstream_format)
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Animated processing task failed for emoji {}: {}", label, e);
+                return Error::Processing.into_response();
+            }
+        };
+        // AVIF를 받는 클라이언트에게는 리사이즈한 프레임을 AVIF로 다시 묶는다. 실패하면 WebP 그대로
+        #[cfg(feature = "avif")]
+        let (bytes, content_type) = match variant {
+            Variant::Avif(_) if content_type == ContentType::Webp && is_animated_webp(&bytes) => {
+                let started = Instant::now();
+                let options = avif::Options {
+                    binary: &state.config.avif_binary,
+                    speed: state.config.avif_speed,
+                    quality: state.config.quality.at(size).avif.unwrap_or(state.config.avif_quality),
+                };
+                match avif::encode_animated(bytes.to_vec(), &options, deadline).await {
+                    Ok(out) => {
+                        timing.push("avif", started.elapsed());
+                        info!("Animated AVIF encoded - emoji: {}, {} → {} bytes", label, bytes.len(), out.len());
+                        (Arc::new(out), ContentType::Avif)
+                    }
+                    Err(e) => {
+                        warn!("AVIF encode failed for emoji {} (input {}), serving WebP: {:#}", label, blocklist::content_hash(&body), e);
+                        ::metrics::counter!(metrics::ENCODE_FALLBACKS_TOTAL, "from" => "avif", "to" => "webp").increment(1);
+                        (bytes, content_type)
+                    }
+                }
+            }
+            _ => (bytes, content_type),
+        };
+        if state.blocklist.blocks_content(&bytes) {
+            warn!("Blocked output hash for emoji {}", label);
+            return Error::Blocked.into_response();
+        }
+        cache_insert(&state, key, bytes.clone()).await;
+
+        return ImageResponse::new(bytes, content_type)
+            .cache_control(&cache_control)
+            .tags(&tags)
+            .source(src.clone())
+            .server_timing(&timing)
+            .animation_encode(encode_params)
+            .respond(&req);
+    }
+
+    // 정적 WebP 처리: 디코드 → 종횡비 유지하며 리사이즈 → WebP 인코드
+    // 테넌트 설정으로 만드는 결과는 실험에 넣지 않는다
+    let (profile, webp) = match (&tenant_webp, &state.config.encoder_experiment) {
+        (Some(webp), _) => (None, webp),
+        (None, Some(e)) if e.pick() => (Some(e.name.as_str()), &e.webp),
+        (None, Some(_)) => (Some(experiment::CONTROL), &state.config.webp),
+        (None, None) => (None, &state.config.webp),
+    };
+    let webp = state.config.quality.webp_options(size, webp).into_owned();
+    let input = body.clone();
+    let cancel = imaging::Cancel { deadline, abandoned };
+    // `cwebp`를 기다리는 동안 워커 스레드를 붙잡지 않도록 블로킹 스레드에서
+    let result = tokio::task::spawn_blocking(move || imaging::resize_static(&input, size, &webp, &effects, &cancel)).await;
+    let out = match result {
+        Err(e) => {
+            error!("Static processing task failed for emoji {}: {}", label, e);
+            return Error::Processing.into_response();
+        }
+        Ok(result) => result,
+    };
+    let out = match out {
+        Ok(o) => o,
+        Err(ImagingError::Decode(e)) => {
+            error!("Decode error for emoji {}: {}", label, e);
+            return Error::Decode.into_response();
+        }
+        // 디코드는 됐으니 500 대신 원본을 그대로 제공
+        Err(ImagingError::Encode(e)) => {
+            warn!("Static encode failed for emoji {} (input {}), passing through original: {}",
+                  label, blocklist::content_hash(&body), e);
+            ::metrics::counter!(metrics::ENCODE_FALLBACKS_TOTAL, "from" => "static", "to" => "original").increment(1);
+            let bytes = Arc::new(body.to_vec());
+            if state.blocklist.blocks_content(&bytes) {
+                warn!("Blocked output hash for emoji {}", label);
+                return Error::Blocked.into_response();
+            }
+            cache_insert(&state, key, bytes.clone()).await;
+            return ImageResponse::new(bytes, upstream_format)
+                .cache_control(&cache_control)
+                .tags(&tags)
+                .source(src.clone())
+                .server_timing(&timing)
+                .respond(&req);
+        }
+        Err(ImagingError::Cancelled) => {
+            return Error::DeadlineExceeded.into_response();
+        }
+    };
+    timing.stages(&out.timings);
+    if let Some(profile) = profile {
+        experiment::record(profile, out.bytes.len(), out.timings.encode);
+    }
+    let (original_dimensions, final_dimensions) = (out.original, out.resized);
+    let bytes = Arc::new(out.bytes);
+
+    if state.blocklist.blocks_content(&bytes) {
+        warn!("Blocked output hash for emoji {}", label);
+        return Error::Blocked.into_response();
+    }
+
+    // 캐시 저장
+    cache_insert(&state, key, bytes.clone()).await;
+
+    info!("Static WebP processed - emoji: {}, {}x{} → {}x{}, size: {} bytes", 
+          label, original_dimensions.0, original_dimensions.1, 
+          final_dimensions.0, final_dimensions.1, bytes.len());
+
+    ImageResponse::new(bytes, ContentType::Webp)
+        .cache_control(&cache_control)
+        .tags(&tags)
+        .source(src.clone())
+        .server_timing(&timing)
+        .encoder_profile(profile)
+        .respond(&req)
+}
+
+/// 2차 계층에서 꺼낸 항목을 메모리 캐시로 올린다
+async fn promote(state: &AppState, key: &str, stored: cache::TierEntry) -> Cached {
+    let entry = Cached::stored_at(Arc::new(stored.bytes), stored.stored);
+    state.flights.publish(key, &entry);
+    state.validators.insert(key.to_string(), entry.validator()).await;
+    if spills(state, &entry.bytes) || !admits(state, key) {
+        return entry;
+    }
+    state.stale.insert(key.to_string(), entry.clone()).await;
+    state.cache.insert(key.to_string(), entry.clone()).await;
+    state.popularity.note_insert(key);
+    entry
+}
+
+/// 메모리 캐시에 두지 않고 2차 계층에만 둘 만큼 큰 항목인지 (`CACHE_SPILL_KB`). 큰 애니메이션 몇 개가
+/// 자주 쓰이는 작은 정적 이미지를 밀어내지 않도록 한다. 검증자는 작으므로 그대로 남겨 304는 메모리에서 답한다.
+fn spills(state: &AppState, bytes: &[u8]) -> bool {
+    !state.tiers.is_empty() && state.config.cache_spill_bytes.is_some_and(|max| bytes.len() > max)
+}
+
+/// 입장 제한([`doorkeeper`])을 지나 메모리 캐시에 넣을 키인지
+fn admits(state: &AppState, key: &str) -> bool {
+    let admitted = state.doorkeeper.as_ref().is_none_or(|d| d.admit(key));
+    if state.doorkeeper.is_some() {
+        let outcome = if admitted { "admitted" } else { "deferred" };
+        ::metrics::counter!(metrics::CACHE_ADMISSIONS_TOTAL, "outcome" => outcome).increment(1);
+    }
+    admitted
+}
+
+async fn cache_insert(state: &AppState, key: String, bytes: Arc<Vec<u8>>) {
+    // 2차 계층 기록은 응답을 기다리게 하지 않도록 백그라운드에서.
+    // 이 키의 임대는 기록을 마친 뒤 풀어서, 기다리던 쪽이 계층에서 결과를 찾게 한다
+    // 모양 옵션을 켠 결과는 조합이 많아 메모리에만 둔다 ([`effects`])
+    if !state.tiers.is_empty() && !effects::is_effect_key(&key) {
+        state.tiers.hand_off(&key);
+        let (tiers, key, bytes) = (state.tiers.clone(), key.clone(), bytes.clone());
+        state.tasks.submit("tier_put", 2, move || {
+            let (tiers, key, bytes) = (tiers.clone(), key.clone(), bytes.clone());
+            async move { tiers.put(&key, &bytes).await }
+        });
+    }
+    let entry = Cached::new(bytes);
+    state.flights.publish(&key, &entry);
+    state.validators.insert(key.clone(), entry.validator()).await;
+    state.popularity.note_insert(&key);
+    if spills(state, &entry.bytes) {
+        ::metrics::counter!(metrics::CACHE_SPILLED_TOTAL).increment(1);
+        return;
+    }
+    if !admits(state, &key) {
+        return;
+    }
+    state.stale.insert(key.clone(), entry.clone()).await;
+    state.cache.insert(key, entry).await;
+}
+
+/// 업스트림 장애(5xx, 타임아웃, 본문 읽기 실패)나 과부하 시 보관 중인 만료 항목으로 응답 (stale-if-error)
+async fn stale_response(
+    state: &AppState,
+    key: &str,
+    label: &str,
+    src: &str,
+    tags: &[String],
+    req: &RequestInputs,
+) -> Option<axum::response::Response> {
+    let entry = state.stale.get(key).await?;
+    warn!("Serving stale entry for emoji {}", label);
+    ::metrics::counter!(metrics::STALE_SERVED_TOTAL).increment(1);
+
+    Some(
+        entry
+            .response()
+            .cache_control(STALE_CACHE_CONTROL)
+            .tags(tags)
+            .source(src.to_string())
+            .x_cache("STALE")
+            .warning("110 - \"Response is Stale\"")
+            .respond(req),
+    )
+}
+
+/// 애니메이션 AVIF를 켰고 클라이언트가 받을 수 있으면 리사이즈 변형을 AVIF 변형으로 바꾼다.
+/// 정적 이모지도 캐시 키는 나뉘지만 내용은 같은 WebP다 (캐시 키를 정할 때는 아직 애니메이션인지 모름).
+fn negotiate(config: &Config, variant: Variant, req: &RequestInputs) -> Variant {
+    match variant {
+        Variant::Resized(size) if cfg!(feature = "avif") && config.avif_animated => {
+            if req.get(VaryInput::Accept).is_some_and(accepts_avif) {
+                Variant::Avif(size)
+            } else {
+                variant
+            }
+        }
+        other => other,
+    }
+}
+
+/// `Accept`에 `image/avif`가 있고 `q=0`이 아닌지
+fn accepts_avif(accept: &str) -> bool {
+    accept.split(',').any(|item| {
+        let mut parts = item.split(';').map(str::trim);
+        parts.next().is_some_and(|mime| mime.eq_ignore_ascii_case("image/avif"))
+            && !parts.any(|p| p.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()) == Some(0.0))
+    })
+}
+
+fn format_src(config: &Config, name: &str, variant: Variant) -> String {
+    match variant {
+        Variant::Original => format!("{}/emojis/{}?animated=true", config.upstream_base, name),
+        // 기본 크기보다 크게 줄 때는 업스트림에서도 충분히 큰 크기(2의 거듭제곱)로 받는다
+        Variant::Resized(size) | Variant::Avif(size) | Variant::Png(size) | Variant::Gif(size) if size > config.default_size => format!(
+            "{}/emojis/{}?size={}&animated=true",
+            config.upstream_base,
+            name,
+            size.next_power_of_two()
+        ),
+        _ => format!("{}/emojis/{}?size={}&animated=true", config.upstream_base, name, config.default_size),
+    }
+}
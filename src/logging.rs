@@ -0,0 +1,262 @@
+//! 로그 출력 설정: stdout과 (선택) 회전되는 로그 파일.
+//!
+//! 로그 수집기가 없는 베어메탈 배포를 위해 `LOG_DIR`을 지정하면 시간/크기 기준으로
+//! 회전하고, 회전된 파일은 gzip으로 압축하며, 개수와 보관 기간을 넘긴 파일은 지운다.
+
+use anyhow::{bail, Context};
+use flate2::{write::GzEncoder, Compression};
+use serde::Serialize;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{filter::Directive, prelude::*, EnvFilter};
+
+use crate::config::{self, Config};
+
+const FILE_PREFIX: &str = "emoji-resizer";
+
+/// 시간 기준 회전 주기
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl Rotation {
+    fn period(self, unix_secs: u64) -> u64 {
+        match self {
+            Rotation::Hourly => unix_secs / 3600,
+            Rotation::Daily => unix_secs / 86400,
+            Rotation::Never => 0,
+        }
+    }
+}
+
+impl FromStr for Rotation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "hourly" => Ok(Rotation::Hourly),
+            "daily" => Ok(Rotation::Daily),
+            "never" => Ok(Rotation::Never),
+            other => bail!("unknown rotation {other:?} (expected hourly, daily or never)"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FileLogConfig {
+    pub dir: PathBuf,
+    pub rotation: Rotation,
+    /// 이 크기를 넘기면 주기와 상관없이 회전 (0이면 크기 제한 없음)
+    pub max_bytes: u64,
+    pub compress: bool,
+    /// 보관할 회전 파일 개수
+    pub max_files: usize,
+    /// 이보다 오래된 회전 파일은 삭제 (프라이버시 보관 기간)
+    #[serde(serialize_with = "config::secs")]
+    pub retention: Duration,
+}
+
+/// 전역 tracing 구독자를 설치한다. 파일 로그를 켰다면 돌려받은 guard를 main이
+/// 끝날 때까지 들고 있어야 남은 로그가 flush된다.
+pub fn init(config: &Config) -> anyhow::Result<Option<WorkerGuard>> {
+    let directive: Directive = "info".parse()?;
+    let filter = || EnvFilter::from_default_env().add_directive(directive.clone());
+
+    let stdout = config
+        .log_stdout
+        .then(|| tracing_subscriber::fmt::layer().with_filter(filter()));
+
+    let (file, guard) = match &config.log_file {
+        Some(file_config) => {
+            let writer = RollingWriter::open(file_config.clone())?;
+            let (writer, guard) = tracing_appender::non_blocking(writer);
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer)
+                .with_filter(filter());
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    let registry = tracing_subscriber::registry().with(stdout).with(file);
+
+    // tokio-console 레이어 (기본 127.0.0.1:6669)
+    #[cfg(feature = "console")]
+    let registry = registry.with(console_subscriber::spawn());
+
+    registry.init();
+    Ok(guard)
+}
+
+/// 시간/크기 기준으로 회전하는 로그 파일 writer.
+/// 현재 파일은 `emoji-resizer.log`, 회전된 파일은 `emoji-resizer.<UTC 시각>.log[.gz]`.
+pub struct RollingWriter {
+    config: FileLogConfig,
+    file: File,
+    written: u64,
+    period: u64,
+}
+
+impl RollingWriter {
+    pub fn open(config: FileLogConfig) -> anyhow::Result<Self> {
+        fs::create_dir_all(&config.dir)
+            .with_context(|| format!("creating log dir {}", config.dir.display()))?;
+        let path = current_path(&config.dir);
+        let file = open_append(&path).with_context(|| format!("opening {}", path.display()))?;
+        let meta = file.metadata()?;
+        // 기존 파일이 이전 주기에 쓰인 것이면 첫 기록 때 바로 회전된다
+        let modified = meta.modified().map_or_else(|_| unix_now(), unix_secs);
+        Ok(Self {
+            period: config.rotation.period(modified),
+            written: meta.len(),
+            config,
+            file,
+        })
+    }
+
+    fn should_rotate(&self, incoming: usize) -> bool {
+        if self.config.rotation.period(unix_now()) != self.period {
+            return true;
+        }
+        self.config.max_bytes > 0
+            && self.written > 0
+            && self.written + incoming as u64 > self.config.max_bytes
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let current = current_path(&self.config.dir);
+        let archived = archive_path(&self.config.dir, unix_now());
+        fs::rename(&current, &archived)?;
+        self.file = open_append(&current)?;
+        self.written = 0;
+        self.period = self.config.rotation.period(unix_now());
+
+        // 압축과 정리는 로그 기록 스레드를 막지 않도록 따로
+        let config = self.config.clone();
+        std::thread::spawn(move || {
+            if config.compress {
+                if let Err(e) = compress(&archived) {
+                    eprintln!("log compression failed for {}: {e}", archived.display());
+                }
+            }
+            if let Err(e) = cleanup(&config) {
+                eprintln!("log cleanup failed in {}: {e}", config.dir.display());
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Write for RollingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.should_rotate(buf.len()) {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn current_path(dir: &Path) -> PathBuf {
+    dir.join(format!("{FILE_PREFIX}.log"))
+}
+
+/// 같은 초에 여러 번 회전해도 덮어쓰지 않도록 번호를 붙인다
+fn archive_path(dir: &Path, now: u64) -> PathBuf {
+    let stamp = format_utc(now);
+    let mut path = dir.join(format!("{FILE_PREFIX}.{stamp}.log"));
+    let mut n = 1;
+    while path.exists() || path.with_extension("log.gz").exists() {
+        path = dir.join(format!("{FILE_PREFIX}.{stamp}-{n}.log"));
+        n += 1;
+    }
+    path
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn compress(path: &Path) -> io::Result<()> {
+    let gz_path = path.with_extension("log.gz");
+    let mut input = File::open(path)?;
+    let mut encoder = GzEncoder::new(File::create(&gz_path)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::remove_file(path)
+}
+
+/// 보관 기간이 지났거나 개수 제한을 넘긴 회전 파일 삭제 (이름이 시각순이므로 이름으로 정렬)
+fn cleanup(config: &FileLogConfig) -> io::Result<()> {
+    let current = current_path(&config.dir);
+    let mut archives: Vec<PathBuf> = fs::read_dir(&config.dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            *p != current
+                && p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with(&format!("{FILE_PREFIX}.")))
+        })
+        .collect();
+    archives.sort();
+
+    let cutoff = unix_now().saturating_sub(config.retention.as_secs());
+    let excess = archives.len().saturating_sub(config.max_files);
+    for (i, path) in archives.iter().enumerate() {
+        let expired = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .map(unix_secs)
+            .is_ok_and(|t| t < cutoff);
+        if i < excess || expired {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+fn unix_secs(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn unix_now() -> u64 {
+    unix_secs(SystemTime::now())
+}
+
+/// `YYYYMMDDTHHMMSS` (UTC)
+pub(crate) fn format_utc(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    // civil_from_days (Howard Hinnant)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
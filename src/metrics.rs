@@ -0,0 +1,209 @@
+//! Prometheus 메트릭 레코더와 `/metrics` 엔드포인트.
+
+use crate::AppState;
+use axum::{extract::State, http::header, response::IntoResponse};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use once_cell::sync::OnceCell;
+
+pub const PANICS_TOTAL: &str = "emoji_resizer_panics_total";
+pub const STALE_SERVED_TOTAL: &str = "emoji_resizer_stale_served_total";
+pub const DEADLINE_EXCEEDED_TOTAL: &str = "emoji_resizer_deadline_exceeded_total";
+pub const UPSTREAM_INVALID_TOTAL: &str = "emoji_resizer_upstream_invalid_total";
+pub const TASKS_TOTAL: &str = "emoji_resizer_tasks_total";
+pub const TASKS_RETRIES_TOTAL: &str = "emoji_resizer_tasks_retries_total";
+pub const TASKS_QUEUED: &str = "emoji_resizer_tasks_queued";
+pub const DEGRADED: &str = "emoji_resizer_degraded";
+pub const ENCODE_IN_FLIGHT: &str = "emoji_resizer_encode_in_flight";
+pub const DEGRADED_RESPONSES_TOTAL: &str = "emoji_resizer_degraded_responses_total";
+pub const CACHE_BACKEND_SECONDS: &str = "emoji_resizer_cache_backend_seconds";
+pub const CACHE_BACKEND_ERRORS_TOTAL: &str = "emoji_resizer_cache_backend_errors_total";
+pub const CACHE_CORRUPT_TOTAL: &str = "emoji_resizer_cache_corrupt_total";
+pub const CACHE_SPILLED_TOTAL: &str = "emoji_resizer_cache_spilled_total";
+pub const CACHE_ADMISSIONS_TOTAL: &str = "emoji_resizer_cache_admissions_total";
+pub const CACHE_LEASES_TOTAL: &str = "emoji_resizer_cache_leases_total";
+pub const REQUESTS_COALESCED_TOTAL: &str = "emoji_resizer_requests_coalesced_total";
+pub const CACHE_DUAL_WRITES_TOTAL: &str = "emoji_resizer_cache_dual_writes_total";
+pub const CACHE_MAINTENANCE_RUNS_TOTAL: &str = "emoji_resizer_cache_maintenance_runs_total";
+pub const CACHE_MAINTENANCE_REMOVED_TOTAL: &str = "emoji_resizer_cache_maintenance_removed_total";
+pub const CACHE_MAINTENANCE_RECLAIMED_BYTES_TOTAL: &str = "emoji_resizer_cache_maintenance_reclaimed_bytes_total";
+pub const CACHE_MAINTENANCE_SECONDS: &str = "emoji_resizer_cache_maintenance_seconds";
+pub const CUSTOM_UPLOADS_TOTAL: &str = "emoji_resizer_custom_uploads_total";
+pub const CUSTOM_GC_REMOVED_TOTAL: &str = "emoji_resizer_custom_gc_removed_total";
+pub const CUSTOM_GC_RECLAIMED_BYTES_TOTAL: &str = "emoji_resizer_custom_gc_reclaimed_bytes_total";
+pub const VIPS_RUNS_TOTAL: &str = "emoji_resizer_vips_runs_total";
+pub const ENCODE_FALLBACKS_TOTAL: &str = "emoji_resizer_encode_fallbacks_total";
+pub const UPSTREAM_HTTP_VERSION_TOTAL: &str = "emoji_resizer_upstream_http_version_total";
+pub const UPSTREAM_HTTP_DOWNGRADES_TOTAL: &str = "emoji_resizer_upstream_http_downgrades_total";
+pub const UPSTREAM_HEDGES_TOTAL: &str = "emoji_resizer_upstream_hedges_total";
+pub const UPSTREAM_REDIRECTS_TOTAL: &str = "emoji_resizer_upstream_redirects_total";
+pub const PROCESSING_QUEUED: &str = "emoji_resizer_processing_queued";
+pub const PROCESSING_RUNNING: &str = "emoji_resizer_processing_running";
+pub const ADMISSION_REJECTED_TOTAL: &str = "emoji_resizer_admission_rejected_total";
+pub const ANON_RATE_LIMITED_TOTAL: &str = "emoji_resizer_anon_rate_limited_total";
+pub const CLIENT_DISCONNECTS_TOTAL: &str = "emoji_resizer_client_disconnects_total";
+pub const SHADOW_TOTAL: &str = "emoji_resizer_shadow_total";
+pub const UPSTREAM_QUEUE_SECONDS: &str = "emoji_resizer_upstream_queue_seconds";
+pub const UPSTREAM_THROTTLED_TOTAL: &str = "emoji_resizer_upstream_throttled_total";
+pub const MIRROR_TOTAL: &str = "emoji_resizer_mirror_total";
+pub const ENCODER_PROFILE_TOTAL: &str = "emoji_resizer_encoder_profile_total";
+pub const ENCODER_PROFILE_BYTES: &str = "emoji_resizer_encoder_profile_bytes";
+pub const ENCODER_PROFILE_SECONDS: &str = "emoji_resizer_encoder_profile_seconds";
+pub const MISS_SECONDS: &str = "emoji_resizer_miss_seconds";
+pub const MISS_LATENCY_SECONDS: &str = "emoji_resizer_miss_latency_seconds";
+pub const SLO_MISSES_TOTAL: &str = "emoji_resizer_slo_misses_total";
+pub const SLO_BURN_RATE: &str = "emoji_resizer_slo_burn_rate";
+
+const RT_WORKERS: &str = "tokio_workers";
+const RT_ALIVE_TASKS: &str = "tokio_alive_tasks";
+const RT_GLOBAL_QUEUE_DEPTH: &str = "tokio_global_queue_depth";
+const RT_WORKER_BUSY_SECONDS: &str = "tokio_worker_busy_seconds_total";
+const RT_WORKER_PARKS: &str = "tokio_worker_park_total";
+#[cfg(tokio_unstable)]
+const RT_BLOCKING_THREADS: &str = "tokio_blocking_threads";
+#[cfg(tokio_unstable)]
+const RT_IDLE_BLOCKING_THREADS: &str = "tokio_idle_blocking_threads";
+#[cfg(tokio_unstable)]
+const RT_BLOCKING_QUEUE_DEPTH: &str = "tokio_blocking_queue_depth";
+#[cfg(tokio_unstable)]
+const RT_SPAWNED_TASKS: &str = "tokio_spawned_tasks_total";
+#[cfg(tokio_unstable)]
+const RT_WORKER_POLLS: &str = "tokio_worker_poll_total";
+#[cfg(tokio_unstable)]
+const RT_WORKER_MEAN_POLL_SECONDS: &str = "tokio_worker_mean_poll_seconds";
+
+static HANDLE: OnceCell<PrometheusHandle> = OnceCell::new();
+
+/// 전역 레코더를 설치하고 렌더링용 핸들을 돌려준다. 두 번째 호출부터는 같은 핸들을
+/// 돌려준다 (테스트에서 서버 상태를 여러 번 만드는 경우).
+pub fn install() -> anyhow::Result<PrometheusHandle> {
+    HANDLE.get_or_try_init(install_recorder).cloned()
+}
+
+fn install_recorder() -> anyhow::Result<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(CACHE_BACKEND_SECONDS.to_string()),
+            &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25],
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full(ENCODER_PROFILE_BYTES.to_string()),
+            &[1024.0, 2048.0, 4096.0, 8192.0, 16384.0, 32768.0, 65536.0, 131072.0, 262144.0],
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full(ENCODER_PROFILE_SECONDS.to_string()),
+            &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0],
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full(MISS_SECONDS.to_string()),
+            &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0],
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full(UPSTREAM_QUEUE_SECONDS.to_string()),
+            &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0],
+        )?
+        .install_recorder()?;
+    metrics::describe_counter!(PANICS_TOTAL, "Requests that panicked and were answered with a 500");
+    metrics::describe_counter!(STALE_SERVED_TOTAL, "Expired entries served because the upstream failed");
+    metrics::describe_counter!(DEADLINE_EXCEEDED_TOTAL, "Requests cancelled because the caller's deadline passed");
+    metrics::describe_counter!(UPSTREAM_INVALID_TOTAL, "Upstream responses rejected because they were not images");
+    metrics::describe_counter!(TASKS_TOTAL, "Background jobs by outcome (ok, failed, dropped)");
+    metrics::describe_counter!(TASKS_RETRIES_TOTAL, "Background job retries");
+    metrics::describe_gauge!(TASKS_QUEUED, "Background jobs waiting in the queue");
+    metrics::describe_gauge!(DEGRADED, "1 while the server is in degraded mode");
+    metrics::describe_gauge!(ENCODE_IN_FLIGHT, "Resize/encode jobs currently running or queued");
+    metrics::describe_counter!(DEGRADED_RESPONSES_TOTAL, "Cache misses answered in degraded mode (stale or original)");
+    metrics::describe_histogram!(CACHE_BACKEND_SECONDS, "Latency of second-tier cache operations");
+    metrics::describe_histogram!(MISS_SECONDS, "Time to answer interactive cache misses, by source");
+    metrics::describe_gauge!(MISS_LATENCY_SECONDS, "Cache miss latency percentiles over SLO_WINDOW_SECS, by source");
+    metrics::describe_counter!(SLO_MISSES_TOTAL, "Interactive cache misses by source and whether they met SLO_MISS_MS");
+    metrics::describe_gauge!(SLO_BURN_RATE, "Share of misses over SLO_MISS_MS divided by the error budget, by source");
+    metrics::describe_counter!(CACHE_BACKEND_ERRORS_TOTAL, "Second-tier cache operations that failed or timed out");
+    metrics::describe_counter!(CACHE_CORRUPT_TOTAL, "Second-tier cache entries dropped because they failed validation");
+    metrics::describe_counter!(CACHE_SPILLED_TOTAL, "Entries over CACHE_SPILL_KB stored only in the second tier, not in memory");
+    metrics::describe_counter!(CACHE_ADMISSIONS_TOTAL, "Memory cache admission decisions under CACHE_ADMIT_AFTER, by outcome");
+    metrics::describe_counter!(CACHE_LEASES_TOTAL, "Cold-key encode leases by outcome (leader, follower, expired)");
+    metrics::describe_counter!(REQUESTS_COALESCED_TOTAL, "Requests that waited on an in-process miss for the same key, by outcome (shared, alone)");
+    metrics::describe_counter!(CACHE_DUAL_WRITES_TOTAL, "Second-tier writes mirrored under the previous cache generation");
+    metrics::describe_counter!(CACHE_MAINTENANCE_RUNS_TOTAL, "Second-tier cache maintenance runs by outcome");
+    metrics::describe_counter!(CACHE_MAINTENANCE_REMOVED_TOTAL, "Entries removed by cache maintenance (expired, orphaned)");
+    metrics::describe_counter!(CACHE_MAINTENANCE_RECLAIMED_BYTES_TOTAL, "Bytes freed by cache maintenance");
+    metrics::describe_histogram!(CACHE_MAINTENANCE_SECONDS, "Duration of a cache maintenance pass");
+    metrics::describe_counter!(CUSTOM_UPLOADS_TOTAL, "Custom emote uploads by outcome (created, replaced, quarantined, approved, declined, rejected)");
+    metrics::describe_counter!(CUSTOM_GC_REMOVED_TOTAL, "Unreferenced custom emote blobs removed");
+    metrics::describe_counter!(CUSTOM_GC_RECLAIMED_BYTES_TOTAL, "Bytes freed by custom emote blob cleanup");
+    metrics::describe_counter!(VIPS_RUNS_TOTAL, "libvips backend runs by operation and outcome (ok, error)");
+    metrics::describe_counter!(ENCODE_FALLBACKS_TOTAL, "Outputs served in a fallback format after encoding failed, by from/to");
+    metrics::describe_counter!(UPSTREAM_HTTP_VERSION_TOTAL, "Upstream responses by source and negotiated HTTP version");
+    metrics::describe_counter!(UPSTREAM_HTTP_DOWNGRADES_TOTAL, "Times a source fell back from HTTP/2 prior knowledge to negotiation");
+    metrics::describe_counter!(UPSTREAM_HEDGES_TOTAL, "Hedged upstream requests by source and which request answered first");
+    metrics::describe_counter!(UPSTREAM_REDIRECTS_TOTAL, "Upstream redirects by source and whether the redirect policy followed them");
+    metrics::describe_gauge!(PROCESSING_QUEUED, "Encodes waiting for a processing slot, by priority");
+    metrics::describe_gauge!(PROCESSING_RUNNING, "Encodes holding a processing slot");
+    metrics::describe_counter!(ADMISSION_REJECTED_TOTAL, "Requests turned away early because the processing queue was deep, by status");
+    metrics::describe_counter!(ANON_RATE_LIMITED_TOTAL, "Requests without an API key turned away by the per-IP limit");
+    metrics::describe_counter!(CLIENT_DISCONNECTS_TOTAL, "Image requests the client abandoned before the response, by what happened to the work");
+    metrics::describe_histogram!(UPSTREAM_QUEUE_SECONDS, "Time upstream fetches waited for the per-host rate limit or concurrency cap");
+    metrics::describe_counter!(UPSTREAM_THROTTLED_TOTAL, "Upstream 429 responses that paused requests to that host");
+    metrics::describe_counter!(SHADOW_TOTAL, "Requests mirrored to the shadow deployment by outcome (sent, failed, dropped)");
+    metrics::describe_counter!(MIRROR_TOTAL, "Origin mirror copies by outcome (stored, served)");
+    metrics::describe_counter!(ENCODER_PROFILE_TOTAL, "Static encodes by encoder profile while an encoder experiment runs");
+    metrics::describe_histogram!(ENCODER_PROFILE_BYTES, "Static encode output size by encoder profile");
+    metrics::describe_histogram!(ENCODER_PROFILE_SECONDS, "Static encode time by encoder profile");
+    metrics::describe_gauge!(RT_WORKERS, "Tokio worker threads");
+    metrics::describe_gauge!(RT_ALIVE_TASKS, "Tasks currently alive in the runtime");
+    metrics::describe_gauge!(RT_GLOBAL_QUEUE_DEPTH, "Tasks waiting in the runtime injection queue");
+    metrics::describe_counter!(RT_WORKER_BUSY_SECONDS, "Time each worker spent busy");
+    metrics::describe_counter!(RT_WORKER_PARKS, "Times each worker parked");
+    #[cfg(tokio_unstable)]
+    {
+        metrics::describe_gauge!(RT_BLOCKING_THREADS, "Threads in the blocking pool");
+        metrics::describe_gauge!(RT_IDLE_BLOCKING_THREADS, "Idle threads in the blocking pool");
+        metrics::describe_gauge!(RT_BLOCKING_QUEUE_DEPTH, "Jobs waiting for a blocking thread");
+        metrics::describe_counter!(RT_SPAWNED_TASKS, "Tasks spawned since startup");
+        metrics::describe_counter!(RT_WORKER_POLLS, "Task polls per worker");
+        metrics::describe_gauge!(RT_WORKER_MEAN_POLL_SECONDS, "EWMA of task poll time per worker");
+    }
+    Ok(handle)
+}
+
+/// 스크레이프 시점의 tokio 런타임 상태를 게이지/카운터에 기록한다.
+/// 이미지 작업이 executor를 막는지(바쁜 워커, 쌓이는 큐) 진단하는 용도.
+fn record_runtime() {
+    let rt = tokio::runtime::Handle::current().metrics();
+    let workers = rt.num_workers();
+    metrics::gauge!(RT_WORKERS).set(workers as f64);
+    metrics::gauge!(RT_ALIVE_TASKS).set(rt.num_alive_tasks() as f64);
+    metrics::gauge!(RT_GLOBAL_QUEUE_DEPTH).set(rt.global_queue_depth() as f64);
+
+    for w in 0..workers {
+        let worker = w.to_string();
+        metrics::counter!(RT_WORKER_BUSY_SECONDS, "worker" => worker.clone())
+            .absolute(rt.worker_total_busy_duration(w).as_secs());
+        metrics::counter!(RT_WORKER_PARKS, "worker" => worker.clone())
+            .absolute(rt.worker_park_count(w));
+        #[cfg(tokio_unstable)]
+        {
+            metrics::counter!(RT_WORKER_POLLS, "worker" => worker.clone())
+                .absolute(rt.worker_poll_count(w));
+            metrics::gauge!(RT_WORKER_MEAN_POLL_SECONDS, "worker" => worker)
+                .set(rt.worker_mean_poll_time(w).as_secs_f64());
+        }
+    }
+
+    #[cfg(tokio_unstable)]
+    {
+        metrics::gauge!(RT_BLOCKING_THREADS).set(rt.num_blocking_threads() as f64);
+        metrics::gauge!(RT_IDLE_BLOCKING_THREADS).set(rt.num_idle_blocking_threads() as f64);
+        metrics::gauge!(RT_BLOCKING_QUEUE_DEPTH).set(rt.blocking_queue_depth() as f64);
+        metrics::counter!(RT_SPAWNED_TASKS).absolute(rt.spawned_tasks_count());
+    }
+}
+
+pub async fn handler(State(state): State<AppState>) -> impl IntoResponse {
+    record_runtime();
+    state.slo.publish();
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
@@ -0,0 +1,274 @@
+//! 라우터 공통 미들웨어.
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, uri::PathAndQuery, HeaderValue, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::{
+    any::Any,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    auth::Auth,
+    error::Error,
+    metrics::{ADMISSION_REJECTED_TOTAL, ANON_RATE_LIMITED_TOTAL, DEADLINE_EXCEEDED_TOTAL, PANICS_TOTAL},
+    privacy::client_ip,
+    tenant::Tenant,
+    AppState,
+};
+
+/// 패닉으로 만들어진 응답임을 표시하는 마커
+#[derive(Clone, Copy)]
+struct Panicked;
+
+/// `CatchPanicLayer`의 응답 생성기: 패닉 메트릭을 올리고 마커가 붙은 500을 돌려준다.
+pub fn handle_panic(err: Box<dyn Any + Send + 'static>) -> Response {
+    let detail = if let Some(s) = err.downcast_ref::<String>() {
+        s.as_str()
+    } else if let Some(s) = err.downcast_ref::<&str>() {
+        s
+    } else {
+        "unknown panic payload"
+    };
+    metrics::counter!(PANICS_TOTAL).increment(1);
+    error!("handler panicked: {detail}");
+
+    let mut res = StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    res.extensions_mut().insert(Panicked);
+    res
+}
+
+/// 패닉 응답을 요청 ID가 담긴 구조화된 JSON 500으로 바꾼다.
+/// `CatchPanicLayer` 바깥, `SetRequestIdLayer` 안쪽에 둘 것.
+pub async fn panic_response(req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-")
+        .to_owned();
+
+    let res = next.run(req).await;
+    if res.extensions().get::<Panicked>().is_none() {
+        return res;
+    }
+
+    error!("request {request_id} aborted by panic");
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({
+            "error": "internal server error",
+            "request_id": request_id,
+        })),
+    )
+        .into_response()
+}
+
+/// 접근 로그 (`target: "access"`). 클라이언트 IP는 프라이버시 모드에 따라 익명화된다.
+/// `SetRequestIdLayer` 안쪽에 둘 것.
+pub async fn access_log(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = req.method().clone();
+    let path = req.uri().path().to_owned();
+    let client = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ci| client_ip(req.headers(), ci.0, state.config.trust_forwarded_for))
+        .map_or_else(|| "-".to_string(), |ip| state.privacy.display_ip(ip));
+    let request_id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-")
+        .to_owned();
+
+    let res = next.run(req).await;
+    info!(
+        target: "access",
+        client = %client,
+        method = %method,
+        path = %path,
+        status = res.status().as_u16(),
+        latency_ms = start.elapsed().as_millis() as u64,
+        request_id = %request_id,
+    );
+    res
+}
+
+/// 호출자가 정한 요청 마감 시각. 핸들러가 블로킹 작업(프레임 처리)을 중단하는 데 쓴다.
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline(pub Instant);
+
+/// `x-request-deadline-ms: <밀리초>` 또는 `Request-Timeout: <초>` 헤더의 남은 시간
+fn requested_budget(req: &Request) -> Option<Duration> {
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+    if let Some(ms) = header("x-request-deadline-ms").and_then(|v| v.trim().parse::<u64>().ok()) {
+        return Some(Duration::from_millis(ms));
+    }
+    header("request-timeout")
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|s| s.is_finite() && *s >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+/// 마감 헤더가 있으면 그 시각까지만 요청을 처리하고, 넘기면 future를 버려서
+/// 업스트림 fetch 등 남은 작업을 취소한 뒤 504로 응답한다.
+pub async fn deadline(mut req: Request, next: Next) -> Response {
+    let Some(budget) = requested_budget(&req) else {
+        return next.run(req).await;
+    };
+    let deadline = Instant::now() + budget;
+    req.extensions_mut().insert(Deadline(deadline));
+
+    match tokio::time::timeout_at(deadline.into(), next.run(req)).await {
+        Ok(res) => res,
+        Err(_) => {
+            metrics::counter!(DEADLINE_EXCEEDED_TOTAL).increment(1);
+            Error::DeadlineExceeded.into_response()
+        }
+    }
+}
+
+/// JSON을 원하는 클라이언트(`Accept`에 `application/problem+json`이나 `application/json`)에게는 [`Error`]로
+/// 끝난 응답의 짧은 문구 대신 problem details 본문을 준다. 상태 코드와 다른 헤더는 그대로 둔다.
+pub async fn problem_json(req: Request, next: Next) -> Response {
+    let wants_json = req
+        .headers()
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.split(';').next().unwrap_or_default().trim())
+        .any(|v| v.eq_ignore_ascii_case("application/problem+json") || v.eq_ignore_ascii_case("application/json"));
+    let res = next.run(req).await;
+    let Some(&error) = res.extensions().get::<Error>() else {
+        return res;
+    };
+    let (mut parts, body) = res.into_parts();
+    // 같은 주소의 에러 본문이 `Accept`에 따라 달라진다
+    let mut varies = parts.headers.get_all(header::VARY).iter().filter_map(|v| v.to_str().ok()).flat_map(|v| v.split(','));
+    if !varies.any(|v| v.trim().eq_ignore_ascii_case("accept")) {
+        parts.headers.append(header::VARY, HeaderValue::from_static("accept"));
+    }
+    if !wants_json {
+        return Response::from_parts(parts, body);
+    }
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+    Response::from_parts(parts, axum::body::Body::from(error.problem().to_string()))
+}
+
+/// 대기열 깊이나 익명 요청 제한으로 거르지 않는 경로 (상태 점검·메트릭·관리 API)
+const ADMISSION_EXEMPT: [&str; 4] = ["/healthz", "/readyz", "/metrics", "/admin"];
+
+fn exempt(path: &str) -> bool {
+    ADMISSION_EXEMPT.iter().any(|p| path == *p || path.starts_with(&format!("{p}/")))
+}
+
+/// API 키로 요청을 가르고([`Auth`]와 키가 맞은 [`Tenant`]를 요청 확장에 넣는다) 익명 요청에 IP당
+/// 제한(`ANON_RATE_LIMIT`)을 건다. 넘으면 윈도 길이를 `Retry-After`로 실어 429로 응답한다.
+pub async fn auth(State(state): State<AppState>, mut req: Request, next: Next) -> Response {
+    let tenant = Tenant::find(&state.config.tenants, req.headers()).cloned();
+    let auth = match tenant {
+        Some(_) => Auth::ApiKey,
+        None => Auth::classify(&state.config.api_keys, req.headers()),
+    };
+    req.extensions_mut().insert(auth);
+    if let Some(tenant) = tenant {
+        req.extensions_mut().insert(tenant);
+    }
+    let Some(limiter) = state.anon_limiter.as_ref().filter(|_| !auth.is_authenticated()) else {
+        return next.run(req).await;
+    };
+    let path = req.uri().path();
+    let Some(ConnectInfo(peer)) = req.extensions().get::<ConnectInfo<SocketAddr>>().copied() else {
+        return next.run(req).await;
+    };
+    if exempt(path) || limiter.allow(client_ip(req.headers(), peer, state.config.trust_forwarded_for)) {
+        return next.run(req).await;
+    }
+    metrics::counter!(ANON_RATE_LIMITED_TOTAL).increment(1);
+    let mut res = Error::Throttled.into_response();
+    res.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(state.config.anon_rate_window.as_secs()));
+    res
+}
+
+/// 표본으로 고른 요청을 스테이징 인스턴스에도 보낸다 (`SHADOW_URL`). 상태 점검·관리 API는 보내지 않는다.
+pub async fn shadow(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    if let Some(shadow) = state.shadow.as_ref().filter(|s| !exempt(req.uri().path()) && s.pick(req.method(), req.headers())) {
+        shadow.send(req.method().clone(), req.uri(), req.headers());
+    }
+    next.run(req).await
+}
+
+/// 인코딩 대기열이 포화되기 전에 새 요청을 일찍 돌려보낸다 (`ADMISSION_QUEUE_SOFT`/`_HARD`).
+/// 돌려보낸 클라이언트가 한꺼번에 돌아오지 않도록 `Retry-After`를 기준값부터 두 배 사이에서 흔든다.
+pub async fn admission(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let config = &state.config;
+    let path = req.uri().path();
+    if exempt(path) {
+        return next.run(req).await;
+    }
+    let Err(rejected) = state.processing.admit(config.admission_queue_soft, config.admission_queue_hard) else {
+        return next.run(req).await;
+    };
+    warn!("Admission control rejected {} with {}", path, rejected.status());
+    metrics::counter!(ADMISSION_REJECTED_TOTAL, "status" => rejected.status().as_str().to_owned()).increment(1);
+    let base = config.admission_retry_after.as_secs().max(1);
+    let jitter = (uuid::Uuid::new_v4().as_u128() % u128::from(base + 1)) as u64;
+    let mut res = rejected.into_response();
+    res.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(base + jitter));
+    res
+}
+
+/// 라우팅 전에 경로를 정규화해서 같은 이모지가 여러 캐시 키로 갈리지 않게 한다.
+/// `/e/123.WEBP/`, `/e/123..webp` → `/e/123.webp`
+pub async fn normalize_path(mut req: Request, next: Next) -> Response {
+    if let Some(path) = normalized(req.uri().path()) {
+        let path_and_query = match req.uri().query() {
+            Some(query) => format!("{path}?{query}"),
+            None => path,
+        };
+        let mut parts = req.uri().clone().into_parts();
+        if let Ok(pq) = PathAndQuery::try_from(path_and_query) {
+            parts.path_and_query = Some(pq);
+            if let Ok(uri) = Uri::from_parts(parts) {
+                *req.uri_mut() = uri;
+            }
+        }
+    }
+    next.run(req).await
+}
+
+/// 정규화할 이미지 확장자
+const EXTENSIONS: [&str; 6] = ["webp", "png", "gif", "jpg", "jpeg", "avif"];
+
+/// 끝의 `/`를 떼고, 마지막 조각의 연속된 `.`을 하나로 줄이고 이미지 확장자를 소문자로.
+/// 바꿀 게 없으면 `None`.
+fn normalized(path: &str) -> Option<String> {
+    let trimmed = path.trim_end_matches('/');
+    let trimmed = if trimmed.is_empty() { "/" } else { trimmed };
+    let (dir, last) = trimmed.rsplit_once('/').unwrap_or(("", trimmed));
+
+    let mut segment = String::with_capacity(last.len());
+    for c in last.chars() {
+        if !(c == '.' && segment.ends_with('.')) {
+            segment.push(c);
+        }
+    }
+    if let Some((stem, ext)) = segment.rsplit_once('.') {
+        let lower = ext.to_ascii_lowercase();
+        if lower != ext && EXTENSIONS.contains(&lower.as_str()) {
+            segment = format!("{stem}.{lower}");
+        }
+    }
+
+    let out = if trimmed == "/" { trimmed.to_string() } else { format!("{dir}/{segment}") };
+    (out != path).then_some(out)
+}
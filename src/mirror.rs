@@ -0,0 +1,62 @@
+//! 업스트림 원본 보관 미러 (`MIRROR_DIR`).
+//!
+//! 업스트림에서 받아 검증을 통과한 원본을 이모지마다 한 벌씩 디스크에 남겨 두고, Discord 등에서 이모지가
+//! 지워져 업스트림이 404를 돌려주면 이 사본으로 계속 제공한다 (커뮤니티용 보관 미러). 받을 때마다 덮어쓰므로
+//! 파일의 수정 시각은 업스트림에서 마지막으로 확인한 시각이고, 그로부터 `MIRROR_RETENTION_DAYS`가 지난 사본은
+//! 정리 때 지운다. 저장 형식은 디스크 캐시 계층과 같다(`ab/<sha1>.bin`).
+//!
+//! 크기를 지정하지 않고 받은 원본(`/original`)은 리사이즈용으로 받은 160px 응답과 따로 보관해서, 작은 사본이
+//! 큰 사본을 덮어쓰지 않게 한다. 길드 보관(`/admin/archive`) 매니페스트는 `archives/<길드 ID>.json`에 둔다.
+
+use std::{path::PathBuf, time::Duration};
+use tracing::warn;
+
+use crate::{
+    cache::{DiskTier, MaintenanceReport, Tier},
+    metrics::MIRROR_TOTAL,
+};
+
+pub struct Mirror {
+    dir: PathBuf,
+    files: DiskTier,
+}
+
+impl Mirror {
+    /// `retention`이 0이면 지우지 않는다
+    pub fn new(dir: PathBuf, retention: Duration) -> anyhow::Result<Self> {
+        let retention = if retention.is_zero() { Duration::MAX } else { retention };
+        Ok(Self { files: DiskTier::new(dir.clone(), retention, None)?, dir })
+    }
+
+    /// 길드 보관 매니페스트 경로. 샤드 디렉터리가 아니라 정리 대상이 아니다.
+    pub fn manifest_path(&self, guild_id: &str) -> PathBuf {
+        self.dir.join("archives").join(format!("{guild_id}.json"))
+    }
+
+    /// 원본을 남긴다. 같은 키의 이전 사본은 바꿔치기한다.
+    pub async fn store(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        self.files.put(key, bytes).await?;
+        ::metrics::counter!(MIRROR_TOTAL, "outcome" => "stored").increment(1);
+        Ok(())
+    }
+
+    /// `keys` 중 처음 찾은 사본. 없거나 보관 기간이 지났으면 `None`.
+    pub async fn load(&self, keys: &[String]) -> Option<Vec<u8>> {
+        for key in keys {
+            match self.files.get(key).await {
+                Ok(Some(entry)) => {
+                    ::metrics::counter!(MIRROR_TOTAL, "outcome" => "served").increment(1);
+                    return Some(entry.bytes);
+                }
+                Ok(None) => {}
+                Err(e) => warn!("origin mirror read failed for {}: {:#}", key, e),
+            }
+        }
+        None
+    }
+
+    /// 보관 기간이 지난 사본과 중단된 기록의 임시 파일을 지운다
+    pub async fn maintain(&self) -> anyhow::Result<Option<MaintenanceReport>> {
+        self.files.maintain().await
+    }
+}
@@ -0,0 +1,84 @@
+//! 커스텀 이모트 업로드 검사.
+//!
+//! `CUSTOM_MODERATION_URL`이 있으면 정규화한 WebP를 그 주소로 `POST`하고 `{"score": 0.0~1.0}`
+//! 응답을 받는다. 점수가 `CUSTOM_MODERATION_THRESHOLD` 이상이면 업로드를 검토 대기열에 넣는다.
+//! 웹훅이 실패해도 바로 공개하지 않고 대기열로 보낸다. `CUSTOM_MODERATION_MANUAL`이면
+//! 모든 업로드가 관리자 승인을 기다린다.
+
+use anyhow::Context;
+use reqwest::Client;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::config::Config;
+
+pub struct Moderator {
+    http: Client,
+    webhook: Option<String>,
+    threshold: f64,
+    manual: bool,
+}
+
+/// 검사 결과
+pub enum Verdict {
+    Publish,
+    Quarantine { score: Option<f64>, reason: &'static str },
+}
+
+#[derive(Deserialize)]
+struct WebhookResponse {
+    score: f64,
+}
+
+impl Moderator {
+    /// 웹훅도 수동 검토도 없으면 `None` (검사하지 않고 바로 공개)
+    pub fn new(config: &Config) -> anyhow::Result<Option<Self>> {
+        if config.custom_moderation_url.is_none() && !config.custom_moderation_manual {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            http: Client::builder()
+                .user_agent(crate::USER_AGENT.as_str())
+                .timeout(config.upstream_timeout)
+                .build()?,
+            webhook: config.custom_moderation_url.clone(),
+            threshold: config.custom_moderation_threshold,
+            manual: config.custom_moderation_manual,
+        }))
+    }
+
+    pub async fn check(&self, key: &str, webp: &[u8]) -> Verdict {
+        let score = match &self.webhook {
+            Some(url) => match self.score(url, key, webp).await {
+                Ok(score) => Some(score),
+                Err(e) => {
+                    warn!("Moderation webhook failed for custom emote {}: {:#}", key, e);
+                    return Verdict::Quarantine { score: None, reason: "moderation webhook failed" };
+                }
+            },
+            None => None,
+        };
+        match score {
+            Some(score) if score >= self.threshold => Verdict::Quarantine { score: Some(score), reason: "flagged by moderation webhook" },
+            _ if self.manual => Verdict::Quarantine { score, reason: "manual review" },
+            _ => Verdict::Publish,
+        }
+    }
+
+    async fn score(&self, url: &str, key: &str, webp: &[u8]) -> anyhow::Result<f64> {
+        let res: WebhookResponse = self
+            .http
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "image/webp")
+            .header("X-Emote-Key", key)
+            .body(webp.to_vec())
+            .send()
+            .await
+            .context("moderation request")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("moderation response")?;
+        Ok(res.score)
+    }
+}
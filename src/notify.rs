@@ -0,0 +1,188 @@
+//! 운영 이벤트 Discord 웹훅 알림.
+//!
+//! 저하 모드 진입/해제, 업스트림 에러율 급증, 디스크 캐시 사용량 초과, 캐시 미스 SLO 소진을 주기적으로
+//! 점검해 `DISCORD_WEBHOOK_URL`로 보낸다. 같은 종류의 알림은 쿨다운 동안 한 번만 보낸다.
+
+use moka::sync::Cache;
+use reqwest::Client;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tracing::info;
+
+use crate::AppState;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// 에러율을 판단하기 위한 점검 주기당 최소 업스트림 요청 수
+const MIN_REQUESTS: u64 = 20;
+/// SLO 알림을 판단하기 위한 `SLO_WINDOW_SECS` 안의 최소 미스 수
+const MIN_MISSES: usize = 20;
+
+/// 업스트림 fetch 결과 누계 (에러율 계산용)
+#[derive(Default)]
+pub struct UpstreamStats {
+    requests: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl UpstreamStats {
+    pub fn record(&self, ok: bool) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> (u64, u64) {
+        (self.requests.load(Ordering::Relaxed), self.failures.load(Ordering::Relaxed))
+    }
+}
+
+/// 업스트림용 클라이언트는 HTTP/2 prior knowledge라서 웹훅에는 별도 클라이언트를 쓴다
+#[derive(Clone)]
+pub struct Notifier {
+    url: String,
+    http: Client,
+    /// 최근에 보낸 알림 종류 (쿨다운)
+    recent: Cache<&'static str, ()>,
+}
+
+impl Notifier {
+    pub fn new(url: String, cooldown: Duration) -> anyhow::Result<Self> {
+        let http = Client::builder().timeout(Duration::from_secs(10)).build()?;
+        Ok(Self {
+            url,
+            http,
+            recent: Cache::builder().time_to_live(cooldown).build(),
+        })
+    }
+
+    /// 쿨다운 중이 아니면 백그라운드 작업으로 메시지를 보낸다
+    pub fn send(&self, state: &AppState, kind: &'static str, message: String) {
+        if self.recent.contains_key(kind) {
+            return;
+        }
+        self.recent.insert(kind, ());
+        info!("webhook notification - {}: {}", kind, message);
+        let this = self.clone();
+        state.tasks.submit("webhook", 2, move || {
+            let (this, message) = (this.clone(), message.clone());
+            async move {
+                this.http
+                    .post(&this.url)
+                    .json(&serde_json::json!({ "content": message, "allowed_mentions": { "parse": [] } }))
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(())
+            }
+        });
+    }
+}
+
+/// 알림 조건 점검 루프
+pub async fn monitor(state: AppState, notifier: Notifier) {
+    let config = state.config.clone();
+    let mut tick = tokio::time::interval(CHECK_INTERVAL);
+    let (mut last_requests, mut last_failures) = state.upstream_stats.snapshot();
+    let mut was_degraded = false;
+    let mut disk_checked = tokio::time::Instant::now();
+
+    loop {
+        tick.tick().await;
+
+        // 저하 모드 진입/해제
+        let degraded = state.degradation.is_degraded();
+        if degraded != was_degraded {
+            let message = if degraded {
+                "⚠️ emoji-resizer entered degraded mode (encode queue or memory over threshold)"
+            } else {
+                "✅ emoji-resizer left degraded mode"
+            };
+            notifier.send(&state, if degraded { "degraded" } else { "recovered" }, message.into());
+            was_degraded = degraded;
+        }
+
+        // 업스트림 에러율 (이번 점검 주기 동안)
+        let (requests, failures) = state.upstream_stats.snapshot();
+        let (window_requests, window_failures) = (requests - last_requests, failures - last_failures);
+        (last_requests, last_failures) = (requests, failures);
+        if config.notify_error_rate > 0.0 && window_requests >= MIN_REQUESTS {
+            let rate = window_failures as f64 / window_requests as f64;
+            if rate >= config.notify_error_rate {
+                notifier.send(
+                    &state,
+                    "error_rate",
+                    format!(
+                        "🔥 upstream error rate {:.0}% ({}/{} fetches in the last {}s)",
+                        rate * 100.0,
+                        window_failures,
+                        window_requests,
+                        CHECK_INTERVAL.as_secs()
+                    ),
+                );
+            }
+        }
+
+        // 캐시 미스 SLO (소스마다 따로 보고, 넘은 소스를 한 메시지로)
+        if config.notify_slo_burn_rate > 0.0 || config.notify_miss_p99_ms > 0 {
+            let (mut burning, mut slow) = (Vec::new(), Vec::new());
+            for (source, slo) in state.slo.snapshot() {
+                if slo.misses < MIN_MISSES {
+                    continue;
+                }
+                if config.notify_slo_burn_rate > 0.0 && slo.burn_rate >= config.notify_slo_burn_rate {
+                    burning.push(format!("{} {:.1}x ({}/{} slow)", source, slo.burn_rate, slo.slow, slo.misses));
+                }
+                if config.notify_miss_p99_ms > 0 && slo.p99_ms >= config.notify_miss_p99_ms as f64 {
+                    slow.push(format!("{} {:.0} ms", source, slo.p99_ms));
+                }
+            }
+            let window = state.slo.window().as_secs();
+            if !burning.is_empty() {
+                notifier.send(
+                    &state,
+                    "slo_burn",
+                    format!(
+                        "⏱️ cache miss SLO burning over the last {}s (target {} ms): {}",
+                        window,
+                        state.slo.target().as_millis(),
+                        burning.join(", ")
+                    ),
+                );
+            }
+            if !slow.is_empty() {
+                notifier.send(
+                    &state,
+                    "miss_latency",
+                    format!(
+                        "🐢 cache miss p99 over {} ms in the last {}s: {}",
+                        config.notify_miss_p99_ms,
+                        window,
+                        slow.join(", ")
+                    ),
+                );
+            }
+        }
+
+        // 디스크 캐시 사용량 (디렉터리를 훑으므로 드물게)
+        if config.notify_disk_bytes > 0 && disk_checked.elapsed() >= Duration::from_secs(300) {
+            disk_checked = tokio::time::Instant::now();
+            for (tier, used) in state.tiers.usage().await {
+                if used >= config.notify_disk_bytes {
+                    notifier.send(
+                        &state,
+                        "disk_full",
+                        format!(
+                            "💾 {} cache tier is using {} MB (threshold {} MB)",
+                            tier,
+                            used >> 20,
+                            config.notify_disk_bytes >> 20
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
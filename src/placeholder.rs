@@ -0,0 +1,326 @@
+//! 점진적 로딩용 흐린 자리 표시 이미지 (`GET /e/:name/placeholder`).
+//!
+//! 기본 크기 리사이즈 결과(애니메이션이면 첫 프레임)를 16px 안으로 줄이고 흐리게 만든 아주 작은 정적 WebP를
+//! 돌려주고, 같은 이미지의 [ThumbHash](https://evanw.github.io/thumbhash/)를 base64로 `x-thumbhash`에 싣는다.
+//! 해시는 리사이즈한 WebP 응답과 `/e/:name/info`에도 실리므로, 웹 클라이언트는 따로 요청하지 않고 그 자리에서
+//! 그리거나 이 이미지를 먼저 띄운 뒤 본 이미지로 바꾼다. 같은 자리에 대표 색(`x-dominant-color`, `#rrggbb`)도
+//! 실어서 이미지가 오기 전에 담는 칸을 칠할 수 있게 한다.
+//! 원본은 보통 이미지 경로로 가져오므로 차단·캐시·업스트림 처리가 같고, 결과는 원본의 ETag를 키로 따로
+//! 캐시해서 원본이 다시 만들어지면 자리 표시도 새로 만든다. AVIF를 디코드하지 않도록 원본은 협상 없이 WebP로 받는다.
+
+use axum::{
+    body::Bytes,
+    extract::{Extension, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use image::{imageops::FilterType, DynamicImage};
+use std::{f64::consts::PI, sync::Arc};
+use tracing::{error, info, warn};
+
+use crate::{
+    error::Error,
+    imaging::{self, ContentType, ImagingError},
+    middleware,
+    request::EmoteRequest,
+    response::{ImageResponse, RequestInputs, X_CACHE},
+    serve_emoji, AppState, Mode, Variant,
+};
+
+pub const X_THUMBHASH: HeaderName = HeaderName::from_static("x-thumbhash");
+pub const X_DOMINANT_COLOR: HeaderName = HeaderName::from_static("x-dominant-color");
+
+/// 자리 표시 이미지의 긴 변
+const PLACEHOLDER_SIZE: u32 = 16;
+
+/// ThumbHash 입력의 긴 변 (ThumbHash는 100px 이하를 받는다)
+const THUMBHASH_INPUT: u32 = 32;
+
+/// 메모리에 두는 자리 표시 수 (하나에 수백 바이트)
+pub const CAPACITY: u64 = 10_000;
+
+/// 이미지 응답에서 그 이미지에 관한 응답으로 옮기는 헤더 (수명과 엣지 purge 태그, 원본이 캐시에 있었는지를 원본과 맞춘다)
+pub const COPIED: [HeaderName; 4] = [
+    header::CACHE_CONTROL,
+    HeaderName::from_static("surrogate-key"),
+    HeaderName::from_static("cache-tag"),
+    X_CACHE,
+];
+
+pub struct Placeholder {
+    webp: Arc<Vec<u8>>,
+    /// base64로 적은 ThumbHash
+    thumbhash: String,
+    /// 가장 많이 보이는 색 (`#rrggbb`, 전부 투명하면 없음)
+    dominant_color: Option<String>,
+}
+
+impl Placeholder {
+    /// 디코드한 원본에서 자리 표시 이미지와 ThumbHash, 대표 색을 만든다
+    pub fn generate(body: &[u8]) -> Result<Self, ImagingError> {
+        let img = image::load_from_memory(body).map_err(ImagingError::Decode)?;
+        let small = imaging::resize_fit(&img, PLACEHOLDER_SIZE, PLACEHOLDER_SIZE, FilterType::Triangle).blur(1.0);
+        let webp = imaging::encode_webp(&small)?;
+        let input = imaging::resize_fit(&img, THUMBHASH_INPUT, THUMBHASH_INPUT, FilterType::Triangle);
+        Ok(Self {
+            webp: Arc::new(webp),
+            thumbhash: base64(&thumbhash(&input)),
+            dominant_color: dominant_color(&input),
+        })
+    }
+}
+
+pub async fn handler(
+    State(state): State<AppState>,
+    emote: EmoteRequest,
+    deadline: Option<Extension<middleware::Deadline>>,
+    req: RequestInputs,
+) -> Response {
+    let variant = Variant::Resized(state.config.default_size);
+    let deadline = deadline.map(|Extension(d)| d.0);
+    let source = serve_emoji(state.clone(), emote.stem.clone(), variant, deadline, RequestInputs::default(), Mode::Interactive).await;
+    if source.status() != StatusCode::OK {
+        return source;
+    }
+    let (parts, body) = source.into_parts();
+    let Some(etag) = etag(&parts.headers) else {
+        return Error::Processing.into_response();
+    };
+    let placeholder = match state.placeholders.get(&etag).await {
+        Some(placeholder) => placeholder,
+        None => {
+            let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    error!("Reading response body failed: {}", e);
+                    return Error::Processing.into_response();
+                }
+            };
+            match generate(&state, &emote.stem, etag, bytes).await {
+                Some(placeholder) => placeholder,
+                None => return Error::Decode.into_response(),
+            }
+        }
+    };
+
+    let mut res = ImageResponse::new(placeholder.webp.clone(), ContentType::Webp).respond(&req);
+    for name in COPIED {
+        if let Some(value) = parts.headers.get(&name) {
+            res.headers_mut().insert(name, value.clone());
+        }
+    }
+    insert_headers(&mut res, &placeholder);
+    res
+}
+
+/// 리사이즈한 WebP 응답에 `x-thumbhash`와 `x-dominant-color`를 싣는다. 이 ETag로 처음 보는 결과면 그 자리에서 만들어 둔다
+/// (자리 표시 이미지도 함께 만들어지므로 `/placeholder`는 따로 디코드하지 않는다).
+pub async fn attach(state: &AppState, label: &str, res: Response) -> Response {
+    let webp = res.headers().get(header::CONTENT_TYPE).is_some_and(|v| v == ContentType::Webp.as_str());
+    let Some(etag) = etag(res.headers()).filter(|_| webp && res.status() == StatusCode::OK) else {
+        return res;
+    };
+    if let Some(placeholder) = state.placeholders.get(&etag).await {
+        let mut res = res;
+        insert_headers(&mut res, &placeholder);
+        return res;
+    }
+    let (parts, body) = res.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Reading response body failed: {}", e);
+            return Error::Processing.into_response();
+        }
+    };
+    // HEAD나 검증자만 남은 항목처럼 본문이 없으면 다음 요청에서 만든다
+    let placeholder = if bytes.is_empty() { None } else { generate(state, label, etag, bytes.clone()).await };
+    let mut res = Response::from_parts(parts, axum::body::Body::from(bytes));
+    if let Some(placeholder) = placeholder {
+        insert_headers(&mut res, &placeholder);
+    }
+    res
+}
+
+/// 자리 표시를 만들어 `etag`로 캐시한다 (디코드하지 못하면 `None`)
+async fn generate(state: &AppState, label: &str, etag: String, bytes: Bytes) -> Option<Arc<Placeholder>> {
+    let placeholder = match tokio::task::spawn_blocking(move || Placeholder::generate(&bytes)).await {
+        Ok(Ok(placeholder)) => Arc::new(placeholder),
+        Ok(Err(e)) => {
+            warn!("Placeholder generation failed for emoji {}: {}", label, e);
+            return None;
+        }
+        Err(e) => {
+            error!("Placeholder task failed for emoji {}: {}", label, e);
+            return None;
+        }
+    };
+    info!("Placeholder generated - emoji: {}, size: {} bytes", label, placeholder.webp.len());
+    state.placeholders.insert(etag, placeholder.clone()).await;
+    Some(placeholder)
+}
+
+fn etag(headers: &HeaderMap) -> Option<String> {
+    headers.get(header::ETAG).and_then(|v| v.to_str().ok()).map(str::to_owned)
+}
+
+fn insert_headers(res: &mut Response, placeholder: &Placeholder) {
+    if let Ok(value) = HeaderValue::from_str(&placeholder.thumbhash) {
+        res.headers_mut().insert(X_THUMBHASH, value);
+    }
+    if let Some(value) = placeholder.dominant_color.as_deref().and_then(|c| HeaderValue::from_str(c).ok()) {
+        res.headers_mut().insert(X_DOMINANT_COLOR, value);
+    }
+}
+
+/// 채널당 4비트로 나눈 색 칸 중 (불투명도로 가중해) 가장 무거운 칸의 평균 색. 반투명한 가장자리보다
+/// 실제로 보이는 면을 따르도록 거의 투명한 픽셀은 세지 않는다.
+fn dominant_color(img: &DynamicImage) -> Option<String> {
+    let mut buckets = vec![[0.0f64; 4]; 4096];
+    for p in img.to_rgba8().pixels() {
+        let [r, g, b, a] = p.0;
+        if a < 32 {
+            continue;
+        }
+        let weight = f64::from(a) / 255.0;
+        let bucket = &mut buckets[(usize::from(r >> 4) << 8) | (usize::from(g >> 4) << 4) | usize::from(b >> 4)];
+        bucket[0] += weight * f64::from(r);
+        bucket[1] += weight * f64::from(g);
+        bucket[2] += weight * f64::from(b);
+        bucket[3] += weight;
+    }
+    let [r, g, b, weight] = buckets.into_iter().max_by(|a, b| a[3].total_cmp(&b[3]))?;
+    if weight == 0.0 {
+        return None;
+    }
+    let channel = |sum: f64| (sum / weight).round() as u8;
+    Some(format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b)))
+}
+
+/// 원본 ThumbHash 구현을 옮긴 것. 평균 색 위에 합성한 LPQA 채널을 DCT로 줄여 25~35바이트에 담는다.
+fn thumbhash(img: &DynamicImage) -> Vec<u8> {
+    let rgba = img.to_rgba8();
+    let (w, h) = (rgba.width() as usize, rgba.height() as usize);
+    // 부동소수점 연산 순서까지 원본과 같아야 반올림 경계에서 같은 해시가 나온다
+    let pixels: Vec<[f64; 4]> = rgba.pixels().map(|p| p.0.map(f64::from)).collect();
+
+    // 평균 색 (알파로 가중)
+    let (mut avg_r, mut avg_g, mut avg_b, mut avg_a) = (0.0, 0.0, 0.0, 0.0);
+    for [r, g, b, a] in &pixels {
+        let alpha = a / 255.0;
+        avg_r += alpha / 255.0 * r;
+        avg_g += alpha / 255.0 * g;
+        avg_b += alpha / 255.0 * b;
+        avg_a += alpha;
+    }
+    if avg_a > 0.0 {
+        avg_r /= avg_a;
+        avg_g /= avg_a;
+        avg_b /= avg_a;
+    }
+
+    let has_alpha = avg_a < (w * h) as f64;
+    // 알파가 있으면 밝기에 쓰는 비트를 줄인다
+    let l_limit = if has_alpha { 5.0 } else { 7.0 };
+    let long = w.max(h) as f64;
+    let lx = ((l_limit * w as f64 / long).round() as usize).max(1);
+    let ly = ((l_limit * h as f64 / long).round() as usize).max(1);
+
+    let (mut l, mut p, mut q, mut a) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for [r, g, b, alpha] in &pixels {
+        let alpha = alpha / 255.0;
+        let r = avg_r * (1.0 - alpha) + alpha / 255.0 * r;
+        let g = avg_g * (1.0 - alpha) + alpha / 255.0 * g;
+        let b = avg_b * (1.0 - alpha) + alpha / 255.0 * b;
+        l.push((r + g + b) / 3.0);
+        p.push((r + g) / 2.0 - b);
+        q.push(r - g);
+        a.push(alpha);
+    }
+
+    let encode = |channel: &[f64], nx: usize, ny: usize| {
+        let (mut dc, mut ac, mut scale) = (0.0, Vec::new(), 0.0f64);
+        let mut fx = vec![0.0; w];
+        for cy in 0..ny {
+            let mut cx = 0;
+            while cx * ny < nx * (ny - cy) {
+                for (x, f) in fx.iter_mut().enumerate() {
+                    *f = (PI / w as f64 * cx as f64 * (x as f64 + 0.5)).cos();
+                }
+                let mut f = 0.0;
+                for y in 0..h {
+                    let fy = (PI / h as f64 * cy as f64 * (y as f64 + 0.5)).cos();
+                    for x in 0..w {
+                        f += channel[x + y * w] * fx[x] * fy;
+                    }
+                }
+                f /= (w * h) as f64;
+                if cx > 0 || cy > 0 {
+                    ac.push(f);
+                    scale = scale.max(f.abs());
+                } else {
+                    dc = f;
+                }
+                cx += 1;
+            }
+        }
+        if scale > 0.0 {
+            for f in &mut ac {
+                *f = 0.5 + 0.5 / scale * *f;
+            }
+        }
+        (dc, ac, scale)
+    };
+    let (l_dc, l_ac, l_scale) = encode(&l, lx.max(3), ly.max(3));
+    let (p_dc, p_ac, p_scale) = encode(&p, 3, 3);
+    let (q_dc, q_ac, q_scale) = encode(&q, 3, 3);
+    let alpha = has_alpha.then(|| encode(&a, 5, 5));
+
+    let landscape = w > h;
+    let header24 = (63.0 * l_dc).round() as u32
+        | ((31.5 + 31.5 * p_dc).round() as u32) << 6
+        | ((31.5 + 31.5 * q_dc).round() as u32) << 12
+        | ((31.0 * l_scale).round() as u32) << 18
+        | u32::from(has_alpha) << 23;
+    let header16 = (if landscape { ly } else { lx }) as u32
+        | ((63.0 * p_scale).round() as u32) << 3
+        | ((63.0 * q_scale).round() as u32) << 9
+        | u32::from(landscape) << 15;
+    let mut hash = vec![
+        (header24 & 255) as u8,
+        ((header24 >> 8) & 255) as u8,
+        (header24 >> 16) as u8,
+        (header16 & 255) as u8,
+        (header16 >> 8) as u8,
+    ];
+    let mut factors = vec![l_ac, p_ac, q_ac];
+    if let Some((a_dc, a_ac, a_scale)) = alpha {
+        hash.push((15.0 * a_dc).round() as u8 | ((15.0 * a_scale).round() as u8) << 4);
+        factors.push(a_ac);
+    }
+    let start = hash.len();
+    for (i, f) in factors.iter().flatten().enumerate() {
+        if start + i / 2 == hash.len() {
+            hash.push(0);
+        }
+        hash[start + i / 2] |= ((15.0 * f).round() as u8) << ((i & 1) * 4);
+    }
+    hash
+}
+
+/// 표준 base64 (패딩 포함)
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,82 @@
+//! 인기 이모지 선제 갱신.
+//!
+//! 키별 요청률을 지수 감쇠 점수로 추적하고, 점수 상위 키가 메모리 캐시 TTL에 가까워지면
+//! 만료되기 전에 백그라운드에서 다시 만들어 넣는다. 인기 이모지가 한꺼번에 만료되며
+//! 생기는 지연 급증을 완화하는 용도.
+
+use moka::sync::Cache;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// 점수 반감기: 10분 전 요청은 지금 요청의 절반으로 친다
+const HALF_LIFE: Duration = Duration::from_secs(600);
+
+struct Score {
+    value: f64,
+    updated: Instant,
+}
+
+impl Score {
+    fn decayed(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.value * 0.5f64.powf(elapsed / HALF_LIFE.as_secs_f64())
+    }
+}
+
+pub struct Popularity {
+    scores: Cache<String, Arc<Mutex<Score>>>,
+    /// 메모리 캐시에 마지막으로 넣은 시각 (TTL 만료 시점 계산용)
+    inserted: Cache<String, Instant>,
+}
+
+impl Popularity {
+    pub fn new(capacity: u64, ttl: Duration) -> Self {
+        Self {
+            // 한동안 요청이 없으면 점수도 의미가 없으므로 함께 정리
+            scores: Cache::builder()
+                .max_capacity(capacity)
+                .time_to_idle(HALF_LIFE * 6)
+                .build(),
+            inserted: Cache::builder().max_capacity(capacity).time_to_live(ttl).build(),
+        }
+    }
+
+    pub fn record(&self, key: &str) {
+        let now = Instant::now();
+        let score = self.scores.get_with(key.to_string(), || {
+            Arc::new(Mutex::new(Score { value: 0.0, updated: now }))
+        });
+        let mut score = score.lock().unwrap();
+        score.value = score.decayed(now) + 1.0;
+        score.updated = now;
+    }
+
+    pub fn note_insert(&self, key: &str) {
+        self.inserted.insert(key.to_string(), Instant::now());
+    }
+
+    /// 점수 상위 `top`개 중 캐시에 들어간 지 `ttl - lead` 이상 지난 키
+    pub fn due(&self, top: usize, ttl: Duration, lead: Duration) -> Vec<String> {
+        let now = Instant::now();
+        let mut ranked: Vec<(String, f64)> = self
+            .scores
+            .iter()
+            .map(|(k, s)| (k.as_ref().clone(), s.lock().unwrap().decayed(now)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.truncate(top);
+
+        let refresh_after = ttl.saturating_sub(lead);
+        ranked
+            .into_iter()
+            .filter(|(k, _)| {
+                self.inserted
+                    .get(k)
+                    .is_some_and(|t| now.saturating_duration_since(t) >= refresh_after)
+            })
+            .map(|(k, _)| k)
+            .collect()
+    }
+}
@@ -0,0 +1,92 @@
+//! 프라이버시 모드: 로그/통계에 남는 클라이언트 IP 익명화와 보관 기간 관리.
+
+use anyhow::bail;
+use axum::http::HeaderMap;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+
+/// 로그에 IP를 어떻게 남길지
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpMode {
+    /// 그대로 기록
+    Full,
+    /// IPv4는 /24, IPv6는 /48까지만 남김
+    Truncate,
+    /// 솔트를 섞은 해시로 대체 (같은 클라이언트끼리는 묶이지만 역산 불가)
+    Hash,
+}
+
+impl FromStr for IpMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "full" => Ok(IpMode::Full),
+            "truncate" => Ok(IpMode::Truncate),
+            "hash" => Ok(IpMode::Hash),
+            other => bail!("unknown IP mode {other:?} (expected full, truncate or hash)"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Privacy {
+    pub mode: IpMode,
+    salt: String,
+}
+
+impl Privacy {
+    /// 솔트가 없으면 프로세스마다 새로 만든다 (재시작하면 해시가 이어지지 않음)
+    pub fn new(mode: IpMode, salt: Option<String>) -> Self {
+        let salt = salt.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        Self { mode, salt }
+    }
+
+    /// 로그/통계에 남길 IP 표현
+    pub fn display_ip(&self, ip: IpAddr) -> String {
+        match self.mode {
+            IpMode::Full => ip.to_string(),
+            IpMode::Truncate => truncate(ip).to_string(),
+            IpMode::Hash => {
+                let mut h = Sha1::new();
+                h.update(self.salt.as_bytes());
+                h.update(ip.to_string().as_bytes());
+                let hex = format!("{:x}", h.finalize());
+                format!("h:{}", &hex[..16])
+            }
+        }
+    }
+}
+
+fn truncate(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, c, _] = v4.octets();
+            IpAddr::V4(Ipv4Addr::new(a, b, c, 0))
+        }
+        IpAddr::V6(v6) => {
+            let s = v6.segments();
+            IpAddr::V6(Ipv6Addr::new(s[0], s[1], s[2], 0, 0, 0, 0, 0))
+        }
+    }
+}
+
+/// 클라이언트 IP. 프록시 뒤라면 `X-Forwarded-For`의 첫 주소를 믿을 수 있다.
+pub fn client_ip(headers: &HeaderMap, peer: SocketAddr, trust_forwarded_for: bool) -> IpAddr {
+    if trust_forwarded_for {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .and_then(|v| v.trim().parse().ok());
+        if let Some(ip) = forwarded {
+            return ip;
+        }
+    }
+    peer.ip()
+}
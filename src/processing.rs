@@ -0,0 +1,180 @@
+//! 우선순위가 있는 인코딩 대기열.
+//!
+//! 리사이즈/인코딩은 `PROCESSING_CONCURRENCY`개까지만 동시에 돌린다. 자리가 나면 클라이언트가
+//! 기다리는 캐시 미스(높은 우선순위)에 먼저 넘기고, 선제 갱신·형제 크기 준비·캐시 채우기(낮은
+//! 우선순위)는 그다음이다. 낮은 우선순위 작업은 `PROCESSING_BACKGROUND_MAX`개까지만 돌려서
+//! 백그라운드 작업이 자리를 모두 차지해 사용자 요청을 기다리게 하는 일이 없도록 한다.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+use crate::{
+    error::Error,
+    metrics::{PROCESSING_QUEUED, PROCESSING_RUNNING},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    High,
+    Low,
+}
+
+impl Priority {
+    pub fn name(self) -> &'static str {
+        match self {
+            Priority::High => "high",
+            Priority::Low => "low",
+        }
+    }
+}
+
+pub struct Processing {
+    inner: Mutex<Inner>,
+    capacity: usize,
+    background_max: usize,
+}
+
+struct Inner {
+    running: usize,
+    running_low: usize,
+    high: VecDeque<oneshot::Sender<Permit>>,
+    low: VecDeque<oneshot::Sender<Permit>>,
+}
+
+/// 작업 하나가 쓰는 자리. 놓으면(drop) 다음 대기자에게 넘어간다.
+pub struct Permit {
+    /// 대기자에게 넘기지 못해 이미 되돌린 자리는 `None`
+    queue: Option<Arc<Processing>>,
+    priority: Priority,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let Some(queue) = self.queue.take() else {
+            return;
+        };
+        let mut inner = queue.inner.lock().unwrap();
+        inner.release(self.priority);
+        queue.dispatch(&mut inner);
+    }
+}
+
+impl Inner {
+    fn release(&mut self, priority: Priority) {
+        self.running -= 1;
+        if priority == Priority::Low {
+            self.running_low -= 1;
+        }
+    }
+}
+
+impl Processing {
+    pub fn new(capacity: usize, background_max: usize) -> Arc<Self> {
+        Arc::new(Self {
+            inner: Mutex::new(Inner { running: 0, running_low: 0, high: VecDeque::new(), low: VecDeque::new() }),
+            capacity: capacity.max(1),
+            background_max: background_max.clamp(1, capacity.max(1)),
+        })
+    }
+
+    /// 자리가 날 때까지 기다린다. 기다리는 동안 취소(drop)되면 대기열에서 빠진다.
+    pub async fn acquire(self: &Arc<Self>, priority: Priority) -> Permit {
+        let rx = {
+            let mut inner = self.inner.lock().unwrap();
+            // 취소된 대기자가 앞을 막지 않도록
+            inner.high.retain(|tx| !tx.is_closed());
+            inner.low.retain(|tx| !tx.is_closed());
+            if self.admits(&inner, priority) {
+                return self.start(&mut inner, priority);
+            }
+            let (tx, rx) = oneshot::channel();
+            match priority {
+                Priority::High => inner.high.push_back(tx),
+                Priority::Low => inner.low.push_back(tx),
+            }
+            self.record(&inner);
+            rx
+        };
+        // 넘겨준 쪽이 살아 있는 동안 채널은 닫히지 않는다
+        rx.await.expect("processing queue dropped a waiter")
+    }
+
+    /// 자리를 기다리는 작업 수
+    pub fn queued(&self, priority: Priority) -> usize {
+        let inner = self.inner.lock().unwrap();
+        match priority {
+            Priority::High => inner.high.iter().filter(|tx| !tx.is_closed()).count(),
+            Priority::Low => inner.low.iter().filter(|tx| !tx.is_closed()).count(),
+        }
+    }
+
+    /// 포화되기 전에 새 요청을 돌려보낼지 (admission control). 백그라운드 작업은 사용자 요청에 밀리므로
+    /// 사용자 요청 대기만 본다: `hard` 이상이면 [`Error::Overloaded`], `soft` 이상이면 [`Error::Throttled`].
+    /// 0인 한계는 쓰지 않는다.
+    pub fn admit(&self, soft: usize, hard: usize) -> Result<(), Error> {
+        let queued = self.queued(Priority::High);
+        if hard > 0 && queued >= hard {
+            return Err(Error::Overloaded);
+        }
+        if soft > 0 && queued >= soft {
+            return Err(Error::Throttled);
+        }
+        Ok(())
+    }
+
+    fn admits(&self, inner: &Inner, priority: Priority) -> bool {
+        inner.running < self.capacity
+            && match priority {
+                // 먼저 온 같은 등급 대기자를 앞지르지 않는다
+                Priority::High => inner.high.is_empty(),
+                Priority::Low => inner.high.is_empty() && inner.low.is_empty() && inner.running_low < self.background_max,
+            }
+    }
+
+    fn start(self: &Arc<Self>, inner: &mut Inner, priority: Priority) -> Permit {
+        inner.running += 1;
+        if priority == Priority::Low {
+            inner.running_low += 1;
+        }
+        self.record(inner);
+        Permit { queue: Some(self.clone()), priority }
+    }
+
+    /// 빈 자리를 높은 우선순위 대기자부터 넘긴다. 이미 취소된 대기자는 건너뛴다.
+    fn dispatch(self: &Arc<Self>, inner: &mut Inner) {
+        while inner.running < self.capacity {
+            let priority = if !inner.high.is_empty() {
+                Priority::High
+            } else if !inner.low.is_empty() && inner.running_low < self.background_max {
+                Priority::Low
+            } else {
+                break;
+            };
+            let tx = match priority {
+                Priority::High => inner.high.pop_front(),
+                Priority::Low => inner.low.pop_front(),
+            }
+            .expect("checked above");
+            if tx.is_closed() {
+                continue;
+            }
+            let permit = self.start(inner, priority);
+            // 받기 직전에 취소됐으면 자리를 되돌리고 다음 대기자로 (잠금을 쥔 채라 drop에 맡기지 않는다)
+            if let Err(mut permit) = tx.send(permit) {
+                permit.queue = None;
+                inner.release(priority);
+            }
+        }
+        self.record(inner);
+    }
+
+    fn record(&self, inner: &Inner) {
+        for (priority, queued) in [(Priority::High, inner.high.len()), (Priority::Low, inner.low.len())] {
+            metrics::gauge!(PROCESSING_QUEUED, "priority" => priority.name()).set(queued as f64);
+        }
+        metrics::gauge!(PROCESSING_RUNNING).set(inner.running as f64);
+    }
+}
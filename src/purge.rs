@@ -0,0 +1,102 @@
+//! 엣지 캐시 무효화: `Surrogate-Key`/`Cache-Tag` 태그 생성과 Cloudflare/Fastly purge API 연동.
+
+use anyhow::{bail, Context};
+use reqwest::Client;
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::config::redact;
+
+/// 이모지 응답에 붙일 캐시 태그. 엣지에서 이모지 단위로 한 번에 날릴 수 있게
+/// ID 태그를 맨 앞에 둔다.
+pub fn tags(emoji_id: &str, source: &str, size: u32) -> Vec<String> {
+    vec![
+        emoji_tag(emoji_id),
+        format!("source-{source}"),
+        format!("size-{size}"),
+    ]
+}
+
+pub fn emoji_tag(emoji_id: &str) -> String {
+    format!("emoji-{emoji_id}")
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum CdnProvider {
+    Cloudflare {
+        zone_id: String,
+        #[serde(serialize_with = "redact::secret")]
+        api_token: String,
+    },
+    Fastly {
+        service_id: String,
+        #[serde(serialize_with = "redact::secret")]
+        api_key: String,
+    },
+}
+
+impl CdnProvider {
+    /// `CDN_PURGE_PROVIDER=cloudflare|fastly` 와 제공자별 자격 증명을 읽는다. 미설정이면 `None`.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let var = |name: &str| std::env::var(name).with_context(|| format!("{name} is required"));
+        match std::env::var("CDN_PURGE_PROVIDER").unwrap_or_default().trim() {
+            "" => Ok(None),
+            "cloudflare" => Ok(Some(Self::Cloudflare {
+                zone_id: var("CLOUDFLARE_ZONE_ID")?,
+                api_token: var("CLOUDFLARE_API_TOKEN")?,
+            })),
+            "fastly" => Ok(Some(Self::Fastly {
+                service_id: var("FASTLY_SERVICE_ID")?,
+                api_key: var("FASTLY_API_KEY")?,
+            })),
+            other => bail!("unknown CDN_PURGE_PROVIDER {other:?} (expected cloudflare or fastly)"),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Cloudflare { .. } => "cloudflare",
+            Self::Fastly { .. } => "fastly",
+        }
+    }
+}
+
+/// CDN purge API 호출기. 업스트림용 클라이언트는 HTTP/2 prior knowledge라서
+/// 제공자 API에는 ALPN 협상을 하는 별도 클라이언트를 쓴다.
+#[derive(Clone)]
+pub struct CdnPurger {
+    provider: CdnProvider,
+    http: Client,
+}
+
+impl CdnPurger {
+    pub fn new(provider: CdnProvider) -> anyhow::Result<Self> {
+        let http = Client::builder().timeout(Duration::from_secs(10)).build()?;
+        Ok(Self { provider, http })
+    }
+
+    pub fn provider(&self) -> &CdnProvider {
+        &self.provider
+    }
+
+    pub async fn purge_tags(&self, tags: &[String]) -> anyhow::Result<()> {
+        let req = match &self.provider {
+            CdnProvider::Cloudflare { zone_id, api_token } => self
+                .http
+                .post(format!("https://api.cloudflare.com/client/v4/zones/{zone_id}/purge_cache"))
+                .bearer_auth(api_token)
+                .json(&serde_json::json!({ "tags": tags })),
+            CdnProvider::Fastly { service_id, api_key } => self
+                .http
+                .post(format!("https://api.fastly.com/service/{service_id}/purge"))
+                .header("Fastly-Key", api_key)
+                .header("Surrogate-Key", tags.join(" ")),
+        };
+        let resp = req.send().await.context("purge request failed")?;
+        if !resp.status().is_success() {
+            bail!("{} purge returned {}", self.provider.name(), resp.status());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+//! 최근 실패한 이미지 요청 기록 (`RECENT_ERRORS`, `GET /admin/errors`).
+//!
+//! "이모지 X가 안 나온다"는 제보를 받았을 때 로그를 뒤지지 않고 바로 볼 수 있도록, 에러로 끝난 이미지 응답을
+//! 최근 `RECENT_ERRORS`개까지 메모리에 남긴다. 처리 경로가 응답에 실어 둔 [`Error`]를 그대로 읽으므로 소스와
+//! 무관하게 한곳에서 기록하며, 만료 항목이나 원본으로 대신 내보낸 요청은 실패로 치지 않는다. 가득 차면 가장
+//! 오래된 것부터 버리고, 재시작하면 비워진다.
+
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::Error;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ErrorRecord {
+    /// 기록한 시각 (유닉스 초)
+    pub at: u64,
+    /// 로그에 쓰는 이모지 이름 (Discord는 이모지 ID, 다른 소스는 `소스/…`)
+    pub emoji: String,
+    /// 실패한 단계 (fetch, validate, storage, blocklist, deadline, decode, encode 등)
+    pub stage: &'static str,
+    /// [`Error::kind`]
+    pub error: &'static str,
+    /// 클라이언트에게 돌려준 상태 코드
+    pub status: u16,
+    /// 원인이 된 업스트림
+    pub upstream: Option<&'static str>,
+    /// 업스트림이 실제로 돌려준 상태 코드
+    pub upstream_status: Option<u16>,
+    pub message: String,
+    /// 접근 로그와 맞춰 볼 요청 ID
+    pub request_id: Option<String>,
+}
+
+/// 최근 실패를 보관하는 고리 버퍼
+pub struct RecentErrors {
+    capacity: usize,
+    records: Mutex<VecDeque<ErrorRecord>>,
+}
+
+impl RecentErrors {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, records: Mutex::new(VecDeque::with_capacity(capacity)) }
+    }
+
+    /// `emoji` 요청이 `error`로 끝났다 (`RECENT_ERRORS=0`이면 남기지 않는다)
+    pub fn record(&self, emoji: &str, error: Error, request_id: Option<&str>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let record = ErrorRecord {
+            at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            emoji: emoji.to_string(),
+            stage: stage(error),
+            error: error.kind(),
+            status: error.status().as_u16(),
+            upstream: error.source().map(|s| s.name()),
+            upstream_status: error.upstream_status().map(|s| s.as_u16()),
+            message: error.to_string(),
+            request_id: request_id.map(str::to_owned),
+        };
+        let mut records = self.records.lock().unwrap();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// 최근 것부터 최대 `limit`개. `emoji`를 주면 그 이모지 것만.
+    pub fn list(&self, emoji: Option<&str>, limit: usize) -> Vec<ErrorRecord> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|r| emoji.is_none_or(|e| r.emoji == e))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// 에러가 난 처리 단계
+fn stage(error: Error) -> &'static str {
+    match error {
+        Error::NotFound
+        | Error::UpstreamTimeout { .. }
+        | Error::UpstreamUnreachable { .. }
+        | Error::UpstreamStatus { .. }
+        | Error::UpstreamRead { .. }
+        | Error::UpstreamTooLarge { .. }
+        | Error::UpstreamRedirect { .. } => "fetch",
+        Error::UpstreamInvalid { .. } => "validate",
+        Error::Storage => "storage",
+        Error::Blocked => "blocklist",
+        Error::BadRequest(_) => "request",
+        Error::Throttled | Error::Overloaded => "admission",
+        Error::DeadlineExceeded => "deadline",
+        Error::Decode => "decode",
+        Error::Processing => "encode",
+    }
+}
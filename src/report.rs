@@ -0,0 +1,221 @@
+//! 공개 인스턴스 운영자를 위한 신고 접수 (`POST /report/:emoji_id`).
+//!
+//! 신고는 메모리에 이모지별로 집계되고 `/admin/reports`에서 조회해 차단 목록 처리로 넘긴다.
+
+use crate::{privacy::client_ip, AppState};
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use moka::sync::Cache;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{info, warn};
+
+/// 이모지당 보관하는 최근 신고 사유 수
+const MAX_REASONS: usize = 20;
+const MAX_REASON_LEN: usize = 500;
+/// 집계하는 서로 다른 이모지 수 상한 (스팸으로 메모리가 불어나지 않게)
+const MAX_REPORTED: usize = 10_000;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportRecord {
+    pub count: u64,
+    /// 최근 사유 (오래된 것부터)
+    pub reasons: Vec<String>,
+    pub first_reported: u64,
+    pub last_reported: u64,
+}
+
+#[derive(Default)]
+pub struct ReportStore {
+    records: RwLock<HashMap<String, ReportRecord>>,
+}
+
+impl ReportStore {
+    /// 신고를 기록한다. 상한에 걸려 버려졌으면 `false`.
+    fn record(&self, emoji_id: &str, reason: Option<String>) -> bool {
+        let now = unix_now();
+        let mut records = self.records.write().unwrap();
+        if !records.contains_key(emoji_id) && records.len() >= MAX_REPORTED {
+            return false;
+        }
+        let rec = records.entry(emoji_id.to_string()).or_insert_with(|| ReportRecord {
+            count: 0,
+            reasons: Vec::new(),
+            first_reported: now,
+            last_reported: now,
+        });
+        rec.count += 1;
+        rec.last_reported = now;
+        if let Some(reason) = reason {
+            if rec.reasons.len() == MAX_REASONS {
+                rec.reasons.remove(0);
+            }
+            rec.reasons.push(reason);
+        }
+        true
+    }
+
+    /// 신고 수 내림차순
+    pub fn list(&self) -> Vec<(String, ReportRecord)> {
+        let mut list: Vec<_> = self
+            .records
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        list.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(&b.0)));
+        list
+    }
+
+    pub fn get(&self, emoji_id: &str) -> Option<ReportRecord> {
+        self.records.read().unwrap().get(emoji_id).cloned()
+    }
+
+    pub fn dismiss(&self, emoji_id: &str) -> bool {
+        self.records.write().unwrap().remove(emoji_id).is_some()
+    }
+
+    /// 마지막 신고가 `retention`보다 오래된 기록을 지운다. 지운 개수를 돌려준다.
+    pub fn prune(&self, retention: Duration) -> usize {
+        let cutoff = unix_now().saturating_sub(retention.as_secs());
+        let mut records = self.records.write().unwrap();
+        let before = records.len();
+        records.retain(|_, rec| rec.last_reported >= cutoff);
+        before - records.len()
+    }
+}
+
+/// IP별 고정 윈도 카운터. 윈도가 지나면 항목이 만료되며 초기화된다.
+pub struct RateLimiter {
+    limit: u32,
+    windows: Cache<IpAddr, Arc<AtomicU32>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            windows: Cache::builder()
+                .max_capacity(100_000)
+                .time_to_live(window)
+                .build(),
+        }
+    }
+
+    pub fn allow(&self, ip: IpAddr) -> bool {
+        let counter = self.windows.get_with(ip, || Arc::new(AtomicU32::new(0)));
+        counter.fetch_add(1, Ordering::Relaxed) < self.limit
+    }
+}
+
+/// hCaptcha 토큰 검증기 (`HCAPTCHA_SECRET` 설정 시)
+pub struct Captcha {
+    secret: String,
+    http: Client,
+}
+
+#[derive(Deserialize)]
+struct SiteVerify {
+    success: bool,
+}
+
+impl Captcha {
+    pub fn new(secret: String) -> anyhow::Result<Self> {
+        let http = Client::builder().timeout(Duration::from_secs(5)).build()?;
+        Ok(Self { secret, http })
+    }
+
+    async fn verify(&self, token: &str, ip: IpAddr) -> anyhow::Result<bool> {
+        let ip = ip.to_string();
+        let resp: SiteVerify = self
+            .http
+            .post("https://api.hcaptcha.com/siteverify")
+            .form(&[("secret", self.secret.as_str()), ("response", token), ("remoteip", &ip)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(resp.success)
+    }
+}
+
+/// 신고 접수에 필요한 상태 묶음
+pub struct Reports {
+    pub store: ReportStore,
+    pub limiter: RateLimiter,
+    pub captcha: Option<Captcha>,
+    /// 프록시 뒤에서 `X-Forwarded-For` 첫 주소를 클라이언트 IP로 믿을지
+    pub trust_forwarded_for: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ReportRequest {
+    #[serde(default)]
+    reason: Option<String>,
+    #[serde(default, rename = "h-captcha-response")]
+    captcha_token: Option<String>,
+}
+
+pub async fn submit(
+    State(state): State<AppState>,
+    Path(emoji_id): Path<String>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<ReportRequest>,
+) -> Response {
+    let reports = &state.reports;
+    if emoji_id.is_empty() || !emoji_id.bytes().all(|b| b.is_ascii_digit()) {
+        return (StatusCode::BAD_REQUEST, "invalid emoji id").into_response();
+    }
+
+    let ip = client_ip(&headers, peer, reports.trust_forwarded_for);
+    if !reports.limiter.allow(ip) {
+        return (StatusCode::TOO_MANY_REQUESTS, "too many reports").into_response();
+    }
+
+    if let Some(captcha) = &reports.captcha {
+        let Some(token) = req.captcha_token.as_deref().filter(|t| !t.is_empty()) else {
+            return (StatusCode::BAD_REQUEST, "captcha required").into_response();
+        };
+        match captcha.verify(token, ip).await {
+            Ok(true) => {}
+            Ok(false) => return (StatusCode::FORBIDDEN, "captcha failed").into_response(),
+            Err(e) => {
+                warn!("hCaptcha verification error: {e:#}");
+                return (StatusCode::SERVICE_UNAVAILABLE, "captcha unavailable").into_response();
+            }
+        }
+    }
+
+    let reason = req
+        .reason
+        .map(|r| r.trim().chars().take(MAX_REASON_LEN).collect::<String>())
+        .filter(|r| !r.is_empty());
+    if !reports.store.record(&emoji_id, reason) {
+        warn!("report store full, dropping report for emoji {}", emoji_id);
+        return (StatusCode::SERVICE_UNAVAILABLE, "report intake full").into_response();
+    }
+    info!("Abuse report received - emoji: {}, from: {}", emoji_id, state.privacy.display_ip(ip));
+    (StatusCode::ACCEPTED, "report received").into_response()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
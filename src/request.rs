@@ -0,0 +1,106 @@
+//! 이모지 주소(`/e/:name` 등)의 요청 인자 추출기.
+//!
+//! 경로 마지막 조각의 이름·확장자·DPR 접미사(`123@2x.webp`)와 쿼리(`?size`, `?passthrough`, 모양 옵션)를
+//! 핸들러 밖에서 한 번에 풀고 검증한다. 잘못된 인자는 무엇이 틀렸는지 적은 400으로 돌려준다.
+
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, Query, RawPathParams},
+    http::request::Parts,
+    response::Redirect,
+};
+
+use crate::{
+    canonical_url, config::Config, effects::Effects, error::Error, path_extension, pick_variant, tenant::Tenant, EmojiParams, Variant,
+};
+
+/// 받아 주는 가장 큰 DPR 배율
+const MAX_DPR: f32 = 4.0;
+
+#[derive(Debug)]
+pub struct EmoteRequest {
+    /// 경로 마지막 조각에서 DPR 접미사만 뗀 것 (`123.webp`). 확장자 판단에 쓴다.
+    pub name: String,
+    /// 확장자와 DPR 접미사를 뗀 이름 (Discord는 이모지 ID)
+    pub stem: String,
+    /// `@2x` 같은 기기 픽셀 배율 (없으면 1)
+    pub dpr: f32,
+    pub params: EmojiParams,
+    /// 쿼리로 고른 모양 옵션 (`?trim=1`, `?stroke=2,ffffff`, `?shadow=2,2,4,000000`, `?pad_px=8`)
+    pub effects: Effects,
+}
+
+impl EmoteRequest {
+    /// 경로 마지막 조각을 이름과 DPR로 나눈다. `@`가 있으면 `<배율>x` 꼴이어야 하고
+    /// 확장자는 그 뒤에 온다 (`123@1.5x.webp`).
+    fn parse_name(segment: &str) -> Result<(String, String, f32), Error> {
+        let (name, dpr) = match segment.rsplit_once('@') {
+            Some((stem, suffix)) => {
+                let (dpr, extension) = suffix.split_once('x').ok_or(Error::BadRequest("dpr suffix must look like @2x"))?;
+                let dpr = dpr
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|_| extension.is_empty() || extension.starts_with('.'))
+                    .ok_or(Error::BadRequest("dpr suffix must look like @2x"))?;
+                if !(1.0..=MAX_DPR).contains(&dpr) {
+                    return Err(Error::BadRequest("dpr must be between 1 and 4"));
+                }
+                (format!("{stem}{extension}"), dpr)
+            }
+            None => (segment.to_string(), 1.0),
+        };
+        let stem = name.split('.').next().unwrap_or(&name).to_string();
+        if stem.is_empty() {
+            return Err(Error::BadRequest("emoji name is required"));
+        }
+        Ok((name, stem, dpr))
+    }
+
+    /// DPR을 반영해 응답 종류를 고른다. 허용 목록 밖의 크기는 `{prefix}/{name}?size=N`으로,
+    /// 배율을 곱한 크기가 목록에 없으면 가까운 크기의 정규 주소(`{prefix}/{stem}.webp?size=N`, `.png`·`.gif`는 그 확장자)로 보낸다.
+    /// 모양 옵션은 리다이렉트할 주소에도 그대로 싣는다.
+    pub fn variant(&self, config: &Config, prefix: &str) -> Result<Variant, Redirect> {
+        if self.dpr == 1.0 {
+            let path = format!("{prefix}/{}", self.name);
+            return pick_variant(config, &self.params, &path).map_err(|redirect| {
+                if self.effects.is_none() {
+                    return redirect;
+                }
+                let size = config.snap_size(self.params.size.unwrap_or(config.default_size));
+                Redirect::permanent(&self.effects.carry(&format!("{path}?size={size}")))
+            });
+        }
+        let base = self.params.size.unwrap_or(config.default_size);
+        let scaled = (base as f32 * self.dpr).round() as u32;
+        let params = EmojiParams { size: Some(scaled), ..self.params.clone() };
+        pick_variant(config, &params, prefix).map_err(|_| {
+            let variant = Variant::Resized(config.snap_size(scaled));
+            let variant = variant.with_extension(path_extension(&self.name, variant));
+            Redirect::permanent(&self.effects.carry(&canonical_url(config, &format!("{prefix}/{}", self.stem), variant)))
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for EmoteRequest {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let path = RawPathParams::from_request_parts(parts, state)
+            .await
+            .map_err(|_| Error::BadRequest("invalid path"))?;
+        let segment = path.iter().last().map(|(_, value)| value).ok_or(Error::BadRequest("emoji name is required"))?;
+        let (name, stem, dpr) = Self::parse_name(segment)?;
+        let Query(mut params) = Query::<EmojiParams>::try_from_uri(&parts.uri)
+            .map_err(|_| Error::BadRequest("size must be a positive integer"))?;
+        if params.size == Some(0) {
+            return Err(Error::BadRequest("size must be a positive integer"));
+        }
+        // 크기를 안 적은 테넌트 요청은 그 테넌트의 기본 크기로 (크기는 캐시 키에 들어가므로 따로 나누지 않는다)
+        if let Some(tenant) = parts.extensions.get::<Tenant>() {
+            params.size = params.size.or(Some(tenant.size));
+        }
+        let effects = Effects::from_params(&params)?;
+        Ok(Self { name, stem, dpr, params, effects })
+    }
+}
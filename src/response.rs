@@ -0,0 +1,365 @@
+//! 이미지 응답 조립.
+//!
+//! 200과 304가 같은 검증자 집합(ETag, Cache-Control, Vary)을 싣도록 (RFC 9110 §15.4.5)
+//! 헤더 구성을 한곳에서 한다. 이미지 본문에는 항상 `Content-Length`를 붙인다.
+//!
+//! CDN 뒤의 오리진으로서 엣지가 재검증하며 보내는 `If-None-Match`는 약한 비교(§13.1.2)로 맞추고,
+//! 캐시에서 꺼낸 응답에는 미리 계산한 ETag와 `Age`를 실어 엣지가 신선도를 바르게 계산하게 한다.
+//!
+//! `Vary`는 직접 적지 않는다. 핸들러가 [`RequestInputs`]로 읽은 협상 입력만 실리므로,
+//! 협상 기능이 늘어나도 CDN이 다른 클라이언트용 변형을 섞어 주는 일(캐시 오염)이 없다.
+
+use async_trait::async_trait;
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sha1::{Digest, Sha1};
+use std::{
+    convert::Infallible,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    auth::Auth,
+    effects::Effects,
+    imaging::{ContentType, StageTimings},
+    tenant::Tenant,
+};
+
+pub const X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+const X_SOURCE_URL: HeaderName = HeaderName::from_static("x-source-url");
+const SURROGATE_KEY: HeaderName = HeaderName::from_static("surrogate-key");
+const CACHE_TAG: HeaderName = HeaderName::from_static("cache-tag");
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+const X_ANIMATION_ENCODE: HeaderName = HeaderName::from_static("x-animation-encode");
+const X_ENCODER_PROFILE: HeaderName = HeaderName::from_static("x-encoder-profile");
+const X_CACHE_AGE: HeaderName = HeaderName::from_static("x-cache-age");
+
+pub struct ImageResponse<'a> {
+    bytes: Arc<Vec<u8>>,
+    content_type: ContentType,
+    cache_control: &'a str,
+    tags: &'a [String],
+    source: Option<String>,
+    x_cache: &'static str,
+    warning: Option<&'static str>,
+    server_timing: Option<String>,
+    animation_encode: Option<String>,
+    encoder_profile: Option<&'a str>,
+    etag: Option<Arc<str>>,
+    age: Option<Duration>,
+    /// 바이트 없이 검증자만 있는 응답의 본문 길이 ([`ImageResponse::validated`])
+    length: Option<usize>,
+}
+
+impl<'a> ImageResponse<'a> {
+    pub fn new(bytes: Arc<Vec<u8>>, content_type: ContentType) -> Self {
+        Self {
+            bytes,
+            content_type,
+            cache_control: "no-cache",
+            tags: &[],
+            source: None,
+            x_cache: "MISS",
+            warning: None,
+            server_timing: None,
+            animation_encode: None,
+            encoder_profile: None,
+            etag: None,
+            age: None,
+            length: None,
+        }
+    }
+
+    pub fn cache_control(mut self, value: &'a str) -> Self {
+        self.cache_control = value;
+        self
+    }
+
+    /// `Surrogate-Key`(Fastly, 공백 구분) / `Cache-Tag`(Cloudflare, 쉼표 구분) 태그
+    pub fn tags(mut self, tags: &'a [String]) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn source(mut self, url: String) -> Self {
+        self.source = Some(url);
+        self
+    }
+
+    /// `x-cache` 값 (`HIT` / `MISS` / `STALE`)
+    pub fn x_cache(mut self, value: &'static str) -> Self {
+        self.x_cache = value;
+        self
+    }
+
+    pub fn warning(mut self, value: &'static str) -> Self {
+        self.warning = Some(value);
+        self
+    }
+
+    pub fn server_timing(mut self, timing: &ServerTiming) -> Self {
+        self.server_timing = timing.header_value();
+        self
+    }
+
+    /// 애니메이션 크기 예산으로 고른 인코딩 수준 (새로 인코딩한 응답에만)
+    pub fn animation_encode(mut self, params: Option<String>) -> Self {
+        self.animation_encode = params;
+        self
+    }
+
+    /// 인코더 실험 중 이 응답을 만든 프로필 (새로 인코딩한 응답에만)
+    pub fn encoder_profile(mut self, profile: Option<&'a str>) -> Self {
+        self.encoder_profile = profile;
+        self
+    }
+
+    /// 미리 계산한 ETag (없으면 본문으로 계산)
+    pub fn etag(mut self, etag: Arc<str>) -> Self {
+        self.etag = Some(etag);
+        self
+    }
+
+    /// 캐시에 들어온 뒤 지난 시간. `Age`(초)와 디버깅용 `x-cache-age`(밀리초까지)로 싣는다 (새로 만든 응답에는 없음)
+    pub fn age(mut self, age: Duration) -> Self {
+        self.age = Some(age);
+        self
+    }
+
+    /// 바이트가 캐시에서 밀려나 검증자만 남은 항목의 응답. 본문이 없으므로
+    /// [`RequestInputs::is_head`]나 [`RequestInputs::none_match`]일 때만 쓴다.
+    pub fn validated(etag: Arc<str>, content_type: ContentType, length: usize) -> Self {
+        Self { length: Some(length), ..Self::new(Arc::default(), content_type).etag(etag) }
+    }
+
+    /// 요청의 `If-None-Match`가 현재 ETag와 맞으면 304, 아니면 200으로 응답
+    pub fn respond(self, req: &RequestInputs) -> Response {
+        let etag = self.etag.clone().unwrap_or_else(|| make_etag(&self.bytes).into());
+        let mut headers = HeaderMap::new();
+        insert(&mut headers, header::ETAG, &etag);
+        if let Some(age) = self.age {
+            insert(&mut headers, header::AGE, &age.as_secs().to_string());
+            insert(&mut headers, X_CACHE_AGE, &format!("{:.3}", age.as_secs_f64()));
+        }
+        insert(&mut headers, header::CACHE_CONTROL, self.cache_control);
+        if let Some(vary) = req.vary() {
+            insert(&mut headers, header::VARY, &vary);
+        }
+        if !self.tags.is_empty() {
+            insert(&mut headers, SURROGATE_KEY, &self.tags.join(" "));
+            insert(&mut headers, CACHE_TAG, &self.tags.join(","));
+        }
+        insert(&mut headers, X_CACHE, self.x_cache);
+        if let Some(warning) = self.warning {
+            insert(&mut headers, header::WARNING, warning);
+        }
+        if let Some(timing) = &self.server_timing {
+            insert(&mut headers, SERVER_TIMING, timing);
+        }
+        if let Some(params) = &self.animation_encode {
+            insert(&mut headers, X_ANIMATION_ENCODE, params);
+        }
+        if let Some(profile) = self.encoder_profile {
+            insert(&mut headers, X_ENCODER_PROFILE, profile);
+        }
+
+        if none_match(&req.headers, &etag) {
+            return (StatusCode::NOT_MODIFIED, headers).into_response();
+        }
+
+        insert(&mut headers, header::CONTENT_TYPE, self.content_type.as_str());
+        insert(&mut headers, header::CONTENT_LENGTH, &self.length.unwrap_or(self.bytes.len()).to_string());
+        insert(&mut headers, X_SOURCE_URL, self.source.as_deref().unwrap_or("-"));
+        let body = Arc::try_unwrap(self.bytes).unwrap_or_else(|b| b.as_ref().clone());
+        (headers, body).into_response()
+    }
+}
+
+/// 응답 내용을 바꿀 수 있는 요청 헤더 (콘텐츠 협상 입력)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VaryInput {
+    Accept,
+    Dpr,
+    SaveData,
+    Origin,
+    /// 테넌트 키 (`Authorization`과 `X-Api-Key` 둘 다)
+    ApiKey,
+}
+
+impl VaryInput {
+    const ALL: [VaryInput; 5] = [VaryInput::Accept, VaryInput::Dpr, VaryInput::SaveData, VaryInput::Origin, VaryInput::ApiKey];
+
+    fn header(self) -> HeaderName {
+        match self {
+            VaryInput::Accept => header::ACCEPT,
+            VaryInput::Dpr => HeaderName::from_static("dpr"),
+            VaryInput::SaveData => HeaderName::from_static("save-data"),
+            VaryInput::Origin => header::ORIGIN,
+            VaryInput::ApiKey => header::AUTHORIZATION,
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    fn vary_name(self) -> &'static str {
+        match self {
+            VaryInput::Accept => "Accept",
+            VaryInput::Dpr => "DPR",
+            VaryInput::SaveData => "Save-Data",
+            VaryInput::Origin => "Origin",
+            VaryInput::ApiKey => "Authorization, X-Api-Key",
+        }
+    }
+}
+
+/// 요청 헤더를 감싸서 응답을 고르는 데 어떤 협상 입력을 읽었는지 기록한다.
+/// 헤더가 없어도 읽었다면 기록한다 (없다는 사실도 응답을 정한 것이므로).
+/// 캐시 키를 정하기 전에 읽어야 HIT/MISS/STALE/304 응답이 같은 `Vary`를 싣는다.
+#[derive(Default)]
+pub struct RequestInputs {
+    headers: HeaderMap,
+    used: AtomicU8,
+    head: bool,
+    auth: Auth,
+    tenant: Option<Tenant>,
+    effects: Effects,
+}
+
+impl RequestInputs {
+    pub fn new(headers: HeaderMap) -> Self {
+        Self { headers, used: AtomicU8::new(0), head: false, auth: Auth::Anonymous, tenant: None, effects: Effects::NONE }
+    }
+
+    /// 주소로 고른 모양 옵션을 싣는다. 헤더가 아니라 주소에서 오므로 `Vary`와는 상관없다.
+    pub fn with_effects(mut self, effects: Effects) -> Self {
+        self.effects = effects;
+        self
+    }
+
+    pub fn effects(&self) -> Effects {
+        self.effects
+    }
+
+    /// 키가 맞은 테넌트. 테넌트 설정이 응답을 바꿀 수 있을 때 부르므로 `Vary`에 키 헤더를 싣는다.
+    pub fn tenant(&self) -> Option<&Tenant> {
+        self.used.fetch_or(VaryInput::ApiKey.bit(), Ordering::Relaxed);
+        self.tenant.as_ref()
+    }
+
+    /// `API_KEYS`의 키로 인증된 요청인지 (응답 내용은 같고 캐시 수명·접근 범위만 다르다)
+    pub fn is_authenticated(&self) -> bool {
+        self.auth.is_authenticated()
+    }
+
+    /// 본문 없이 헤더만 원하는 `HEAD` 요청인지
+    pub fn is_head(&self) -> bool {
+        self.head
+    }
+
+    /// 접근 로그에 남는 요청 ID (`x-request-id`)
+    pub fn request_id(&self) -> Option<&str> {
+        self.headers.get("x-request-id").and_then(|v| v.to_str().ok())
+    }
+
+    /// `If-None-Match`가 `etag`와 맞는지 (맞으면 304로 답할 수 있다)
+    pub fn none_match(&self, etag: &str) -> bool {
+        none_match(&self.headers, etag)
+    }
+
+    pub fn get(&self, input: VaryInput) -> Option<&str> {
+        self.used.fetch_or(input.bit(), Ordering::Relaxed);
+        self.headers.get(input.header()).and_then(|v| v.to_str().ok())
+    }
+
+    /// 지금까지 읽은 입력으로 만든 `Vary` 값 (읽은 게 없으면 `None`)
+    pub fn vary(&self) -> Option<String> {
+        let used = self.used.load(Ordering::Relaxed);
+        let names: Vec<_> = VaryInput::ALL
+            .into_iter()
+            .filter(|i| used & i.bit() != 0)
+            .map(VaryInput::vary_name)
+            .collect();
+        (!names.is_empty()).then(|| names.join(", "))
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestInputs {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            head: parts.method == Method::HEAD,
+            auth: parts.extensions.get::<Auth>().copied().unwrap_or_default(),
+            tenant: parts.extensions.get::<Tenant>().cloned(),
+            ..Self::new(parts.headers.clone())
+        })
+    }
+}
+
+/// 단계별 소요 시간을 모아 `Server-Timing: cache;dur=0.1, fetch;dur=35.2, ...`로 내보낸다
+#[derive(Default)]
+pub struct ServerTiming {
+    entries: Vec<(&'static str, Duration)>,
+}
+
+impl ServerTiming {
+    pub fn push(&mut self, name: &'static str, dur: Duration) {
+        self.entries.push((name, dur));
+    }
+
+    pub fn stages(&mut self, t: &StageTimings) {
+        self.push("decode", t.decode);
+        if let Some(resize) = t.resize {
+            self.push("resize", resize);
+        }
+        self.push("encode", t.encode);
+    }
+
+    fn header_value(&self) -> Option<String> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let mut out = String::new();
+        for (i, (name, dur)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            let _ = write!(out, "{name};dur={:.1}", dur.as_secs_f64() * 1000.0);
+        }
+        Some(out)
+    }
+}
+
+fn insert(headers: &mut HeaderMap, name: HeaderName, value: &str) {
+    if let Ok(v) = HeaderValue::from_str(value) {
+        headers.insert(name, v);
+    }
+}
+
+pub fn make_etag(bytes: &[u8]) -> String {
+    let hash = Sha1::digest(bytes);
+    format!("W/\"{:x}\"", hash)
+}
+
+/// `If-None-Match`의 태그 중 하나가 `etag`와 약한 비교로 같은지. 엣지가 압축하면서
+/// `W/`를 붙이거나 떼도 같은 표현으로 본다.
+fn none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|s| s.split(','))
+        .any(|t| t.trim() == "*" || opaque(t) == opaque(etag))
+}
@@ -0,0 +1,219 @@
+//! S3 호환 오브젝트 저장소 2차 캐시 계층 (`CACHE_S3_BUCKET`).
+//!
+//! AWS S3, Cloudflare R2, MinIO처럼 경로 방식 주소(`<endpoint>/<bucket>/<key>`)를 받는 저장소에 SigV4로 서명해
+//! GET/PUT/DELETE만 쓴다. 오브젝트 이름은 디스크 계층처럼 키의 SHA-1(`<prefix>ab/abcdef….bin`)이고, 저장 시각을
+//! `x-amz-meta-stored`(유닉스 초)에 적어 TTL이 지난 항목은 읽을 때 지운다. 다시 읽히지 않는 항목은 남으므로
+//! 물리 삭제는 버킷 수명 주기 규칙에 맡긴다. 임대는 잡지 않는다 (인스턴스마다 따로 만든다).
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use reqwest::{Client, Method, StatusCode};
+use ring::{digest, hmac};
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    cache::{Tier, TierEntry},
+    config::{self, redact},
+};
+
+/// 저장 시각을 적는 사용자 메타데이터 헤더
+const STORED_HEADER: &str = "x-amz-meta-stored";
+
+#[derive(Clone, Debug, Serialize)]
+pub struct S3Config {
+    /// 저장소 주소 (`CACHE_S3_ENDPOINT`, 기본값: `https://s3.<region>.amazonaws.com`)
+    pub endpoint: String,
+    /// 버킷 (`CACHE_S3_BUCKET`)
+    pub bucket: String,
+    /// 서명 리전 (`CACHE_S3_REGION` 또는 `AWS_REGION`, 기본값: `us-east-1`, R2는 `auto`)
+    pub region: String,
+    /// 오브젝트 이름 접두사 (`CACHE_S3_PREFIX`, 기본값: `emoji-cache/`)
+    pub prefix: String,
+    /// `CACHE_S3_ACCESS_KEY_ID` 또는 `AWS_ACCESS_KEY_ID`
+    pub access_key_id: String,
+    /// `CACHE_S3_SECRET_ACCESS_KEY` 또는 `AWS_SECRET_ACCESS_KEY`
+    #[serde(serialize_with = "redact::secret")]
+    pub secret_access_key: String,
+    /// 항목 수명 (`CACHE_S3_TTL_SECS`, 기본값: 7일)
+    #[serde(serialize_with = "config::secs")]
+    pub ttl: Duration,
+}
+
+impl S3Config {
+    /// `CACHE_S3_BUCKET`이 없으면 `None`. 버킷이 있으면 자격 증명은 필수.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let var = |names: &[&str]| names.iter().find_map(|name| std::env::var(name).ok().filter(|v| !v.trim().is_empty()));
+        let Some(bucket) = var(&["CACHE_S3_BUCKET"]) else {
+            return Ok(None);
+        };
+        let region = var(&["CACHE_S3_REGION", "AWS_REGION"]).unwrap_or_else(|| "us-east-1".into());
+        let endpoint = var(&["CACHE_S3_ENDPOINT"]).unwrap_or_else(|| format!("https://s3.{region}.amazonaws.com"));
+        let prefix = var(&["CACHE_S3_PREFIX"]).unwrap_or_else(|| "emoji-cache/".into());
+        Ok(Some(Self {
+            endpoint: endpoint.trim().trim_end_matches('/').into(),
+            bucket: bucket.trim().into(),
+            region: region.trim().into(),
+            prefix: prefix.trim().trim_start_matches('/').into(),
+            access_key_id: var(&["CACHE_S3_ACCESS_KEY_ID", "AWS_ACCESS_KEY_ID"])
+                .context("CACHE_S3_ACCESS_KEY_ID is required with CACHE_S3_BUCKET")?,
+            secret_access_key: var(&["CACHE_S3_SECRET_ACCESS_KEY", "AWS_SECRET_ACCESS_KEY"])
+                .context("CACHE_S3_SECRET_ACCESS_KEY is required with CACHE_S3_BUCKET")?,
+            ttl: Duration::from_secs(config::env_or("CACHE_S3_TTL_SECS", 7 * 24 * 3600)?),
+        }))
+    }
+}
+
+/// SigV4 `Authorization` 헤더 값. `headers`는 서명할 헤더 전부(이름은 소문자)이고
+/// `x-amz-date`와 `x-amz-content-sha256`을 담고 있어야 한다. 쿼리 문자열은 쓰지 않는다.
+pub fn authorization(config: &S3Config, method: &str, path: &str, headers: &BTreeMap<String, String>) -> String {
+    let header = |name: &str| headers.get(name).map(String::as_str).unwrap_or_default();
+    let amz_date = header("x-amz-date");
+    let scope = format!("{}/{}/s3/aws4_request", &amz_date[..amz_date.len().min(8)], config.region);
+    let signed_headers = headers.keys().map(String::as_str).collect::<Vec<_>>().join(";");
+    let canonical_headers: String = headers.iter().map(|(name, value)| format!("{name}:{}\n", value.trim())).collect();
+    let canonical = format!(
+        "{method}\n{}\n\n{canonical_headers}\n{signed_headers}\n{}",
+        uri_encode(path),
+        header("x-amz-content-sha256")
+    );
+    let string_to_sign = format!("AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}", sha256_hex(canonical.as_bytes()));
+
+    let mut key = format!("AWS4{}", config.secret_access_key).into_bytes();
+    for part in [&amz_date[..amz_date.len().min(8)], &config.region, "s3", "aws4_request"] {
+        key = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), part.as_bytes()).as_ref().to_vec();
+    }
+    let signature = hex(hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), string_to_sign.as_bytes()).as_ref());
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        config.access_key_id
+    )
+}
+
+/// 경로의 각 조각을 RFC 3986 비예약 문자만 남기고 퍼센트 인코딩한다 (`/`는 그대로)
+fn uri_encode(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => out.push(byte as char),
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex(digest::digest(&digest::SHA256, bytes).as_ref())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// 오브젝트 저장소 계층. 업스트림용 클라이언트는 HTTP/2 prior knowledge라서 별도 클라이언트를 쓴다.
+pub struct S3Tier {
+    config: S3Config,
+    http: Client,
+    /// 서명에 넣는 `host` (엔드포인트의 호스트와 포트)
+    host: String,
+}
+
+impl S3Tier {
+    pub fn new(config: S3Config) -> anyhow::Result<Self> {
+        let host = config
+            .endpoint
+            .split_once("://")
+            .map_or(config.endpoint.as_str(), |(_, rest)| rest)
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        if host.is_empty() {
+            bail!("invalid CACHE_S3_ENDPOINT {:?}", config.endpoint);
+        }
+        let http = Client::builder().timeout(Duration::from_secs(10)).build()?;
+        Ok(Self { config, http, host })
+    }
+
+    /// 버킷 안 경로 (`/<bucket>/<prefix>ab/abcdef….bin`)
+    fn path(&self, key: &str) -> String {
+        let hex = format!("{:x}", Sha1::digest(key.as_bytes()));
+        format!("/{}/{}{}/{hex}.bin", self.config.bucket, self.config.prefix, &hex[..2])
+    }
+
+    /// 서명한 요청. `extra`는 함께 서명할 헤더.
+    async fn send(&self, method: Method, key: &str, body: Vec<u8>, extra: &[(&str, String)]) -> anyhow::Result<reqwest::Response> {
+        let path = self.path(key);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let mut headers = BTreeMap::from([
+            ("host".to_string(), self.host.clone()),
+            ("x-amz-content-sha256".to_string(), sha256_hex(&body)),
+            ("x-amz-date".to_string(), format!("{}Z", crate::logging::format_utc(now))),
+        ]);
+        headers.extend(extra.iter().map(|(name, value)| (name.to_string(), value.clone())));
+        let authorization = authorization(&self.config, method.as_str(), &path, &headers);
+
+        let mut request = self.http.request(method, format!("{}{}", self.config.endpoint, uri_encode(&path)));
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        request
+            .header(reqwest::header::AUTHORIZATION, authorization)
+            .body(body)
+            .send()
+            .await
+            .context("s3 request failed")
+    }
+}
+
+#[async_trait]
+impl Tier for S3Tier {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<TierEntry>> {
+        let res = self.send(Method::GET, key, Vec::new(), &[]).await?;
+        match res.status() {
+            StatusCode::NOT_FOUND => return Ok(None),
+            status if !status.is_success() => bail!("s3 GET returned {status}"),
+            _ => {}
+        }
+        let stored = res
+            .headers()
+            .get(STORED_HEADER)
+            .and_then(|v| v.to_str().ok()?.parse().ok())
+            .map_or_else(SystemTime::now, |secs| UNIX_EPOCH + Duration::from_secs(secs));
+        if SystemTime::now().duration_since(stored).unwrap_or_default() > self.config.ttl {
+            let _ = self.remove(key).await;
+            return Ok(None);
+        }
+        let bytes = res.bytes().await.context("s3 body read failed")?.to_vec();
+        Ok(Some(TierEntry { bytes, stored }))
+    }
+
+    async fn put(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        let stored = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let extra = [
+            ("content-type", "application/octet-stream".to_string()),
+            (STORED_HEADER, stored.to_string()),
+        ];
+        let res = self.send(Method::PUT, key, bytes.to_vec(), &extra).await?;
+        if !res.status().is_success() {
+            bail!("s3 PUT returned {}", res.status());
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        let res = self.send(Method::DELETE, key, Vec::new(), &[]).await?;
+        if !res.status().is_success() && res.status() != StatusCode::NOT_FOUND {
+            bail!("s3 DELETE returned {}", res.status());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+//! 이모트 검색 (`GET /search?q=`).
+//!
+//! 커스텀 이모트 이름과, `DISCORD_BOT_TOKEN`이 있으면 `DISCORD_GUILD_IDS` 길드의 이모지
+//! 이름에서 찾아 이 서버의 주소로 돌려준다. 정확히 같은 이름, 접두어, 부분 문자열, 글자 순서만
+//! 맞는 이름, 오타 한 글자 순으로 앞에 둔다. 길드 이모지 목록은 `GUILD_EMOJI_LIST_TTL_SECS`
+//! 동안 재사용한다.
+
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use moka::future::Cache;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::{config::Config, custom, AppState};
+
+/// `?limit`이 없을 때 돌려줄 결과 수
+const DEFAULT_LIMIT: usize = 25;
+const MAX_LIMIT: usize = 100;
+/// 이보다 긴 검색어는 이름 규칙상 맞을 수 없다
+const MAX_QUERY_LEN: usize = 64;
+
+pub struct Guilds {
+    /// Discord API는 HTTP/2 prior knowledge를 보장하지 않으므로 별도 클라이언트
+    http: Client,
+    api_base: String,
+    token: String,
+    guild_ids: Vec<String>,
+    /// 길드별 이모지 목록
+    lists: Cache<String, Arc<Vec<GuildEmoji>>>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct GuildEmoji {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub animated: bool,
+    /// 부스트가 끊겨 쓸 수 없게 된 이모지는 `false`
+    #[serde(default)]
+    pub available: Option<bool>,
+}
+
+impl Guilds {
+    /// 봇 토큰이 없으면 `None`. 길드 목록이 비어 있으면 검색에는 쓰지 않고 보관(`/admin/archive`)에만 쓴다.
+    pub fn new(config: &Config) -> anyhow::Result<Option<Self>> {
+        let Some(token) = &config.discord_bot_token else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            http: Client::builder()
+                .user_agent(crate::USER_AGENT.as_str())
+                .timeout(config.upstream_timeout)
+                .build()?,
+            api_base: config.discord_api_base.clone(),
+            token: token.clone(),
+            guild_ids: config.discord_guild_ids.clone(),
+            lists: Cache::builder().max_capacity(256).time_to_live(config.guild_emoji_list_ttl).build(),
+        }))
+    }
+
+    async fn fetch_list(&self, guild: &str) -> anyhow::Result<Arc<Vec<GuildEmoji>>> {
+        let list: Vec<GuildEmoji> = self
+            .http
+            .get(format!("{}/guilds/{guild}/emojis", self.api_base))
+            .header("Authorization", format!("Bot {}", self.token))
+            .send()
+            .await
+            .context("guild emojis request")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("guild emojis response")?;
+        Ok(Arc::new(list))
+    }
+
+    /// 길드 하나의 목록 (`DISCORD_GUILD_IDS`에 없는 길드도 봇이 들어가 있으면 된다)
+    pub async fn list(&self, guild: &str) -> anyhow::Result<Arc<Vec<GuildEmoji>>> {
+        self.lists.try_get_with(guild.to_string(), self.fetch_list(guild)).await.map_err(|e| anyhow::anyhow!("{e:#}"))
+    }
+
+    /// 검색할 길드별 목록. 불러오지 못한 길드는 로그만 남기고 건너뛴다.
+    async fn lists(&self) -> Vec<(String, Arc<Vec<GuildEmoji>>)> {
+        let mut out = Vec::new();
+        for guild in &self.guild_ids {
+            match self.list(guild).await {
+                Ok(list) => out.push((guild.clone(), list)),
+                Err(e) => error!("Guild emoji list failed for {}: {:#}", guild, e),
+            }
+        }
+        out
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchParams {
+    q: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Hit {
+    name: String,
+    source: &'static str,
+    url: String,
+    animated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guild: Option<String>,
+    #[serde(skip)]
+    score: u8,
+}
+
+pub async fn handler(State(state): State<AppState>, Query(params): Query<SearchParams>) -> Response {
+    let query = params.q.as_deref().unwrap_or_default().trim().to_ascii_lowercase();
+    if query.is_empty() {
+        return (StatusCode::BAD_REQUEST, "q is required").into_response();
+    }
+    if query.len() > MAX_QUERY_LEN {
+        return (StatusCode::BAD_REQUEST, "q is too long").into_response();
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let mut hits = Vec::new();
+    if let Some(store) = state.custom.as_ref().filter(|_| !state.blocklist.blocks_source("custom")) {
+        // 색인을 읽지 못하면 커스텀 이모트 없이 나머지 결과만
+        let listed = store.list(None, None).await.unwrap_or_else(|e| {
+            error!("Custom emote search skipped: {:#}", e);
+            Vec::new()
+        });
+        for (key, entry) in listed.into_iter().filter(|(_, e)| !e.private) {
+            let (guild, name) = custom::split_key(&key);
+            if let Some(score) = score(&query, name) {
+                hits.push(Hit {
+                    name: name.to_string(),
+                    source: "custom",
+                    url: format!("/custom/{key}.webp"),
+                    animated: entry.animated,
+                    guild: guild.map(String::from),
+                    score,
+                });
+            }
+        }
+    }
+    if let Some(guilds) = state.guilds.as_ref().filter(|_| !state.blocklist.blocks_source("discord")) {
+        for (guild, list) in guilds.lists().await {
+            for emoji in list.iter() {
+                if emoji.available == Some(false) || state.blocklist.blocks_id(&emoji.id) {
+                    continue;
+                }
+                if let Some(score) = score(&query, &emoji.name) {
+                    hits.push(Hit {
+                        name: emoji.name.clone(),
+                        source: "discord",
+                        url: format!("/e/{}.webp", emoji.id),
+                        animated: emoji.animated,
+                        guild: Some(guild.clone()),
+                        score,
+                    });
+                }
+            }
+        }
+    }
+    // 점수가 같으면 짧은 이름(더 많이 일치한 이름)부터
+    hits.sort_by(|a, b| (a.score, a.name.len(), &a.name).cmp(&(b.score, b.name.len(), &b.name)));
+    hits.truncate(limit);
+    Json(serde_json::json!({ "query": query, "results": hits })).into_response()
+}
+
+/// 낮을수록 잘 맞는다. 맞지 않으면 `None`. `query`는 소문자.
+fn score(query: &str, name: &str) -> Option<u8> {
+    let name = name.to_ascii_lowercase();
+    if name == query {
+        Some(0)
+    } else if name.starts_with(query) {
+        Some(1)
+    } else if name.contains(query) {
+        Some(2)
+    } else if is_subsequence(query, &name) {
+        Some(3)
+    } else if query.len() >= 3 && within_one_edit(query, &name) {
+        Some(4)
+    } else {
+        None
+    }
+}
+
+/// `prt` ⊂ `party`처럼 글자 순서만 맞는지
+fn is_subsequence(query: &str, name: &str) -> bool {
+    let mut rest = name.chars();
+    query.chars().all(|c| rest.any(|n| n == c))
+}
+
+/// 이름 전체나 검색어 길이만큼의 앞부분과 편집 거리가 1 이하인지 (`pary` → `party_parrot`)
+fn within_one_edit(query: &str, name: &str) -> bool {
+    let prefix: String = name.chars().take(query.chars().count()).collect();
+    edit_distance(query, &prefix) <= 1 || edit_distance(query, name) <= 1
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
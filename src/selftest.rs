@@ -0,0 +1,79 @@
+//! 부팅 시 이미징 파이프라인 자체 점검.
+//!
+//! 내장 샘플 이미지를 디코드 → 리사이즈 → 인코드까지 돌려 보고, 실패하면
+//! `/readyz`가 준비 완료를 보고하지 않도록 한다.
+
+use crate::effects::Effects;
+use crate::imaging::{self, AnimFrame, WebpOptions, TARGET_SIZE};
+use anyhow::{bail, ensure, Context};
+use image::{codecs::webp::WebPDecoder, AnimationDecoder, DynamicImage, GenericImageView, Rgba, RgbaImage};
+use rayon::ThreadPool;
+use std::io::Cursor;
+
+/// 100x100 반투명 그라디언트 샘플 (Discord 이모지 기본 크기)
+fn sample_frame(phase: u8) -> DynamicImage {
+    let img = RgbaImage::from_fn(100, 100, |x, y| {
+        Rgba([(x * 2) as u8, (y * 2) as u8, phase, ((x + y) as u8) | 0x80])
+    });
+    DynamicImage::ImageRgba8(img)
+}
+
+fn check_static(webp: &WebpOptions) -> anyhow::Result<()> {
+    let sample = imaging::encode_webp(&sample_frame(0)).context("encoding static sample")?;
+    ensure!(!imaging::is_animated_webp(&sample), "static sample detected as animated");
+
+    let out = imaging::resize_static(&sample, TARGET_SIZE, webp, &Effects::NONE, &imaging::Cancel::default()).context("static pipeline")?;
+    ensure!(
+        out.resized == (TARGET_SIZE, TARGET_SIZE),
+        "unexpected resize result {:?}",
+        out.resized
+    );
+
+    let decoded = image::load_from_memory(&out.bytes).context("decoding static output")?;
+    ensure!(
+        decoded.dimensions() == out.resized,
+        "output dimensions mismatch: {:?}",
+        decoded.dimensions()
+    );
+    Ok(())
+}
+
+fn check_animated(pool: &ThreadPool) -> anyhow::Result<()> {
+    let frames = [0u8, 255]
+        .iter()
+        .map(|&phase| {
+            Ok(AnimFrame {
+                webp: imaging::encode_webp(&sample_frame(phase))?,
+                delay_ms: 100,
+            })
+        })
+        .collect::<Result<Vec<_>, imaging::ImagingError>>()
+        .context("encoding animated sample frames")?;
+    let Some(sample) = imaging::mux_animated_webp(100, 100, 0, &frames) else {
+        bail!("muxing animated sample failed");
+    };
+    ensure!(imaging::is_animated_webp(&sample), "animated sample not detected as animated");
+
+    let out = imaging::resize_animated_webp(&sample, TARGET_SIZE, &Effects::NONE, pool, &imaging::Cancel::default(), None).context("animated pipeline")?;
+    ensure!(
+        out.resized == (TARGET_SIZE, TARGET_SIZE),
+        "unexpected resize result {:?}",
+        out.resized
+    );
+
+    let decoder = WebPDecoder::new(Cursor::new(&out.bytes)).context("opening animated output")?;
+    ensure!(decoder.has_animation(), "animated output lost its animation");
+    let decoded = decoder
+        .into_frames()
+        .collect_frames()
+        .context("decoding animated output")?;
+    ensure!(decoded.len() == frames.len(), "decoded {} frames", decoded.len());
+    Ok(())
+}
+
+/// 정적/애니메이션 샘플을 모두 점검한다. CPU 작업이므로 blocking 스레드에서 호출할 것.
+pub fn run(pool: &ThreadPool, webp: &WebpOptions) -> anyhow::Result<()> {
+    check_static(webp).context("static self-test")?;
+    check_animated(pool).context("animated self-test")?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! 요청 섀도잉 (`SHADOW_URL`).
+//!
+//! 이미지 요청 중 `SHADOW_PERCENT`만큼을 같은 경로·쿼리로 스테이징 인스턴스에도 보내서, 새 인코더 버전을
+//! 실제 트래픽 모양으로 시험할 수 있게 한다. 섀도 요청은 따로 띄워 두고 응답은 읽어 버리기만 하므로 본 요청의
+//! 지연이나 결과에는 영향이 없다. 동시에 떠 있는 섀도 요청은 `SHADOW_CONCURRENCY`개까지이고, 넘치면 그 요청은
+//! 보내지 않는다 (스테이징이 느려져도 이쪽에 작업이 쌓이지 않도록). 콘텐츠 협상과 조건부 요청 헤더만 옮기고
+//! 인증 헤더나 쿠키는 옮기지 않으며, 스테이징이 구분할 수 있도록 `X-Shadow-Request: 1`을 붙인다.
+
+use axum::http::{header, HeaderMap, HeaderName, Method, Uri};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::Semaphore;
+use tracing::debug;
+
+use crate::metrics::SHADOW_TOTAL;
+
+/// 섀도 요청에 붙이는 표시. 이 헤더가 있는 요청은 다시 섀도잉하지 않는다.
+pub const SHADOW_HEADER: &str = "x-shadow-request";
+
+/// 스테이징으로 옮기는 요청 헤더
+const FORWARDED: [HeaderName; 7] = [
+    header::ACCEPT,
+    header::USER_AGENT,
+    header::IF_NONE_MATCH,
+    HeaderName::from_static("dpr"),
+    HeaderName::from_static("save-data"),
+    header::ORIGIN,
+    HeaderName::from_static("x-request-id"),
+];
+
+pub struct Shadow {
+    client: reqwest::Client,
+    /// 스테이징 인스턴스 주소 (끝의 `/` 없이)
+    base: String,
+    percent: f64,
+    slots: Arc<Semaphore>,
+}
+
+impl Shadow {
+    pub fn new(base: &str, percent: f64, concurrency: usize, timeout: Duration) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder().timeout(timeout).build()?;
+        Ok(Self {
+            client,
+            base: base.trim_end_matches('/').to_string(),
+            percent,
+            slots: Arc::new(Semaphore::new(concurrency.max(1))),
+        })
+    }
+
+    /// 이 요청을 섀도잉할지 고른다 (이미 섀도 요청이면 하지 않는다)
+    pub fn pick(&self, method: &Method, headers: &HeaderMap) -> bool {
+        if !matches!(*method, Method::GET | Method::HEAD) || headers.contains_key(SHADOW_HEADER) {
+            return false;
+        }
+        let roll = (uuid::Uuid::new_v4().as_u128() % 10_000) as f64;
+        roll < self.percent * 100.0
+    }
+
+    /// 같은 요청을 스테이징으로 보내고 기다리지 않는다
+    pub fn send(&self, method: Method, uri: &Uri, headers: &HeaderMap) {
+        let Ok(permit) = self.slots.clone().try_acquire_owned() else {
+            metrics::counter!(SHADOW_TOTAL, "outcome" => "dropped").increment(1);
+            return;
+        };
+        let path = uri.path_and_query().map_or(uri.path(), |p| p.as_str());
+        let mut req = self.client.request(method, format!("{}{path}", self.base)).header(SHADOW_HEADER, "1");
+        for name in &FORWARDED {
+            for value in headers.get_all(name) {
+                req = req.header(name, value);
+            }
+        }
+        let path = path.to_string();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let outcome = match req.send().await {
+                // 본문까지 받아야 스테이징이 응답을 끝까지 만든 시간이 된다
+                Ok(res) => match res.bytes().await {
+                    Ok(_) => "sent",
+                    Err(e) => {
+                        debug!("shadow request body failed for {}: {}", path, e);
+                        "failed"
+                    }
+                },
+                Err(e) => {
+                    debug!("shadow request failed for {}: {}", path, e);
+                    "failed"
+                }
+            };
+            metrics::counter!(SHADOW_TOTAL, "outcome" => outcome).increment(1);
+        });
+    }
+}
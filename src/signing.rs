@@ -0,0 +1,37 @@
+//! 비공개 커스텀 이모트용 만료 서명 (`?exp=<유닉스 초>&sig=<hex>`).
+//!
+//! 서명은 `CUSTOM_SIGNING_KEY`로 만든 HMAC-SHA1이며, 서명하는 내용은 색인 키(`길드/이름`)와 만료 시각이다.
+//! 크기·확장자·버전은 서명에 넣지 않으므로 한 번 받은 주소로 같은 이모트의 다른 크기도 볼 수 있다.
+
+use ring::hmac;
+
+use crate::admin;
+
+/// RFC 2104 HMAC (SHA-1). 예전에 나간 서명과 같은 형식을 유지하려고 SHA-1을 쓴다.
+pub fn hmac_sha1(key: &[u8], message: &[u8]) -> [u8; 20] {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, key);
+    hmac::sign(&key, message).as_ref().try_into().expect("SHA-1 tags are 20 bytes")
+}
+
+/// `key`(색인 키)를 `exp`까지 열어 주는 서명
+pub fn sign(secret: &str, key: &str, exp: u64) -> String {
+    let mac = hmac_sha1(secret.as_bytes(), format!("{key}\n{exp}").as_bytes());
+    mac.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Rejected {
+    Expired,
+    Invalid,
+}
+
+/// `now`(유닉스 초) 기준으로 서명을 확인한다
+pub fn verify(secret: &str, key: &str, exp: u64, sig: &str, now: u64) -> Result<(), Rejected> {
+    if !admin::constant_time_eq(sign(secret, key, exp).as_bytes(), sig.to_ascii_lowercase().as_bytes()) {
+        return Err(Rejected::Invalid);
+    }
+    if exp <= now {
+        return Err(Rejected::Expired);
+    }
+    Ok(())
+}
@@ -0,0 +1,146 @@
+//! Slack 워크스페이스 커스텀 이모지 소스 (`/slack/:workspace/:emoji_name`).
+//!
+//! 워크스페이스별 토큰으로 `emoji.list`를 불러 이름을 이미지 URL로 바꾼 뒤 Discord
+//! 이모지와 같은 경로로 리사이즈/캐시한다. 목록은 `SLACK_EMOJI_LIST_TTL_SECS` 동안
+//! 재사용하므로 새로 추가한 이모지는 그 뒤에 보인다.
+
+use anyhow::{bail, Context};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use moka::future::Cache;
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tracing::{error, warn};
+
+use crate::{
+    middleware, pick_variant, purge, serve_image, upstream::Upstream, AppState, EmojiParams, Fetch, Mode, Origin,
+    RequestInputs,
+};
+
+/// `alias:다른이름`을 따라가는 최대 횟수 (순환 별칭 방지)
+const MAX_ALIAS_HOPS: usize = 4;
+
+pub struct Slack {
+    /// `upstream::Source::Slack` 클라이언트 (Slack API와 이미지 CDN은 HTTP/2 prior knowledge를 보장하지 않음)
+    http: Upstream,
+    api_base: String,
+    /// 워크스페이스 이름 → 봇 토큰 (`SLACK_TOKENS`)
+    tokens: HashMap<String, String>,
+    /// 워크스페이스별 `emoji.list` 결과 (이름 → URL 또는 `alias:이름`)
+    lists: Cache<String, Arc<HashMap<String, String>>>,
+}
+
+#[derive(Deserialize)]
+struct EmojiList {
+    ok: bool,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    emoji: HashMap<String, String>,
+}
+
+impl Slack {
+    /// 토큰이 하나도 없으면 `None` (라우트를 붙이지 않는다)
+    pub fn new(
+        tokens: HashMap<String, String>,
+        api_base: String,
+        list_ttl: Duration,
+        http: Upstream,
+    ) -> anyhow::Result<Option<Self>> {
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            http,
+            api_base,
+            tokens,
+            lists: Cache::builder().max_capacity(64).time_to_live(list_ttl).build(),
+        }))
+    }
+
+    /// 이모지 이름을 이미지 URL로. 모르는 워크스페이스나 이름이면 `None`.
+    async fn resolve(&self, workspace: &str, name: &str) -> anyhow::Result<Option<String>> {
+        let Some(token) = self.tokens.get(workspace) else {
+            return Ok(None);
+        };
+        let list = self
+            .lists
+            .try_get_with(workspace.to_string(), self.fetch_list(token))
+            .await
+            .map_err(|e| anyhow::anyhow!("{e:#}"))?;
+
+        let mut name = name;
+        for _ in 0..=MAX_ALIAS_HOPS {
+            match list.get(name).map(String::as_str) {
+                Some(v) => match v.strip_prefix("alias:") {
+                    Some(target) => name = target,
+                    None => return Ok(Some(v.to_string())),
+                },
+                None => return Ok(None),
+            }
+        }
+        warn!("slack emoji alias chain too long in {}: {}", workspace, name);
+        Ok(None)
+    }
+
+    async fn fetch_list(&self, token: &str) -> anyhow::Result<Arc<HashMap<String, String>>> {
+        let list: EmojiList = self
+            .http
+            .send(|c| c.get(format!("{}/emoji.list", self.api_base)).bearer_auth(token))
+            .await
+            .context("emoji.list request")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("emoji.list response")?;
+        if !list.ok {
+            bail!("emoji.list failed: {}", list.error.as_deref().unwrap_or("unknown error"));
+        }
+        Ok(Arc::new(list.emoji))
+    }
+}
+
+pub async fn handler(
+    State(state): State<AppState>,
+    Path((workspace, name)): Path<(String, String)>,
+    Query(params): Query<EmojiParams>,
+    deadline: Option<Extension<middleware::Deadline>>,
+    req: RequestInputs,
+) -> Response {
+    let Some(slack) = state.slack.clone() else {
+        return (StatusCode::NOT_FOUND, "slack source disabled").into_response();
+    };
+    // `:party_parrot:`이나 `party_parrot.webp`처럼 적어도 된다
+    let name = name.split('.').next().unwrap_or(&name).trim_matches(':').to_string();
+    let variant = match pick_variant(&state.config, &params, &format!("/slack/{workspace}/{name}")) {
+        Ok(v) => v,
+        Err(redirect) => return redirect.into_response(),
+    };
+    if state.blocklist.blocks_source("slack") {
+        warn!("Blocked slack emoji requested: {}/{}", workspace, name);
+        return (StatusCode::GONE, "emoji unavailable").into_response();
+    }
+
+    let url = match slack.resolve(&workspace, &name).await {
+        Ok(Some(url)) => url,
+        Ok(None) => return (StatusCode::NOT_FOUND, "emoji not found").into_response(),
+        Err(e) => {
+            error!("Slack emoji lookup failed for {}/{}: {:#}", workspace, name, e);
+            return (StatusCode::BAD_GATEWAY, "slack lookup failed").into_response();
+        }
+    };
+
+    let label = format!("slack/{workspace}/{name}");
+    let origin = Origin {
+        tags: purge::tags(&label, "slack", variant.size()),
+        key_base: label.clone(),
+        url,
+        label,
+        fetch: Fetch::Http(slack.http.clone()),
+    };
+    serve_image(state, origin, variant, deadline.map(|Extension(d)| d.0), req, Mode::Interactive).await
+}
@@ -0,0 +1,122 @@
+//! 캐시 미스 지연 SLO (`SLO_MISS_MS`, `SLO_MISS_TARGET`, `SLO_WINDOW_SECS`).
+//!
+//! 캐시에 없어 업스트림에서 받아 새로 만든 응답(`x-cache: MISS`)의 소요 시간을 소스별로 최근 `SLO_WINDOW_SECS`
+//! 동안 모아 백분위수(p50/p95/p99)와 SLO 소진율(burn rate)을 낸다. 소진율은 목표 시간을 넘긴 미스 비율을 허용
+//! 오차(`1 - SLO_MISS_TARGET`)로 나눈 값이라, 1이면 오차 예산을 딱 맞춰 쓰는 중이고 그보다 크면 예산을 당겨 쓰는
+//! 중이다. 업스트림이 느려지거나 CPU가 모자라면 히트 지연보다 미스 지연이 먼저 나빠지므로 이른 신호가 된다.
+//! 값은 `/metrics`를 긁을 때와 `GET /admin/slo`에서 계산하고, 웹훅 알림은 [`crate::notify::monitor`]가 본다.
+
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::metrics::{MISS_LATENCY_SECONDS, MISS_SECONDS, SLO_BURN_RATE, SLO_MISSES_TOTAL};
+
+/// 소스마다 기억하는 최대 표본 수 (창 안에 더 많으면 오래된 것부터 버린다)
+const MAX_SAMPLES: usize = 10_000;
+
+/// 소스 하나의 창 안 미스 지연 요약
+#[derive(Clone, Debug, Serialize)]
+pub struct SourceSlo {
+    pub misses: usize,
+    /// 목표 시간을 넘긴 미스 수
+    pub slow: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub burn_rate: f64,
+}
+
+pub struct Slo {
+    target: Duration,
+    /// 목표 시간 안에 끝나야 하는 미스 비율
+    objective: f64,
+    window: Duration,
+    samples: Mutex<BTreeMap<&'static str, VecDeque<(Instant, Duration)>>>,
+}
+
+impl Slo {
+    pub fn new(target: Duration, objective: f64, window: Duration) -> Self {
+        Self { target, objective, window, samples: Mutex::default() }
+    }
+
+    /// `source`의 미스 하나가 `elapsed` 걸렸다
+    pub fn record(&self, source: &'static str, elapsed: Duration) {
+        let met = if elapsed <= self.target { "met" } else { "missed" };
+        ::metrics::counter!(SLO_MISSES_TOTAL, "source" => source, "slo" => met).increment(1);
+        ::metrics::histogram!(MISS_SECONDS, "source" => source).record(elapsed.as_secs_f64());
+        let mut samples = self.samples.lock().unwrap();
+        let window = samples.entry(source).or_default();
+        if window.len() == MAX_SAMPLES {
+            window.pop_front();
+        }
+        window.push_back((Instant::now(), elapsed));
+    }
+
+    /// 창 안의 표본으로 소스별 요약을 낸다 (표본이 없는 소스는 뺀다)
+    pub fn snapshot(&self) -> BTreeMap<&'static str, SourceSlo> {
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap();
+        let mut out = BTreeMap::new();
+        for (&source, window) in samples.iter_mut() {
+            while window.front().is_some_and(|(at, _)| now.duration_since(*at) > self.window) {
+                window.pop_front();
+            }
+            if window.is_empty() {
+                continue;
+            }
+            let mut sorted: Vec<Duration> = window.iter().map(|(_, d)| *d).collect();
+            sorted.sort_unstable();
+            let slow = sorted.iter().filter(|d| **d > self.target).count();
+            let budget = 1.0 - self.objective;
+            let slow_ratio = slow as f64 / sorted.len() as f64;
+            out.insert(
+                source,
+                SourceSlo {
+                    misses: sorted.len(),
+                    slow,
+                    p50_ms: millis(percentile(&sorted, 0.5)),
+                    p95_ms: millis(percentile(&sorted, 0.95)),
+                    p99_ms: millis(percentile(&sorted, 0.99)),
+                    burn_rate: if budget > 0.0 { slow_ratio / budget } else if slow > 0 { f64::INFINITY } else { 0.0 },
+                },
+            );
+        }
+        out
+    }
+
+    /// 요약을 게이지로 내보낸다 (`/metrics`를 긁을 때)
+    pub fn publish(&self) {
+        for (source, slo) in self.snapshot() {
+            for (quantile, ms) in [("0.5", slo.p50_ms), ("0.95", slo.p95_ms), ("0.99", slo.p99_ms)] {
+                ::metrics::gauge!(MISS_LATENCY_SECONDS, "source" => source, "quantile" => quantile).set(ms / 1000.0);
+            }
+            ::metrics::gauge!(SLO_BURN_RATE, "source" => source).set(slo.burn_rate);
+        }
+    }
+
+    pub fn target(&self) -> Duration {
+        self.target
+    }
+
+    pub fn objective(&self) -> f64 {
+        self.objective
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+}
+
+/// 정렬된 표본의 nearest-rank 백분위수
+fn percentile(sorted: &[Duration], q: f64) -> Duration {
+    let rank = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
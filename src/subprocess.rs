@@ -0,0 +1,49 @@
+//! 외부 명령 백엔드(`cwebp`, `vips`, `avifenc`) 공용: 임시 파일과 마감 시각이 있는 실행.
+
+use anyhow::{bail, Context};
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Instant,
+};
+use tokio::process::Command;
+
+/// 처리 중에만 쓰는 임시 파일. 끝나면(실패해도) 지운다.
+pub struct Scratch(PathBuf);
+
+impl Scratch {
+    /// 임시 디렉터리 안의 `name`
+    pub fn new(name: String) -> Self {
+        Self(std::env::temp_dir().join(name))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// `command`를 실행하고 끝나기를 기다린다. `deadline`이 지나면 프로세스를 죽이고 실패로 돌려주며,
+/// 0이 아닌 종료 코드는 stderr를 붙여 실패로 돌려준다.
+pub async fn run_with_deadline(mut command: Command, deadline: Option<Instant>) -> anyhow::Result<()> {
+    let program = command.as_std().get_program().to_string_lossy().into_owned();
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::piped()).kill_on_drop(true);
+    let run = command.output();
+    let output = match deadline {
+        Some(at) => match tokio::time::timeout_at(at.into(), run).await {
+            Ok(output) => output,
+            Err(_) => bail!("{program} exceeded the request deadline"),
+        },
+        None => run.await,
+    }
+    .with_context(|| format!("running {program}"))?;
+    if !output.status.success() {
+        bail!("{program} failed ({}): {}", output.status, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
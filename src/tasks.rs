@@ -0,0 +1,96 @@
+//! 백그라운드 작업 실행기.
+//!
+//! 선제 갱신, 캐시 워밍, CDN purge 전파, 2차 캐시 기록처럼 응답과 무관한 작업을
+//! 제한된 큐와 동시 실행 수 안에서 돌린다. 실패하면 지수 백오프로 재시도하고,
+//! 큐가 가득 차면 새 작업을 버린다 (부하가 몰릴 때 작업이 끝없이 쌓이지 않도록).
+
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+use tokio::sync::{mpsc, Semaphore};
+use tracing::warn;
+
+use crate::metrics::{TASKS_QUEUED, TASKS_RETRIES_TOTAL, TASKS_TOTAL};
+
+type JobFn = Box<dyn Fn() -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>> + Send + Sync>;
+
+struct Job {
+    name: &'static str,
+    retries: u32,
+    run: JobFn,
+}
+
+#[derive(Clone)]
+pub struct Tasks {
+    tx: mpsc::Sender<Job>,
+}
+
+impl Tasks {
+    /// 디스패처를 띄운다. tokio 런타임 안에서 호출할 것.
+    pub fn new(concurrency: usize, capacity: usize) -> Self {
+        let (tx, mut rx) = mpsc::channel::<Job>(capacity.max(1));
+        let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+        let depth = tx.clone();
+        tokio::spawn(async move {
+            while let Some(job) = rx.recv().await {
+                metrics::gauge!(TASKS_QUEUED).set((depth.max_capacity() - depth.capacity()) as f64);
+                let Ok(permit) = permits.clone().acquire_owned().await else {
+                    break;
+                };
+                tokio::spawn(async move {
+                    run(job).await;
+                    drop(permit);
+                });
+            }
+        });
+        Self { tx }
+    }
+
+    /// 작업을 큐에 넣는다. 실패하면 `retries`번까지 다시 시도한다.
+    /// 큐가 가득 찼으면 버리고 `false`를 돌려준다.
+    pub fn submit<F, Fut>(&self, name: &'static str, retries: u32, f: F) -> bool
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let job = Job {
+            name,
+            retries,
+            run: Box::new(move || Box::pin(f())),
+        };
+        match self.tx.try_send(job) {
+            Ok(()) => {
+                metrics::gauge!(TASKS_QUEUED)
+                    .set((self.tx.max_capacity() - self.tx.capacity()) as f64);
+                true
+            }
+            Err(_) => {
+                warn!("task queue full, dropping {name} job");
+                metrics::counter!(TASKS_TOTAL, "job" => name, "outcome" => "dropped").increment(1);
+                false
+            }
+        }
+    }
+}
+
+async fn run(job: Job) {
+    let mut attempt = 0;
+    loop {
+        match (job.run)().await {
+            Ok(()) => {
+                metrics::counter!(TASKS_TOTAL, "job" => job.name, "outcome" => "ok").increment(1);
+                return;
+            }
+            Err(e) if attempt < job.retries => {
+                attempt += 1;
+                metrics::counter!(TASKS_RETRIES_TOTAL, "job" => job.name).increment(1);
+                let backoff = Duration::from_millis(200 << attempt.min(6));
+                warn!("{} job failed (attempt {}), retrying in {:?}: {:#}", job.name, attempt, backoff, e);
+                tokio::time::sleep(backoff).await;
+            }
+            Err(e) => {
+                warn!("{} job failed: {:#}", job.name, e);
+                metrics::counter!(TASKS_TOTAL, "job" => job.name, "outcome" => "failed").increment(1);
+                return;
+            }
+        }
+    }
+}
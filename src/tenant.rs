@@ -0,0 +1,41 @@
+//! 테넌트(API 키를 받은 서비스)별 변환 기본값 (`TENANTS`).
+//!
+//! `TENANTS`에 적은 이름마다 `TENANT_<이름>_KEY`(API 키)로 요청을 알아보고, `?size`가 없을 때의 크기
+//! (`TENANT_<이름>_SIZE`)와 정적 WebP 인코더 설정(`TENANT_<이름>_WEBP_*`)을 그 테넌트 것으로 바꾼다.
+//! 크기는 원래 캐시 키에 들어 있으므로 따로 키를 나누지 않는다. 인코더 설정이 기본값과 다를 때만 리사이즈
+//! 결과의 캐시 키에 설정 꼬리표(`~webp-m6-n80-t0`)를 붙인다. 꼬리표는 테넌트 이름이 아니라 설정값으로
+//! 만들므로 기본값과 같은 테넌트는 공용 항목을, 설정이 같은 테넌트끼리는 서로의 항목을 함께 쓴다.
+//! 테넌트 키도 `API_KEYS`와 같은 인증된 요청으로 본다.
+
+use axum::http::HeaderMap;
+use serde::Serialize;
+
+use crate::{admin, auth, config::redact, imaging::WebpOptions};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Tenant {
+    pub name: String,
+    #[serde(serialize_with = "redact::secret")]
+    pub key: String,
+    /// `?size`가 없을 때의 크기 (`OUTPUT_SIZES` 중 하나)
+    pub size: u32,
+    pub webp: WebpOptions,
+}
+
+impl Tenant {
+    /// 헤더의 키가 맞는 테넌트
+    pub fn find<'a>(tenants: &'a [Tenant], headers: &HeaderMap) -> Option<&'a Tenant> {
+        auth::presented_keys(headers)
+            .find_map(|key| tenants.iter().find(|t| admin::constant_time_eq(t.key.as_bytes(), key.as_bytes())))
+    }
+}
+
+/// 기본값과 다른 인코더 설정으로 만든 결과의 캐시 키
+pub fn cache_key(key: &str, webp: &WebpOptions) -> String {
+    format!("{key}~webp-m{}-n{}-t{}", webp.method, webp.near_lossless, webp.target_size)
+}
+
+/// [`cache_key`]의 꼬리표를 뗀 원래 키
+pub fn base_key(key: &str) -> &str {
+    key.split_once('~').map_or(key, |(base, _)| base)
+}
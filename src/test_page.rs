@@ -0,0 +1,157 @@
+//! 이모지 확인용 HTML 페이지 (`GET /e/:name/test`).
+//!
+//! 설정된 크기(`OUTPUT_SIZES`)마다의 리사이즈 결과와 원본 통과(`?passthrough`), `/original`을 한 화면에 나란히
+//! 그리고 각각의 형식·바이트 수를 적는다. 어떤 크기를 쓸지 고르거나 "64px에서 깨진다"처럼 구체적으로 제보할 때
+//! 쓴다. 표에 적는 값은 페이지를 만들 때 서버가 직접 받아 본 결과이고(캐시에 없으면 낮은 우선순위로 만들어
+//! 캐시에 넣는다), 이미지는 보통 주소로 불러오므로 브라우저는 방금 만든 캐시 항목을 받는다. 리사이즈 결과는
+//! WebP 기준이라, AVIF를 켜 두었으면 브라우저는 협상으로 다른 형식을 받을 수 있다.
+
+use axum::{
+    extract::{Extension, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use tracing::error;
+
+use crate::{
+    canonical_url, error::Error, middleware, request::EmoteRequest, response::RequestInputs, serve_emoji, AppState,
+    Mode, Variant,
+};
+
+/// 페이지가 불러오는 이미지만 허용한다 (이름이 그대로 페이지에 들어가므로)
+const CONTENT_SECURITY_POLICY: &str = "default-src 'none'; img-src 'self'; style-src 'unsafe-inline'";
+
+/// 표의 한 줄
+struct Row {
+    label: String,
+    url: String,
+    outcome: Result<(String, usize), String>,
+}
+
+pub async fn handler(
+    State(state): State<AppState>,
+    emote: EmoteRequest,
+    deadline: Option<Extension<middleware::Deadline>>,
+) -> Response {
+    let emoji_id = emote.stem;
+    if state.blocklist.blocks_id(&emoji_id) {
+        return Error::Blocked.into_response();
+    }
+    let deadline = deadline.map(|Extension(d)| d.0);
+
+    let mut variants: Vec<(String, String, Variant)> = state
+        .config
+        .output_sizes
+        .iter()
+        .map(|&size| {
+            let variant = Variant::Resized(size);
+            (format!("{size}px"), format!("/e/{}", canonical_url(&state.config, &emoji_id, variant)), variant)
+        })
+        .collect();
+    variants.push(("passthrough".into(), format!("/e/{emoji_id}.webp?passthrough=1"), Variant::Passthrough));
+    variants.push(("original".into(), format!("/e/{emoji_id}/original"), Variant::Original));
+
+    let mut rows = Vec::new();
+    let mut first_error = None;
+    for (label, url, variant) in variants {
+        let res = serve_emoji(state.clone(), emoji_id.clone(), variant, deadline, RequestInputs::default(), Mode::Warm).await;
+        let outcome = if res.status() == StatusCode::OK {
+            let content_type = res
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("-")
+                .to_string();
+            match axum::body::to_bytes(res.into_body(), usize::MAX).await {
+                Ok(bytes) => Ok((content_type, bytes.len())),
+                Err(e) => {
+                    error!("Reading response body failed: {}", e);
+                    Err("read failed".to_string())
+                }
+            }
+        } else {
+            let message = res.extensions().get::<Error>().map_or_else(|| res.status().to_string(), Error::to_string);
+            first_error.get_or_insert(res);
+            Err(message)
+        };
+        rows.push(Row { label, url, outcome });
+    }
+    // 하나도 만들지 못했으면 (없는 이모지 등) 페이지 대신 그 에러를 그대로
+    if rows.iter().all(|r| r.outcome.is_err()) {
+        if let Some(res) = first_error {
+            return res;
+        }
+    }
+
+    (
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::CONTENT_SECURITY_POLICY, CONTENT_SECURITY_POLICY),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        ],
+        Html(render(&emoji_id, &rows)),
+    )
+        .into_response()
+}
+
+fn render(emoji_id: &str, rows: &[Row]) -> String {
+    let emoji_id = escape(emoji_id);
+    let mut cells = String::new();
+    for row in rows {
+        let (label, url) = (escape(&row.label), escape(&row.url));
+        let detail = match &row.outcome {
+            Ok((content_type, bytes)) => {
+                format!("<img src=\"{url}\" alt=\"{emoji_id} {label}\"><br>{} · {}", escape(content_type), format_bytes(*bytes))
+            }
+            Err(message) => format!("<span class=\"error\">{}</span>", escape(message)),
+        };
+        cells.push_str(&format!("<figure><figcaption><a href=\"{url}\">{label}</a></figcaption>{detail}</figure>\n"));
+    }
+    format!(
+        "<!doctype html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{emoji_id} test</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+main {{ display: flex; flex-wrap: wrap; align-items: flex-end; gap: 1.5em; }}
+figure {{ margin: 0; text-align: center; font-size: 0.85em; }}
+figcaption {{ margin-bottom: 0.5em; font-weight: bold; }}
+img {{ display: block; margin: 0 auto 0.5em; background: repeating-conic-gradient(#ddd 0 25%, #fff 0 50%) 0 0 / 16px 16px; }}
+.error {{ color: #b00; }}
+</style>
+</head>
+<body>
+<h1>{emoji_id}</h1>
+<main>
+{cells}</main>
+</body>
+</html>
+"
+    )
+}
+
+fn format_bytes(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{bytes} B")
+    } else {
+        format!("{:.1} KiB ({bytes} B)", bytes as f64 / 1024.0)
+    }
+}
+
+/// HTML 본문·속성에 넣을 수 있게 특수 문자를 바꾼다
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
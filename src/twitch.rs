@@ -0,0 +1,311 @@
+//! Twitch 채팅 배지/치어모트 소스.
+//!
+//! - `/twitch/badges/:channel/:set/:version` - 배지 (`subscriber/12`처럼 세트와 버전)
+//! - `/twitch/cheermotes/:channel/:prefix/:bits` - 치어모트. `bits`는 아무 비트 수나 받아서
+//!   그 비트 수가 속하는 티어로 맞춘다 (`cheer/1500` → 1000 티어)
+//!
+//! `channel`은 방송인 ID이고, `global`이면 전역 배지/치어모트를 쓴다. Helix API 앱
+//! 토큰은 client credentials로 받아 두고 만료되거나 401을 받으면 다시 받는다.
+//! 목록은 `TWITCH_LIST_TTL_SECS` 동안 재사용한다.
+
+use anyhow::Context;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use moka::future::Cache;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::Config, middleware, pick_variant, purge, serve_image, upstream::Upstream, AppState, EmojiParams, Fetch, Mode,
+    Origin, RequestInputs,
+};
+
+/// 토큰 만료 이만큼 전에 미리 새로 받는다
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+/// 접두어(소문자) → (최소 비트, 이미지 URL), 최소 비트 오름차순
+type CheerTiers = HashMap<String, Vec<(u64, String)>>;
+
+pub struct Twitch {
+    /// `upstream::Source::Twitch` 클라이언트 (Helix API와 배지 CDN은 HTTP/2 prior knowledge를 보장하지 않음)
+    http: Upstream,
+    api_base: String,
+    auth_base: String,
+    client_id: String,
+    client_secret: String,
+    /// 앱 토큰과 만료 시각
+    token: Mutex<Option<(String, Instant)>>,
+    /// 채널별 `세트/버전` → 이미지 URL
+    badges: Cache<String, Arc<HashMap<String, String>>>,
+    /// 채널별 치어모트 티어
+    cheermotes: Cache<String, Arc<CheerTiers>>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct Helix<T> {
+    data: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct BadgeSet {
+    set_id: String,
+    versions: Vec<BadgeVersion>,
+}
+
+#[derive(Deserialize)]
+struct BadgeVersion {
+    id: String,
+    image_url_4x: String,
+}
+
+#[derive(Deserialize)]
+struct Cheermote {
+    prefix: String,
+    tiers: Vec<CheerTier>,
+}
+
+#[derive(Deserialize)]
+struct CheerTier {
+    min_bits: u64,
+    images: HashMap<String, CheerImages>,
+}
+
+#[derive(Deserialize)]
+struct CheerImages {
+    #[serde(default)]
+    animated: HashMap<String, String>,
+    #[serde(default, rename = "static")]
+    still: HashMap<String, String>,
+}
+
+impl CheerImages {
+    /// 가장 큰 배율의 이미지 (애니메이션 우선)
+    fn largest(&self) -> Option<&String> {
+        largest_scale(&self.animated).or_else(|| largest_scale(&self.still))
+    }
+}
+
+/// 배율(`"1"`, `"1.5"`, `"4"` …) → URL 중 가장 큰 배율
+fn largest_scale(set: &HashMap<String, String>) -> Option<&String> {
+    set.iter()
+        .filter_map(|(scale, url)| Some((scale.parse::<f64>().ok()?, url)))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, url)| url)
+}
+
+impl Twitch {
+    /// 자격 증명이 없으면 `None` (라우트를 붙이지 않는다)
+    pub fn new(config: &Config, http: Upstream) -> anyhow::Result<Option<Self>> {
+        let (Some(client_id), Some(client_secret)) = (&config.twitch_client_id, &config.twitch_client_secret) else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            http,
+            api_base: config.twitch_api_base.clone(),
+            auth_base: config.twitch_auth_base.clone(),
+            client_id: client_id.clone(),
+            client_secret: client_secret.clone(),
+            token: Mutex::new(None),
+            badges: Cache::builder().max_capacity(256).time_to_live(config.twitch_list_ttl).build(),
+            cheermotes: Cache::builder().max_capacity(256).time_to_live(config.twitch_list_ttl).build(),
+        }))
+    }
+
+    async fn token(&self) -> anyhow::Result<String> {
+        let mut slot = self.token.lock().await;
+        if let Some((token, expires)) = slot.as_ref() {
+            if Instant::now() + TOKEN_REFRESH_MARGIN < *expires {
+                return Ok(token.clone());
+            }
+        }
+        let res: TokenResponse = self
+            .http
+            .send(|c| {
+                c.post(format!("{}/token", self.auth_base)).form(&[
+                    ("client_id", self.client_id.as_str()),
+                    ("client_secret", self.client_secret.as_str()),
+                    ("grant_type", "client_credentials"),
+                ])
+            })
+            .await
+            .context("twitch token request")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("twitch token response")?;
+        info!("obtained twitch app token (expires in {}s)", res.expires_in);
+        *slot = Some((res.access_token.clone(), Instant::now() + Duration::from_secs(res.expires_in)));
+        Ok(res.access_token)
+    }
+
+    /// Helix GET. 401이면 토큰을 버리고 한 번 더 시도한다.
+    async fn helix<T: DeserializeOwned>(&self, path: &str, channel: &str) -> anyhow::Result<Vec<T>> {
+        let mut query = Vec::new();
+        if channel != "global" {
+            query.push(("broadcaster_id", channel));
+        }
+        for attempt in 0..2 {
+            let token = self.token().await?;
+            let res = self
+                .http
+                .send(|c| {
+                    c.get(format!("{}{path}", self.api_base))
+                        .query(&query)
+                        .header("Client-Id", &self.client_id)
+                        .bearer_auth(&token)
+                })
+                .await
+                .with_context(|| format!("{path} request"))?;
+            if res.status() == reqwest::StatusCode::UNAUTHORIZED && attempt == 0 {
+                warn!("twitch app token rejected, requesting a new one");
+                *self.token.lock().await = None;
+                continue;
+            }
+            let body: Helix<T> = res.error_for_status()?.json().await.with_context(|| format!("{path} response"))?;
+            return Ok(body.data);
+        }
+        unreachable!("the second attempt always returns")
+    }
+
+    async fn badges(&self, channel: &str) -> anyhow::Result<Arc<HashMap<String, String>>> {
+        let path = if channel == "global" { "/chat/badges/global" } else { "/chat/badges" };
+        let sets: Vec<BadgeSet> = self.helix(path, channel).await?;
+        let mut map = HashMap::new();
+        for set in sets {
+            for version in set.versions {
+                map.insert(format!("{}/{}", set.set_id, version.id), version.image_url_4x);
+            }
+        }
+        Ok(Arc::new(map))
+    }
+
+    async fn cheermotes(&self, channel: &str) -> anyhow::Result<Arc<CheerTiers>> {
+        let list: Vec<Cheermote> = self.helix("/bits/cheermotes", channel).await?;
+        let mut map = HashMap::new();
+        for cheermote in list {
+            let mut tiers: Vec<_> = cheermote
+                .tiers
+                .into_iter()
+                .filter_map(|tier| Some((tier.min_bits, tier.images.get("dark")?.largest()?.clone())))
+                .collect();
+            tiers.sort_by_key(|(min_bits, _)| *min_bits);
+            map.insert(cheermote.prefix.to_ascii_lowercase(), tiers);
+        }
+        Ok(Arc::new(map))
+    }
+
+    async fn resolve_badge(&self, channel: &str, set: &str, version: &str) -> anyhow::Result<Option<String>> {
+        let badges = self
+            .badges
+            .try_get_with(channel.to_string(), self.badges(channel))
+            .await
+            .map_err(|e| anyhow::anyhow!("{e:#}"))?;
+        Ok(badges.get(&format!("{set}/{version}")).cloned())
+    }
+
+    /// 비트 수가 속하는 티어의 (최소 비트, URL)
+    async fn resolve_cheermote(&self, channel: &str, prefix: &str, bits: u64) -> anyhow::Result<Option<(u64, String)>> {
+        let cheermotes = self
+            .cheermotes
+            .try_get_with(channel.to_string(), self.cheermotes(channel))
+            .await
+            .map_err(|e| anyhow::anyhow!("{e:#}"))?;
+        Ok(cheermotes
+            .get(prefix)
+            .and_then(|tiers| tiers.iter().rev().find(|(min_bits, _)| *min_bits <= bits))
+            .cloned())
+    }
+}
+
+pub async fn badge_handler(
+    State(state): State<AppState>,
+    Path((channel, set, version)): Path<(String, String, String)>,
+    Query(params): Query<EmojiParams>,
+    deadline: Option<Extension<middleware::Deadline>>,
+    req: RequestInputs,
+) -> Response {
+    let Some(twitch) = state.twitch.clone() else {
+        return (StatusCode::NOT_FOUND, "twitch source disabled").into_response();
+    };
+    let version = version.split('.').next().unwrap_or(&version).to_string();
+    let label = format!("twitch/badges/{channel}/{set}/{version}");
+    let url = match twitch.resolve_badge(&channel, &set, &version).await {
+        Ok(Some(url)) => url,
+        Ok(None) => return (StatusCode::NOT_FOUND, "badge not found").into_response(),
+        Err(e) => {
+            error!("Twitch badge lookup failed for {}: {:#}", label, e);
+            return (StatusCode::BAD_GATEWAY, "twitch lookup failed").into_response();
+        }
+    };
+    serve(state, twitch, label, url, params, deadline, req).await
+}
+
+pub async fn cheermote_handler(
+    State(state): State<AppState>,
+    Path((channel, prefix, bits)): Path<(String, String, String)>,
+    Query(params): Query<EmojiParams>,
+    deadline: Option<Extension<middleware::Deadline>>,
+    req: RequestInputs,
+) -> Response {
+    let Some(twitch) = state.twitch.clone() else {
+        return (StatusCode::NOT_FOUND, "twitch source disabled").into_response();
+    };
+    let prefix = prefix.to_ascii_lowercase();
+    let Ok(bits) = bits.split('.').next().unwrap_or(&bits).parse::<u64>() else {
+        return (StatusCode::BAD_REQUEST, "bits must be a number").into_response();
+    };
+    let (min_bits, url) = match twitch.resolve_cheermote(&channel, &prefix, bits).await {
+        Ok(Some(tier)) => tier,
+        Ok(None) => return (StatusCode::NOT_FOUND, "cheermote not found").into_response(),
+        Err(e) => {
+            error!("Twitch cheermote lookup failed for {}/{}: {:#}", channel, prefix, e);
+            return (StatusCode::BAD_GATEWAY, "twitch lookup failed").into_response();
+        }
+    };
+    // 같은 티어의 비트 수는 한 캐시 항목을 함께 쓴다
+    let label = format!("twitch/cheermotes/{channel}/{prefix}/{min_bits}");
+    serve(state, twitch, label, url, params, deadline, req).await
+}
+
+async fn serve(
+    state: AppState,
+    twitch: Arc<Twitch>,
+    label: String,
+    url: String,
+    params: EmojiParams,
+    deadline: Option<Extension<middleware::Deadline>>,
+    req: RequestInputs,
+) -> Response {
+    let variant = match pick_variant(&state.config, &params, &format!("/{label}")) {
+        Ok(v) => v,
+        Err(redirect) => return redirect.into_response(),
+    };
+    if state.blocklist.blocks_source("twitch") {
+        warn!("Blocked twitch image requested: {}", label);
+        return (StatusCode::GONE, "emoji unavailable").into_response();
+    }
+    let origin = Origin {
+        tags: purge::tags(&label, "twitch", variant.size()),
+        key_base: label.clone(),
+        url,
+        label,
+        fetch: Fetch::Http(twitch.http.clone()),
+    };
+    serve_image(state, origin, variant, deadline.map(|Extension(d)| d.0), req, Mode::Interactive).await
+}
@@ -0,0 +1,545 @@
+//! 소스별 업스트림 HTTP 클라이언트.
+//!
+//! Discord CDN은 HTTP/2 prior knowledge를 받아 주지만 Slack·페디버스 인스턴스·Twitch는
+//! 보장하지 않으므로 소스마다 클라이언트를 따로 두고 연결 풀 크기, HTTP 버전, 타임아웃을
+//! 각각 정한다 (`UPSTREAM_<소스>_*`).
+//!
+//! 업스트림이나 중간 프록시가 HTTP/1.1만 말하면 prior knowledge 연결이 깨지므로, 그럴 때는
+//! ALPN으로 협상하는 클라이언트로 다시 보내고 한동안 그쪽만 쓴다(`UPSTREAM_H2_FALLBACK`).
+//! 실제로 쓰인 프로토콜은 `emoji_resizer_upstream_http_version_total`로 센다.
+//!
+//! 연결할 주소는 [`IpFamily`]에 따라 골라 순서를 정한다. 커넥터는 첫 주소의 패밀리로 먼저 붙고
+//! 300ms 안에 안 되면 다른 패밀리를 함께 시도하므로(happy eyeballs), IPv6 경로가 깨진 호스트에서는
+//! `ipv4`로 순서만 바꾸거나 `ipv4-only`로 아예 빼면 된다 (`UPSTREAM_IP_FAMILY`).
+//!
+//! `UPSTREAM_<소스>_HEDGE_MS`를 주면 GET 요청이 그 시간 안에 응답하지 않을 때 같은 요청을 하나 더
+//! 보내고 먼저 성공한 쪽을 쓴다 (꼬리 지연 줄이기).
+//!
+//! 모든 요청은 보내기 전에 호스트별 속도·동시 요청 제한을 지난다 ([`crate::hostlimit`]).
+//!
+//! 리다이렉트는 소스마다 정한 횟수(`UPSTREAM_<소스>_MAX_REDIRECTS`)까지만 따라간다. Discord CDN은
+//! 리다이렉트하지 않으므로 기본값이 0이다. 다른 호스트로 가는 리다이렉트는 허용 목록
+//! (`UPSTREAM_<소스>_REDIRECT_HOSTS`)에 있어야 하고, 내부 주소로 가는 것은 목록과 상관없이 막는다
+//! ([`check_redirect`]). Discord 밖의 소스는 이름이 내부 주소로만 풀리는 호스트에도 연결하지 않는다.
+
+use anyhow::bail;
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    header, redirect, Client, Method, RequestBuilder, Response, StatusCode, Url, Version,
+};
+use serde::Serialize;
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+use crate::{config, hostlimit::HostLimits, metrics, processing::Priority};
+
+/// 유휴 연결 유지 시간과 TCP keepalive 간격 (모든 소스 공통)
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 되돌아간 뒤 다시 prior knowledge를 시도하기까지의 간격
+const DOWNGRADE_TTL: Duration = Duration::from_secs(600);
+
+/// 업스트림 요청을 보내는 소스
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Discord,
+    Slack,
+    Fedi,
+    Twitch,
+}
+
+impl Source {
+    pub const ALL: [Source; 4] = [Source::Discord, Source::Slack, Source::Fedi, Source::Twitch];
+
+    /// 환경변수 이름에 들어가는 대문자 이름 (`UPSTREAM_DISCORD_HTTP` 등)
+    pub fn env_name(self) -> &'static str {
+        match self {
+            Source::Discord => "DISCORD",
+            Source::Slack => "SLACK",
+            Source::Fedi => "FEDI",
+            Source::Twitch => "TWITCH",
+        }
+    }
+
+    /// 메트릭 라벨
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::Discord => "discord",
+            Source::Slack => "slack",
+            Source::Fedi => "fedi",
+            Source::Twitch => "twitch",
+        }
+    }
+}
+
+/// 업스트림과 주고받을 HTTP 버전
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpVersion {
+    /// 협상 없이 바로 HTTP/2 (`h2`). 연결이 깨지면 [`HttpVersion::Auto`]로 되돌아갈 수 있다.
+    #[serde(rename = "h2")]
+    Http2,
+    /// HTTP/1.1만 (`http1`)
+    Http1,
+    /// TLS ALPN으로 협상 (`auto`, 평문이면 HTTP/1.1)
+    Auto,
+}
+
+impl FromStr for HttpVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "h2" | "http2" => Ok(HttpVersion::Http2),
+            "http1" | "http1.1" | "h1" => Ok(HttpVersion::Http1),
+            "auto" => Ok(HttpVersion::Auto),
+            _ => bail!("unknown HTTP version {s:?} (expected h2, http1 or auto)"),
+        }
+    }
+}
+
+/// 업스트림 주소의 IP 패밀리 선택
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum IpFamily {
+    /// 시스템 리졸버 순서 그대로 (`auto`)
+    #[serde(rename = "auto")]
+    Auto,
+    /// IPv4 먼저, IPv6는 happy eyeballs로 (`ipv4`)
+    #[serde(rename = "ipv4")]
+    PreferIpv4,
+    /// IPv6 먼저, IPv4는 happy eyeballs로 (`ipv6`)
+    #[serde(rename = "ipv6")]
+    PreferIpv6,
+    /// IPv4만 (`ipv4-only`)
+    #[serde(rename = "ipv4-only")]
+    Ipv4Only,
+    /// IPv6만 (`ipv6-only`)
+    #[serde(rename = "ipv6-only")]
+    Ipv6Only,
+}
+
+impl FromStr for IpFamily {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(IpFamily::Auto),
+            "ipv4" => Ok(IpFamily::PreferIpv4),
+            "ipv6" => Ok(IpFamily::PreferIpv6),
+            "ipv4-only" => Ok(IpFamily::Ipv4Only),
+            "ipv6-only" => Ok(IpFamily::Ipv6Only),
+            _ => bail!("unknown IP family {s:?} (expected auto, ipv4, ipv6, ipv4-only or ipv6-only)"),
+        }
+    }
+}
+
+impl IpFamily {
+    /// 리졸버가 돌려준 주소를 거르고 순서를 정한다 (같은 패밀리 안의 순서는 유지)
+    pub fn arrange(self, mut addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        match self {
+            IpFamily::Auto => {}
+            IpFamily::PreferIpv4 => addrs.sort_by_key(SocketAddr::is_ipv6),
+            IpFamily::PreferIpv6 => addrs.sort_by_key(SocketAddr::is_ipv4),
+            IpFamily::Ipv4Only => addrs.retain(SocketAddr::is_ipv4),
+            IpFamily::Ipv6Only => addrs.retain(SocketAddr::is_ipv6),
+        }
+        addrs
+    }
+}
+
+/// 시스템 리졸버 결과를 [`IpFamily`]대로 고쳐 주는 리졸버. `public_only`면 내부 주소([`is_internal_ip`])를
+/// 빼므로, 이름이 내부 주소로 풀리는 호스트(리다이렉트나 이모지 목록이 가리킨 곳)에는 연결하지 않는다.
+struct FamilyResolver {
+    family: IpFamily,
+    public_only: bool,
+}
+
+impl Resolve for FamilyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let (family, public_only) = (self.family, self.public_only);
+        Box::pin(async move {
+            let mut found: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if public_only {
+                let total = found.len();
+                found.retain(|addr| !is_internal_ip(addr.ip()));
+                if found.is_empty() && total > 0 {
+                    return Err(format!("{} resolves only to internal addresses", name.as_str()).into());
+                }
+            }
+            let addrs = family.arrange(found);
+            if addrs.is_empty() {
+                return Err(format!("no {family:?} address for {}", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// 소스 하나의 클라이언트 설정
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ClientSettings {
+    pub http: HttpVersion,
+    /// 호스트별 유휴 연결 최대 개수
+    pub pool_size: usize,
+    /// 요청 전체 타임아웃
+    #[serde(serialize_with = "config::secs")]
+    pub timeout: Duration,
+    /// GET 응답이 이만큼 늦으면 같은 요청을 하나 더 보낸다 (`None`이면 끔)
+    #[serde(serialize_with = "config::optional_secs")]
+    pub hedge_after: Option<Duration>,
+    /// 호스트별 초당 요청 수 상한 (`None`이면 제한 없음)
+    pub rate_limit: Option<f64>,
+    /// 속도 제한 안에서 한꺼번에 보낼 수 있는 요청 수
+    pub burst: u32,
+    /// 호스트별 동시 요청 상한 (`None`이면 제한 없음)
+    pub max_concurrency: Option<usize>,
+    /// 따라갈 리다이렉트 최대 횟수 (0이면 리다이렉트 응답을 실패로)
+    pub max_redirects: u8,
+    /// 처음 요청과 다른 호스트로 리다이렉트할 때 허용하는 호스트. `example.com`은 그 호스트만,
+    /// `.example.com`은 하위 도메인까지.
+    pub redirect_hosts: Vec<String>,
+}
+
+/// 소스별 클라이언트 설정 모음
+#[derive(Clone, Debug, Serialize)]
+pub struct Settings {
+    pub discord: ClientSettings,
+    pub slack: ClientSettings,
+    pub fedi: ClientSettings,
+    pub twitch: ClientSettings,
+    /// prior knowledge가 실패하면 협상으로 되돌아갈지 (`UPSTREAM_H2_FALLBACK`, 기본값: true)
+    pub h2_fallback: bool,
+    /// 모든 소스에 공통인 IP 패밀리 선택 (`UPSTREAM_IP_FAMILY`, 기본값: `auto`)
+    pub ip_family: IpFamily,
+}
+
+impl Settings {
+    /// 설정하지 않았을 때의 값. Discord만 HTTP/2 prior knowledge와 큰 풀을 쓴다.
+    pub fn defaults(source: Source, timeout: Duration) -> ClientSettings {
+        match source {
+            Source::Discord => ClientSettings {
+                http: HttpVersion::Http2,
+                pool_size: 32,
+                timeout,
+                hedge_after: None,
+                rate_limit: None,
+                burst: 1,
+                max_concurrency: None,
+                max_redirects: 0,
+                redirect_hosts: Vec::new(),
+            },
+            _ => ClientSettings {
+                http: HttpVersion::Auto,
+                pool_size: 8,
+                timeout,
+                hedge_after: None,
+                rate_limit: None,
+                burst: 1,
+                max_concurrency: None,
+                max_redirects: 3,
+                redirect_hosts: Vec::new(),
+            },
+        }
+    }
+
+    pub fn get_mut(&mut self, source: Source) -> &mut ClientSettings {
+        match source {
+            Source::Discord => &mut self.discord,
+            Source::Slack => &mut self.slack,
+            Source::Fedi => &mut self.fedi,
+            Source::Twitch => &mut self.twitch,
+        }
+    }
+}
+
+/// 소스별 클라이언트. 복제는 연결 풀을 공유한다.
+#[derive(Clone)]
+pub struct Upstreams {
+    discord: Upstream,
+    slack: Upstream,
+    fedi: Upstream,
+    twitch: Upstream,
+}
+
+impl Upstreams {
+    pub fn new(settings: &Settings) -> anyhow::Result<Self> {
+        let upstream = |source: Source, client: &ClientSettings| {
+            Upstream::new(source, client, settings.h2_fallback, settings.ip_family)
+        };
+        Ok(Self {
+            discord: upstream(Source::Discord, &settings.discord)?,
+            slack: upstream(Source::Slack, &settings.slack)?,
+            fedi: upstream(Source::Fedi, &settings.fedi)?,
+            twitch: upstream(Source::Twitch, &settings.twitch)?,
+        })
+    }
+
+    pub fn client(&self, source: Source) -> &Upstream {
+        match source {
+            Source::Discord => &self.discord,
+            Source::Slack => &self.slack,
+            Source::Fedi => &self.fedi,
+            Source::Twitch => &self.twitch,
+        }
+    }
+}
+
+/// 소스 하나의 클라이언트와, prior knowledge를 쓸 때 되돌아갈 협상 클라이언트
+#[derive(Clone)]
+pub struct Upstream {
+    source: Source,
+    primary: Client,
+    fallback: Option<Client>,
+    hedge_after: Option<Duration>,
+    /// 마지막으로 되돌아간 시각
+    downgraded: Arc<Mutex<Option<Instant>>>,
+    limits: Arc<HostLimits>,
+}
+
+impl Upstream {
+    fn new(source: Source, settings: &ClientSettings, h2_fallback: bool, family: IpFamily) -> anyhow::Result<Self> {
+        let fallback = (settings.http == HttpVersion::Http2 && h2_fallback)
+            .then(|| build(source, &ClientSettings { http: HttpVersion::Auto, ..settings.clone() }, family))
+            .transpose()?;
+        Ok(Self {
+            source,
+            primary: build(source, settings, family)?,
+            fallback,
+            hedge_after: settings.hedge_after,
+            downgraded: Arc::default(),
+            limits: Arc::new(HostLimits::new(source.name(), settings)),
+        })
+    }
+
+    pub fn source(&self) -> Source {
+        self.source
+    }
+
+    /// 사용자 요청 우선순위로 [`Upstream::send_as`]
+    pub async fn send(&self, request: impl Fn(&Client) -> RequestBuilder) -> reqwest::Result<Response> {
+        self.send_as(Priority::High, request).await
+    }
+
+    /// `request`로 만든 요청을 호스트별 제한([`HostLimits`])을 지켜 보낸다. prior knowledge 연결이 깨지면
+    /// 협상 클라이언트로 한 번 더 보내고, 그게 성공하면 [`DOWNGRADE_TTL`] 동안 협상 클라이언트만 쓴다.
+    pub async fn send_as(&self, priority: Priority, request: impl Fn(&Client) -> RequestBuilder) -> reqwest::Result<Response> {
+        let host = request(&self.primary).build()?.url().authority().to_string();
+        let _permit = self.limits.acquire(&host, priority).await;
+        let result = self.dispatch(&request).await;
+        if let Some(resp) = result.as_ref().ok().filter(|r| r.status() == StatusCode::TOO_MANY_REQUESTS) {
+            let retry_after = resp
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map_or(Duration::from_secs(1), Duration::from_secs);
+            warn!("{} upstream {} throttled us, pausing for {:?}", self.source.name(), host, retry_after);
+            self.limits.pause(&host, retry_after);
+        }
+        result
+    }
+
+    async fn dispatch(&self, request: &impl Fn(&Client) -> RequestBuilder) -> reqwest::Result<Response> {
+        let result = match self.fallback.as_ref() {
+            Some(fallback) if self.is_downgraded() => self.attempt(fallback, request).await,
+            Some(fallback) => match self.attempt(&self.primary, request).await {
+                Err(e) if is_protocol_failure(&e) => {
+                    let retry = self.attempt(fallback, request).await;
+                    if let Ok(resp) = &retry {
+                        warn!(
+                            "{} upstream rejected HTTP/2 prior knowledge ({e}), negotiated {:?} instead",
+                            self.source.name(),
+                            resp.version()
+                        );
+                        *self.downgraded.lock().unwrap() = Some(Instant::now());
+                        ::metrics::counter!(metrics::UPSTREAM_HTTP_DOWNGRADES_TOTAL, "source" => self.source.name())
+                            .increment(1);
+                    }
+                    retry
+                }
+                other => other,
+            },
+            None => self.attempt(&self.primary, request).await,
+        };
+        if let Ok(resp) = &result {
+            ::metrics::counter!(
+                metrics::UPSTREAM_HTTP_VERSION_TOTAL,
+                "source" => self.source.name(),
+                "version" => version_label(resp.version())
+            )
+            .increment(1);
+        }
+        result
+    }
+
+    /// 요청 하나를 보낸다. 헤징을 켰고 GET이면 늦을 때 하나 더 보내 먼저 성공한 응답을 쓴다.
+    async fn attempt(&self, client: &Client, request: &impl Fn(&Client) -> RequestBuilder) -> reqwest::Result<Response> {
+        let req = request(client).build()?;
+        let hedge = match self.hedge_after {
+            Some(delay) if req.method() == Method::GET => req.try_clone().map(|r| (delay, r)),
+            _ => None,
+        };
+        let first = client.execute(req);
+        let Some((delay, second)) = hedge else {
+            return first.await;
+        };
+        tokio::pin!(first);
+        tokio::select! {
+            res = &mut first => return res,
+            _ = tokio::time::sleep(delay) => {}
+        }
+        let second = client.execute(second);
+        tokio::pin!(second);
+        // 한쪽이 실패하면 남은 쪽을 기다린다
+        let (res, winner) = tokio::select! {
+            res = &mut first => match res {
+                Ok(resp) => (Ok(resp), "primary"),
+                Err(_) => (second.await, "hedge"),
+            },
+            res = &mut second => match res {
+                Ok(resp) => (Ok(resp), "hedge"),
+                Err(_) => (first.await, "primary"),
+            },
+        };
+        ::metrics::counter!(metrics::UPSTREAM_HEDGES_TOTAL, "source" => self.source.name(), "winner" => winner).increment(1);
+        res
+    }
+
+    fn is_downgraded(&self) -> bool {
+        let mut slot = self.downgraded.lock().unwrap();
+        match *slot {
+            Some(at) if at.elapsed() < DOWNGRADE_TTL => true,
+            Some(_) => {
+                info!("retrying HTTP/2 prior knowledge for the {} upstream", self.source.name());
+                *slot = None;
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+/// 연결이나 프로토콜 수준의 실패 (응답을 받지 못함). 타임아웃은 프로토콜 탓이 아니므로 뺀다.
+fn is_protocol_failure(e: &reqwest::Error) -> bool {
+    (e.is_connect() || e.is_request()) && !e.is_timeout()
+}
+
+fn version_label(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_11 => "HTTP/1.1",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "other",
+    }
+}
+
+/// 리다이렉트를 `max_redirects`번까지, [`check_redirect`]를 지나는 것만 따라간다
+fn redirect_policy(source: Source, settings: &ClientSettings) -> redirect::Policy {
+    let (max, hosts) = (settings.max_redirects, settings.redirect_hosts.clone());
+    redirect::Policy::custom(move |attempt| {
+        let verdict = match attempt.previous() {
+            previous if previous.len() > max as usize => Err(format!("more than {max} redirects")),
+            [origin, ..] => check_redirect(origin, attempt.url(), &hosts),
+            [] => Ok(()),
+        };
+        let outcome = if verdict.is_ok() { "followed" } else { "rejected" };
+        ::metrics::counter!(metrics::UPSTREAM_REDIRECTS_TOTAL, "source" => source.name(), "outcome" => outcome).increment(1);
+        match verdict {
+            Ok(()) => attempt.follow(),
+            Err(reason) => {
+                warn!("refusing {} upstream redirect to {}: {}", source.name(), attempt.url(), reason);
+                attempt.error(reason)
+            }
+        }
+    })
+}
+
+/// 처음 요청한 `origin`에서 `to`로 리다이렉트해도 되는지. 같은 호스트(포트까지)는 그대로 따라가고, 다른
+/// 호스트는 `hosts`에 있어야 한다. 내부 주소(루프백·사설·링크 로컬 등의 IP나 `localhost`)는 목록에 있어도
+/// 막는다. 호스트 이름이 내부 주소로 풀리는 경우는 연결할 때 [`FamilyResolver`]가 막는다 (Discord 밖의 소스).
+pub fn check_redirect(origin: &Url, to: &Url, hosts: &[String]) -> Result<(), String> {
+    if !matches!(to.scheme(), "http" | "https") {
+        return Err(format!("{} URLs are not fetched", to.scheme()));
+    }
+    if origin.scheme() == "https" && to.scheme() == "http" {
+        return Err("redirect downgrades https to http".into());
+    }
+    if to.host_str() == origin.host_str() && to.port_or_known_default() == origin.port_or_known_default() {
+        return Ok(());
+    }
+    let Some(host) = to.host_str().map(str::to_ascii_lowercase) else {
+        return Err("redirect has no host".into());
+    };
+    if is_internal(&host) {
+        return Err(format!("{host} is an internal address"));
+    }
+    let allowed = hosts.iter().any(|pattern| match pattern.strip_prefix('.') {
+        Some(domain) => host == domain || host.ends_with(pattern.as_str()),
+        None => host == *pattern,
+    });
+    if !allowed {
+        return Err(format!("{host} is not in the redirect allowlist"));
+    }
+    Ok(())
+}
+
+/// 루프백·사설·링크 로컬·CGNAT·미지정 주소나 `localhost`
+fn is_internal(host: &str) -> bool {
+    if host == "localhost" || host.ends_with(".localhost") {
+        return true;
+    }
+    host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().is_ok_and(is_internal_ip)
+}
+
+/// 루프백·사설·링크 로컬·CGNAT·미지정 주소
+fn is_internal_ip(ip: IpAddr) -> bool {
+    let v4 = |ip: std::net::Ipv4Addr| {
+        let [a, b, ..] = ip.octets();
+        ip.is_loopback()
+            || ip.is_private()
+            || ip.is_link_local()
+            || ip.is_unspecified()
+            || ip.is_broadcast()
+            || a == 0
+            || (a == 100 && b & 0xc0 == 64)
+    };
+    match ip {
+        IpAddr::V4(ip) => v4(ip),
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80
+                || ip.to_ipv4_mapped().is_some_and(v4)
+        }
+    }
+}
+
+fn build(source: Source, settings: &ClientSettings, family: IpFamily) -> anyhow::Result<Client> {
+    let mut builder = Client::builder()
+        .user_agent(crate::USER_AGENT.as_str())
+        .redirect(redirect_policy(source, settings))
+        .pool_max_idle_per_host(settings.pool_size)
+        .pool_idle_timeout(IDLE_TIMEOUT)
+        .tcp_keepalive(Some(IDLE_TIMEOUT))
+        .timeout(settings.timeout);
+    // Discord CDN 주소는 운영자가 정하므로(사내 프록시일 수 있음) 내부 주소를 막지 않는다
+    let public_only = source != Source::Discord;
+    if family != IpFamily::Auto || public_only {
+        builder = builder.dns_resolver(Arc::new(FamilyResolver { family, public_only }));
+    }
+    let builder = match settings.http {
+        HttpVersion::Http2 => builder.http2_prior_knowledge(),
+        HttpVersion::Http1 => builder.http1_only(),
+        HttpVersion::Auto => builder,
+    };
+    Ok(builder.build()?)
+}
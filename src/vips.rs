@@ -0,0 +1,32 @@
+//! libvips 처리 백엔드 (`vips` 기능).
+//!
+//! `vips thumbnail` 명령을 띄워 리사이즈한다. libvips는 이미지를 조각 단위로 흘려 처리하므로
+//! 프레임이 수백 장인 애니메이션이나 내장 디코더가 없는 포맷도 적은 메모리로 다룬다.
+//! 어떤 작업을 맡길지는 `VIPS_OPERATIONS`로 고르고, 실패하면 호출하는 쪽이 순수 Rust
+//! 경로로 되돌아간다.
+
+use anyhow::Context;
+use std::time::Instant;
+use tokio::process::Command;
+
+use crate::subprocess::{run_with_deadline, Scratch};
+
+/// `size` 박스에 맞춰 줄인 무손실 WebP. 애니메이션은 모든 프레임을 유지한다.
+/// `deadline`이 지나면 프로세스를 죽이고 실패로 돌려준다.
+pub async fn thumbnail(binary: &str, input: &[u8], size: u32, deadline: Option<Instant>) -> anyhow::Result<Vec<u8>> {
+    let id = uuid::Uuid::new_v4();
+    let (src, dst) = (Scratch::new(format!("vips-{id}.in")), Scratch::new(format!("vips-{id}.webp")));
+    tokio::fs::write(src.path(), input).await.with_context(|| format!("writing {}", src.path().display()))?;
+
+    let mut command = Command::new(binary);
+    command
+        .arg("thumbnail")
+        // `n=-1`: 애니메이션의 모든 프레임을 읽는다
+        .arg(format!("{}[n=-1]", src.path().display()))
+        .arg(format!("{}[lossless]", dst.path().display()))
+        .arg(size.to_string())
+        .arg("--height")
+        .arg(size.to_string());
+    run_with_deadline(command, deadline).await?;
+    tokio::fs::read(dst.path()).await.with_context(|| format!("reading {}", dst.path().display()))
+}
@@ -0,0 +1,287 @@
+//! 접근 로그 재생 워머.
+//!
+//! 접근 로그(`access` 타깃)에서 최근 많이 요청된 `/e/<이모지>` 경로를 뽑아 미리
+//! 가져온다. 인코더 설정을 바꿔 캐시를 비운 뒤 콜드 스타트를 줄이는 용도.
+//! 서버 안에서는 관리 API(`POST /admin/warm`)로, 밖에서는
+//! `emoji-resizer warm --from-access-log <파일>...`로 실행 중인 인스턴스에 요청을 보낸다.
+//!
+//! 부팅 때는 `WARM_SEED_FILE`의 이모지를 채우고, `READY_WARM_FRACTION`을 주면 그 비율만큼 캐시될 때까지
+//! `/readyz`를 준비 안 됨으로 둬서 로드 밸런서가 빨리 응답할 수 있는 인스턴스에만 트래픽을 보내게 한다.
+//! 목록을 다 돌았거나 `READY_WARM_TIMEOUT_SECS`가 지나면 비율에 못 미쳐도 경고를 남기고 준비 완료로 돌린다.
+
+use anyhow::{bail, Context};
+use flate2::read::GzDecoder;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{BufRead, BufReader, Read},
+    path::{Path as FsPath, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::{response::RequestInputs, AppState};
+
+pub const DEFAULT_TOP: usize = 1000;
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// 접근 로그 한 줄에서 성공(200/304)한 `/e/<이름>` 요청의 이모지 ID를 꺼낸다.
+/// stdout 로그의 ANSI 색 코드가 섞여 있어도 동작한다.
+fn parse_line(line: &str) -> Option<String> {
+    if !line.contains("access") {
+        return None;
+    }
+    let line = strip_ansi(line);
+    let field = |name: &str| {
+        let start = line.find(&format!("{name}="))? + name.len() + 1;
+        line[start..].split_whitespace().next()
+    };
+    if !matches!(field("status")?, "200" | "304") {
+        return None;
+    }
+    let name = field("path")?.strip_prefix("/e/")?;
+    let id = name.split('.').next().unwrap_or(name);
+    (!id.is_empty() && !id.contains('/')).then(|| id.to_string())
+}
+
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // CSI 시퀀스: ESC [ ... <문자>
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// 로그 파일 하나를 읽어 ID별 요청 수를 센다 (`.gz`는 풀어서 읽음)
+pub fn count_file(path: &FsPath, counts: &mut HashMap<String, u64>) -> anyhow::Result<()> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|e| e == "gz") {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    for line in BufReader::new(reader).lines() {
+        // 잘린 gzip 등으로 중간에 실패해도 그때까지 센 것은 쓴다
+        let Ok(line) = line else { break };
+        if let Some(id) = parse_line(&line) {
+            *counts.entry(id).or_default() += 1;
+        }
+    }
+    Ok(())
+}
+
+/// 요청 수 내림차순 상위 `top`개 ID
+pub fn rank(counts: HashMap<String, u64>, top: usize) -> Vec<String> {
+    let mut ranked: Vec<_> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked.into_iter().take(top).map(|(id, _)| id).collect()
+}
+
+/// 로그 디렉터리의 현재 파일과 가장 최근 회전 파일 `archives`개
+pub fn recent_logs(dir: &FsPath, archives: usize) -> anyhow::Result<Vec<PathBuf>> {
+    let mut rotated: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("reading {}", dir.display()))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("emoji-resizer.") && n != "emoji-resizer.log")
+        })
+        .collect();
+    // 회전 파일 이름은 시각순이므로 이름 역순이 최신순
+    rotated.sort_by(|a, b| b.cmp(a));
+    rotated.truncate(archives);
+    let mut paths = vec![dir.join("emoji-resizer.log")];
+    paths.extend(rotated);
+    paths.retain(|p| p.exists());
+    Ok(paths)
+}
+
+/// 서버 안에서 이모지 핸들러를 직접 호출해 캐시를 채운다. `(성공, 실패)` 수를 돌려준다.
+pub async fn warm_local(state: AppState, ids: Vec<String>, concurrency: usize) -> (usize, usize) {
+    warm_each(state, ids, concurrency, |_| {}).await
+}
+
+/// [`warm_local`]과 같고, 이모지 하나를 마칠 때마다 성공 여부로 `done`을 부른다
+async fn warm_each(
+    state: AppState,
+    ids: Vec<String>,
+    concurrency: usize,
+    mut done: impl FnMut(bool),
+) -> (usize, usize) {
+    let mut tasks = JoinSet::new();
+    let (mut ok, mut failed) = (0, 0);
+    let mut tally = |res: Result<bool, _>| {
+        let success = matches!(res, Ok(true));
+        done(success);
+        if success {
+            ok += 1;
+        } else {
+            failed += 1;
+        }
+    };
+    for id in ids {
+        if tasks.len() >= concurrency.max(1) {
+            if let Some(res) = tasks.join_next().await {
+                tally(res);
+            }
+        }
+        let state = state.clone();
+        tasks.spawn(async move {
+            let variant = crate::Variant::Resized(state.config.default_size);
+            let res = crate::serve_emoji(state, id, variant, None, RequestInputs::default(), crate::Mode::Warm).await;
+            res.status().is_success()
+        });
+    }
+    while let Some(res) = tasks.join_next().await {
+        tally(res);
+    }
+    (ok, failed)
+}
+
+/// 부팅 워밍 진행 상황. 목표가 0이면(시드나 `READY_WARM_FRACTION`이 없으면) 처음부터 준비 완료다.
+#[derive(Debug, Default)]
+pub struct Warmup {
+    total: usize,
+    required: usize,
+    cached: AtomicUsize,
+    /// 목록을 다 돌았거나 기다리는 시간이 지나서 더 기다리지 않는다
+    settled: AtomicBool,
+}
+
+impl Warmup {
+    /// 시드 `total`개 중 `fraction`(0~1)만큼 캐시되기를 기다린다
+    pub fn new(total: usize, fraction: f64) -> Self {
+        Self { total, required: (total as f64 * fraction).ceil() as usize, ..Self::default() }
+    }
+
+    pub fn ready(&self) -> bool {
+        self.settled.load(Ordering::Acquire) || self.cached.load(Ordering::Relaxed) >= self.required
+    }
+
+    /// `(캐시된 수, 기다리는 수)`
+    pub fn progress(&self) -> (usize, usize) {
+        (self.cached.load(Ordering::Relaxed), self.required)
+    }
+
+    fn settle(&self, reason: &str) {
+        if !self.ready() {
+            let (cached, required) = self.progress();
+            warn!("warm-up {reason} with {cached}/{required} seed emojis cached, marking ready anyway");
+        }
+        self.settled.store(true, Ordering::Release);
+    }
+}
+
+/// 시드 파일: 한 줄에 이모지 ID 하나. 빈 줄과 `#` 주석은 건너뛰고 중복은 처음 것만 남긴다.
+pub fn read_seed(path: &FsPath) -> anyhow::Result<Vec<String>> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading warm seed {}", path.display()))?;
+    let mut seen = HashSet::new();
+    Ok(text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|id| !id.is_empty() && seen.insert(id.to_string()))
+        .map(String::from)
+        .collect())
+}
+
+/// 부팅 시드를 채우면서 `warmup`에 진행 상황을 남긴다. `timeout`이 0이 아니면 그 뒤에는 기다리지 않는다.
+pub async fn seed(state: AppState, ids: Vec<String>, warmup: Arc<Warmup>, timeout: Duration) {
+    info!("warm-up - seeding {} emojis, ready after {}", warmup.total, warmup.required);
+    if !timeout.is_zero() {
+        let warmup = warmup.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            warmup.settle("timed out");
+        });
+    }
+    let progress = warmup.clone();
+    let (ok, failed) = warm_each(state, ids, DEFAULT_CONCURRENCY, |success| {
+        if success {
+            progress.cached.fetch_add(1, Ordering::Relaxed);
+        }
+    })
+    .await;
+    info!("warm-up done - {} ok, {} failed", ok, failed);
+    warmup.settle("finished");
+}
+
+/// `emoji-resizer warm --from-access-log <파일>... [--top N] [--target URL] [--concurrency N]`
+pub async fn cli(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let mut files = Vec::new();
+    let mut top = DEFAULT_TOP;
+    let mut concurrency = DEFAULT_CONCURRENCY;
+    let mut target = "http://127.0.0.1:53292".to_string();
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().with_context(|| format!("{flag} needs a value"));
+        match arg.as_str() {
+            "--from-access-log" => files.push(PathBuf::from(value(&arg)?)),
+            "--top" => top = value(&arg)?.parse().context("invalid --top")?,
+            "--concurrency" => concurrency = value(&arg)?.parse().context("invalid --concurrency")?,
+            "--target" => target = value(&arg)?.trim_end_matches('/').to_string(),
+            // --from-access-log 뒤에 파일을 여러 개 나열해도 된다
+            other if !other.starts_with("--") && !files.is_empty() => files.push(PathBuf::from(other)),
+            other => bail!("unknown argument {other:?}"),
+        }
+    }
+    if files.is_empty() {
+        bail!("usage: emoji-resizer warm --from-access-log <file>... [--top N] [--target URL] [--concurrency N]");
+    }
+
+    let mut counts = HashMap::new();
+    for file in &files {
+        count_file(file, &mut counts)?;
+    }
+    let ids = rank(counts, top);
+    println!("warming {} emojis against {}", ids.len(), target);
+
+    let http = reqwest::Client::new();
+    let mut tasks = JoinSet::new();
+    let (mut ok, mut failed) = (0usize, 0usize);
+    let mut tally = |res: Result<bool, _>| match res {
+        Ok(true) => ok += 1,
+        _ => failed += 1,
+    };
+    for id in ids {
+        if tasks.len() >= concurrency.max(1) {
+            if let Some(res) = tasks.join_next().await {
+                tally(res);
+            }
+        }
+        let (http, url) = (http.clone(), format!("{target}/e/{id}.webp"));
+        tasks.spawn(async move {
+            match http.get(&url).send().await {
+                Ok(r) if r.status().is_success() => true,
+                Ok(r) => {
+                    eprintln!("{url}: {}", r.status());
+                    false
+                }
+                Err(e) => {
+                    eprintln!("{url}: {e}");
+                    false
+                }
+            }
+        });
+    }
+    while let Some(res) = tasks.join_next().await {
+        tally(res);
+    }
+    println!("warm done: {ok} ok, {failed} failed");
+    Ok(())
+}
@@ -0,0 +1,307 @@
+//! GIF 폐기 방식과 APNG 블렌딩 합성 테스트.
+//!
+//! 픽스처는 `support`의 GIF/APNG 빌더(`gif`·`png` 인코더)로 만들고, 프레임마다 캔버스의 몇 점을 브라우저가 그리는
+//! 결과와 비교한다. 마지막으로 내장 애니메이션 경로를 통과한 WebP에도 잔상이 없고 지연·반복 횟수가 그대로인지 본다.
+
+mod support;
+
+use emoji_resizer::{
+    animation::{self, Animation},
+    effects::Effects,
+    imaging,
+};
+use image::{codecs::webp::WebPDecoder, AnimationDecoder, RgbaImage};
+use std::io::Cursor;
+use support::*;
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const CLEAR: [u8; 4] = [0, 0, 0, 0];
+/// 팔레트 0 빨강, 1 초록, 2 파랑
+const PALETTE: [[u8; 3]; 3] = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+
+fn decode(data: &[u8]) -> Animation {
+    assert!(animation::is_animated(data));
+    animation::decode(data).unwrap()
+}
+
+fn pixel(animation: &Animation, frame: usize, x: u32, y: u32) -> [u8; 4] {
+    animation.frames[frame].image.get_pixel(x, y).0
+}
+
+#[test]
+fn gif_keep_leaves_earlier_frames_in_place() {
+    let gif = gif_animation(4, 4, &PALETTE, None, &[GifFrame::fill(0, 0, 4, 4, 0), GifFrame::fill(0, 0, 1, 1, 2)]);
+    let a = decode(&gif);
+    assert_eq!((a.width, a.height, a.frames.len()), (4, 4, 2));
+    assert_eq!(pixel(&a, 1, 0, 0), BLUE);
+    assert_eq!(pixel(&a, 1, 3, 3), RED);
+}
+
+#[test]
+fn gif_background_disposal_clears_to_transparent() {
+    let frames = [
+        GifFrame { disposal: 2, ..GifFrame::fill(0, 0, 4, 4, 0) },
+        GifFrame::fill(1, 1, 1, 1, 2),
+    ];
+    let a = decode(&gif_animation(4, 4, &PALETTE, None, &frames));
+    assert_eq!(pixel(&a, 0, 0, 0), RED);
+    // 빨강이 남아 있으면 잔상
+    assert_eq!(pixel(&a, 1, 0, 0), CLEAR);
+    assert_eq!(pixel(&a, 1, 1, 1), BLUE);
+}
+
+#[test]
+fn gif_previous_disposal_restores_the_canvas() {
+    let frames = [
+        GifFrame::fill(0, 0, 4, 4, 0),
+        GifFrame { disposal: 3, ..GifFrame::fill(0, 0, 2, 2, 1) },
+        GifFrame::fill(3, 3, 1, 1, 2),
+    ];
+    let a = decode(&gif_animation(4, 4, &PALETTE, None, &frames));
+    assert_eq!(pixel(&a, 1, 0, 0), GREEN);
+    assert_eq!(pixel(&a, 2, 0, 0), RED);
+    assert_eq!(pixel(&a, 2, 1, 1), RED);
+    assert_eq!(pixel(&a, 2, 3, 3), BLUE);
+}
+
+#[test]
+fn gif_transparent_index_shows_what_is_underneath() {
+    let mut overlay = GifFrame { transparent: Some(1), ..GifFrame::fill(0, 0, 4, 4, 1) };
+    overlay.indices[5] = 2; // (1, 1)
+    let a = decode(&gif_animation(4, 4, &PALETTE, None, &[GifFrame::fill(0, 0, 4, 4, 0), overlay]));
+    assert_eq!(pixel(&a, 1, 0, 0), RED);
+    assert_eq!(pixel(&a, 1, 1, 1), BLUE);
+
+    // 첫 프레임의 투명색은 빈 캔버스가 비친다
+    let first = GifFrame { transparent: Some(0), ..GifFrame::fill(0, 0, 4, 4, 0) };
+    let a = decode(&gif_animation(4, 4, &PALETTE, None, &[first, GifFrame::fill(0, 0, 1, 1, 2)]));
+    assert_eq!(pixel(&a, 0, 2, 2), CLEAR);
+}
+
+#[test]
+fn gif_frames_outside_the_screen_are_clipped() {
+    let frames = [GifFrame::fill(0, 0, 4, 4, 0), GifFrame::fill(3, 3, 4, 4, 2)];
+    let a = decode(&gif_animation(4, 4, &PALETTE, None, &frames));
+    assert_eq!(a.frames[1].image.dimensions(), (4, 4));
+    assert_eq!(pixel(&a, 1, 3, 3), BLUE);
+    assert_eq!(pixel(&a, 1, 2, 2), RED);
+}
+
+#[test]
+fn gif_local_palettes_and_interlacing() {
+    // 줄마다 다른 색 (줄 번호 = 색 번호) 8x8 인터레이스, 지역 팔레트는 회색 단계
+    let gray: Vec<[u8; 3]> = (0..8).map(|i| [i * 30, i * 30, i * 30]).collect();
+    let interlaced = GifFrame {
+        indices: (0..8).flat_map(|row| [row; 8]).collect(),
+        palette: Some(gray),
+        interlaced: true,
+        ..GifFrame::fill(0, 0, 8, 8, 0)
+    };
+    let a = decode(&gif_animation(8, 8, &PALETTE, None, &[GifFrame::fill(0, 0, 8, 8, 0), interlaced]));
+    for row in 0..8u8 {
+        let v = row * 30;
+        assert_eq!(pixel(&a, 1, 4, row as u32), [v, v, v, 255], "row {row}");
+    }
+}
+
+#[test]
+fn gif_delays_and_loop_counts_follow_browsers() {
+    let frames = [
+        GifFrame { delay_cs: 0, ..GifFrame::fill(0, 0, 1, 1, 0) },
+        GifFrame { delay_cs: 1, ..GifFrame::fill(0, 0, 1, 1, 1) },
+        GifFrame { delay_cs: 5, ..GifFrame::fill(0, 0, 1, 1, 2) },
+    ];
+    let a = decode(&gif_animation(1, 1, &PALETTE, Some(0), &frames));
+    let delays: Vec<u32> = a.frames.iter().map(|f| f.delay_ms).collect();
+    assert_eq!(delays, [100, 100, 50]);
+    assert_eq!(a.loop_count, 0);
+    // 확장이 없으면 한 번, n이면 처음 재생 + n번
+    assert_eq!(decode(&gif_animation(1, 1, &PALETTE, None, &frames)).loop_count, 1);
+    assert_eq!(decode(&gif_animation(1, 1, &PALETTE, Some(2), &frames)).loop_count, 3);
+}
+
+#[test]
+fn single_frame_and_broken_inputs_are_not_animated() {
+    assert!(!animation::is_animated(&gif()));
+    assert!(!animation::is_animated(&apng()));
+    assert!(!animation::is_animated(&static_webp()));
+    let mut truncated = gif_animation(4, 4, &PALETTE, None, &[GifFrame::fill(0, 0, 4, 4, 0), GifFrame::fill(0, 0, 4, 4, 1)]);
+    truncated.truncate(40);
+    assert!(!animation::is_animated(&truncated));
+}
+
+#[test]
+fn apng_over_blends_and_source_replaces() {
+    let half_blue = [0, 0, 255, 128];
+    let base = ApngFrame::fill(0, 0, 2, 2, RED);
+    let over = ApngFrame { blend: 1, ..ApngFrame::fill(0, 0, 1, 1, half_blue) };
+    let source = ApngFrame { blend: 0, ..ApngFrame::fill(1, 1, 1, 1, half_blue) };
+    let a = decode(&apng_animation(2, 2, 0, None, &[base, over, source]));
+    assert_eq!(a.frames.len(), 3);
+    assert_eq!(pixel(&a, 0, 0, 0), RED);
+    let [r, g, b, alpha] = pixel(&a, 1, 0, 0);
+    // `image`의 합성은 부동소수점이라 반올림 차이로 한 단계 어긋날 수 있다
+    assert_eq!(g, 0);
+    assert!(alpha >= 254, "{alpha}");
+    assert!((126..=129).contains(&r) && (126..=129).contains(&b), "{r} {b}");
+    assert_eq!(pixel(&a, 2, 1, 1), half_blue);
+    assert_eq!(pixel(&a, 2, 1, 0), RED);
+}
+
+#[test]
+fn apng_dispose_ops() {
+    let frames = [
+        ApngFrame { dispose: 0, ..ApngFrame::fill(0, 0, 4, 4, RED) },
+        ApngFrame { dispose: 2, ..ApngFrame::fill(0, 0, 2, 2, GREEN) },
+        ApngFrame { dispose: 1, ..ApngFrame::fill(2, 2, 2, 2, BLUE) },
+        ApngFrame { blend: 1, ..ApngFrame::fill(0, 0, 1, 1, CLEAR) },
+    ];
+    let a = decode(&apng_animation(4, 4, 2, None, &frames));
+    assert_eq!(a.loop_count, 2);
+    assert_eq!(pixel(&a, 1, 0, 0), GREEN);
+    // previous: 초록을 그리기 전 빨강으로
+    assert_eq!(pixel(&a, 2, 0, 0), RED);
+    assert_eq!(pixel(&a, 2, 3, 3), BLUE);
+    // background: 파랑 자리가 투명해진다
+    assert_eq!(pixel(&a, 3, 3, 3), CLEAR);
+    assert_eq!(pixel(&a, 3, 0, 0), RED);
+}
+
+#[test]
+fn apng_first_frame_previous_disposal_clears() {
+    let frames = [
+        ApngFrame { dispose: 2, ..ApngFrame::fill(0, 0, 2, 2, RED) },
+        ApngFrame { blend: 1, ..ApngFrame::fill(0, 0, 1, 1, BLUE) },
+    ];
+    let a = decode(&apng_animation(2, 2, 0, None, &frames));
+    assert_eq!(pixel(&a, 1, 1, 1), CLEAR);
+}
+
+#[test]
+fn apng_default_image_is_not_a_frame() {
+    let default = ApngFrame::fill(0, 0, 2, 2, GREEN);
+    let frames = [ApngFrame::fill(0, 0, 2, 2, RED), ApngFrame { delay: (3, 0), ..ApngFrame::fill(0, 0, 1, 1, BLUE) }];
+    let a = decode(&apng_animation(2, 2, 0, Some(&default), &frames));
+    assert_eq!(a.frames.len(), 2);
+    assert_eq!(pixel(&a, 0, 0, 0), RED);
+    assert_eq!(pixel(&a, 1, 0, 0), BLUE);
+    assert_eq!(pixel(&a, 1, 1, 1), RED);
+    let delays: Vec<u32> = a.frames.iter().map(|f| f.delay_ms).collect();
+    // 분모 0은 1/100초
+    assert_eq!(delays, [100, 30]);
+}
+
+#[test]
+fn apng_palettes_and_low_bit_depths() {
+    // 4비트 팔레트 + tRNS
+    let (w, h) = (6u32, 5u32);
+    let index = |x: u32, y: u32| ((x + y) % 3) as u8;
+    let rows: Vec<u8> = (0..h).flat_map(|y| (0..w).step_by(2).map(move |x| (index(x, y) << 4) | index(x + 1, y))).collect();
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, w, h);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Four);
+    encoder.set_palette(PALETTE.concat());
+    encoder.set_trns(vec![255, 255, 0]);
+    encoder.set_animated(2, 0).unwrap();
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&rows).unwrap();
+    writer.set_frame_dimension(2, 1).unwrap();
+    writer.write_image_data(&[0x11]).unwrap();
+    writer.finish().unwrap();
+
+    let a = decode(&out);
+    for y in 0..h {
+        for x in 0..w {
+            let expected = match index(x, y) {
+                0 => RED,
+                1 => GREEN,
+                _ => [0, 0, 255, 0],
+            };
+            assert_eq!(pixel(&a, 0, x, y), expected, "({x}, {y})");
+        }
+    }
+    assert_eq!(pixel(&a, 1, 0, 0), GREEN);
+}
+
+/// 두 번째 `fcTL`의 너비를 고쳐 쓰고 CRC를 다시 계산한다 (인코더는 캔버스 밖으로 나가는 프레임을 만들지 않는다)
+fn set_second_frame_width(apng: &mut [u8], width: u32) {
+    let at = apng.windows(4).enumerate().filter(|(_, w)| *w == b"fcTL").nth(1).unwrap().0;
+    let body = at + 4;
+    apng[body + 4..body + 8].copy_from_slice(&width.to_be_bytes());
+    let mut crc = flate2::Crc::new();
+    crc.update(&apng[at..body + 26]);
+    apng[body + 26..body + 30].copy_from_slice(&crc.sum().to_be_bytes());
+}
+
+#[test]
+fn apng_frames_outside_the_canvas_are_rejected() {
+    let mut apng = apng_animation(2, 2, 0, None, &[ApngFrame::fill(0, 0, 2, 2, RED), ApngFrame::fill(1, 1, 1, 1, BLUE)]);
+    set_second_frame_width(&mut apng, 2);
+    assert!(animation::decode(&apng).is_err());
+}
+
+fn webp_frames(webp: &[u8]) -> Vec<RgbaImage> {
+    let decoder = WebPDecoder::new(Cursor::new(webp)).unwrap();
+    decoder.into_frames().collect_frames().unwrap().into_iter().map(|f| f.into_buffer()).collect()
+}
+
+#[test]
+fn resized_gif_animation_has_no_ghosting() {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let out = imaging::resize_animated_webp(&animated_gif(), 32, &Effects::NONE, &pool, &imaging::Cancel::default(), None).unwrap();
+    assert_eq!(out.original, (64, 64));
+    assert_eq!(out.resized, (32, 32));
+    assert!(imaging::is_animated_webp(&out.bytes));
+    let frames = webp_frames(&out.bytes);
+    assert_eq!(frames.len(), 4);
+    for (i, frame) in frames.iter().enumerate() {
+        // 사각형은 이번 프레임 자리(8i..8i+8, 8..16)에만 있고 앞 프레임 자리는 투명
+        for j in 0..4 {
+            let [r, g, b, alpha] = frame.get_pixel(j * 8 + 4, 12).0;
+            if j == i as u32 {
+                assert!(r < 5 && g < 5 && b > 250 && alpha > 250, "frame {i}: {:?}", [r, g, b, alpha]);
+            } else {
+                assert_eq!(alpha, 0, "frame {i} square {j}");
+            }
+        }
+    }
+}
+
+fn webp_delays(webp: &[u8]) -> Vec<u32> {
+    let decoder = WebPDecoder::new(Cursor::new(webp)).unwrap();
+    decoder.into_frames().collect_frames().unwrap().iter().map(|f| f.delay().numer_denom_ms()).map(|(n, d)| n / d).collect()
+}
+
+/// 애니메이션 WebP `ANIM` 청크의 반복 횟수
+fn webp_loop_count(webp: &[u8]) -> u16 {
+    let at = webp.windows(4).position(|w| w == b"ANIM").expect("ANIM chunk");
+    u16::from_le_bytes([webp[at + 12], webp[at + 13]])
+}
+
+#[test]
+fn resized_animations_keep_delays_and_loop_count() {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let frames: Vec<_> = [(0u8, 40u32), (128, 120), (255, 70)]
+        .iter()
+        .map(|&(phase, delay_ms)| imaging::AnimFrame { webp: imaging::encode_webp(&sample_image(phase)).unwrap(), delay_ms })
+        .collect();
+    let source = imaging::mux_animated_webp(100, 100, 3, &frames).unwrap();
+    let out = imaging::resize_animated_webp(&source, 40, &Effects::NONE, &pool, &imaging::Cancel::default(), None).unwrap();
+    assert_eq!(out.resized, (40, 40));
+    assert_eq!(webp_loop_count(&out.bytes), 3);
+    assert_eq!(webp_delays(&out.bytes), [40, 120, 70]);
+    assert!(webp_frames(&out.bytes).iter().all(|f| f.dimensions() == (40, 40)));
+
+    // GIF의 NETSCAPE 반복(처음 재생 + 2번)은 WebP 반복 3번으로
+    let gif_frames = [
+        GifFrame { delay_cs: 3, ..GifFrame::fill(0, 0, 4, 4, 0) },
+        GifFrame { delay_cs: 9, ..GifFrame::fill(0, 0, 4, 4, 1) },
+    ];
+    let gif = gif_animation(4, 4, &PALETTE, Some(2), &gif_frames);
+    let out = imaging::resize_animated_webp(&gif, 2, &Effects::NONE, &pool, &imaging::Cancel::default(), None).unwrap();
+    assert_eq!(webp_loop_count(&out.bytes), 3);
+    assert_eq!(webp_delays(&out.bytes), [30, 90]);
+}
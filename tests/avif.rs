@@ -0,0 +1,88 @@
+//! 애니메이션 AVIF (`cargo test --features avif`). 실제 libavif 대신 인자와 첫 프레임을 기록하고
+//! 준비한 AVIF를 내놓는 가짜 `avifenc` 스크립트를 쓴다.
+
+#![cfg(feature = "avif")]
+
+mod support;
+
+use reqwest::{header, StatusCode};
+use std::os::unix::fs::PermissionsExt;
+use support::*;
+
+/// `ftypavis` 브랜드만 갖춘 가짜 이미지 시퀀스
+const FAKE_AVIS: &[u8] = b"\0\0\0\x1cftypavis\0\0\0\0avisavifmif1miaf";
+
+/// 마지막 인자(출력 경로)에 [`FAKE_AVIS`]를 쓴다
+fn fake_avifenc(dir: &std::path::Path) -> std::path::PathBuf {
+    let fixture = dir.join("fixture.avif");
+    std::fs::write(&fixture, FAKE_AVIS).unwrap();
+    let script = dir.join("avifenc");
+    let body = format!(
+        "#!/bin/sh\necho \"$@\" > {args}\nfor a; do case \"$a\" in *-0.y4m) head -c 128 \"$a\" > {frame};; esac; out=\"$a\"; done\ncp {fixture} \"$out\"\n",
+        args = dir.join("args").display(),
+        frame = dir.join("frame").display(),
+        fixture = fixture.display(),
+    );
+    std::fs::write(&script, body).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+async fn get_accepting(app: &TestApp, path: &str, accept: &str) -> reqwest::Response {
+    app.client.get(app.url(path)).header(header::ACCEPT, accept).send().await.unwrap()
+}
+
+#[tokio::test]
+async fn animated_emoji_is_served_as_avif_when_accepted() {
+    let dir = temp_dir("avif");
+    let binary = fake_avifenc(&dir);
+    let app = spawn_app_with(|c| {
+        c.avif_animated = true;
+        c.avif_binary = binary.display().to_string();
+    })
+    .await;
+    let path = format!("/e/{ANIMATED_ID}.webp");
+
+    let res = get_accepting(&app, &path, "image/avif,image/webp,*/*;q=0.8").await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/avif");
+    assert_eq!(res.headers()[header::VARY], "Accept");
+    assert!(res.headers()["server-timing"].to_str().unwrap().contains("avif"));
+    assert_eq!(res.bytes().await.unwrap(), FAKE_AVIS);
+    let args = std::fs::read_to_string(dir.join("args")).unwrap();
+    assert!(args.starts_with("--timescale 1000 -s 6 -q 60 --duration 80 "), "{args}");
+    assert_eq!(args.matches("--duration").count(), 3, "{args}");
+    let frame = std::fs::read(dir.join("frame")).unwrap();
+    assert!(frame.starts_with(b"YUV4MPEG2 W160 H160 F30:1 Ip A1:1 C444alpha XCOLORRANGE=FULL\nFRAME\n"));
+
+    // 캐시에서 꺼낸 시퀀스도 AVIF로 내보낸다
+    let res = get_accepting(&app, &path, "image/avif").await;
+    assert_eq!((res.headers()["x-cache"].to_str().unwrap(), res.headers()[header::CONTENT_TYPE].to_str().unwrap()), ("HIT", "image/avif"));
+
+    // AVIF를 받지 않는 클라이언트와 정적 이모지는 WebP
+    for accept in ["image/webp", "image/avif;q=0"] {
+        let res = get_accepting(&app, &path, accept).await;
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp", "{accept}");
+        assert_eq!(res.headers()[header::VARY], "Accept");
+    }
+    let res = get_accepting(&app, &format!("/e/{STATIC_ID}.webp"), "image/avif").await;
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp");
+}
+
+#[tokio::test]
+async fn avif_failures_fall_back_to_webp() {
+    let dir = temp_dir("avif-broken");
+    let script = dir.join("avifenc");
+    std::fs::write(&script, "#!/bin/sh\necho 'ERROR: Failed to encode' >&2\nexit 1\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let app = spawn_app_with(|c| {
+        c.avif_animated = true;
+        c.avif_binary = script.display().to_string();
+    })
+    .await;
+
+    let res = get_accepting(&app, &format!("/e/{ANIMATED_ID}.webp"), "image/avif").await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp");
+    assert!(emoji_resizer::imaging::is_animated_webp(&res.bytes().await.unwrap()));
+}
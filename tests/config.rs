@@ -0,0 +1,37 @@
+//! 설정 파일 읽기
+
+use emoji_resizer::config::parse_file;
+
+#[test]
+fn config_file_reads_flat_toml() {
+    let text = r#"
+# 배포별 설정
+bind_addr = "127.0.0.1:8080"
+CACHE_CAPACITY = 20000   # 항목 수
+output_sizes = [64, 160, 320]
+passthrough_default = true
+UPSTREAM_BASE_URL = 'https://cdn.example.com'
+ADMIN_TOKEN = "a#b"
+"#;
+    let entries = parse_file(text).unwrap();
+    let pairs: Vec<_> = entries.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    assert_eq!(
+        pairs,
+        [
+            ("ADMIN_TOKEN", "a#b"),
+            ("BIND_ADDR", "127.0.0.1:8080"),
+            ("CACHE_CAPACITY", "20000"),
+            ("OUTPUT_SIZES", "64,160,320"),
+            ("PASSTHROUGH_DEFAULT", "true"),
+            ("UPSTREAM_BASE_URL", "https://cdn.example.com"),
+        ]
+    );
+}
+
+#[test]
+fn config_file_rejects_tables_bare_lines_and_duplicates() {
+    assert!(parse_file("[cache]\ncapacity = 1").is_err());
+    assert!(parse_file("cache = { capacity = 1 }").is_err());
+    assert!(parse_file("CACHE_CAPACITY").is_err());
+    assert!(parse_file("cache_capacity = 1\nCACHE_CAPACITY = 2").is_err());
+}
@@ -0,0 +1,105 @@
+//! libwebp 인코더 (`cargo test --features libwebp`). 실제 `cwebp` 대신 인자를 기록하고 준비한
+//! WebP를 내놓는 가짜 스크립트를 쓴다.
+
+#![cfg(feature = "libwebp")]
+
+mod support;
+
+use emoji_resizer::{
+    config::{Quality, QualityMatrix},
+    effects::Effects,
+    imaging::{self, WebpOptions},
+};
+use reqwest::StatusCode;
+use std::os::unix::fs::PermissionsExt;
+use support::*;
+
+/// 마지막 인자(`-o` 뒤의 출력 경로)에 `output`을 복사한다
+fn fake_cwebp(dir: &std::path::Path, output: &[u8]) -> std::path::PathBuf {
+    let fixture = dir.join("fixture.webp");
+    std::fs::write(&fixture, output).unwrap();
+    let script = dir.join("cwebp");
+    let body = format!(
+        "#!/bin/sh\necho \"$@\" > {args}\nfor out; do :; done\ncp {fixture} \"$out\"\n",
+        args = dir.join("args").display(),
+        fixture = fixture.display(),
+    );
+    std::fs::write(&script, body).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+#[tokio::test]
+async fn static_outputs_use_the_configured_cwebp_knobs() {
+    let dir = temp_dir("cwebp");
+    let output = static_webp();
+    let binary = fake_cwebp(&dir, &output);
+    let app = spawn_app_with(|c| {
+        c.webp = WebpOptions { method: 6, near_lossless: 60, binary: binary.display().to_string(), ..WebpOptions::default() };
+    })
+    .await;
+
+    let res = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.bytes().await.unwrap(), output);
+    let args = std::fs::read_to_string(dir.join("args")).unwrap();
+    assert!(args.starts_with("-quiet -m 6 -lossless -near_lossless 60 "), "{args}");
+    assert!(args.contains(".pam -o "), "{args}");
+
+    // 목표 크기를 주면 손실 압축으로 바꾼다
+    let options = WebpOptions { target_size: 4096, binary: binary.display().to_string(), ..WebpOptions::default() };
+    tokio::task::spawn_blocking(move || imaging::resize_static(&static_webp(), 64, &options, &Effects::NONE, &imaging::Cancel::default()))
+        .await
+        .unwrap()
+        .unwrap();
+    let args = std::fs::read_to_string(dir.join("args")).unwrap();
+    assert!(args.starts_with("-quiet -m 4 -size 4096 ") && !args.contains("-lossless"), "{args}");
+}
+
+#[tokio::test]
+async fn the_quality_matrix_picks_lossy_quality_per_size() {
+    let dir = temp_dir("cwebp-quality");
+    let binary = fake_cwebp(&dir, &static_webp());
+    let app = spawn_app_with(|c| {
+        c.output_sizes = vec![64, 160];
+        c.webp = WebpOptions { binary: binary.display().to_string(), ..WebpOptions::default() };
+        c.quality = QualityMatrix {
+            default: Quality { webp: Some(80), avif: Some(55) },
+            sizes: [(64, Quality { webp: Some(70), avif: None })].into(),
+        };
+    })
+    .await;
+
+    let res = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let args = std::fs::read_to_string(dir.join("args")).unwrap();
+    assert!(args.starts_with("-quiet -m 4 -q 80 ") && !args.contains("-lossless"), "{args}");
+
+    // 작은 크기는 그 크기의 화질로
+    let res = app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let args = std::fs::read_to_string(dir.join("args")).unwrap();
+    assert!(args.starts_with("-quiet -m 4 -q 70 "), "{args}");
+}
+
+#[test]
+fn cwebp_failures_fall_back_to_the_builtin_encoder() {
+    let options = WebpOptions { binary: "/nonexistent/cwebp".into(), ..WebpOptions::default() };
+    let out = imaging::resize_static(&static_webp(), 64, &options, &Effects::NONE, &imaging::Cancel::default()).unwrap();
+    assert_eq!(out.resized, (64, 64));
+    assert!(image::load_from_memory(&out.bytes).is_ok());
+}
+
+#[test]
+fn a_cwebp_past_the_deadline_is_killed_and_falls_back() {
+    let dir = temp_dir("cwebp-slow");
+    let script = dir.join("cwebp");
+    std::fs::write(&script, "#!/bin/sh\nsleep 10\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let options = WebpOptions { binary: script.display().to_string(), ..WebpOptions::default() };
+    let started = std::time::Instant::now();
+    let cancel = imaging::Cancel::at(Some(started + std::time::Duration::from_millis(300)));
+    let out = imaging::resize_static(&static_webp(), 64, &options, &Effects::NONE, &cancel).unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert!(image::load_from_memory(&out.bytes).is_ok());
+}
@@ -0,0 +1,42 @@
+//! 처리 경로 에러의 상태 코드·재시도 판단과 응답 변환
+
+use axum::{http::StatusCode, response::IntoResponse};
+use emoji_resizer::{error::Error, upstream::Source};
+
+#[test]
+fn upstream_failures_carry_source_and_retryability() {
+    let source = Source::Slack;
+    let cases = [
+        (Error::UpstreamTimeout { source }, StatusCode::GATEWAY_TIMEOUT, true),
+        (Error::UpstreamUnreachable { source }, StatusCode::BAD_GATEWAY, true),
+        (Error::UpstreamStatus { source, status: StatusCode::SERVICE_UNAVAILABLE }, StatusCode::BAD_GATEWAY, true),
+        (Error::UpstreamStatus { source, status: StatusCode::TOO_MANY_REQUESTS }, StatusCode::BAD_GATEWAY, true),
+        (Error::UpstreamStatus { source, status: StatusCode::FORBIDDEN }, StatusCode::BAD_GATEWAY, false),
+        (Error::UpstreamInvalid { source, reason: "html" }, StatusCode::BAD_GATEWAY, false),
+    ];
+    for (error, status, retryable) in cases {
+        assert_eq!(error.status(), status, "{error:?}");
+        assert_eq!(error.retryable(), retryable, "{error:?}");
+        assert_eq!(error.source(), Some(Source::Slack));
+    }
+    assert_eq!(
+        Error::UpstreamStatus { source, status: StatusCode::FORBIDDEN }.to_string(),
+        "upstream error (slack upstream, status 403 Forbidden)"
+    );
+    assert_eq!(Error::UpstreamStatus { source, status: StatusCode::FORBIDDEN }.code(), "upstream_forbidden");
+    assert_eq!(Error::UpstreamStatus { source, status: StatusCode::BAD_GATEWAY }.code(), "upstream_unavailable");
+    assert_eq!(Error::UpstreamStatus { source, status: StatusCode::GONE }.code(), "upstream_status");
+    assert_eq!((Error::Blocked.source(), Error::Blocked.retryable()), (None, false));
+    assert!(Error::DeadlineExceeded.retryable());
+}
+
+#[tokio::test]
+async fn responses_keep_the_short_body_and_expose_the_error() {
+    let error = Error::UpstreamTooLarge { source: Source::Discord };
+    let res = error.into_response();
+    assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(res.extensions().get::<Error>(), Some(&error));
+    assert_eq!(res.headers()["x-error-code"], "upstream_too_large");
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], b"upstream payload too large");
+}
@@ -0,0 +1,126 @@
+//! 인코더 파이프라인 골든 이미지 회귀 테스트.
+//!
+//! 고정된 합성 입력을 파이프라인에 통과시킨 결과를 `tests/golden/`에 저장된 기준
+//! 이미지와 픽셀 단위로 비교한다. 인코더나 필터가 바뀌어 약간 달라지는 것은 PSNR
+//! 허용치 안에서 통과시키고, 눈에 띄게 나빠지면 실패한다.
+//! 의도한 변경이라면 `UPDATE_GOLDEN=1 cargo test --test golden`으로 기준을 다시 만든다.
+
+use emoji_resizer::{
+    effects::Effects,
+    imaging::{self, AnimFrame},
+};
+use image::{codecs::webp::WebPDecoder, AnimationDecoder, DynamicImage, Rgba, RgbaImage};
+use std::{io::Cursor, path::PathBuf};
+
+/// 이보다 PSNR이 낮으면(dB) 회귀로 본다. 무손실 WebP라 보통은 완전히 같다.
+const MIN_PSNR: f64 = 40.0;
+
+const SIZES: [u32; 3] = [32, 64, 160];
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name)
+}
+
+/// 색 그라디언트 + 체커보드 + 반투명 원: 필터 링잉과 알파 처리를 모두 드러내는 패턴
+fn pattern(w: u32, h: u32, phase: u8) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(w, h, |x, y| {
+        let checker = ((x / 8 + y / 8) % 2) as u8 * 64;
+        let (cx, cy) = (x as i64 - w as i64 / 2, y as i64 - h as i64 / 2);
+        let r = (w.min(h) / 3) as i64;
+        let alpha = if cx * cx + cy * cy < r * r { 160 } else { 255 };
+        Rgba([
+            ((x * 255) / w) as u8 ^ checker,
+            ((y * 255) / h) as u8,
+            phase.wrapping_add(checker),
+            alpha,
+        ])
+    }))
+}
+
+fn psnr(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    let mse = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&x, &y)| (x as f64 - y as f64).powi(2))
+        .sum::<f64>()
+        / a.as_raw().len() as f64;
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0f64.powi(2) / mse).log10()
+    }
+}
+
+fn decode_frames(bytes: &[u8]) -> Vec<RgbaImage> {
+    let decoder = WebPDecoder::new(Cursor::new(bytes)).unwrap();
+    if decoder.has_animation() {
+        decoder
+            .into_frames()
+            .collect_frames()
+            .unwrap()
+            .into_iter()
+            .map(|f| f.into_buffer())
+            .collect()
+    } else {
+        vec![image::load_from_memory(bytes).unwrap().to_rgba8()]
+    }
+}
+
+/// 파이프라인 출력 `actual`을 골든 파일과 비교한다
+fn check_golden(name: &str, actual: &[u8]) {
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read(&path).unwrap_or_else(|_| {
+        panic!("missing golden {}; run with UPDATE_GOLDEN=1 to create it", path.display())
+    });
+
+    let (got, want) = (decode_frames(actual), decode_frames(&expected));
+    assert_eq!(got.len(), want.len(), "{name}: frame count changed");
+    for (i, (g, w)) in got.iter().zip(&want).enumerate() {
+        assert_eq!(g.dimensions(), w.dimensions(), "{name}: frame {i} dimensions changed");
+        let score = psnr(g, w);
+        assert!(score >= MIN_PSNR, "{name}: frame {i} PSNR {score:.1} dB < {MIN_PSNR} dB");
+    }
+}
+
+fn webp(img: &DynamicImage) -> Vec<u8> {
+    imaging::encode_webp(img).unwrap()
+}
+
+/// 정적 WebP, 박스에 맞춤(종횡비 유지) — 정사각/가로/세로 입력 x 출력 크기
+#[test]
+fn static_webp_fit() {
+    let inputs = [("square", 100, 100), ("wide", 240, 90), ("tall", 64, 200)];
+    for (label, w, h) in inputs {
+        let input = webp(&pattern(w, h, 0));
+        for size in SIZES {
+            let out = imaging::resize_static(&input, size, &imaging::WebpOptions::default(), &Effects::NONE, &imaging::Cancel::default()).unwrap();
+            let (ow, oh) = out.resized;
+            assert!(ow <= size && oh <= size && (ow == size || oh == size), "{label}@{size}: {ow}x{oh}");
+            check_golden(&format!("static-{label}-{size}.webp"), &out.bytes);
+        }
+    }
+}
+
+/// 애니메이션 WebP, 프레임별로 박스에 맞춤
+#[test]
+fn animated_webp_fit() {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let frames: Vec<_> = [0u8, 96, 192]
+        .iter()
+        .map(|&phase| AnimFrame {
+            webp: webp(&pattern(120, 80, phase)),
+            delay_ms: 70,
+        })
+        .collect();
+    let input = imaging::mux_animated_webp(120, 80, 0, &frames).unwrap();
+
+    for size in SIZES {
+        let out = imaging::resize_animated_webp(&input, size, &Effects::NONE, &pool, &imaging::Cancel::default(), None).unwrap();
+        check_golden(&format!("animated-wide-{size}.webp"), &out.bytes);
+    }
+}
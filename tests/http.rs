@@ -0,0 +1,2301 @@
+//! 가짜 업스트림을 상대로 한 HTTP 수준 통합 테스트
+
+mod support;
+
+use image::{codecs::webp::WebPDecoder, AnimationDecoder, GenericImageView, ImageDecoder};
+use emoji_resizer::{experiment::Experiment, imaging::WebpOptions, tenant::Tenant};
+use reqwest::{header, StatusCode};
+use std::{collections::HashMap, io::Cursor, sync::Arc, time::Duration};
+use support::*;
+
+#[tokio::test]
+async fn health_and_readiness() {
+    let app = spawn_app().await;
+    assert_eq!(app.get("/healthz").await.status(), StatusCode::OK);
+    // 부팅 자체 점검이 통과해야 준비 완료
+    assert_eq!(app.get("/readyz").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn readiness_waits_for_the_warm_up_seed() {
+    let dir = temp_dir("warm-seed");
+    let seed = dir.join("seed.txt");
+    std::fs::write(&seed, format!("# 인기 이모지\n{STATIC_ID}\n{ANIMATED_ID}\n\n{REMOVED_ID}\n{STATIC_ID}\n{SLOW_ID}\n")).unwrap();
+
+    // 느린 이모지까지 기다리면 아직 준비 안 됨
+    let waiting = spawn_app_with(|c| {
+        c.warm_seed_file = Some(seed.clone());
+        c.ready_warm_fraction = 1.0;
+        c.upstreams.discord.timeout = Duration::from_secs(10);
+    })
+    .await;
+    let res = waiting.get("/readyz").await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(res.text().await.unwrap().starts_with("warming up ("));
+
+    // 네 개 중 세 개면 느린 이모지를 기다리지 않는다
+    let app = spawn_app_with(|c| {
+        c.warm_seed_file = Some(seed.clone());
+        c.ready_warm_fraction = 0.75;
+        c.upstreams.discord.timeout = Duration::from_secs(10);
+    })
+    .await;
+    let mut ready = false;
+    for _ in 0..40 {
+        if app.get("/readyz").await.status() == StatusCode::OK {
+            ready = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(ready);
+    assert_eq!(app.upstream.hits(STATIC_ID), 1);
+    assert_eq!(app.get(&format!("/e/{ANIMATED_ID}.webp")).await.headers()["x-cache"], "HIT");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn admin_config_dump_redacts_secrets() {
+    let app = spawn_app_with(|c| {
+        c.admin_token = Some("admin-secret".into());
+        c.custom_signing_key = Some("sign-key".into());
+        c.slack_tokens = HashMap::from([("acme".to_string(), "xoxb-secret".to_string())]);
+        c.cache_lease = Duration::from_millis(250);
+    })
+    .await;
+    assert_eq!(app.get("/admin/config").await.status(), StatusCode::UNAUTHORIZED);
+    let res = app.client.get(app.url("/admin/config")).bearer_auth("admin-secret").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.text().await.unwrap();
+    assert!(!body.contains("admin-secret") && !body.contains("sign-key") && !body.contains("xoxb-secret"), "{body}");
+    let config: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(config["admin_token"], "<redacted>");
+    assert_eq!(config["custom_upload_token"], serde_json::Value::Null);
+    assert_eq!(config["slack_tokens"], serde_json::json!({ "acme": "<redacted>" }));
+    assert_eq!(config["cache_lease"], 0.25);
+    assert_eq!(config["emoji_cache"]["max_age"], 86400);
+    assert_eq!(config["upstreams"]["discord"]["http"], "h2");
+}
+
+#[tokio::test]
+async fn admin_cache_preview_shows_the_stored_bytes_privately() {
+    let app = spawn_app_with(|c| {
+        c.admin_token = Some("admin-secret".into());
+        c.output_sizes = vec![64, 160];
+    })
+    .await;
+    let preview = |key: &str| app.client.get(app.url(&format!("/admin/cache/{key}/preview"))).bearer_auth("admin-secret").send();
+    assert_eq!(preview(STATIC_ID).await.unwrap().status(), StatusCode::NOT_FOUND);
+
+    let served = app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await.bytes().await.unwrap();
+    let res = preview(&format!("{STATIC_ID}@64")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp");
+    assert_eq!(res.headers()[header::CONTENT_DISPOSITION], format!("inline; filename=\"{STATIC_ID}@64.webp\""));
+    assert_eq!(res.headers()[header::CACHE_CONTROL], "private, no-store");
+    assert_eq!(res.headers()["x-cache-layer"], "memory");
+    assert_eq!(res.bytes().await.unwrap(), served);
+
+    // 미리 보기는 서빙 경로를 타지 않으므로 업스트림도 다시 부르지 않는다
+    assert_eq!(app.upstream.hits(STATIC_ID), 1);
+    assert_eq!(app.client.get(app.url(&format!("/admin/cache/{STATIC_ID}@64/preview"))).send().await.unwrap().status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn admin_errors_lists_recent_failures_per_emoji() {
+    let app = spawn_app_with(|c| {
+        c.admin_token = Some("admin-secret".into());
+        c.recent_errors = 2;
+    })
+    .await;
+    let errors = |query: &str| {
+        let req = app.client.get(app.url(&format!("/admin/errors{query}"))).bearer_auth("admin-secret").send();
+        async move { req.await.unwrap().json::<serde_json::Value>().await.unwrap() }
+    };
+    assert_eq!(errors("").await, serde_json::json!([]));
+
+    let res = app.client.get(app.url(&format!("/e/{ERROR_ID}.webp"))).header("x-request-id", "req-1").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(app.get(&format!("/e/{MISSING_ID}.webp")).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.status(), StatusCode::OK);
+
+    let list = errors("").await;
+    assert_eq!(list.as_array().unwrap().len(), 2);
+    assert_eq!(list[0]["emoji"], MISSING_ID);
+    assert_eq!(list[0]["stage"], "fetch");
+    assert_eq!(list[0]["status"], 404);
+    let entry = &list[1];
+    assert_eq!(entry["emoji"], ERROR_ID);
+    assert_eq!(entry["stage"], "fetch");
+    assert_eq!(entry["error"], "upstream_status");
+    assert_eq!(entry["upstream"], "discord");
+    assert_eq!(entry["upstream_status"], 500);
+    assert_eq!(entry["request_id"], "req-1");
+    assert!(entry["at"].as_u64().unwrap() > 0);
+
+    let filtered = errors(&format!("?emoji={ERROR_ID}")).await;
+    assert_eq!(filtered.as_array().unwrap().len(), 1);
+    assert_eq!(errors("?limit=1").await[0]["emoji"], MISSING_ID);
+
+    // 용량을 넘으면 가장 오래된 것부터 버린다
+    app.get(&format!("/e/{MISSING_ID}.webp")).await;
+    assert_eq!(errors(&format!("?emoji={ERROR_ID}")).await, serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn admin_slo_reports_miss_latency_per_source() {
+    let app = spawn_app_with(|c| {
+        c.admin_token = Some("admin-secret".into());
+        c.slo_miss_target = Duration::from_secs(30);
+    })
+    .await;
+    let slo = || {
+        let req = app.client.get(app.url("/admin/slo")).bearer_auth("admin-secret").send();
+        async move { req.await.unwrap().json::<serde_json::Value>().await.unwrap() }
+    };
+    assert_eq!(slo().await["sources"], serde_json::json!({}));
+
+    // 미스만 센다 (두 번째 요청은 히트)
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.status(), StatusCode::OK);
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.status(), StatusCode::OK);
+    let summary = slo().await;
+    assert_eq!(summary["target_ms"], 30_000);
+    assert_eq!(summary["objective"], 0.99);
+    let discord = &summary["sources"]["discord"];
+    assert_eq!(discord["misses"], 1);
+    assert_eq!(discord["slow"], 0);
+    assert_eq!(discord["burn_rate"], 0.0);
+    assert!(discord["p99_ms"].as_f64().unwrap() > 0.0);
+
+    let metrics = app.get("/metrics").await.text().await.unwrap();
+    assert!(metrics.contains("emoji_resizer_slo_burn_rate{source=\"discord\"}"));
+    assert!(metrics.contains("emoji_resizer_miss_latency_seconds{source=\"discord\",quantile=\"0.99\"}"));
+}
+
+#[tokio::test]
+async fn test_page_renders_every_size_side_by_side() {
+    let app = spawn_app_with(|c| c.output_sizes = vec![64, 160]).await;
+    let res = app.get(&format!("/e/{STATIC_ID}/test")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "text/html; charset=utf-8");
+    assert_eq!(res.headers()[header::CACHE_CONTROL], "no-store");
+    let page = res.text().await.unwrap();
+    for src in [
+        format!("/e/{STATIC_ID}.webp?size=64"),
+        format!("/e/{STATIC_ID}.webp"),
+        format!("/e/{STATIC_ID}.webp?passthrough=1"),
+        format!("/e/{STATIC_ID}/original"),
+    ] {
+        assert!(page.contains(&format!("<img src=\"{src}\"")), "{src} missing from {page}");
+    }
+    assert!(page.contains("image/webp · "));
+
+    // 표를 만들면서 캐시에 넣었으므로 페이지가 불러오는 이미지는 HIT
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await.headers()["x-cache"], "HIT");
+    assert_eq!(app.get(&format!("/e/{MISSING_ID}/test")).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn placeholder_is_a_tiny_blurred_still_with_a_thumbhash() {
+    let app = spawn_app().await;
+    let path = format!("/e/{ANIMATED_ID}/placeholder");
+    let first = app.get(&path).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(first.headers()[header::CONTENT_TYPE], "image/webp");
+    assert_eq!(first.headers()["x-cache"], "MISS");
+    let hash = first.headers()["x-thumbhash"].to_str().unwrap().to_string();
+    assert!(hash.len() >= 28 && hash.len() % 4 == 0, "{hash}");
+    let etag = first.headers()[header::ETAG].clone();
+    let img = image::load_from_memory(&first.bytes().await.unwrap()).unwrap();
+    assert!(img.width() <= 16 && img.height() <= 16, "{:?}", img.dimensions());
+
+    // 같은 원본이면 다시 만들지 않는다
+    let second = app.client.head(app.url(&path)).send().await.unwrap();
+    assert_eq!(second.headers()["x-cache"], "HIT");
+    assert_eq!(second.headers()["x-thumbhash"], hash.as_str());
+    let res = app.client.get(app.url(&path)).header(header::IF_NONE_MATCH, etag).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(app.upstream.hits(ANIMATED_ID), 1);
+
+    assert_eq!(app.get(&format!("/e/{MISSING_ID}/placeholder")).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn resized_responses_and_info_carry_the_thumbhash_and_dominant_color() {
+    let app = spawn_app().await;
+    let first = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(first.headers()["x-cache"], "MISS");
+    let hash = first.headers()["x-thumbhash"].to_str().unwrap().to_string();
+    let color = first.headers()["x-dominant-color"].to_str().unwrap().to_string();
+    assert!(color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit()), "{color}");
+    let hit = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(hit.headers()["x-cache"], "HIT");
+    assert_eq!(hit.headers()["x-thumbhash"], hash.as_str());
+    assert_eq!(hit.headers()["x-dominant-color"], color.as_str());
+    // 원본 통과는 리사이즈 결과가 아니므로 싣지 않는다
+    assert!(app.get(&format!("/e/{STATIC_ID}.webp?passthrough=1")).await.headers().get("x-thumbhash").is_none());
+
+    let info: serde_json::Value = app.get(&format!("/e/{STATIC_ID}/info")).await.json().await.unwrap();
+    assert_eq!(info["thumbhash"], hash.as_str());
+    assert_eq!(info["dominant_color"], color.as_str());
+    assert_eq!(info["content_type"], "image/webp");
+    assert_eq!((info["width"].as_u64(), info["height"].as_u64()), (Some(160), Some(160)));
+    assert_eq!(info["animated"], false);
+    assert_eq!(info["url"], format!("/e/{STATIC_ID}.webp"));
+    assert_eq!(info["placeholder"], format!("/e/{STATIC_ID}/placeholder"));
+
+    let animated: serde_json::Value = app.get(&format!("/e/{ANIMATED_ID}/info")).await.json().await.unwrap();
+    assert_eq!(animated["animated"], true);
+    assert!(animated["thumbhash"].is_string());
+    assert_eq!(app.upstream.hits(STATIC_ID), 2);
+    assert_eq!(app.get(&format!("/e/{MISSING_ID}/info")).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn static_emoji_is_resized_and_cached() {
+    let app = spawn_app().await;
+    let path = format!("/e/{STATIC_ID}.webp");
+
+    let first = app.get(&path).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(first.headers()[header::CONTENT_TYPE], "image/webp");
+    assert_eq!(first.headers()["x-cache"], "MISS");
+    let body = first.bytes().await.unwrap();
+    let img = image::load_from_memory(&body).unwrap();
+    assert_eq!(img.dimensions(), (160, 160));
+
+    let second = app.get(&path).await;
+    assert_eq!(second.headers()["x-cache"], "HIT");
+    assert_eq!(second.bytes().await.unwrap(), body);
+    assert_eq!(app.upstream.hits(STATIC_ID), 1);
+}
+
+#[tokio::test]
+async fn equivalent_paths_share_one_cache_entry() {
+    let app = spawn_app().await;
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.headers()["x-cache"], "MISS");
+    for path in [format!("/e/{STATIC_ID}.WEBP/"), format!("/e/{STATIC_ID}..webp"), format!("/e/{STATIC_ID}.WebP?size=160")] {
+        let res = app.get(&path).await;
+        assert_eq!(res.status(), StatusCode::OK, "{path}");
+        assert_eq!(res.headers()["x-cache"], "HIT", "{path}");
+    }
+    assert_eq!(app.upstream.hits(STATIC_ID), 1);
+    assert_eq!(app.get("/healthz/").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn conditional_request_returns_304_with_validators() {
+    let app = spawn_app().await;
+    let path = format!("/e/{STATIC_ID}.webp");
+
+    let first = app.get(&path).await;
+    let etag = first.headers()[header::ETAG].clone();
+    let cache_control = first.headers()[header::CACHE_CONTROL].clone();
+
+    let res = app
+        .client
+        .get(app.url(&path))
+        .header(header::IF_NONE_MATCH, etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(res.headers()[header::ETAG], etag);
+    assert_eq!(res.headers()[header::CACHE_CONTROL], cache_control);
+}
+
+#[tokio::test]
+async fn edge_revalidation_matches_weakly_and_reports_age() {
+    let app = spawn_app().await;
+    let path = format!("/e/{STATIC_ID}.webp");
+    let conditional = |tag: String| app.client.get(app.url(&path)).header(header::IF_NONE_MATCH, tag).send();
+
+    // 새로 만든 응답에는 Age가 없다
+    let first = app.get(&path).await;
+    assert!(first.headers().get(header::AGE).is_none());
+    let etag = first.headers()[header::ETAG].to_str().unwrap().to_string();
+    let strong = etag.trim_start_matches("W/").to_string();
+    assert_ne!(strong, etag);
+
+    // 엣지가 `W/`를 뗀 태그나 여러 태그를 보내도 맞춘다
+    for tag in [strong.clone(), format!("\"other\", {etag}")] {
+        let res = conditional(tag.clone()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED, "{tag}");
+        assert_eq!(res.headers()[header::ETAG], etag.as_str());
+        assert!(res.headers().contains_key(header::AGE));
+    }
+    assert_eq!(conditional("\"other\"".into()).await.unwrap().status(), StatusCode::OK);
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let hit = app.get(&path).await;
+    assert_eq!(hit.headers()["x-cache"], "HIT");
+    assert_eq!(hit.headers()[header::ETAG], etag.as_str());
+    assert!(hit.headers()[header::AGE].to_str().unwrap().parse::<u64>().unwrap() >= 1);
+}
+
+#[tokio::test]
+async fn animated_emoji_keeps_its_frames() {
+    let app = spawn_app().await;
+    let res = app.get(&format!("/e/{ANIMATED_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["x-animation-encode"], "bits=8; frame-step=1; fits=1");
+    let body = res.bytes().await.unwrap();
+
+    let decoder = WebPDecoder::new(Cursor::new(&body[..])).unwrap();
+    assert!(decoder.has_animation());
+    let frames = decoder.into_frames().collect_frames().unwrap();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0].buffer().dimensions(), (160, 160));
+}
+
+#[tokio::test]
+async fn animated_gif_is_composited_into_animated_webp() {
+    let app = spawn_app().await;
+    let res = app.get(&format!("/e/{ANIMATED_GIF_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp");
+    let body = res.bytes().await.unwrap();
+
+    let frames = WebPDecoder::new(Cursor::new(&body[..])).unwrap().into_frames().collect_frames().unwrap();
+    assert_eq!(frames.len(), 4);
+    assert_eq!(frames[0].buffer().dimensions(), (160, 160));
+    // 배경으로 폐기하므로 마지막 프레임에 첫 사각형이 남지 않는다
+    assert_eq!(frames[3].buffer().get_pixel(20, 60).0[3], 0);
+    assert_eq!(frames[3].buffer().get_pixel(140, 60).0, [0, 0, 255, 255]);
+}
+
+#[tokio::test]
+async fn oversized_animation_steps_down_until_the_floor() {
+    let app = spawn_app_with(|c| {
+        c.animated_budget = Some(emoji_resizer::imaging::Budget { max_bytes: 64, min_fps: 5.0 });
+    })
+    .await;
+    let res = app.get(&format!("/e/{ANIMATED_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    // 64바이트에는 절대 못 들어가므로 바닥까지 내려간 가장 작은 결과
+    let params = res.headers()["x-animation-encode"].to_str().unwrap().to_string();
+    assert!(params.ends_with("fits=0"), "{params}");
+    let body = res.bytes().await.unwrap();
+
+    let frames = WebPDecoder::new(Cursor::new(&body[..]))
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    // 240ms짜리 3프레임: 2프레임(8.3fps)까지는 솎지만 1프레임으로는 줄이지 않는다
+    assert!(params.contains("frame-step=2"), "{params}");
+    assert_eq!(frames.len(), 2);
+    let total: u32 = frames.iter().map(|f| f.delay().numer_denom_ms().0).sum();
+    assert_eq!(total, 240);
+}
+
+#[tokio::test]
+async fn broken_animation_falls_back_to_a_static_first_frame() {
+    let app = spawn_app().await;
+    let res = app.get(&format!("/e/{TRUNCATED_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp");
+    assert!(res.headers().get("x-animation-encode").is_none());
+    let body = res.bytes().await.unwrap();
+
+    let decoder = WebPDecoder::new(Cursor::new(&body[..])).unwrap();
+    assert!(!decoder.has_animation());
+    assert_eq!(image::load_from_memory(&body).unwrap().dimensions(), (160, 160));
+}
+
+#[tokio::test]
+async fn encoder_experiment_tags_the_misses_it_encodes() {
+    let app = spawn_app_with(|c| {
+        let webp = WebpOptions { method: 6, ..c.webp.clone() };
+        c.encoder_experiment = Some(Experiment { name: "method-6".into(), percent: 100.0, webp });
+    })
+    .await;
+    let path = format!("/e/{STATIC_ID}.webp");
+    let miss = app.get(&path).await;
+    assert_eq!(miss.status(), StatusCode::OK);
+    assert_eq!(miss.headers()["x-encoder-profile"], "method-6");
+    // 캐시에서 꺼낸 응답은 새로 인코딩하지 않았으므로 태그가 없다
+    let hit = app.get(&path).await;
+    assert_eq!(hit.headers()["x-cache"], "HIT");
+    assert!(hit.headers().get("x-encoder-profile").is_none());
+    let metrics = app.get("/metrics").await.text().await.unwrap();
+    assert!(metrics.contains(r#"emoji_resizer_encoder_profile_total{profile="method-6"}"#), "{metrics}");
+    assert!(metrics.contains(r#"emoji_resizer_encoder_profile_bytes_bucket{profile="method-6""#), "{metrics}");
+
+    let never = Experiment { name: "off".into(), percent: 0.0, webp: WebpOptions::default() };
+    assert!((0..1000).all(|_| !never.pick()));
+}
+
+#[tokio::test]
+async fn tenant_defaults_split_cache_keys_only_when_they_change_the_output() {
+    let app = spawn_app_with(|c| {
+        c.output_sizes = vec![64, 160];
+        c.admin_token = Some("secret".into());
+        let lossy = WebpOptions { near_lossless: 80, ..c.webp.clone() };
+        let tenant = |name: &str, size, webp: &WebpOptions| Tenant { name: name.into(), key: format!("{name}-key"), size, webp: webp.clone() };
+        c.tenants = vec![tenant("small", 64, &c.webp), tenant("lossy", 160, &lossy), tenant("lossy_too", 160, &lossy)];
+    })
+    .await;
+    let get = |path: String, key: &str| app.client.get(app.url(&path)).header("x-api-key", key).send();
+
+    // 같은 주소라도 키에 따라 답이 달라질 수 있으므로 익명 응답에도 키 헤더를 `Vary`로 싣는다
+    let anonymous = app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await;
+    assert_eq!(anonymous.headers()["x-cache"], "MISS");
+    assert_eq!(anonymous.headers()[header::VARY], "Authorization, X-Api-Key");
+
+    // 기본 크기만 다른 테넌트는 크기를 안 적어도 그 크기의 공용 항목을 쓴다
+    let small = get(format!("/e/{STATIC_ID}.webp"), "small-key").await.unwrap();
+    assert_eq!(small.headers()["x-cache"], "HIT");
+    let body = small.bytes().await.unwrap();
+    assert_eq!(image::load_from_memory(&body).unwrap().dimensions(), (64, 64));
+
+    // 인코더 설정이 다르면 따로 만들고, 설정이 같은 테넌트끼리는 나눠 쓴다
+    let lossy = get(format!("/e/{STATIC_ID}.webp?size=64"), "lossy-key").await.unwrap();
+    assert_eq!(lossy.headers()["x-cache"], "MISS");
+    let shared = get(format!("/e/{STATIC_ID}.webp?size=64"), "lossy_too-key").await.unwrap();
+    assert_eq!(shared.headers()["x-cache"], "HIT");
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await.headers()["x-cache"], "HIT");
+
+    let listing: serde_json::Value = app.get(&format!("/e/{STATIC_ID}/variants")).await.json().await.unwrap();
+    let keys: Vec<_> = listing["variants"].as_array().unwrap().iter().map(|v| v["key"].as_str().unwrap().to_string()).collect();
+    assert_eq!(keys, [format!("{STATIC_ID}@64"), format!("{STATIC_ID}@64~webp-m4-n80-t0")]);
+
+    // 테넌트 키는 설정 덤프에서 가린다
+    let config: serde_json::Value =
+        app.client.get(app.url("/admin/config")).bearer_auth("secret").send().await.unwrap().json().await.unwrap();
+    assert_eq!(config["tenants"][1]["name"], "lossy");
+    assert_eq!(config["tenants"][1]["key"], "<redacted>");
+}
+
+#[tokio::test]
+async fn text_responses_are_compressed_but_images_are_not() {
+    let app = spawn_app().await;
+    // 압축된 그대로 받도록 자동 해제를 끈 클라이언트
+    let client = reqwest::Client::builder().no_gzip().build().unwrap();
+    let get = |path: String| client.get(app.url(&path)).header(header::ACCEPT_ENCODING, "gzip").send();
+
+    let res = get(format!("/e/{STATIC_ID}.webp")).await.unwrap();
+    assert!(res.headers().get(header::CONTENT_ENCODING).is_none());
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp");
+
+    let res = get("/metrics".into()).await.unwrap();
+    assert_eq!(res.headers()[header::CONTENT_ENCODING], "gzip");
+    assert_eq!(res.headers()[header::VARY], "accept-encoding");
+    let mut text = String::new();
+    std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(&res.bytes().await.unwrap()[..]), &mut text).unwrap();
+    assert!(text.contains("emoji_resizer"), "{text}");
+}
+
+#[tokio::test]
+async fn upstream_not_found_is_404() {
+    let app = spawn_app().await;
+    assert_eq!(app.get(&format!("/e/{MISSING_ID}.webp")).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn upstream_error_without_stale_copy_is_502() {
+    let app = spawn_app().await;
+    assert_eq!(app.get(&format!("/e/{ERROR_ID}.webp")).await.status(), StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn upstream_failures_get_distinct_codes_and_problem_details() {
+    let app = spawn_app().await;
+    let cases = [
+        (MISSING_ID, StatusCode::NOT_FOUND, "not_found", false),
+        (FORBIDDEN_ID, StatusCode::BAD_GATEWAY, "upstream_forbidden", false),
+        (UNSUPPORTED_ID, StatusCode::BAD_GATEWAY, "upstream_unsupported_media_type", false),
+        (ERROR_ID, StatusCode::BAD_GATEWAY, "upstream_unavailable", true),
+    ];
+    for (id, status, code, retryable) in cases {
+        // 이미지 클라이언트는 짧은 문구 그대로, 코드는 헤더로
+        let res = app.get(&format!("/e/{id}.webp")).await;
+        assert_eq!(res.status(), status, "{id}");
+        assert_eq!(res.headers()["x-error-code"], code);
+        assert_eq!(res.headers()[header::VARY], "accept");
+        assert!(res.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/plain"));
+
+        let res = app
+            .client
+            .get(app.url(&format!("/e/{id}.webp")))
+            .header(header::ACCEPT, "application/problem+json, */*;q=0.1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), status);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "application/problem+json");
+        let problem: serde_json::Value = res.json().await.unwrap();
+        assert_eq!(problem["type"], format!("urn:emoji-resizer:error:{code}"));
+        assert_eq!(problem["code"], code);
+        assert_eq!(problem["status"], status.as_u16());
+        assert_eq!(problem["retryable"], retryable, "{id}");
+        assert!(problem["detail"].as_str().is_some_and(|d| !d.is_empty()));
+    }
+}
+
+#[tokio::test]
+async fn upstream_redirects_follow_the_per_source_policy() {
+    // Discord CDN은 리다이렉트하지 않으므로 기본값은 따라가지 않는다
+    let app = spawn_app().await;
+    let res = app.get(&format!("/e/{REDIRECT_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(res.headers()["x-error-code"], "upstream_redirect");
+    assert_eq!(app.upstream.hits(STATIC_ID), 0);
+
+    let app = spawn_app_with(|c| {
+        c.upstreams.discord.max_redirects = 2;
+        c.upstreams.discord.redirect_hosts = vec!["localhost".into()];
+    })
+    .await;
+    // 같은 호스트 안에서는 따라간다
+    let res = app.get(&format!("/e/{REDIRECT_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(app.upstream.hits(STATIC_ID), 1);
+    // 허용 목록에 있어도 내부 주소로는 가지 않는다
+    let res = app.get(&format!("/e/{OFFSITE_REDIRECT_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(res.headers()["x-error-code"], "upstream_redirect");
+    assert_eq!(app.upstream.hits(STATIC_ID), 1);
+}
+
+#[tokio::test]
+async fn non_image_upstream_body_is_rejected_and_not_cached() {
+    let app = spawn_app().await;
+    let path = format!("/e/{HTML_ID}.webp");
+    assert_eq!(app.get(&path).await.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(app.get(&path).await.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(app.upstream.hits(HTML_ID), 2);
+}
+
+#[tokio::test]
+async fn passthrough_serves_original_bytes_with_their_type() {
+    let app = spawn_app().await;
+
+    let res = app.get(&format!("/e/{GIF_ID}.gif?passthrough=1")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/gif");
+    assert_eq!(res.bytes().await.unwrap(), gif());
+
+    let res = app.get(&format!("/e/{APNG_ID}.png?passthrough=1")).await;
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/png");
+    assert_eq!(res.bytes().await.unwrap(), apng());
+}
+
+#[tokio::test]
+async fn request_deadline_cancels_slow_upstream() {
+    let app = spawn_app().await;
+    let res = app
+        .client
+        .get(app.url(&format!("/e/{SLOW_ID}.webp")))
+        .header("x-request-deadline-ms", "200")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::GATEWAY_TIMEOUT);
+}
+
+#[tokio::test]
+async fn client_disconnects_cancel_or_finish_the_work() {
+    use emoji_resizer::config::ClientDisconnect;
+    let spawn = |policy| {
+        spawn_app_with(move |c| {
+            c.client_disconnect = policy;
+            c.upstreams.discord.timeout = Duration::from_secs(10);
+        })
+    };
+    let (cancel, finish) = (spawn(ClientDisconnect::Cancel).await, spawn(ClientDisconnect::Finish).await);
+    // 첫 요청은 업스트림이 3초 늦게 답하므로 클라이언트가 먼저 포기한다
+    for app in [&cancel, &finish] {
+        let abandoned = app.client.get(app.url(&format!("/e/{FLAKY_ID}.webp"))).timeout(Duration::from_millis(300)).send().await;
+        assert!(abandoned.is_err());
+    }
+    let cached = |app: &TestApp| {
+        let url = app.url(&format!("/e/{FLAKY_ID}/variants"));
+        let client = app.client.clone();
+        async move {
+            let listing: serde_json::Value = client.get(url).send().await.unwrap().json().await.unwrap();
+            listing["variants"].as_array().unwrap().len()
+        }
+    };
+
+    // finish: 버려진 요청도 끝까지 만들어 캐시에 넣는다
+    let mut finished = false;
+    for _ in 0..60 {
+        if cached(&finish).await > 0 {
+            finished = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(finished, "abandoned render was not cached");
+    assert_eq!(finish.get(&format!("/e/{FLAKY_ID}.webp")).await.headers()["x-cache"], "HIT");
+
+    // cancel: 업스트림 요청째 버려져 업스트림이 답한 뒤에도 아무것도 남지 않는다
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(cached(&cancel).await, 0);
+    let metrics = cancel.get("/metrics").await.text().await.unwrap();
+    assert!(metrics.contains(r#"emoji_resizer_client_disconnects_total{outcome="cancelled"}"#), "{metrics}");
+    assert!(metrics.contains(r#"emoji_resizer_client_disconnects_total{outcome="finished"}"#), "{metrics}");
+}
+
+#[tokio::test]
+async fn sampled_requests_are_shadowed_to_staging() {
+    let staging = spawn_app().await;
+    let staging_url = staging.url("");
+    let app = spawn_app_with(|c| {
+        c.shadow_url = Some(staging_url);
+        c.shadow_percent = 100.0;
+    })
+    .await;
+    let res = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    app.get("/healthz").await;
+
+    // 스테이징도 같은 이모지를 받아 만든다 (본 요청은 기다리지 않는다)
+    let mut sent = false;
+    for _ in 0..50 {
+        let metrics = app.get("/metrics").await.text().await.unwrap();
+        if metrics.contains(r#"emoji_resizer_shadow_total{outcome="sent"}"#) {
+            sent = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(sent, "shadowed request never completed");
+    assert_eq!(staging.upstream.hits(STATIC_ID), 1);
+    assert_eq!(staging.upstream.total_hits(), 1);
+}
+
+#[tokio::test]
+async fn upstream_fetches_respect_the_per_host_limits() {
+    let app = spawn_app_with(|c| {
+        c.output_sizes = vec![64, 96, 160];
+        c.upstreams.discord.rate_limit = Some(5.0);
+        c.upstreams.discord.burst = 1;
+    })
+    .await;
+    // 초당 5개, 한꺼번에 1개까지라 두 번째와 세 번째 fetch는 0.2초씩 기다린다
+    let started = std::time::Instant::now();
+    for size in [64, 96, 160] {
+        assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp?size={size}")).await.status(), StatusCode::OK);
+    }
+    assert!(started.elapsed() >= Duration::from_millis(350), "{:?}", started.elapsed());
+    let metrics = app.get("/metrics").await.text().await.unwrap();
+    assert!(metrics.contains(r#"emoji_resizer_upstream_queue_seconds_count{source="discord",priority="high"}"#), "{metrics}");
+
+    // 429를 받으면 `Retry-After` 동안 같은 호스트로 보내지 않는다
+    assert_ne!(app.get(&format!("/e/{THROTTLED_ID}.webp")).await.status(), StatusCode::OK);
+    let started = std::time::Instant::now();
+    assert_eq!(app.get(&format!("/e/{ANIMATED_ID}.webp")).await.status(), StatusCode::OK);
+    assert!(started.elapsed() >= Duration::from_millis(500), "{:?}", started.elapsed());
+    let metrics = app.get("/metrics").await.text().await.unwrap();
+    assert!(metrics.contains(r#"emoji_resizer_upstream_throttled_total{source="discord"}"#), "{metrics}");
+}
+
+#[tokio::test]
+async fn disk_maintenance_removes_expired_and_orphaned_files() {
+    let dir = temp_dir("maintenance");
+    let app = spawn_app_with(|c| {
+        c.cache_dir = Some(dir.clone());
+        c.admin_token = Some("secret".into());
+    })
+    .await;
+
+    let old = std::time::SystemTime::now() - Duration::from_secs(30 * 24 * 3600);
+    let backdate = |path: &std::path::Path| {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"x").unwrap();
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(old).unwrap();
+    };
+    let expired = dir.join("ab").join(format!("ab{}.bin", "0".repeat(38)));
+    let stale_tmp = dir.join("cd").join("cd00.bin.tmp.0123");
+    let stray = dir.join("cd").join("notes.txt");
+    let unrelated = dir.join("README");
+    backdate(&expired);
+    backdate(&stale_tmp);
+    std::fs::write(&stray, b"x").unwrap();
+    std::fs::write(&unrelated, b"x").unwrap();
+
+    let res = app
+        .client
+        .post(app.url("/admin/maintenance"))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    let disk = &body["tiers"]["disk"];
+    assert_eq!(disk["expired"], 1);
+    assert_eq!(disk["orphaned"], 2);
+    assert_eq!(disk["removed_dirs"], 2);
+
+    assert!(!expired.exists() && !stale_tmp.exists() && !stray.exists());
+    // 샤드가 아닌 파일은 건드리지 않음
+    assert!(unrelated.exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn original_endpoint_serves_upstream_bytes_cached_separately() {
+    let app = spawn_app().await;
+
+    let resized = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(resized.status(), StatusCode::OK);
+
+    let first = app.get(&format!("/e/{STATIC_ID}/original")).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(first.headers()[header::CONTENT_TYPE], "image/webp");
+    assert_eq!(first.headers()["x-cache"], "MISS");
+    assert_eq!(first.bytes().await.unwrap(), static_webp());
+
+    let second = app.get(&format!("/e/{STATIC_ID}/original")).await;
+    assert_eq!(second.headers()["x-cache"], "HIT");
+    // 리사이즈 결과와 원본은 각각 한 번씩만 가져온다
+    assert_eq!(app.upstream.hits(STATIC_ID), 2);
+
+    let gif = app.get(&format!("/e/{GIF_ID}/original")).await;
+    assert_eq!(gif.headers()[header::CONTENT_TYPE], "image/gif");
+    assert_eq!(gif.bytes().await.unwrap(), support::gif());
+}
+
+#[tokio::test]
+async fn original_endpoint_rejects_oversized_payloads() {
+    let app = spawn_app_with(|c| c.original_max_bytes = 64).await;
+    let res = app.get(&format!("/e/{STATIC_ID}/original")).await;
+    assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+    // 리사이즈 경로에는 적용하지 않음
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn resize_path_rejects_upstream_bodies_over_the_limit() {
+    let app = spawn_app_with(|c| {
+        c.upstream_max_bytes = 64;
+        c.original_max_bytes = 1 << 20;
+    })
+    .await;
+    let res = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(res.headers()["x-error-code"], "upstream_too_large");
+    // 원본 경로는 `ORIGINAL_MAX_BYTES`를 따른다
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}/original")).await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn endless_upstream_bodies_are_cut_off_at_the_limit() {
+    let endless = spawn_endless().await;
+    let app = spawn_app_with(|c| {
+        c.upstream_base = endless.clone();
+        c.upstreams.discord.http = emoji_resizer::upstream::HttpVersion::Http1;
+        c.upstream_max_bytes = 64 * 1024;
+    })
+    .await;
+    let res = tokio::time::timeout(Duration::from_secs(10), app.get(&format!("/e/{STATIC_ID}.webp"))).await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(res.headers()["x-error-code"], "upstream_too_large");
+}
+
+#[tokio::test]
+async fn corrupted_disk_entry_is_dropped_and_refetched() {
+    use sha1::{Digest, Sha1};
+
+    let dir = temp_dir("corrupt");
+    let hex = format!("{:x}", Sha1::digest(STATIC_ID.as_bytes()));
+    let path = dir.join(&hex[..2]).join(format!("{hex}.bin"));
+    // 크래시로 잘린 파일 흉내: 정상 WebP의 앞부분만
+    let truncated = static_webp()[..40].to_vec();
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, &truncated).unwrap();
+
+    let app = spawn_app_with(|c| c.cache_dir = Some(dir.clone())).await;
+    let res = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["x-cache"], "MISS");
+    let body = res.bytes().await.unwrap();
+    assert_eq!(app.upstream.hits(STATIC_ID), 1);
+
+    // 백그라운드 기록이 정상 항목으로 교체
+    for _ in 0..50 {
+        if std::fs::read(&path).is_ok_and(|b| b == body) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(std::fs::read(&path).unwrap(), body);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn generation_migration_dual_writes_the_previous_generation() {
+    use sha1::{Digest, Sha1};
+
+    let dir = temp_dir("generation");
+    let file = |key: &str| {
+        let hex = format!("{:x}", Sha1::digest(key.as_bytes()));
+        dir.join(&hex[..2]).join(format!("{hex}.bin"))
+    };
+    let migrating = spawn_app_with(|c| {
+        c.cache_dir = Some(dir.clone());
+        c.cache_generation = "v2".into();
+        c.cache_generation_previous = Some(String::new());
+    })
+    .await;
+    let upstream = migrating.upstream.base.clone();
+    assert_eq!(migrating.get(&format!("/e/{STATIC_ID}.webp")).await.status(), StatusCode::OK);
+    for _ in 0..50 {
+        if file(STATIC_ID).exists() && file(&format!("v2/{STATIC_ID}")).exists() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(file(&format!("v2/{STATIC_ID}")).exists());
+    assert!(file(STATIC_ID).exists());
+
+    // 아직 세대를 모르는 인스턴스도 디스크에서 히트
+    let old = spawn_app_with(|c| {
+        c.cache_dir = Some(dir.clone());
+        c.upstream_base = upstream.clone();
+    })
+    .await;
+    let res = old.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(migrating.upstream.hits(STATIC_ID), 1);
+
+    // 이전 세대를 주지 않으면 새 세대 키에만
+    let plain = spawn_app_with(|c| {
+        c.cache_dir = Some(dir.clone());
+        c.upstream_base = upstream;
+        c.cache_generation = "v2".into();
+    })
+    .await;
+    assert_eq!(plain.get(&format!("/e/{ANIMATED_ID}.webp")).await.status(), StatusCode::OK);
+    for _ in 0..50 {
+        if file(&format!("v2/{ANIMATED_ID}")).exists() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(file(&format!("v2/{ANIMATED_ID}")).exists());
+    assert!(!file(ANIMATED_ID).exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn mirrored_originals_outlive_upstream_removal() {
+    use sha1::{Digest, Sha1};
+
+    let dir = temp_dir("mirror");
+    let app = spawn_app_with(|c| {
+        c.mirror_dir = Some(dir.clone());
+        c.output_sizes = vec![64, 160];
+        c.sibling_warm_sizes = vec![];
+    })
+    .await;
+    assert_eq!(app.get(&format!("/e/{REMOVED_ID}.webp")).await.status(), StatusCode::OK);
+
+    // 보관은 백그라운드에서
+    let hex = format!("{:x}", Sha1::digest(REMOVED_ID.as_bytes()));
+    let path = dir.join(&hex[..2]).join(format!("{hex}.bin"));
+    for _ in 0..50 {
+        if path.exists() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(std::fs::read(&path).unwrap(), static_webp());
+
+    // 업스트림에서 지워진 뒤 처음 보는 크기도 보관 사본으로 만든다
+    let res = app.get(&format!("/e/{REMOVED_ID}.webp?size=64")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(app.upstream.hits(REMOVED_ID), 2);
+    let img = image::load_from_memory(&res.bytes().await.unwrap()).unwrap();
+    assert_eq!(img.width(), 64);
+
+    // 보관한 적 없는 이모지는 그대로 404
+    assert_eq!(app.get(&format!("/e/{MISSING_ID}.webp")).await.status(), StatusCode::NOT_FOUND);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn guild_archive_mirrors_originals_and_writes_a_manifest() {
+    let dir = temp_dir("archive");
+    let app = spawn_app_with(|c| {
+        c.mirror_dir = Some(dir.clone());
+        c.admin_token = Some("admin-secret".into());
+        c.output_sizes = vec![64, 160];
+    })
+    .await;
+    let admin = |method: reqwest::Method, guild: &str| {
+        app.client.request(method, app.url(&format!("/admin/archive/{guild}"))).bearer_auth("admin-secret").send()
+    };
+    assert_eq!(admin(reqwest::Method::POST, "not-a-guild").await.unwrap().status(), StatusCode::BAD_REQUEST);
+    assert_eq!(admin(reqwest::Method::POST, "900000000000000099").await.unwrap().status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(admin(reqwest::Method::GET, GUILD_ID).await.unwrap().status(), StatusCode::NOT_FOUND);
+
+    let res = admin(reqwest::Method::POST, GUILD_ID).await.unwrap();
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+    assert_eq!(res.json::<serde_json::Value>().await.unwrap()["queued"], 3);
+
+    let mut manifest = serde_json::Value::Null;
+    for _ in 0..100 {
+        let res = admin(reqwest::Method::GET, GUILD_ID).await.unwrap();
+        if res.status() == StatusCode::OK {
+            manifest = res.json().await.unwrap();
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(manifest["guild_id"], GUILD_ID, "{manifest}");
+    let emojis = manifest["emojis"].as_array().unwrap();
+    let names: Vec<_> = emojis.iter().map(|e| e["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["blob_locked", "blobdance", "party_blob"]);
+    let party = &emojis[2];
+    assert_eq!(party["id"], STATIC_ID);
+    assert_eq!(party["original"]["bytes"], static_webp().len());
+    assert_eq!(party["sizes"], serde_json::json!([64, 160]));
+    assert!(party.get("failed").is_none(), "{party}");
+
+    // 리사이즈 결과는 캐시에 이미 있다
+    let res = app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await;
+    assert_eq!(res.headers()["x-cache"], "HIT");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn evicted_entries_still_answer_revalidation_and_head() {
+    // 메모리 캐시가 바이트를 하나도 들고 있지 못하는 상태
+    let app = spawn_app_with(|c| c.cache_capacity = 0).await;
+    let path = format!("/e/{STATIC_ID}.webp");
+    let first = app.get(&path).await;
+    assert_eq!(first.headers()["x-cache"], "MISS");
+    let etag = first.headers()[header::ETAG].clone();
+    let length = first.bytes().await.unwrap().len();
+
+    let res = app.client.get(app.url(&path)).header(header::IF_NONE_MATCH, etag.clone()).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(res.headers()["x-cache"], "VALIDATED");
+    assert_eq!(res.headers()[header::ETAG], etag);
+    assert!(res.headers().contains_key(header::AGE));
+
+    let res = app.client.head(app.url(&path)).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["x-cache"], "VALIDATED");
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp");
+    assert_eq!(res.headers()[header::CONTENT_LENGTH], length.to_string().as_str());
+    assert_eq!(app.upstream.hits(STATIC_ID), 1);
+
+    // 본문이 필요한 요청과 맞지 않는 검증자는 다시 만든다
+    let res = app.client.get(app.url(&path)).header(header::IF_NONE_MATCH, "\"other\"").send().await.unwrap();
+    assert_eq!((res.status(), res.headers()["x-cache"].to_str().unwrap()), (StatusCode::OK, "MISS"));
+    assert_eq!(app.upstream.hits(STATIC_ID), 2);
+}
+
+#[tokio::test]
+async fn age_survives_promotion_from_the_disk_tier() {
+    use sha1::{Digest, Sha1};
+
+    // 2분 전에 저장된 디스크 항목
+    let dir = temp_dir("age");
+    let hex = format!("{:x}", Sha1::digest(STATIC_ID.as_bytes()));
+    let path = dir.join(&hex[..2]).join(format!("{hex}.bin"));
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, static_webp()).unwrap();
+    let stored = std::time::SystemTime::now() - Duration::from_secs(120);
+    std::fs::File::options().write(true).open(&path).unwrap().set_modified(stored).unwrap();
+
+    let app = spawn_app_with(|c| c.cache_dir = Some(dir.clone())).await;
+    for _ in 0..2 {
+        // 디스크에서 올라온 뒤 메모리에서 꺼내도 저장 시각은 그대로
+        let res = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+        assert_eq!(res.headers()["x-cache"], "HIT");
+        let age: u64 = res.headers()[header::AGE].to_str().unwrap().parse().unwrap();
+        assert!((120..130).contains(&age), "{age}");
+        let precise: f64 = res.headers()["x-cache-age"].to_str().unwrap().parse().unwrap();
+        assert!(precise >= 120.0 && precise < age as f64 + 1.0, "{precise}");
+    }
+    assert_eq!(app.upstream.hits(STATIC_ID), 0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn concurrent_misses_share_one_fetch_and_encode() {
+    let app = Arc::new(spawn_app().await);
+    let path = format!("/e/{LAGGY_ID}.webp");
+    let requests: Vec<_> = (0..20)
+        .map(|_| {
+            let (app, path) = (app.clone(), path.clone());
+            tokio::spawn(async move {
+                let res = app.get(&path).await;
+                let cache = res.headers()["x-cache"].to_str().unwrap().to_string();
+                let timing = res.headers()["server-timing"].to_str().unwrap().to_string();
+                (cache, timing, res.bytes().await.unwrap())
+            })
+        })
+        .collect();
+    let mut results = Vec::new();
+    for request in requests {
+        results.push(request.await.unwrap());
+    }
+    assert_eq!(app.upstream.hits(LAGGY_ID), 1);
+    assert_eq!(results.iter().filter(|(cache, _, _)| cache == "MISS").count(), 1);
+    assert!(results.iter().all(|(_, _, body)| *body == results[0].2));
+    // 기다린 요청은 `coalesce` 구간이 붙는다
+    assert!(results.iter().filter(|(cache, _, _)| cache == "HIT").all(|(_, timing, _)| timing.contains("coalesce")));
+}
+
+#[tokio::test]
+async fn one_off_keys_are_not_admitted_to_memory() {
+    let app = spawn_app_with(|c| c.cache_admit_after = 2).await;
+    let url = format!("/e/{STATIC_ID}.webp");
+    // 처음 본 키는 메모리에 넣지 않으므로 두 번째도 다시 만든다
+    assert_eq!(app.get(&url).await.headers()["x-cache"], "MISS");
+    assert_eq!(app.get(&url).await.headers()["x-cache"], "MISS");
+    assert_eq!(app.get(&url).await.headers()["x-cache"], "HIT");
+    assert_eq!(app.upstream.hits(STATIC_ID), 2);
+}
+
+#[tokio::test]
+async fn oversized_entries_skip_memory_and_are_served_from_disk() {
+    let dir = temp_dir("spill");
+    let app = spawn_app_with(|c| {
+        c.cache_dir = Some(dir.clone());
+        c.cache_spill_bytes = Some(1);
+    })
+    .await;
+    let res = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let etag = res.headers()[header::ETAG].to_str().unwrap().to_string();
+
+    // 디스크 기록은 백그라운드라 올라올 때까지 기다린다
+    let mut layers = serde_json::Value::Null;
+    for _ in 0..50 {
+        let listing: serde_json::Value = app.get(&format!("/e/{STATIC_ID}/variants")).await.json().await.unwrap();
+        layers = listing["variants"][0]["layers"].clone();
+        if layers.as_array().unwrap().contains(&"disk".into()) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(layers, serde_json::json!(["disk", "validator"]));
+
+    let res = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(res.headers()["x-cache"], "HIT");
+    let res = app.client.get(app.url(&format!("/e/{STATIC_ID}.webp"))).header(header::IF_NONE_MATCH, &etag).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(app.upstream.hits(STATIC_ID), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn cold_keys_are_encoded_once_across_instances_sharing_a_disk_tier() {
+    let dir = temp_dir("lease");
+    let a = spawn_app_with(|c| c.cache_dir = Some(dir.clone())).await;
+    let upstream = a.upstream.base.clone();
+    let b = spawn_app_with(|c| {
+        c.cache_dir = Some(dir.clone());
+        c.upstream_base = upstream;
+    })
+    .await;
+
+    let path = format!("/e/{ANIMATED_ID}.webp?size=64");
+    let requests: Vec<_> = (0..6)
+        .map(|i| {
+            let app = if i % 2 == 0 { &a } else { &b };
+            let (client, url) = (app.client.clone(), app.url(&path));
+            tokio::spawn(async move { client.get(url).send().await.unwrap() })
+        })
+        .collect();
+    let mut bodies = Vec::new();
+    for request in requests {
+        let res = request.await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        bodies.push(res.bytes().await.unwrap());
+    }
+    // 한 곳만 가져와 인코딩하고 나머지는 디스크 계층에 올라온 결과를 쓴다
+    assert_eq!(a.upstream.hits(ANIMATED_ID), 1);
+    assert!(bodies.windows(2).all(|w| w[0] == w[1]));
+    // 임대는 디스크 기록을 마친 뒤 백그라운드에서 풀린다
+    let leases = || {
+        std::fs::read_dir(&dir)
+            .unwrap()
+            .flat_map(|shard| std::fs::read_dir(shard.unwrap().path()).unwrap())
+            .filter(|entry| entry.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "lease"))
+            .count()
+    };
+    for _ in 0..50 {
+        if leases() == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(leases(), 0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn sizes_snap_to_the_configured_whitelist() {
+    let app = spawn_app_with(|c| c.output_sizes = vec![32, 64, 160]).await;
+
+    let res = app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let img = image::load_from_memory(&res.bytes().await.unwrap()).unwrap();
+    assert_eq!(img.dimensions(), (64, 64));
+
+    // 목록에 없는 크기는 가장 가까운 크기의 URL로 리다이렉트 (같은 거리면 큰 쪽)
+    let snapped = app.get(&format!("/e/{STATIC_ID}.webp?size=48")).await;
+    assert!(snapped.url().as_str().ends_with("?size=64"));
+    assert_eq!(snapped.headers()["x-cache"], "HIT");
+    let huge = app.get(&format!("/e/{STATIC_ID}.webp?size=4000")).await;
+    assert!(huge.url().as_str().ends_with("?size=160"));
+
+    // 크기별로 따로 캐시되지만 기본 크기는 ?size 없는 요청과 같은 항목
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.headers()["x-cache"], "HIT");
+    assert_eq!(app.upstream.hits(STATIC_ID), 2);
+}
+
+#[tokio::test]
+async fn requests_without_a_size_use_the_configured_default() {
+    let app = spawn_app_with(|c| {
+        c.output_sizes = vec![64, 160];
+        c.default_size = 64;
+    })
+    .await;
+    let res = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(image::load_from_memory(&res.bytes().await.unwrap()).unwrap().dimensions(), (64, 64));
+    let res = app.get(&format!("/e/{STATIC_ID}.webp?size=160")).await;
+    assert_eq!(image::load_from_memory(&res.bytes().await.unwrap()).unwrap().dimensions(), (160, 160));
+}
+
+#[tokio::test]
+async fn dpr_suffix_scales_the_requested_size() {
+    let app = spawn_app_with(|c| c.output_sizes = vec![32, 64, 160]).await;
+
+    let res = app.get(&format!("/e/{STATIC_ID}@2x.webp?size=32")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let img = image::load_from_memory(&res.bytes().await.unwrap()).unwrap();
+    assert_eq!(img.dimensions(), (64, 64));
+    // 배율을 곱한 크기와 ?size로 고른 크기는 같은 캐시 항목
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await.headers()["x-cache"], "HIT");
+
+    // 곱한 크기가 목록에 없으면 가까운 크기의 정규 주소로
+    let snapped = app.get(&format!("/e/{STATIC_ID}@3x.webp?size=32")).await;
+    assert!(snapped.url().as_str().ends_with(&format!("/e/{STATIC_ID}.webp?size=64")), "{}", snapped.url());
+    let default = app.get(&format!("/e/{STATIC_ID}@1.5x")).await;
+    assert!(default.url().as_str().ends_with(&format!("/e/{STATIC_ID}.webp")), "{}", default.url());
+    // PNG로 요청했으면 리다이렉트해도 PNG
+    let png = app.get(&format!("/e/{STATIC_ID}@3x.png?size=32")).await;
+    assert!(png.url().as_str().ends_with(&format!("/e/{STATIC_ID}.png?size=64")), "{}", png.url());
+    assert_eq!(png.headers()[header::CONTENT_TYPE], "image/png");
+}
+
+#[tokio::test]
+async fn malformed_request_parameters_are_rejected_with_a_reason() {
+    let app = spawn_app().await;
+    for (path, reason) in [
+        (format!("/e/{STATIC_ID}.webp?size=big"), "size must be a positive integer"),
+        (format!("/e/{STATIC_ID}.webp?size=0"), "size must be a positive integer"),
+        (format!("/e/{STATIC_ID}@9x.webp"), "dpr must be between 1 and 4"),
+        (format!("/e/{STATIC_ID}@retina.webp"), "dpr suffix must look like @2x"),
+        ("/e/@2x.webp".to_string(), "emoji name is required"),
+    ] {
+        let res = app.get(&path).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{path}");
+        assert_eq!(res.text().await.unwrap(), reason, "{path}");
+    }
+    assert_eq!(app.upstream.total_hits(), 0);
+}
+
+#[tokio::test]
+async fn variants_endpoint_lists_what_is_cached() {
+    let app = spawn_app_with(|c| c.output_sizes = vec![64, 160]).await;
+    let empty: serde_json::Value = app.get(&format!("/e/{STATIC_ID}/variants")).await.json().await.unwrap();
+    assert_eq!(empty["variants"], serde_json::json!([]));
+
+    let small = app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await;
+    let etag = small.headers()[header::ETAG].to_str().unwrap().to_string();
+    let length = small.bytes().await.unwrap().len();
+    app.get(&format!("/e/{STATIC_ID}.webp?passthrough=1")).await;
+
+    let listing: serde_json::Value = app.get(&format!("/e/{STATIC_ID}.webp/variants")).await.json().await.unwrap();
+    assert_eq!(listing["emoji_id"], STATIC_ID);
+    let variants = listing["variants"].as_array().unwrap();
+    assert_eq!(variants.len(), 2, "{listing}");
+    assert_eq!(variants[0]["key"], format!("{STATIC_ID}@64"));
+    assert_eq!(variants[0]["kind"], "resized");
+    assert_eq!(variants[0]["size"], 64);
+    assert_eq!(variants[0]["content_type"], "image/webp");
+    assert_eq!(variants[0]["bytes"], length);
+    assert_eq!(variants[0]["etag"], etag);
+    assert_eq!(variants[0]["layers"], serde_json::json!(["memory", "stale", "validator"]));
+    assert_eq!(variants[1]["kind"], "passthrough");
+    assert_eq!(variants[1]["size"], serde_json::Value::Null);
+}
+
+#[tokio::test]
+async fn sibling_sizes_are_warmed_in_the_background() {
+    let app = spawn_app_with(|c| {
+        c.output_sizes = vec![32, 64, 160];
+        c.sibling_warm_sizes = vec![32, 160];
+    })
+    .await;
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.headers()["x-cache"], "MISS");
+    // 백그라운드 작업이 업스트림을 부르고 캐시에 넣을 때까지 기다린다
+    for _ in 0..50 {
+        if app.upstream.hits(STATIC_ID) == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let res = app.get(&format!("/e/{STATIC_ID}.webp?size=32")).await;
+    assert_eq!(res.headers()["x-cache"], "HIT");
+    assert_eq!(image::load_from_memory(&res.bytes().await.unwrap()).unwrap().dimensions(), (32, 32));
+
+    // 64px은 함께 데우는 크기가 아니고, 32/160px은 이미 캐시돼 있어 다시 만들지 않는다
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await.headers()["x-cache"], "MISS");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(app.upstream.hits(STATIC_ID), 3);
+}
+
+#[tokio::test]
+async fn path_extension_and_size_pick_the_output_format() {
+    let app = spawn_app_with(|c| c.output_sizes = vec![64, 160]).await;
+
+    let res = app.get(&format!("/e/{STATIC_ID}.png?size=64")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/png");
+    let png = res.bytes().await.unwrap();
+    let (width, height, pixels) = support::decode_png(&png);
+    assert_eq!((width, height), (64, 64));
+    let webp = image::load_from_memory(&app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await.bytes().await.unwrap()).unwrap();
+    assert_eq!(pixels, webp.to_rgba8().into_raw(), "PNG is a lossless copy of the WebP result");
+
+    // 포맷마다 캐시 키가 따로라 서로 덮어쓰지 않는다
+    let res = app.get(&format!("/e/{STATIC_ID}.png?size=64")).await;
+    assert_eq!(res.headers()["x-cache"], "HIT");
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/png");
+    let res = app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await;
+    assert_eq!(res.headers()["x-cache"], "HIT");
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp");
+
+    // 정적 이모지의 `.gif`는 한 장짜리 GIF
+    let res = app.get(&format!("/e/{STATIC_ID}.gif")).await;
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/gif");
+    let still = emoji_resizer::animation::decode(&res.bytes().await.unwrap()).unwrap();
+    assert_eq!((still.width, still.height, still.frames.len()), (160, 160, 1));
+    // 256색으로 줄여도 원래 색에서 크게 벗어나지 않는다
+    let webp = image::load_from_memory(&app.get(&format!("/e/{STATIC_ID}.webp")).await.bytes().await.unwrap()).unwrap().to_rgba8();
+    let error: u64 = still.frames[0].image.pixels().zip(webp.pixels()).map(|(a, b)| (0..3).map(|c| a[c].abs_diff(b[c]) as u64).sum::<u64>()).sum();
+    assert!(error / (160 * 160 * 3) <= 4, "mean channel error {}", error / (160 * 160 * 3));
+
+    // 애니메이션은 프레임과 지연, 반복을 그대로 GIF로
+    let res = app.get(&format!("/e/{ANIMATED_ID}.gif?size=64")).await;
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/gif");
+    let gif = emoji_resizer::animation::decode(&res.bytes().await.unwrap()).unwrap();
+    let source = WebPDecoder::new(Cursor::new(animated_webp())).unwrap().into_frames().collect_frames().unwrap();
+    assert_eq!((gif.width, gif.height, gif.loop_count), (64, 64, 0));
+    assert_eq!(gif.frames.len(), source.len());
+    for (frame, original) in gif.frames.iter().zip(&source) {
+        let (num, den) = original.delay().numer_denom_ms();
+        assert_eq!(frame.delay_ms, num / den);
+    }
+    // 애니메이션의 `.png`는 첫 프레임
+    let res = app.get(&format!("/e/{ANIMATED_ID}.png")).await;
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/png");
+    assert_eq!(support::decode_png(&res.bytes().await.unwrap()).0, 160);
+
+    // 크기를 경로에 적으면 `?size` 정규 주소로
+    let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+    let location = |path: &str| {
+        let request = client.get(app.url(path)).send();
+        async move { request.await.unwrap().headers().get(header::LOCATION).map(|v| v.to_str().unwrap().to_string()) }
+    };
+    assert_eq!(location(&format!("/e/{STATIC_ID}/64.png")).await, Some(format!("/e/{STATIC_ID}.png?size=64")));
+    assert_eq!(location(&format!("/e/{STATIC_ID}/70.webp")).await, Some(format!("/e/{STATIC_ID}.webp?size=64")));
+    assert_eq!(location(&format!("/e/{STATIC_ID}.webp/160.gif")).await, Some(format!("/e/{STATIC_ID}.gif")));
+    assert_eq!(location(&format!("/e/{STATIC_ID}/64")).await, Some(format!("/e/{STATIC_ID}.webp?size=64")));
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}/big.png")).await.status(), StatusCode::NOT_FOUND);
+    // 이름이 같은 하위 경로는 그대로
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}/info")).await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn png_and_gif_outputs_from_png_and_gif_sources() {
+    let app = spawn_app_with(|c| c.output_sizes = vec![64, 160]).await;
+
+    // 정적 PNG 원본의 `.png`도 WebP 결과의 무손실 사본
+    let res = app.get(&format!("/e/{STATIC_PNG_ID}.png?size=64")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/png");
+    let (width, height, pixels) = support::decode_png(&res.bytes().await.unwrap());
+    assert_eq!((width, height), (64, 64));
+    let webp = image::load_from_memory(&app.get(&format!("/e/{STATIC_PNG_ID}.webp?size=64")).await.bytes().await.unwrap()).unwrap();
+    assert_eq!(pixels, webp.to_rgba8().into_raw());
+
+    // 애니메이션 GIF 원본의 `.gif`는 프레임·지연·반복을 지키고 앞 프레임이 남지 않는다
+    let res = app.get(&format!("/e/{ANIMATED_GIF_ID}.gif?size=64")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/gif");
+    let gif = emoji_resizer::animation::decode(&res.bytes().await.unwrap()).unwrap();
+    assert_eq!((gif.width, gif.height, gif.loop_count, gif.frames.len()), (64, 64, 0, 4));
+    for (i, frame) in gif.frames.iter().enumerate() {
+        assert_eq!(frame.delay_ms, 100);
+        for j in 0..4 {
+            let [r, g, b, alpha] = frame.image.get_pixel(j * 16 + 8, 24).0;
+            if j == i as u32 {
+                assert!(r < 16 && g < 16 && b > 240 && alpha == 255, "frame {i}: {:?}", [r, g, b, alpha]);
+            } else {
+                assert_eq!(alpha, 0, "frame {i} square {j}");
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn trim_crops_transparent_borders_before_resizing() {
+    let app = spawn_app_with(|c| {
+        c.admin_token = Some("admin-secret".into());
+        c.output_sizes = vec![64, 160];
+    })
+    .await;
+    let decode = |bytes: &[u8]| image::load_from_memory(bytes).unwrap().to_rgba8();
+
+    // 여백째로 맞추면 사각형은 가운데 일부만 차지한다
+    let plain = decode(&app.get(&format!("/e/{PADDED_ID}.webp")).await.bytes().await.unwrap());
+    assert_eq!(plain.dimensions(), (160, 160));
+    assert_eq!(plain.get_pixel(0, 0)[3], 0);
+
+    // 잘라 낸 50x40을 160 박스에 맞춘다
+    let res = app.get(&format!("/e/{PADDED_ID}.webp?trim=1")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["x-cache"], "MISS", "trimmed output has its own cache key");
+    let trimmed = decode(&res.bytes().await.unwrap());
+    assert_eq!(trimmed.dimensions(), (160, 128));
+    assert!(trimmed.pixels().all(|p| p[3] == 255), "no transparent border is left");
+    assert_eq!(app.get(&format!("/e/{PADDED_ID}.webp?trim=1")).await.headers()["x-cache"], "HIT");
+    assert_eq!(app.get(&format!("/e/{PADDED_ID}.webp")).await.headers()["x-cache"], "HIT");
+    let png = app.get(&format!("/e/{PADDED_ID}.png?trim=1&size=64")).await.bytes().await.unwrap();
+    assert_eq!(support::decode_png(&png).0, 64);
+
+    // 크기를 맞추는 리다이렉트도 옵션을 들고 간다
+    let snapped = app.get(&format!("/e/{PADDED_ID}.webp?size=48&trim=1")).await;
+    assert!(snapped.url().as_str().ends_with("?size=64&trim=1"), "{}", snapped.url());
+    assert_eq!(app.get(&format!("/e/{PADDED_ID}.webp?trim=maybe")).await.status(), StatusCode::BAD_REQUEST);
+
+    // purge는 옵션을 켠 결과도 지운다
+    let purged = app.client.delete(app.url(&format!("/admin/cache/{PADDED_ID}"))).bearer_auth("admin-secret").send().await.unwrap();
+    assert_eq!(purged.status(), StatusCode::OK);
+    assert_eq!(app.get(&format!("/e/{PADDED_ID}.webp?trim=1")).await.headers()["x-cache"], "MISS");
+}
+
+#[tokio::test]
+async fn pad_px_adds_a_transparent_margin_around_the_result() {
+    let app = spawn_app_with(|c| c.output_sizes = vec![64, 160]).await;
+    let decode = |bytes: &[u8]| image::load_from_memory(bytes).unwrap().to_rgba8();
+
+    let res = app.get(&format!("/e/{STATIC_ID}.webp?size=64&pad_px=8")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["x-cache"], "MISS");
+    let padded = decode(&res.bytes().await.unwrap());
+    assert_eq!(padded.dimensions(), (80, 80));
+    assert!(padded.enumerate_pixels().all(|(x, y, p)| {
+        let inside = (8..72).contains(&x) && (8..72).contains(&y);
+        (p[3] > 0) == inside
+    }));
+    let plain = decode(&app.get(&format!("/e/{STATIC_ID}.webp?size=64")).await.bytes().await.unwrap());
+    assert_eq!(image::imageops::crop_imm(&padded, 8, 8, 64, 64).to_image(), plain, "margin is added after resizing");
+
+    // 자른 뒤 여백을 더하고, 애니메이션과 GIF는 프레임마다
+    let both = decode(&app.get(&format!("/e/{PADDED_ID}.webp?trim=1&pad_px=4")).await.bytes().await.unwrap());
+    assert_eq!(both.dimensions(), (168, 136));
+    let animation = WebPDecoder::new(Cursor::new(app.get(&format!("/e/{ANIMATED_ID}.webp?size=64&pad_px=8")).await.bytes().await.unwrap())).unwrap();
+    assert_eq!(animation.dimensions(), (80, 80));
+    let gif = emoji_resizer::animation::decode(&app.get(&format!("/e/{ANIMATED_ID}.gif?size=64&pad_px=8")).await.bytes().await.unwrap()).unwrap();
+    assert_eq!((gif.width, gif.height, gif.frames.len()), (80, 80, 3));
+    assert!(gif.frames.iter().all(|frame| frame.image.get_pixel(3, 40)[3] == 0));
+
+    let snapped = app.get(&format!("/e/{STATIC_ID}.webp?pad_px=2&size=48&trim=1")).await;
+    assert!(snapped.url().as_str().ends_with("?size=64&trim=1&pad_px=2"), "{}", snapped.url());
+    for bad in ["65", "-1", "wide"] {
+        assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp?pad_px={bad}")).await.status(), StatusCode::BAD_REQUEST, "{bad}");
+    }
+}
+
+#[tokio::test]
+async fn stroke_outlines_the_alpha_silhouette() {
+    let app = spawn_app_with(|c| c.output_sizes = vec![64, 160]).await;
+    let decode = |bytes: &[u8]| image::load_from_memory(bytes).unwrap().to_rgba8();
+
+    // 자른 160x128 빨간 사각형 둘레에 3픽셀 초록 테두리, 캔버스는 그만큼 커진다
+    let res = app.get(&format!("/e/{PADDED_ID}.webp?trim=1&stroke=3,00FF00")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let outlined = decode(&res.bytes().await.unwrap());
+    assert_eq!(outlined.dimensions(), (166, 134));
+    assert_eq!(outlined.get_pixel(83, 67).0, [200, 40, 40, 255], "the emote stays on top");
+    for (x, y) in [(0, 67), (165, 67), (83, 0), (83, 133), (1, 67)] {
+        assert_eq!(outlined.get_pixel(x, y).0, [0, 255, 0, 255], "({x}, {y})");
+    }
+    // 모서리는 원판으로 넓혀 둥글다
+    assert_eq!(outlined.get_pixel(0, 0)[3], 0);
+
+    // 테두리 다음에 여백, 애니메이션은 프레임마다
+    let both = decode(&app.get(&format!("/e/{PADDED_ID}.webp?trim=1&stroke=3,00ff00&pad_px=2")).await.bytes().await.unwrap());
+    assert_eq!(both.dimensions(), (170, 138));
+    assert_eq!(both.get_pixel(2, 69).0, [0, 255, 0, 255]);
+    assert_eq!(both.get_pixel(1, 69)[3], 0);
+    let gif = emoji_resizer::animation::decode(&app.get(&format!("/e/{ANIMATED_ID}.gif?size=64&stroke=2")).await.bytes().await.unwrap()).unwrap();
+    assert_eq!((gif.width, gif.height, gif.frames.len()), (68, 68, 3));
+    assert!(gif.frames.iter().all(|frame| frame.image.get_pixel(34, 0).0 == [255, 255, 255, 255]));
+
+    // 폭 0은 옵션 없음과 같은 항목
+    app.get(&format!("/e/{PADDED_ID}.webp?trim=1")).await;
+    assert_eq!(app.get(&format!("/e/{PADDED_ID}.webp?trim=1&stroke=0")).await.headers()["x-cache"], "HIT");
+    let snapped = app.get(&format!("/e/{STATIC_ID}.webp?stroke=2,FFAA00&size=48")).await;
+    assert!(snapped.url().as_str().ends_with("?size=64&stroke=2,ffaa00"), "{}", snapped.url());
+    for bad in ["17", "2,fff", "2,gggggg", "wide", "2,"] {
+        assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp?stroke={bad}")).await.status(), StatusCode::BAD_REQUEST, "{bad}");
+    }
+}
+
+#[tokio::test]
+async fn shadow_is_composited_beneath_the_emote() {
+    let app = spawn_app_with(|c| c.output_sizes = vec![64, 160]).await;
+    let decode = |bytes: &[u8]| image::load_from_memory(bytes).unwrap().to_rgba8();
+
+    // 번짐 없는 그림자는 이모트를 (4, 4) 옮긴 모양, 캔버스는 사방으로 4씩
+    let res = app.get(&format!("/e/{STATIC_ID}.webp?size=64&shadow=4,4,0,0000ff")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let sharp = decode(&res.bytes().await.unwrap());
+    assert_eq!(sharp.dimensions(), (72, 72));
+    // 그림자 알파는 옮겨 온 이모트 픽셀의 알파 (샘플은 0x80 이상)
+    let [r, g, b, alpha] = sharp.get_pixel(70, 70).0;
+    assert!([r, g, b] == [0, 0, 255] && alpha >= 0x80, "{:?}", [r, g, b, alpha]);
+    assert_eq!(sharp.get_pixel(1, 1)[3], 0);
+    assert_eq!(sharp.get_pixel(70, 1)[3], 0);
+
+    // 번진 그림자는 가장자리에서 옅어지고 이모트는 그대로 위에
+    let soft = decode(&app.get(&format!("/e/{PADDED_ID}.webp?trim=1&shadow=0,0,8")).await.bytes().await.unwrap());
+    assert_eq!(soft.dimensions(), (184, 152));
+    assert_eq!(soft.get_pixel(92, 76).0, [200, 40, 40, 255]);
+    let edge = soft.get_pixel(9, 76).0;
+    assert!(edge[..3] == [0, 0, 0] && (1..0x80).contains(&edge[3]), "{edge:?}");
+    assert!(soft.get_pixel(4, 76)[3] < edge[3]);
+    assert_eq!(soft.get_pixel(0, 0)[3], 0);
+
+    // 테두리 → 그림자 → 여백, 애니메이션은 프레임마다
+    let all = decode(&app.get(&format!("/e/{PADDED_ID}.webp?trim=1&stroke=2&shadow=3,3,0&pad_px=1")).await.bytes().await.unwrap());
+    assert_eq!(all.dimensions(), (172, 140));
+    let gif = emoji_resizer::animation::decode(&app.get(&format!("/e/{ANIMATED_ID}.gif?size=64&shadow=2,2,0,000000")).await.bytes().await.unwrap()).unwrap();
+    assert_eq!((gif.width, gif.height, gif.frames.len()), (68, 68, 3));
+
+    let snapped = app.get(&format!("/e/{STATIC_ID}.webp?shadow=-2,1,3&size=48")).await;
+    assert!(snapped.url().as_str().ends_with("?size=64&shadow=-2,1,3,00000080"), "{}", snapped.url());
+    for bad in ["1,2", "1,2,3,zz", "33,0,0", "0,0,-1", "1,2,3,000000,9"] {
+        assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp?shadow={bad}")).await.status(), StatusCode::BAD_REQUEST, "{bad}");
+    }
+}
+
+#[tokio::test]
+async fn mismatched_extensions_redirect_to_the_canonical_url() {
+    let dir = temp_dir("custom-extensions");
+    let app = spawn_app_with(|c| {
+        c.output_sizes = vec![64, 160];
+        c.custom_dir = Some(dir.clone());
+        c.custom_upload_token = Some("upload-secret".into());
+    })
+    .await;
+    let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+    let location = |res: &reqwest::Response| (res.status(), res.headers().get(header::LOCATION).map(|v| v.to_str().unwrap().to_string()));
+    let get = |path: String| client.get(app.url(&path)).send();
+
+    // 만들지 않는 포맷은 응답을 만들지 않고 바로 보낸다
+    let res = get(format!("/e/{STATIC_ID}.jpg?size=64")).await.unwrap();
+    assert_eq!(location(&res), (StatusCode::PERMANENT_REDIRECT, Some(format!("/e/{STATIC_ID}.webp?size=64"))));
+    assert_eq!(app.upstream.hits(STATIC_ID), 0);
+    // 패스스루는 원본 포맷 그대로라 따지지 않는다
+    assert_eq!(get(format!("/e/{STATIC_ID}.png?passthrough=1")).await.unwrap().status(), StatusCode::OK);
+
+    // `/custom`·`/c`의 `.gif`는 애니메이션에만 맞는 별칭
+
+    let put = client.put(app.url("/custom/blob_heart")).bearer_auth("upload-secret").body(static_webp()).send();
+    let created: serde_json::Value = put.await.unwrap().json().await.unwrap();
+    let res = get("/custom/blob_heart.gif".into()).await.unwrap();
+    assert_eq!(location(&res), (StatusCode::PERMANENT_REDIRECT, Some("/custom/blob_heart.webp".into())));
+    let hash = created["emote"]["hash"].as_str().unwrap();
+    let res = get(format!("/c/{hash}.gif")).await.unwrap();
+    assert_eq!(location(&res), (StatusCode::PERMANENT_REDIRECT, Some(format!("/c/{hash}.webp"))));
+}
+
+#[tokio::test]
+async fn slack_emoji_names_resolve_through_emoji_list() {
+    let app = spawn_app().await;
+
+    let res = app.get(&format!("/slack/{SLACK_WORKSPACE}/:partyparrot:")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["cache-tag"], "emoji-slack/acme/partyparrot,source-slack,size-160");
+    let frames = WebPDecoder::new(Cursor::new(&res.bytes().await.unwrap()[..]))
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    assert_eq!(frames.len(), 3);
+
+    // 별칭을 따라가고, 목록은 한 번만 불러온다
+    let alias = app.get(&format!("/slack/{SLACK_WORKSPACE}/yes.webp?size=160")).await;
+    assert_eq!(alias.status(), StatusCode::OK);
+    assert_eq!(alias.headers()["x-cache"], "MISS");
+    assert_eq!(app.upstream.hits("slack:emoji.list"), 1);
+    // Discord 캐시 키와 섞이지 않는다
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.headers()["x-cache"], "MISS");
+
+    assert_eq!(app.get(&format!("/slack/{SLACK_WORKSPACE}/nope")).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/slack/other/partyparrot").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn slack_static_png_emoji_is_served_as_webp() {
+    let app = spawn_app().await;
+
+    let res = app.get(&format!("/slack/{SLACK_WORKSPACE}/shipit.webp?size=160")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp");
+    let decoder = WebPDecoder::new(Cursor::new(res.bytes().await.unwrap())).unwrap();
+    assert_eq!(decoder.dimensions(), (160, 160));
+}
+
+#[tokio::test]
+async fn slack_auth_failure_is_502() {
+    let app = spawn_app_with(|c| {
+        c.slack_tokens.insert(SLACK_WORKSPACE.into(), "xoxb-revoked".into());
+    })
+    .await;
+    let res = app.get(&format!("/slack/{SLACK_WORKSPACE}/partyparrot")).await;
+    assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn each_source_uses_its_own_http_version() {
+    use emoji_resizer::upstream::HttpVersion;
+    use reqwest::Version;
+
+    // 기본값: Discord는 HTTP/2 prior knowledge, 나머지는 협상(평문이면 HTTP/1.1)
+    let app = spawn_app().await;
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.status(), StatusCode::OK);
+    assert_eq!(app.get(&format!("/slack/{SLACK_WORKSPACE}/partyparrot")).await.status(), StatusCode::OK);
+    assert_eq!(app.upstream.version(STATIC_ID), Some(Version::HTTP_2));
+    assert_eq!(app.upstream.version(ANIMATED_ID), Some(Version::HTTP_11));
+
+    let app = spawn_app_with(|c| {
+        c.upstreams.discord.http = HttpVersion::Http1;
+        c.upstreams.slack.http = HttpVersion::Http2;
+    })
+    .await;
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.status(), StatusCode::OK);
+    assert_eq!(app.get(&format!("/slack/{SLACK_WORKSPACE}/partyparrot")).await.status(), StatusCode::OK);
+    assert_eq!(app.upstream.version(STATIC_ID), Some(Version::HTTP_11));
+    assert_eq!(app.upstream.version(ANIMATED_ID), Some(Version::HTTP_2));
+}
+
+#[tokio::test]
+async fn http2_prior_knowledge_falls_back_to_negotiation() {
+    let http1 = spawn_http1_only().await;
+    let app = spawn_app_with(|c| c.upstream_base = http1.clone()).await;
+
+    // 첫 요청은 prior knowledge가 깨진 뒤 협상으로 다시 보내고, 다음부터는 바로 협상한다
+    for id in [STATIC_ID, GIF_ID] {
+        let res = app.get(&format!("/e/{id}.webp")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp");
+    }
+    let metrics = app.get("/metrics").await.text().await.unwrap();
+    assert!(
+        metrics.contains(r#"emoji_resizer_upstream_http_downgrades_total{source="discord"} 1"#),
+        "{metrics}"
+    );
+    assert!(
+        metrics.contains(r#"emoji_resizer_upstream_http_version_total{source="discord",version="HTTP/1.1"}"#),
+        "{metrics}"
+    );
+
+    // 되돌아가기를 끄면 프로토콜 오류가 그대로 502
+    let app = spawn_app_with(|c| {
+        c.upstream_base = http1.clone();
+        c.upstreams.h2_fallback = false;
+    })
+    .await;
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.status(), StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn upstream_ip_family_filters_resolved_addresses() {
+    use emoji_resizer::upstream::IpFamily;
+
+    // 가짜 CDN은 127.0.0.1에만 있으므로 이름으로 붙으면 IPv4 주소가 필요하다
+    let upstream = spawn_upstream().await;
+    let by_name = upstream.base.replace("127.0.0.1", "localhost");
+    for (family, status) in [
+        (IpFamily::Ipv4Only, StatusCode::OK),
+        (IpFamily::PreferIpv6, StatusCode::OK),
+        (IpFamily::Ipv6Only, StatusCode::BAD_GATEWAY),
+    ] {
+        let app = spawn_app_with(|c| {
+            c.upstream_base = by_name.clone();
+            c.upstreams.ip_family = family;
+        })
+        .await;
+        assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.status(), status, "{family:?}");
+    }
+
+    let v4: std::net::SocketAddr = "192.0.2.1:443".parse().unwrap();
+    let v6: std::net::SocketAddr = "[2001:db8::1]:443".parse().unwrap();
+    assert_eq!(IpFamily::PreferIpv4.arrange(vec![v6, v4]), [v4, v6]);
+    assert_eq!(IpFamily::PreferIpv6.arrange(vec![v4, v6]), [v6, v4]);
+    assert_eq!(IpFamily::Ipv4Only.arrange(vec![v6, v4]), [v4]);
+    assert_eq!(IpFamily::Auto.arrange(vec![v6, v4]), [v6, v4]);
+}
+
+#[tokio::test]
+async fn slow_upstream_requests_are_hedged() {
+    let app = spawn_app_with(|c| c.upstreams.discord.hedge_after = Some(Duration::from_millis(100))).await;
+
+    // 첫 요청은 타임아웃(2초)보다 늦지만 100ms 뒤 보낸 두 번째 요청이 먼저 답한다
+    let started = std::time::Instant::now();
+    let res = app.get(&format!("/e/{FLAKY_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(started.elapsed() < Duration::from_secs(1), "{:?}", started.elapsed());
+    assert_eq!(app.upstream.hits(FLAKY_ID), 2);
+    let metrics = app.get("/metrics").await.text().await.unwrap();
+    assert!(metrics.contains(r#"emoji_resizer_upstream_hedges_total{source="discord",winner="hedge"}"#), "{metrics}");
+
+    // 제때 답하는 요청은 하나만 보낸다
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.status(), StatusCode::OK);
+    assert_eq!(app.upstream.hits(STATIC_ID), 1);
+}
+
+#[tokio::test]
+async fn fedi_emoji_resolve_on_allowlisted_instances() {
+    let upstream = spawn_upstream().await;
+    let misskey = spawn_misskey(&upstream).await;
+    let app = spawn_app_with(|c| c.fedi_instances.push(misskey.clone())).await;
+    let mastodon = app.upstream.base.trim_start_matches("http://").to_string();
+
+    // Mastodon: 애니메이션 `url`을 쓴다
+    let res = app.get(&format!("/fedi/{mastodon}/:blobcat:")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["cache-tag"], format!("emoji-fedi/{mastodon}/blobcat,source-fedi,size-160"));
+    let frames = WebPDecoder::new(Cursor::new(&res.bytes().await.unwrap()[..]))
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(app.get(&format!("/fedi/{mastodon}/blobcat")).await.headers()["x-cache"], "HIT");
+    assert_eq!(app.upstream.hits("fedi:custom_emojis"), 1);
+
+    // 정적 PNG 이모지도 WebP로 다시 인코딩한다
+    let png = app.get(&format!("/fedi/{mastodon}/blobcat_png.webp")).await;
+    assert_eq!(png.status(), StatusCode::OK);
+    assert_eq!(png.headers()[header::CONTENT_TYPE], "image/webp");
+    let decoder = WebPDecoder::new(Cursor::new(png.bytes().await.unwrap())).unwrap();
+    assert_eq!(decoder.dimensions(), (160, 160));
+
+    // Misskey: Mastodon API가 없으면 `/api/emojis`의 이름과 별칭으로 찾는다
+    let misskey = misskey.trim_start_matches("http://");
+    assert_eq!(app.get(&format!("/fedi/{misskey}/neko_wave.webp")).await.status(), StatusCode::OK);
+    assert_eq!(app.get(&format!("/fedi/{misskey}/wave")).await.status(), StatusCode::OK);
+    assert_eq!(app.get(&format!("/fedi/{misskey}/blobcat")).await.status(), StatusCode::NOT_FOUND);
+
+    // 허용 목록에 없는 인스턴스는 조회하지 않는다
+    assert_eq!(app.get("/fedi/mastodon.example/blobcat").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn non_discord_sources_refuse_names_that_resolve_inside() {
+    let upstream = spawn_upstream().await;
+    let internal = upstream.base.replace("127.0.0.1", "localhost");
+    let app = spawn_app_with(|c| {
+        c.fedi_instances.push(internal.clone());
+        // Discord CDN 주소는 운영자가 정하므로 내부 주소여도 연결한다
+        c.upstream_base = internal.clone();
+    })
+    .await;
+    let instance = internal.trim_start_matches("http://");
+    assert_ne!(app.get(&format!("/fedi/{instance}/blobcat")).await.status(), StatusCode::OK);
+    assert_eq!(upstream.hits("fedi:custom_emojis"), 0);
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.status(), StatusCode::OK);
+    assert_eq!(upstream.hits(STATIC_ID), 1);
+}
+
+#[tokio::test]
+async fn twitch_badges_and_cheermotes_resolve_through_helix() {
+    let app = spawn_app().await;
+
+    let global = app.get("/twitch/badges/global/subscriber/0").await;
+    assert_eq!(global.status(), StatusCode::OK);
+    assert_eq!(global.headers()["cache-tag"], "emoji-twitch/badges/global/subscriber/0,source-twitch,size-160");
+    // 배지 원본은 PNG지만 WebP로 다시 인코딩한다
+    assert_eq!(global.headers()[header::CONTENT_TYPE], "image/webp");
+    let decoder = WebPDecoder::new(Cursor::new(global.bytes().await.unwrap())).unwrap();
+    assert_eq!(decoder.dimensions(), (160, 160));
+    let channel = app.get(&format!("/twitch/badges/{TWITCH_CHANNEL}/subscriber/12.webp")).await;
+    assert_eq!(channel.status(), StatusCode::OK);
+    assert_eq!(app.get("/twitch/badges/global/subscriber/12").await.status(), StatusCode::NOT_FOUND);
+
+    // 비트 수는 속한 티어로 맞춰지므로 1500비트와 100비트가 같은 캐시 항목을 쓴다
+    let cheer = app.get("/twitch/cheermotes/global/Cheer/1500").await;
+    assert_eq!(cheer.status(), StatusCode::OK);
+    assert_eq!(cheer.headers()["cache-tag"], "emoji-twitch/cheermotes/global/cheer/100,source-twitch,size-160");
+    let frames = WebPDecoder::new(Cursor::new(&cheer.bytes().await.unwrap()[..]))
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(app.get("/twitch/cheermotes/global/cheer/100").await.headers()["x-cache"], "HIT");
+    assert_eq!(app.get("/twitch/cheermotes/global/cheer/99").await.headers()["x-cache"], "MISS");
+    assert_eq!(app.get("/twitch/cheermotes/global/cheer/0").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/twitch/cheermotes/global/cheer/lots").await.status(), StatusCode::BAD_REQUEST);
+
+    // 앱 토큰과 목록은 재사용한다
+    assert_eq!(app.upstream.hits("twitch:token"), 1);
+    assert_eq!(app.upstream.hits("twitch:cheermotes"), 1);
+}
+
+#[tokio::test]
+async fn custom_emotes_upload_normalize_and_serve() {
+    let dir = temp_dir("custom");
+    let configure = |c: &mut emoji_resizer::config::Config| {
+        c.custom_dir = Some(dir.clone());
+        c.custom_upload_token = Some("upload-secret".into());
+        c.custom_max_dimension = 64;
+    };
+    let app = spawn_app_with(configure).await;
+    let put = |path: &str, body: Vec<u8>, token: &str| {
+        app.client.put(app.url(path)).bearer_auth(token).body(body).send()
+    };
+
+    assert_eq!(put("/custom/blob_heart", static_webp(), "wrong").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        put("/custom/blob_heart", b"<html>".to_vec(), "upload-secret").await.unwrap().status(),
+        StatusCode::UNSUPPORTED_MEDIA_TYPE
+    );
+    assert_eq!(put("/custom/x", static_webp(), "upload-secret").await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+    // 100x100 업로드는 64x64 박스에 맞춰 저장된다
+    let created = put("/custom/blob_heart", static_webp(), "upload-secret").await.unwrap();
+    assert_eq!(created.status(), StatusCode::CREATED);
+    let body: serde_json::Value = created.json().await.unwrap();
+    assert_eq!(body["url"], "/custom/blob_heart.webp");
+    assert_eq!((body["emote"]["width"].as_u64(), body["emote"]["animated"].as_bool()), (Some(64), Some(false)));
+
+    let res = app.get("/custom/blob_heart.webp").await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["cache-tag"], "emoji-custom/blob_heart,source-custom,size-160");
+    assert_eq!(app.get("/custom/blob_heart.webp").await.headers()["x-cache"], "HIT");
+    let original = app.get("/custom/blob_heart.webp?passthrough=1").await.bytes().await.unwrap();
+    assert_eq!(image::load_from_memory(&original).unwrap().dimensions(), (64, 64));
+
+    // 교체하면 예전 변형은 캐시에서 빠진다
+    let replaced = put("/custom/blob_heart.webp", animated_webp(), "upload-secret").await.unwrap();
+    assert_eq!(replaced.status(), StatusCode::OK);
+    let res = app.get("/custom/blob_heart.webp").await;
+    assert_eq!(res.headers()["x-cache"], "MISS");
+    let frames = WebPDecoder::new(Cursor::new(&res.bytes().await.unwrap()[..]))
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    assert_eq!(frames.len(), 3);
+
+    // PNG·GIF도 받아서 WebP로 다시 인코딩해 저장한다
+    let png = put("/custom/blob_png", static_png(), "upload-secret").await.unwrap();
+    assert_eq!(png.status(), StatusCode::CREATED);
+    let stored = app.get("/custom/blob_png.webp?passthrough=1").await.bytes().await.unwrap();
+    assert_eq!(image::guess_format(&stored).unwrap(), image::ImageFormat::WebP);
+    assert_eq!(image::load_from_memory(&stored).unwrap().dimensions(), (64, 64));
+    let gif: serde_json::Value = put("/custom/blob_gif", animated_gif(), "upload-secret").await.unwrap().json().await.unwrap();
+    assert_eq!(gif["emote"]["animated"], true);
+
+    // 재시작해도 색인과 저장본이 남아 있다
+    let restarted = spawn_app_with(configure).await;
+    assert_eq!(restarted.get("/custom/blob_heart.webp").await.status(), StatusCode::OK);
+
+    let deleted = app.client.delete(app.url("/custom/blob_heart")).bearer_auth("upload-secret").send().await.unwrap();
+    assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+    assert_eq!(app.get("/custom/blob_heart.webp").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/custom/unknown.webp").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn custom_uploads_are_content_addressed_and_collected() {
+    let dir = temp_dir("custom-dedupe");
+    let app = spawn_app_with(|c| {
+        c.custom_dir = Some(dir.clone());
+        c.admin_token = Some("admin-secret".into());
+        // 기록 없이 바로 지워야 블롭이 정리 대상이 된다
+        c.custom_max_versions = 0;
+        c.custom_deleted_retention = Duration::ZERO;
+    })
+    .await;
+    let put = |name: &str, body: Vec<u8>| {
+        app.client.put(app.url(&format!("/custom/{name}"))).bearer_auth("admin-secret").body(body).send()
+    };
+    let blobs = || std::fs::read_dir(dir.join("blobs")).unwrap().count();
+
+    let first: serde_json::Value = put("heart", static_webp()).await.unwrap().json().await.unwrap();
+    let second: serde_json::Value = put("heart_alias", static_webp()).await.unwrap().json().await.unwrap();
+    assert_eq!(first["deduplicated"], false);
+    assert_eq!(second["deduplicated"], true);
+    assert_eq!(first["hash_url"], second["hash_url"]);
+    assert_eq!(blobs(), 1);
+
+    let hash_url = first["hash_url"].as_str().unwrap().to_string();
+    let res = app.get(&hash_url).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(app.get("/c/0123456789abcdef0123456789abcdef01234567.webp").await.status(), StatusCode::NOT_FOUND);
+
+    // 한 이름이 남아 있는 동안은 정리되지 않는다
+    put("heart", animated_webp()).await.unwrap();
+    let gc = |app: &TestApp| app.client.post(app.url("/admin/maintenance")).bearer_auth("admin-secret").send();
+    let report: serde_json::Value = gc(&app).await.unwrap().json().await.unwrap();
+    assert_eq!(report["custom"]["removed"], 0);
+    assert_eq!(app.get(&hash_url).await.status(), StatusCode::OK);
+
+    let deleted = app.client.delete(app.url("/custom/heart_alias")).bearer_auth("admin-secret").send().await.unwrap();
+    assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+    assert_eq!(app.get(&hash_url).await.status(), StatusCode::NOT_FOUND);
+    let report: serde_json::Value = gc(&app).await.unwrap().json().await.unwrap();
+    assert_eq!(report["custom"]["removed"], 1);
+    assert_eq!(blobs(), 1);
+    assert_eq!(app.get("/custom/heart.webp").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn custom_store_migrates_name_addressed_blobs() {
+    let dir = temp_dir("custom-migrate");
+    let bytes = static_webp();
+    let hash = format!("{:x}", <sha1::Sha1 as sha1::Digest>::digest(&bytes));
+    std::fs::create_dir_all(dir.join("emotes")).unwrap();
+    std::fs::write(dir.join("emotes/legacy.webp"), &bytes).unwrap();
+    // 이름 주소 시절의 색인 (스키마 1단계)
+    let options = sqlx::sqlite::SqliteConnectOptions::new().filename(dir.join("index.db")).create_if_missing(true);
+    let db = sqlx::SqlitePool::connect_with(options).await.unwrap();
+    sqlx::raw_sql(
+        "CREATE TABLE emotes (key TEXT PRIMARY KEY, hash TEXT NOT NULL, bytes INTEGER NOT NULL, width INTEGER NOT NULL,
+         height INTEGER NOT NULL, animated INTEGER NOT NULL, uploaded_at INTEGER NOT NULL);
+         PRAGMA user_version = 1;",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO emotes VALUES ('legacy', ?, ?, 100, 100, 0, 0)")
+        .bind(&hash)
+        .bind(bytes.len() as i64)
+        .execute(&db)
+        .await
+        .unwrap();
+    db.close().await;
+
+    let app = spawn_app_with(|c| c.custom_dir = Some(dir.clone())).await;
+    assert!(dir.join(format!("blobs/{hash}.webp")).exists());
+    assert!(!dir.join("emotes").exists());
+    assert_eq!(app.get("/custom/legacy.webp").await.status(), StatusCode::OK);
+    assert_eq!(app.get(&format!("/c/{hash}.webp")).await.status(), StatusCode::OK);
+}
+
+async fn custom_names(app: &TestApp, query: &str) -> Vec<String> {
+    let body: serde_json::Value = app.get(&format!("/custom{query}")).await.json().await.unwrap();
+    body["emotes"].as_array().unwrap().iter().map(|e| e["name"].as_str().unwrap().to_string()).collect()
+}
+
+#[tokio::test]
+async fn custom_emote_listing_and_metadata_updates() {
+    let dir = temp_dir("custom-meta");
+    let app = spawn_app_with(|c| {
+        c.custom_dir = Some(dir.clone());
+        c.custom_upload_token = Some("upload-secret".into());
+    })
+    .await;
+    let put = |path: &str, body: Vec<u8>| app.client.put(app.url(path)).bearer_auth("upload-secret").body(body).send();
+    let patch = |name: &str, body: serde_json::Value| {
+        app.client.patch(app.url(&format!("/custom/{name}"))).bearer_auth("upload-secret").json(&body).send()
+    };
+
+    assert_eq!(put("/custom/cat_wave?owner=alice&tags=Cats,cute,cats", static_webp()).await.unwrap().status(), StatusCode::CREATED);
+    assert_eq!(put("/custom/dog_wave?owner=bob&tags=dogs", animated_webp()).await.unwrap().status(), StatusCode::CREATED);
+    assert_eq!(put("/custom/bad_tags?tags=no%20spaces", static_webp()).await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+    assert_eq!(custom_names(&app, "").await, ["cat_wave", "dog_wave"]);
+    assert_eq!(custom_names(&app, "?tag=CATS").await, ["cat_wave"]);
+    assert_eq!(custom_names(&app, "?owner=bob").await, ["dog_wave"]);
+
+    // 교체 업로드는 메타데이터를 지정하지 않으면 유지한다
+    put("/custom/cat_wave", animated_webp()).await.unwrap();
+    let body: serde_json::Value = app.get("/custom?owner=alice").await.json().await.unwrap();
+    assert_eq!(body["emotes"][0]["emote"]["tags"], serde_json::json!(["cats", "cute"]));
+
+    let renamed = patch("cat_wave", serde_json::json!({ "name": "cat_hello", "tags": ["greeting"] })).await.unwrap();
+    assert_eq!(renamed.status(), StatusCode::OK);
+    let renamed: serde_json::Value = renamed.json().await.unwrap();
+    assert_eq!(renamed["url"], "/custom/cat_hello.webp");
+    assert_eq!(renamed["emote"]["owner"], "alice");
+    assert_eq!(app.get("/custom/cat_wave.webp").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/custom/cat_hello.webp").await.status(), StatusCode::OK);
+    assert_eq!(custom_names(&app, "?tag=greeting").await, ["cat_hello"]);
+
+    assert_eq!(patch("cat_hello", serde_json::json!({ "name": "dog_wave" })).await.unwrap().status(), StatusCode::CONFLICT);
+    assert_eq!(patch("missing", serde_json::json!({ "tags": [] })).await.unwrap().status(), StatusCode::NOT_FOUND);
+    let anonymous = app.client.patch(app.url("/custom/cat_hello")).json(&serde_json::json!({})).send().await.unwrap();
+    assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+
+    // 이름·태그 변경은 색인에 남아 다시 띄워도 그대로다
+    let restarted = spawn_app_with(|c| c.custom_dir = Some(dir.clone())).await;
+    assert_eq!(custom_names(&restarted, "").await, ["cat_hello", "dog_wave"]);
+    assert_eq!(custom_names(&restarted, "?tag=greeting").await, ["cat_hello"]);
+    assert!(custom_names(&restarted, "?tag=cats").await.is_empty());
+    assert_eq!(custom_names(&restarted, "?owner=alice").await, ["cat_hello"]);
+}
+
+#[tokio::test]
+async fn custom_emote_versions_soft_delete_and_restore() {
+    let dir = temp_dir("custom-versions");
+    let app = spawn_app_with(|c| {
+        c.custom_dir = Some(dir.clone());
+        c.admin_token = Some("admin-secret".into());
+        c.custom_max_versions = 1;
+    })
+    .await;
+    let put = |body: Vec<u8>| app.client.put(app.url("/custom/party")).bearer_auth("admin-secret").body(body).send();
+    let restore = |body: serde_json::Value| {
+        app.client.post(app.url("/admin/custom/party/restore")).bearer_auth("admin-secret").json(&body).send()
+    };
+
+    let v1: serde_json::Value = put(static_webp()).await.unwrap().json().await.unwrap();
+    assert_eq!(v1["emote"]["version"], 1);
+    let v2: serde_json::Value = put(animated_webp()).await.unwrap().json().await.unwrap();
+    assert_eq!(v2["emote"]["version"], 2);
+    // 같은 내용을 다시 올리면 버전이 늘지 않는다
+    let again: serde_json::Value = put(animated_webp()).await.unwrap().json().await.unwrap();
+    assert_eq!(again["emote"]["version"], 2);
+
+    // 덮어쓴 내용은 예전 버전 주소와 해시 주소로 남는다
+    let res = app.get("/custom/party@v1.webp").await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["cache-tag"], format!("emoji-c/{},source-custom,size-160", v1["emote"]["hash"].as_str().unwrap()));
+    assert_eq!(app.get(v1["hash_url"].as_str().unwrap()).await.status(), StatusCode::OK);
+    assert_eq!(app.get("/custom/party@v2.webp").await.status(), StatusCode::OK);
+    assert_eq!(app.get("/custom/party@v9.webp").await.status(), StatusCode::NOT_FOUND);
+
+    // 지워도 관리 API로 되돌릴 수 있다
+    let deleted = app.client.delete(app.url("/custom/party")).bearer_auth("admin-secret").send().await.unwrap();
+    assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+    assert_eq!(app.get("/custom/party.webp").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/custom/party@v1.webp").await.status(), StatusCode::NOT_FOUND);
+    assert!(custom_names(&app, "").await.is_empty());
+    let trash: serde_json::Value =
+        app.client.get(app.url("/admin/custom/deleted")).bearer_auth("admin-secret").send().await.unwrap().json().await.unwrap();
+    assert_eq!(trash["emotes"][0]["name"], "party");
+
+    let restored = restore(serde_json::json!({})).await.unwrap();
+    assert_eq!(restored.status(), StatusCode::OK);
+    assert_eq!(app.get("/custom/party.webp").await.status(), StatusCode::OK);
+
+    // 예전 버전을 되살리면 새 버전이 되고, 기록은 최대 개수만큼만 남는다
+    let restored: serde_json::Value = restore(serde_json::json!({ "version": 1 })).await.unwrap().json().await.unwrap();
+    assert_eq!(restored["emote"]["version"], 3);
+    assert_eq!(restored["emote"]["hash"], v1["emote"]["hash"]);
+    assert_eq!(restored["emote"]["history"].as_array().unwrap().len(), 1);
+    assert_eq!(app.get("/custom/party@v1.webp").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/custom/party@v2.webp").await.status(), StatusCode::OK);
+    let original = app.get("/custom/party.webp?passthrough=1").await.bytes().await.unwrap();
+    assert_eq!(format!("{:x}", <sha1::Sha1 as sha1::Digest>::digest(&original)), v1["emote"]["hash"].as_str().unwrap());
+
+    assert_eq!(restore(serde_json::json!({ "version": 7 })).await.unwrap().status(), StatusCode::NOT_FOUND);
+    let unknown = app.client.post(app.url("/admin/custom/nope/restore")).bearer_auth("admin-secret").send().await.unwrap();
+    assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+
+    // 버전 기록은 색인에 남아 다시 띄워도 그대로다
+    let restarted = spawn_app_with(|c| c.custom_dir = Some(dir.clone())).await;
+    let body: serde_json::Value = restarted.get("/custom").await.json().await.unwrap();
+    assert_eq!(body["emotes"][0]["emote"]["version"], 3);
+    assert_eq!(body["emotes"][0]["emote"]["history"][0]["version"], 2);
+    assert_eq!(restarted.get("/custom/party@v2.webp").await.status(), StatusCode::OK);
+}
+
+async fn search_names(app: &TestApp, query: &str) -> Vec<String> {
+    let body: serde_json::Value = app.get(&format!("/search?{query}")).await.json().await.unwrap();
+    body["results"].as_array().unwrap().iter().map(|r| r["name"].as_str().unwrap().to_string()).collect()
+}
+
+#[tokio::test]
+async fn search_ranks_custom_and_guild_emotes() {
+    let dir = temp_dir("search");
+    let app = spawn_app_with(|c| {
+        c.custom_dir = Some(dir.clone());
+        c.custom_upload_token = Some("upload-secret".into());
+    })
+    .await;
+    for name in ["blob", "blobheart", "catblob"] {
+        let res = app.client.put(app.url(&format!("/custom/{name}"))).bearer_auth("upload-secret").body(static_webp()).send();
+        assert_eq!(res.await.unwrap().status(), StatusCode::CREATED);
+    }
+
+    // 정확히 같은 이름, 접두어, 부분 문자열 순 (같으면 짧은 이름, 이름순). 쓸 수 없는 길드 이모지는 빠진다
+    assert_eq!(search_names(&app, "q=BLOB").await, ["blob", "blobdance", "blobheart", "catblob", "party_blob"]);
+    assert_eq!(search_names(&app, "q=blob&limit=2").await, ["blob", "blobdance"]);
+    // 글자 순서만 맞아도, 오타 한 글자도 찾는다
+    assert_eq!(search_names(&app, "q=bdnc").await, ["blobdance"]);
+    assert_eq!(search_names(&app, "q=blpbheart").await, ["blobheart"]);
+    assert!(search_names(&app, "q=zzz").await.is_empty());
+
+    let body: serde_json::Value = app.get("/search?q=blobdance").await.json().await.unwrap();
+    assert_eq!(body["results"][0]["url"], format!("/e/{ANIMATED_ID}.webp"));
+    assert_eq!((body["results"][0]["source"].as_str(), body["results"][0]["guild"].as_str()), (Some("discord"), Some(GUILD_ID)));
+    let body: serde_json::Value = app.get("/search?q=catblob").await.json().await.unwrap();
+    assert_eq!(body["results"][0]["url"], "/custom/catblob.webp");
+
+    assert_eq!(app.get("/search").await.status(), StatusCode::BAD_REQUEST);
+    // 길드 목록은 재사용한다
+    assert_eq!(app.upstream.hits("discord:guild_emojis"), 1);
+}
+
+#[tokio::test]
+async fn private_custom_emotes_need_a_signed_url() {
+    let dir = temp_dir("custom-private");
+    let app = spawn_app_with(|c| {
+        c.custom_dir = Some(dir.clone());
+        c.admin_token = Some("admin-secret".into());
+        c.custom_signing_key = Some("sign-key".into());
+    })
+    .await;
+    let res = app
+        .client
+        .put(app.url("/custom/secret_blob?private=1"))
+        .bearer_auth("admin-secret")
+        .body(static_webp())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let uploaded: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(uploaded["emote"]["private"], true);
+    assert_eq!(uploaded["hash_url"], serde_json::Value::Null);
+    let hash = uploaded["emote"]["hash"].as_str().unwrap().to_string();
+
+    // 서명 없이는 이름으로도 해시로도 열리지 않고, 공개 목록에도 없다
+    assert_eq!(app.get("/custom/secret_blob.webp").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(app.get(&format!("/c/{hash}.webp")).await.status(), StatusCode::NOT_FOUND);
+    let listed = |auth: bool| {
+        let req = app.client.get(app.url("/custom"));
+        let req = if auth { req.bearer_auth("admin-secret") } else { req };
+        async move { req.send().await.unwrap().json::<serde_json::Value>().await.unwrap()["emotes"].as_array().unwrap().len() }
+    };
+    assert_eq!((listed(false).await, listed(true).await), (0, 1));
+
+    let sign = app.client.post(app.url("/custom/secret_blob/sign?ttl=60"));
+    assert_eq!(sign.try_clone().unwrap().send().await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    let signed: serde_json::Value = sign.bearer_auth("admin-secret").send().await.unwrap().json().await.unwrap();
+    let url = signed["url"].as_str().unwrap().to_string();
+    let res = app.get(&url).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let max_age: u64 = res.headers()[header::CACHE_CONTROL].to_str().unwrap().strip_prefix("private, max-age=").unwrap().parse().unwrap();
+    assert!((59..=60).contains(&max_age), "{max_age}");
+    // 크기를 맞추는 리다이렉트에도 서명이 따라간다
+    let res = app.get(&format!("{url}&size=100")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.url().query().unwrap().contains("sig="), "{}", res.url());
+
+    let tampered = format!("{}0", url.trim_end_matches(|c: char| c.is_ascii_hexdigit()));
+    assert_eq!(app.get(&tampered).await.status(), StatusCode::FORBIDDEN);
+    let expired = format!("/custom/secret_blob.webp?exp=1&sig={}", emoji_resizer::signing::sign("sign-key", "secret_blob", 1));
+    assert_eq!(app.get(&expired).await.text().await.unwrap(), "signature expired");
+
+    // 서명은 RFC 2202의 HMAC-SHA1
+    let mac = emoji_resizer::signing::hmac_sha1(b"Jefe", b"what do ya want for nothing?");
+    let hex: String = mac.iter().map(|b| format!("{b:02x}")).collect();
+    assert_eq!(hex, "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn api_keys_skip_the_anonymous_limit_and_get_longer_edge_caching() {
+    let dir = temp_dir("custom-api-keys");
+    let app = spawn_app_with(|c| {
+        c.api_keys = vec!["key-1".into()];
+        c.anon_rate_limit = 2;
+        c.anon_rate_window = Duration::from_secs(60);
+        c.emoji_cache.s_maxage = None;
+        c.emoji_cache_auth.s_maxage = Some(2_592_000);
+        c.custom_dir = Some(dir.clone());
+        c.admin_token = Some("admin-secret".into());
+        c.custom_signing_key = Some("sign-key".into());
+    })
+    .await;
+    let path = format!("/e/{STATIC_ID}.webp");
+    let with_key = |header: &'static str, value: &'static str| {
+        let req = app.client.get(app.url(&path)).header(header, value);
+        async move { req.send().await.unwrap() }
+    };
+
+    let anonymous = app.get(&path).await;
+    assert_eq!(anonymous.status(), StatusCode::OK);
+    assert!(!anonymous.headers()[header::CACHE_CONTROL].to_str().unwrap().contains("s-maxage"));
+    // 상태 점검은 세지 않는다
+    assert_eq!(app.get("/healthz").await.status(), StatusCode::OK);
+    assert_eq!(app.get(&path).await.status(), StatusCode::OK);
+    let limited = app.get(&path).await;
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(limited.headers()[header::RETRY_AFTER], "60");
+    // 틀린 키는 익명과 같다
+    assert_eq!(with_key("x-api-key", "wrong").await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    for (header, value) in [("authorization", "Bearer key-1"), ("x-api-key", "key-1")] {
+        let res = with_key(header, value).await;
+        assert_eq!(res.status(), StatusCode::OK, "{header}");
+        // 같은 캐시 항목이라도 인증된 요청에는 공유 캐시 수명을 길게
+        assert_eq!(res.headers()["x-cache"], "HIT");
+        assert!(res.headers()[header::CACHE_CONTROL].to_str().unwrap().contains("s-maxage=2592000"));
+        assert!(res.headers().get(header::VARY).is_none_or(|v| !v.to_str().unwrap().contains("Authorization")));
+    }
+
+    // 비공개 이모트는 API 키로 서명 없이 열리지만 공유 캐시에는 남기지 않는다
+    let res = app
+        .client
+        .put(app.url("/custom/secret_blob?private=1"))
+        .bearer_auth("admin-secret")
+        .header("x-api-key", "key-1")
+        .body(static_webp())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let res = app.client.get(app.url("/custom/secret_blob.webp")).header("x-api-key", "key-1").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers()[header::CACHE_CONTROL].to_str().unwrap().starts_with("private, "));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn custom_emotes_are_namespaced_per_guild() {
+    let dir = temp_dir("custom-guilds");
+    let app = spawn_app_with(|c| {
+        c.custom_dir = Some(dir.clone());
+        c.custom_upload_token = Some("upload-secret".into());
+        c.custom_guild_tokens = HashMap::from([("111".to_string(), "guild-key".to_string())]);
+        c.custom_guild_max_emotes = 2;
+    })
+    .await;
+    let put = |path: &str, token: &str| app.client.put(app.url(path)).bearer_auth(token).body(static_webp()).send();
+
+    let created = put("/custom/111/blob", "guild-key").await.unwrap();
+    assert_eq!(created.status(), StatusCode::CREATED);
+    let created: serde_json::Value = created.json().await.unwrap();
+    assert_eq!((created["name"].as_str(), created["guild"].as_str()), (Some("blob"), Some("111")));
+    assert_eq!(created["url"], "/custom/111/blob.webp");
+
+    // 길드 키는 자기 길드에서만 쓸 수 있다
+    assert_eq!(put("/custom/blob", "guild-key").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(put("/custom/222/blob", "guild-key").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(put("/custom/222/blob", "upload-secret").await.unwrap().status(), StatusCode::CREATED);
+
+    // 같은 이름이어도 네임스페이스마다 따로다
+    assert_eq!(app.get("/custom/111/blob.webp").await.status(), StatusCode::OK);
+    assert_eq!(app.get("/custom/blob.webp").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/custom/111/blob/extra.webp").await.status(), StatusCode::NOT_FOUND);
+
+    // 이모트 수 한도 (교체는 새 이모트로 세지 않는다)
+    assert_eq!(put("/custom/111/second", "guild-key").await.unwrap().status(), StatusCode::CREATED);
+    assert_eq!(put("/custom/111/third", "guild-key").await.unwrap().status(), StatusCode::FORBIDDEN);
+    assert_eq!(put("/custom/111/blob", "guild-key").await.unwrap().status(), StatusCode::OK);
+
+    let listed: serde_json::Value = app.get("/custom?guild=111").await.json().await.unwrap();
+    assert_eq!(listed["emotes"].as_array().unwrap().len(), 2);
+    assert_eq!((listed["usage"]["emotes"].as_u64(), listed["usage"]["max_emotes"].as_u64()), (Some(2), Some(2)));
+
+    // 이름을 바꿔도 길드 안에 머문다
+    let renamed = app
+        .client
+        .patch(app.url("/custom/111/second"))
+        .bearer_auth("guild-key")
+        .json(&serde_json::json!({ "name": "renamed" }))
+        .send()
+        .await
+        .unwrap();
+    let renamed: serde_json::Value = renamed.json().await.unwrap();
+    assert_eq!(renamed["url"], "/custom/111/renamed.webp");
+    assert_eq!(app.get("/custom/111/renamed.webp").await.status(), StatusCode::OK);
+
+    let deleted = app.client.delete(app.url("/custom/111/renamed")).bearer_auth("guild-key").send().await.unwrap();
+    assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+    assert_eq!(put("/custom/111/third", "guild-key").await.unwrap().status(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn flagged_custom_uploads_wait_for_review() {
+    let dir = temp_dir("custom-moderation");
+    let upstream = spawn_upstream().await;
+    let moderation_url = format!("{}/moderation", upstream.base);
+    let app = spawn_app_with(|c| {
+        c.custom_dir = Some(dir.clone());
+        c.admin_token = Some("admin-secret".into());
+        c.custom_moderation_url = Some(moderation_url);
+    })
+    .await;
+    let put = |name: &str| app.client.put(app.url(&format!("/custom/{name}"))).bearer_auth("admin-secret").body(static_webp()).send();
+    let admin = |method: reqwest::Method, path: &str| app.client.request(method, app.url(path)).bearer_auth("admin-secret");
+
+    assert_eq!(put("calm").await.unwrap().status(), StatusCode::CREATED);
+    let flagged = put("spicy").await.unwrap();
+    assert_eq!(flagged.status(), StatusCode::ACCEPTED);
+    let flagged: serde_json::Value = flagged.json().await.unwrap();
+    assert_eq!((flagged["status"].as_str(), flagged["score"].as_f64()), (Some("pending"), Some(0.95)));
+    assert_eq!(upstream.hits("moderation"), 2);
+
+    // 승인 전에는 공개되지 않고 검토 대기열에만 있다
+    assert_eq!(app.get("/custom/spicy.webp").await.status(), StatusCode::NOT_FOUND);
+    assert!(!custom_names(&app, "").await.contains(&"spicy".to_string()));
+    let queue: serde_json::Value = admin(reqwest::Method::GET, "/admin/custom/review").send().await.unwrap().json().await.unwrap();
+    assert_eq!(queue["pending"][0]["name"], "spicy");
+    let preview = admin(reqwest::Method::GET, queue["pending"][0]["preview_url"].as_str().unwrap()).send().await.unwrap();
+    assert_eq!(preview.headers()[header::CONTENT_TYPE], "image/webp");
+
+    // 정리해도 대기 중인 블롭은 남는다
+    admin(reqwest::Method::POST, "/admin/maintenance").send().await.unwrap();
+    let approve = |action: &str| admin(reqwest::Method::POST, "/admin/custom/review/spicy").json(&serde_json::json!({ "action": action })).send();
+    let approved = approve("approve").await.unwrap();
+    assert_eq!(approved.status(), StatusCode::OK);
+    assert_eq!(app.get("/custom/spicy.webp").await.status(), StatusCode::OK);
+    assert_eq!(approve("approve").await.unwrap().status(), StatusCode::NOT_FOUND);
+
+    // 거절하면 공개되지 않는다
+    assert_eq!(put("spicy_two").await.unwrap().status(), StatusCode::ACCEPTED);
+    let declined = admin(reqwest::Method::POST, "/admin/custom/review/spicy_two")
+        .json(&serde_json::json!({ "action": "reject" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(declined.status(), StatusCode::NO_CONTENT);
+    assert_eq!(app.get("/custom/spicy_two.webp").await.status(), StatusCode::NOT_FOUND);
+    let queue: serde_json::Value = admin(reqwest::Method::GET, "/admin/custom/review").send().await.unwrap().json().await.unwrap();
+    assert!(queue["pending"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn custom_uploads_are_held_when_moderation_is_unavailable() {
+    let dir = temp_dir("custom-moderation-down");
+    let upstream = spawn_upstream().await;
+    let moderation_url = format!("{}/moderation/broken", upstream.base);
+    let app = spawn_app_with(|c| {
+        c.custom_dir = Some(dir.clone());
+        c.custom_upload_token = Some("upload-secret".into());
+        c.custom_moderation_url = Some(moderation_url);
+    })
+    .await;
+    let res = app.client.put(app.url("/custom/calm")).bearer_auth("upload-secret").body(static_webp()).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["reason"], "moderation webhook failed");
+    assert_eq!(app.get("/custom/calm.webp").await.status(), StatusCode::NOT_FOUND);
+}
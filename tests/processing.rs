@@ -0,0 +1,90 @@
+//! 인코딩 대기열의 우선순위와 백그라운드 작업 상한
+
+use emoji_resizer::{
+    error::Error,
+    processing::{Priority, Processing},
+};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::mpsc;
+
+/// 자리를 얻으면 `name`을 보내고 `hold` 동안 쥐고 있는 작업
+fn spawn_job(queue: &Arc<Processing>, priority: Priority, name: &'static str, done: &mpsc::UnboundedSender<&'static str>) {
+    let (queue, done) = (queue.clone(), done.clone());
+    tokio::spawn(async move {
+        let _permit = queue.acquire(priority).await;
+        done.send(name).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    });
+}
+
+#[tokio::test]
+async fn interactive_work_jumps_ahead_of_background_work() {
+    let queue = Processing::new(1, 1);
+    let (done, mut order) = mpsc::unbounded_channel();
+    let running = queue.acquire(Priority::High).await;
+
+    spawn_job(&queue, Priority::Low, "prewarm-1", &done);
+    spawn_job(&queue, Priority::Low, "prewarm-2", &done);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    spawn_job(&queue, Priority::High, "miss", &done);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    drop(running);
+
+    let mut seen = Vec::new();
+    for _ in 0..3 {
+        seen.push(order.recv().await.unwrap());
+    }
+    assert_eq!(seen, ["miss", "prewarm-1", "prewarm-2"]);
+}
+
+#[tokio::test]
+async fn background_work_never_takes_every_slot() {
+    let queue = Processing::new(2, 1);
+    let background = queue.acquire(Priority::Low).await;
+
+    // 자리가 하나 남아 있어도 두 번째 백그라운드 작업은 기다린다
+    let waiting = tokio::spawn({
+        let queue = queue.clone();
+        async move { drop(queue.acquire(Priority::Low).await) }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!waiting.is_finished());
+
+    // 사용자 요청은 바로 들어간다
+    let interactive = tokio::time::timeout(Duration::from_millis(100), queue.acquire(Priority::High)).await;
+    assert!(interactive.is_ok());
+
+    drop(background);
+    tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn cancelled_waiters_give_their_slot_back() {
+    let queue = Processing::new(1, 1);
+    let running = queue.acquire(Priority::High).await;
+    let cancelled = tokio::time::timeout(Duration::from_millis(20), queue.acquire(Priority::High)).await;
+    assert!(cancelled.is_err());
+    drop(running);
+    assert!(tokio::time::timeout(Duration::from_millis(100), queue.acquire(Priority::Low)).await.is_ok());
+}
+
+#[tokio::test]
+async fn admission_looks_at_interactive_queue_depth() {
+    let queue = Processing::new(1, 1);
+    let _running = queue.acquire(Priority::High).await;
+    let (done, _order) = mpsc::unbounded_channel();
+    assert_eq!(queue.admit(1, 3), Ok(()));
+
+    // 백그라운드 대기는 사용자 요청을 막지 않는다
+    spawn_job(&queue, Priority::Low, "prewarm", &done);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(queue.admit(1, 3), Ok(()));
+
+    spawn_job(&queue, Priority::High, "miss-1", &done);
+    spawn_job(&queue, Priority::High, "miss-2", &done);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(queue.queued(Priority::High), 2);
+    assert_eq!(queue.admit(1, 3), Err(Error::Throttled));
+    assert_eq!(queue.admit(0, 2), Err(Error::Overloaded));
+    assert_eq!(queue.admit(0, 0), Ok(()));
+}
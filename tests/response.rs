@@ -0,0 +1,55 @@
+//! 응답 빌더의 `Vary` 조립 테스트
+
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use emoji_resizer::{
+    imaging::ContentType,
+    response::{make_etag, ImageResponse, RequestInputs, VaryInput},
+};
+use std::sync::Arc;
+
+fn body() -> Arc<Vec<u8>> {
+    Arc::new(b"RIFF\0\0\0\0WEBPVP8 ".to_vec())
+}
+
+fn request(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.insert(*name, HeaderValue::from_static(value));
+    }
+    headers
+}
+
+#[test]
+fn no_vary_when_no_negotiated_input_was_read() {
+    // 헤더가 와 있어도 읽지 않았으면 응답을 바꾸지 않은 것
+    let req = RequestInputs::new(request(&[("accept", "image/avif"), ("origin", "https://a.example")]));
+    let res = ImageResponse::new(body(), ContentType::Webp).respond(&req);
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get(header::VARY).is_none());
+}
+
+#[test]
+fn vary_lists_inputs_that_were_read_even_if_absent() {
+    let req = RequestInputs::new(request(&[("accept", "image/avif,image/webp")]));
+    assert_eq!(req.get(VaryInput::Accept), Some("image/avif,image/webp"));
+    assert_eq!(req.get(VaryInput::SaveData), None);
+    assert_eq!(req.get(VaryInput::Accept), Some("image/avif,image/webp"));
+
+    let res = ImageResponse::new(body(), ContentType::Webp).respond(&req);
+    assert_eq!(res.headers()[header::VARY], "Accept, Save-Data");
+}
+
+#[test]
+fn not_modified_carries_the_same_vary() {
+    let etag = make_etag(&body());
+    let mut headers = request(&[("dpr", "2")]);
+    headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(&etag).unwrap());
+    let req = RequestInputs::new(headers);
+    req.get(VaryInput::Dpr);
+    req.get(VaryInput::Origin);
+
+    let res = ImageResponse::new(body(), ContentType::Webp).respond(&req);
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(res.headers()[header::VARY], "DPR, Origin");
+    assert_eq!(res.headers()[header::ETAG], etag.as_str());
+}
@@ -0,0 +1,699 @@
+//! 통합 테스트 공용: 가짜 Discord CDN과 테스트용 서버 인스턴스.
+//!
+//! 가짜 CDN은 `/emojis/<id>`에서 id별로 정해진 픽스처를 돌려주고 요청 수를 센다.
+//! 다른 소스의 이름 해석 API(Slack `emoji.list`, Mastodon `custom_emojis`, Twitch Helix, Discord 길드 이모지 등)도 같은 서버에서
+//! 흉내 낸다. Misskey 인스턴스는 API 판별을 확인하도록 따로 띄운다([`spawn_misskey`]).
+//! 서버는 실제와 같은 라우터를 임의 포트에 띄우므로 캐시·조건부 요청·에러 경로를
+//! HTTP 수준에서 확인할 수 있다.
+
+#![allow(dead_code)]
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode, Version},
+    response::{IntoResponse, Response},
+    routing::{any, get, post},
+    Router,
+};
+use emoji_resizer::{build_router, build_state, config::Config, imaging, s3::S3Config, upstream};
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+pub const STATIC_ID: &str = "100000000000000001";
+pub const ANIMATED_ID: &str = "100000000000000002";
+pub const GIF_ID: &str = "100000000000000003";
+pub const APNG_ID: &str = "100000000000000004";
+pub const HTML_ID: &str = "100000000000000005";
+pub const MISSING_ID: &str = "100000000000000006";
+pub const ERROR_ID: &str = "100000000000000007";
+pub const SLOW_ID: &str = "100000000000000008";
+/// 뒷부분이 잘린 애니메이션 WebP (첫 프레임만 디코드됨)
+pub const TRUNCATED_ID: &str = "100000000000000009";
+/// 첫 요청만 3초 늦게 답한다 (헤징 확인용)
+pub const FLAKY_ID: &str = "100000000000000010";
+/// 첫 요청에만 이미지를 주고 그 뒤로는 404 (Discord에서 지워진 이모지)
+pub const REMOVED_ID: &str = "100000000000000011";
+/// 항상 `429 Retry-After: 1` (업스트림 스로틀링)
+pub const THROTTLED_ID: &str = "100000000000000012";
+/// 항상 403 (만료된 첨부 URL)
+pub const FORBIDDEN_ID: &str = "100000000000000013";
+/// 항상 415
+pub const UNSUPPORTED_ID: &str = "100000000000000014";
+/// 같은 호스트의 [`STATIC_ID`]로 302
+pub const REDIRECT_ID: &str = "100000000000000015";
+/// 다른 호스트(`localhost`)의 [`STATIC_ID`]로 302
+pub const OFFSITE_REDIRECT_ID: &str = "100000000000000016";
+/// 4프레임 애니메이션 GIF ([`animated_gif`])
+pub const ANIMATED_GIF_ID: &str = "100000000000000017";
+/// 투명한 여백 안에 불투명한 50x40 사각형이 있는 정적 WebP ([`padded_webp`])
+pub const PADDED_ID: &str = "100000000000000018";
+/// 300ms 뒤에 정적 WebP (동시 미스를 겹치게 할 때)
+pub const LAGGY_ID: &str = "100000000000000019";
+/// 정적 PNG ([`static_png`])
+pub const STATIC_PNG_ID: &str = "100000000000000020";
+
+pub const SLACK_WORKSPACE: &str = "acme";
+pub const SLACK_TOKEN: &str = "xoxb-test";
+
+pub const TWITCH_CLIENT_ID: &str = "twitch-client";
+pub const TWITCH_CLIENT_SECRET: &str = "twitch-secret";
+pub const TWITCH_CHANNEL: &str = "12345";
+const TWITCH_TOKEN: &str = "twitch-app-token";
+
+pub const DISCORD_BOT_TOKEN: &str = "discord-bot";
+pub const GUILD_ID: &str = "900000000000000001";
+
+/// 100x100 반투명 그라디언트
+pub fn sample_image(phase: u8) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(100, 100, |x, y| {
+        Rgba([(x * 2) as u8, (y * 2) as u8, phase, ((x + y) as u8) | 0x80])
+    }))
+}
+
+pub fn static_webp() -> Vec<u8> {
+    imaging::encode_webp(&sample_image(0)).unwrap()
+}
+
+pub fn animated_webp() -> Vec<u8> {
+    let frames: Vec<_> = [0u8, 128, 255]
+        .iter()
+        .map(|&phase| imaging::AnimFrame {
+            webp: imaging::encode_webp(&sample_image(phase)).unwrap(),
+            delay_ms: 80,
+        })
+        .collect();
+    imaging::mux_animated_webp(100, 100, 0, &frames).unwrap()
+}
+
+/// [`sample_image`]를 PNG로 (Slack·Mastodon·Twitch 커스텀 이모지는 대부분 PNG)
+pub fn static_png() -> Vec<u8> {
+    let mut out = Vec::new();
+    sample_image(0).write_to(&mut std::io::Cursor::new(&mut out), image::ImageFormat::Png).unwrap();
+    out
+}
+
+/// 100x100 투명 캔버스의 (20, 30)부터 50x40만 불투명
+pub fn padded_webp() -> Vec<u8> {
+    let image = RgbaImage::from_fn(100, 100, |x, y| match (20..70).contains(&x) && (30..70).contains(&y) {
+        true => Rgba([200, 40, 40, 255]),
+        false => Rgba([0, 0, 0, 0]),
+    });
+    imaging::encode_webp(&DynamicImage::ImageRgba8(image)).unwrap()
+}
+
+/// 1x1 GIF (흰 점 하나)
+pub fn gif() -> Vec<u8> {
+    let mut out = Vec::new();
+    let white = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 255])));
+    white.write_to(&mut std::io::Cursor::new(&mut out), image::ImageFormat::Gif).unwrap();
+    out
+}
+
+/// 애니메이션 GIF 한 프레임. 색 번호는 위에서부터 줄 순서로 (`interlaced`면 파일에는 인터레이스 순서로 쓴다).
+#[derive(Clone, Default)]
+pub struct GifFrame {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub indices: Vec<u8>,
+    /// 지역 팔레트
+    pub palette: Option<Vec<[u8; 3]>>,
+    pub transparent: Option<u8>,
+    /// 0/1 남김, 2 배경으로, 3 이전 상태로
+    pub disposal: u8,
+    pub delay_cs: u16,
+    pub interlaced: bool,
+}
+
+impl GifFrame {
+    /// `(x, y)`부터 `width`x`height`를 `index` 한 색으로
+    pub fn fill(x: u16, y: u16, width: u16, height: u16, index: u8) -> Self {
+        Self { x, y, width, height, indices: vec![index; width as usize * height as usize], delay_cs: 10, ..Default::default() }
+    }
+}
+
+/// 전역 팔레트와 프레임들로 GIF89a를 만든다. `loops`가 있으면 `NETSCAPE2.0` 확장을 넣는다.
+pub fn gif_animation(width: u16, height: u16, palette: &[[u8; 3]], loops: Option<u16>, frames: &[GifFrame]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut encoder = gif::Encoder::new(&mut out, width, height, palette.as_flattened()).unwrap();
+    match loops {
+        Some(0) => encoder.set_repeat(gif::Repeat::Infinite).unwrap(),
+        Some(n) => encoder.set_repeat(gif::Repeat::Finite(n)).unwrap(),
+        None => {}
+    }
+    for frame in frames {
+        // 인코더는 받은 순서 그대로 쓰므로 인터레이스 순서로 미리 바꿔 둔다
+        let indices = if frame.interlaced { interlace_rows(&frame.indices, frame.width as usize, frame.height as usize) } else { frame.indices.clone() };
+        encoder
+            .write_frame(&gif::Frame {
+                left: frame.x,
+                top: frame.y,
+                width: frame.width,
+                height: frame.height,
+                buffer: indices.into(),
+                palette: frame.palette.as_ref().map(|p| p.as_flattened().to_vec()),
+                transparent: frame.transparent,
+                dispose: gif::DisposalMethod::from_u8(frame.disposal).unwrap(),
+                delay: frame.delay_cs,
+                interlaced: frame.interlaced,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    drop(encoder);
+    out
+}
+
+fn interlace_rows(indices: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let order = (0..height)
+        .step_by(8)
+        .chain((4..height).step_by(8))
+        .chain((2..height).step_by(4))
+        .chain((1..height).step_by(2));
+    order.flat_map(|row| indices[row * width..(row + 1) * width].to_vec()).collect()
+}
+
+/// APNG 한 프레임 (8비트 RGBA 픽셀, 위에서부터 줄 순서)
+#[derive(Clone)]
+pub struct ApngFrame {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 4]>,
+    /// 지연 분자/분모 (초)
+    pub delay: (u16, u16),
+    /// 0 none, 1 background, 2 previous
+    pub dispose: u8,
+    /// 0 source, 1 over
+    pub blend: u8,
+}
+
+impl ApngFrame {
+    pub fn fill(x: u32, y: u32, width: u32, height: u32, rgba: [u8; 4]) -> Self {
+        Self { x, y, width, height, pixels: vec![rgba; (width * height) as usize], delay: (1, 10), dispose: 0, blend: 0 }
+    }
+}
+
+/// PNG를 (가로, 세로, 8비트 RGBA 픽셀)로 푼다
+pub fn decode_png(data: &[u8]) -> (u32, u32, Vec<u8>) {
+    let image = image::load_from_memory_with_format(data, image::ImageFormat::Png).unwrap().into_rgba8();
+    (image.width(), image.height(), image.into_raw())
+}
+
+/// 8비트 RGBA APNG. `default_image`가 있으면 애니메이션에 들어가지 않는 기본 이미지(`IDAT`)로 먼저 넣는다.
+pub fn apng_animation(width: u32, height: u32, plays: u32, default_image: Option<&ApngFrame>, frames: &[ApngFrame]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, plays).unwrap();
+    encoder.set_sep_def_img(default_image.is_some()).unwrap();
+    let mut writer = encoder.write_header().unwrap();
+    if let Some(image) = default_image {
+        writer.write_image_data(image.pixels.as_flattened()).unwrap();
+    }
+    for frame in frames {
+        // 위치를 바꾸기 전에 크기부터 줄여야 캔버스 밖 검사에 걸리지 않는다
+        writer.reset_frame_position().unwrap();
+        writer.set_frame_dimension(frame.width, frame.height).unwrap();
+        writer.set_frame_position(frame.x, frame.y).unwrap();
+        writer.set_frame_delay(frame.delay.0, frame.delay.1).unwrap();
+        writer.set_dispose_op(png::DisposeOp::from_u8(frame.dispose).unwrap()).unwrap();
+        writer.set_blend_op(png::BlendOp::from_u8(frame.blend).unwrap()).unwrap();
+        writer.write_image_data(frame.pixels.as_flattened()).unwrap();
+    }
+    writer.finish().unwrap();
+    out
+}
+
+/// 투명 바탕 위로 16px 파랑 사각형이 지나가는 64x64, 4프레임 애니메이션 GIF (배경으로 폐기, 무한 반복)
+pub fn animated_gif() -> Vec<u8> {
+    let frames: Vec<GifFrame> = (0..4)
+        .map(|i| GifFrame { disposal: 2, ..GifFrame::fill(i * 16, 16, 16, 16, 1) })
+        .collect();
+    gif_animation(64, 64, &[[255, 0, 0], [0, 0, 255]], Some(0), &frames)
+}
+
+/// 2x2 RGBA 한 프레임짜리 APNG (acTL + fcTL + IDAT)
+pub fn apng() -> Vec<u8> {
+    apng_animation(2, 2, 0, None, &[ApngFrame::fill(0, 0, 2, 2, [255, 0, 0, 200])])
+}
+
+#[derive(Clone, Default)]
+struct Upstream {
+    /// 이름 해석 API가 돌려줄 이미지 URL의 바탕 (`http://127.0.0.1:포트`)
+    base: String,
+    hits: Arc<Mutex<HashMap<String, usize>>>,
+    /// id별 마지막 요청의 HTTP 버전
+    versions: Arc<Mutex<HashMap<String, Version>>>,
+    total: Arc<AtomicUsize>,
+}
+
+pub struct MockUpstream {
+    pub base: String,
+    state: Upstream,
+}
+
+impl MockUpstream {
+    /// 가짜 CDN이 `id`에 대해 받은 요청 수
+    pub fn hits(&self, id: &str) -> usize {
+        self.state.hits.lock().unwrap().get(id).copied().unwrap_or(0)
+    }
+
+    /// 가짜 CDN이 `id`에 대해 마지막으로 받은 요청의 HTTP 버전
+    pub fn version(&self, id: &str) -> Option<Version> {
+        self.state.versions.lock().unwrap().get(id).copied()
+    }
+
+    pub fn total_hits(&self) -> usize {
+        self.state.total.load(Ordering::SeqCst)
+    }
+}
+
+async fn serve_fixture(State(up): State<Upstream>, Path(id): Path<String>, version: Version) -> Response {
+    let hit = {
+        let mut hits = up.hits.lock().unwrap();
+        let hit = hits.entry(id.clone()).or_default();
+        *hit += 1;
+        *hit
+    };
+    up.versions.lock().unwrap().insert(id.clone(), version);
+    up.total.fetch_add(1, Ordering::SeqCst);
+    let image = |ct: &'static str, body: Vec<u8>| ([(header::CONTENT_TYPE, ct)], body).into_response();
+    match id.as_str() {
+        STATIC_ID => image("image/webp", static_webp()),
+        STATIC_PNG_ID => image("image/png", static_png()),
+        ANIMATED_ID => image("image/webp", animated_webp()),
+        GIF_ID => image("image/gif", gif()),
+        APNG_ID => image("image/png", apng()),
+        ANIMATED_GIF_ID => image("image/gif", animated_gif()),
+        PADDED_ID => image("image/webp", padded_webp()),
+        HTML_ID => ([(header::CONTENT_TYPE, "text/html")], "<html>blocked by proxy</html>").into_response(),
+        ERROR_ID => (StatusCode::INTERNAL_SERVER_ERROR, "boom").into_response(),
+        FORBIDDEN_ID => (StatusCode::FORBIDDEN, "This content is no longer available.").into_response(),
+        UNSUPPORTED_ID => StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response(),
+        REDIRECT_ID => (StatusCode::FOUND, [(header::LOCATION, format!("/emojis/{STATIC_ID}"))]).into_response(),
+        OFFSITE_REDIRECT_ID => {
+            let location = format!("{}/emojis/{STATIC_ID}", up.base.replace("127.0.0.1", "localhost"));
+            (StatusCode::FOUND, [(header::LOCATION, location)]).into_response()
+        }
+        TRUNCATED_ID => {
+            let mut body = animated_webp();
+            body.truncate(body.len() * 3 / 4);
+            image("image/webp", body)
+        }
+        FLAKY_ID => {
+            if hit == 1 {
+                tokio::time::sleep(Duration::from_secs(3)).await;
+            }
+            image("image/webp", static_webp())
+        }
+        REMOVED_ID if hit == 1 => image("image/webp", static_webp()),
+        THROTTLED_ID => (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, "1")], "slow down").into_response(),
+        SLOW_ID => {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            image("image/webp", static_webp())
+        }
+        LAGGY_ID => {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            image("image/webp", static_webp())
+        }
+        _ => (StatusCode::NOT_FOUND, "unknown emoji").into_response(),
+    }
+}
+
+/// Slack `emoji.list`: 픽스처 URL 세 개(WebP 둘, 정적 PNG 하나)와 별칭 하나
+async fn slack_emoji_list(State(up): State<Upstream>, headers: HeaderMap) -> Response {
+    *up.hits.lock().unwrap().entry("slack:emoji.list".into()).or_default() += 1;
+    if headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) != Some(&format!("Bearer {SLACK_TOKEN}")) {
+        return axum::Json(serde_json::json!({ "ok": false, "error": "invalid_auth" })).into_response();
+    }
+    axum::Json(serde_json::json!({
+        "ok": true,
+        "emoji": {
+            "partyparrot": format!("{}/emojis/{ANIMATED_ID}", up.base),
+            "thumbsup_custom": format!("{}/emojis/{STATIC_ID}", up.base),
+            "shipit": format!("{}/emojis/{STATIC_PNG_ID}", up.base),
+            "yes": "alias:thumbsup_custom",
+        }
+    }))
+    .into_response()
+}
+
+/// Discord `GET /guilds/:id/emojis`: 쓸 수 있는 이모지 두 개와 부스트가 끊긴 것 하나
+async fn guild_emojis(State(up): State<Upstream>, Path(guild): Path<String>, headers: HeaderMap) -> Response {
+    *up.hits.lock().unwrap().entry("discord:guild_emojis".into()).or_default() += 1;
+    if headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) != Some(&format!("Bot {DISCORD_BOT_TOKEN}")) {
+        return (StatusCode::UNAUTHORIZED, "401: Unauthorized").into_response();
+    }
+    if guild != GUILD_ID {
+        return (StatusCode::NOT_FOUND, "Unknown Guild").into_response();
+    }
+    axum::Json(serde_json::json!([
+        { "id": ANIMATED_ID, "name": "blobdance", "animated": true, "available": true },
+        { "id": STATIC_ID, "name": "party_blob", "animated": false, "available": true },
+        { "id": GIF_ID, "name": "blob_locked", "animated": false, "available": false },
+    ]))
+    .into_response()
+}
+
+/// 업로드 검사 웹훅: 키에 `spicy`가 들어 있으면 높은 점수
+async fn moderation(State(up): State<Upstream>, headers: HeaderMap, body: axum::body::Bytes) -> Response {
+    *up.hits.lock().unwrap().entry("moderation".into()).or_default() += 1;
+    if headers.get(header::CONTENT_TYPE).map(|v| v.as_bytes()) != Some(b"image/webp") || body.is_empty() {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+    let key = headers.get("x-emote-key").and_then(|v| v.to_str().ok()).unwrap_or_default();
+    let score = if key.contains("spicy") { 0.95 } else { 0.1 };
+    axum::Json(serde_json::json!({ "score": score })).into_response()
+}
+
+/// Mastodon `/api/v1/custom_emojis`: 애니메이션 하나와 정적 PNG 하나
+async fn mastodon_custom_emojis(State(up): State<Upstream>) -> Response {
+    *up.hits.lock().unwrap().entry("fedi:custom_emojis".into()).or_default() += 1;
+    axum::Json(serde_json::json!([
+        {
+            "shortcode": "blobcat",
+            "url": format!("{}/emojis/{ANIMATED_ID}", up.base),
+            "static_url": format!("{}/emojis/{STATIC_ID}", up.base),
+            "visible_in_picker": true,
+        },
+        {
+            "shortcode": "blobcat_png",
+            "url": format!("{}/emojis/{STATIC_PNG_ID}", up.base),
+            "static_url": format!("{}/emojis/{STATIC_PNG_ID}", up.base),
+            "visible_in_picker": true,
+        }
+    ]))
+    .into_response()
+}
+
+/// Twitch client credentials 토큰 발급
+async fn twitch_token(State(up): State<Upstream>, body: String) -> Response {
+    *up.hits.lock().unwrap().entry("twitch:token".into()).or_default() += 1;
+    if !body.contains(&format!("client_secret={TWITCH_CLIENT_SECRET}")) {
+        return (StatusCode::BAD_REQUEST, "invalid client").into_response();
+    }
+    axum::Json(serde_json::json!({ "access_token": TWITCH_TOKEN, "expires_in": 3600, "token_type": "bearer" }))
+        .into_response()
+}
+
+fn twitch_authorized(headers: &HeaderMap) -> bool {
+    headers.get("client-id").and_then(|v| v.to_str().ok()) == Some(TWITCH_CLIENT_ID)
+        && headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) == Some(&format!("Bearer {TWITCH_TOKEN}"))
+}
+
+/// Helix 배지 목록: 전역은 `subscriber/0`(실제 배지처럼 PNG), [`TWITCH_CHANNEL`]은 `subscriber/12`
+async fn twitch_badges(
+    State(up): State<Upstream>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if !twitch_authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let (version, id) = match query.get("broadcaster_id").map(String::as_str) {
+        None => ("0", STATIC_PNG_ID),
+        Some(TWITCH_CHANNEL) => ("12", ANIMATED_ID),
+        Some(_) => return axum::Json(serde_json::json!({ "data": [] })).into_response(),
+    };
+    let url = format!("{}/emojis/{id}", up.base);
+    axum::Json(serde_json::json!({
+        "data": [{
+            "set_id": "subscriber",
+            "versions": [{ "id": version, "image_url_1x": url, "image_url_2x": url, "image_url_4x": url }]
+        }]
+    }))
+    .into_response()
+}
+
+/// Helix 치어모트: `Cheer`의 1비트(정지)와 100비트(애니메이션) 티어
+async fn twitch_cheermotes(State(up): State<Upstream>, headers: HeaderMap) -> Response {
+    *up.hits.lock().unwrap().entry("twitch:cheermotes".into()).or_default() += 1;
+    if !twitch_authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let tier = |min_bits: u64, id: &str| {
+        let url = format!("{}/emojis/{id}", up.base);
+        serde_json::json!({
+            "min_bits": min_bits,
+            "id": min_bits.to_string(),
+            "images": {
+                "dark": { "animated": { "1": "unused", "4": url }, "static": { "4": "unused" } },
+                "light": { "animated": { "4": "unused" }, "static": { "4": "unused" } }
+            }
+        })
+    };
+    axum::Json(serde_json::json!({
+        "data": [{ "prefix": "Cheer", "tiers": [tier(100, ANIMATED_ID), tier(1, STATIC_ID)] }]
+    }))
+    .into_response()
+}
+
+/// Misskey `/api/emojis` (`/api/v1/custom_emojis`는 없다). 이미지는 `upstream`의 픽스처를 가리킨다.
+pub async fn spawn_misskey(upstream: &MockUpstream) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let list = serde_json::json!({
+        "emojis": [
+            {
+                "name": "neko_wave",
+                "url": format!("{}/emojis/{STATIC_ID}", upstream.base),
+                "aliases": ["wave", ""],
+                "category": null,
+            }
+        ]
+    });
+    let app = Router::new().route("/api/emojis", get(move || async move { axum::Json(list) }));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+/// 가짜 S3 버킷에 쓰는 자격 증명 (버킷 `emotes`, 접두사 `cache/`)
+pub fn s3_config(endpoint: &str) -> S3Config {
+    S3Config {
+        endpoint: endpoint.into(),
+        bucket: "emotes".into(),
+        region: "us-east-1".into(),
+        prefix: "cache/".into(),
+        access_key_id: "AKIDTEST".into(),
+        secret_access_key: "s3-secret".into(),
+        ttl: Duration::from_secs(3600),
+    }
+}
+
+/// 경로 → (본문, `x-amz-meta-stored`)
+pub type S3Objects = Arc<Mutex<HashMap<String, (Vec<u8>, Option<String>)>>>;
+
+pub struct FakeS3 {
+    pub base: String,
+    pub objects: S3Objects,
+}
+
+/// GET/PUT/DELETE만 받는 S3 흉내. 서명을 [`s3_config`]의 비밀 키로 다시 계산해 맞지 않으면 `403`.
+pub async fn spawn_s3() -> FakeS3 {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let objects = S3Objects::default();
+    let (config, store) = (s3_config(&base), objects.clone());
+    let handler = move |method: axum::http::Method, uri: axum::http::Uri, headers: HeaderMap, body: axum::body::Bytes| {
+        let (config, store) = (config.clone(), store.clone());
+        async move {
+            let authorization = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()).unwrap_or_default();
+            let signed = authorization.split("SignedHeaders=").nth(1).and_then(|rest| rest.split(',').next()).unwrap_or_default();
+            let signed: std::collections::BTreeMap<String, String> = signed
+                .split(';')
+                .map(|name| (name.to_string(), headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default().to_string()))
+                .collect();
+            if authorization != emoji_resizer::s3::authorization(&config, method.as_str(), uri.path(), &signed) {
+                return StatusCode::FORBIDDEN.into_response();
+            }
+            let path = uri.path().to_string();
+            match method.as_str() {
+                "GET" => match store.lock().unwrap().get(&path).cloned() {
+                    Some((body, Some(stored))) => ([("x-amz-meta-stored", stored)], body).into_response(),
+                    Some((body, None)) => body.into_response(),
+                    None => StatusCode::NOT_FOUND.into_response(),
+                },
+                "PUT" => {
+                    let stored = headers.get("x-amz-meta-stored").map(|v| v.to_str().unwrap().to_string());
+                    store.lock().unwrap().insert(path, (body.to_vec(), stored));
+                    StatusCode::OK.into_response()
+                }
+                "DELETE" => {
+                    store.lock().unwrap().remove(&path);
+                    StatusCode::NO_CONTENT.into_response()
+                }
+                _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+            }
+        }
+    };
+    let app = Router::new().route("/*path", any(handler));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    FakeS3 { base, objects }
+}
+
+/// HTTP/1.1만 말하는 CDN (h2 prior knowledge 연결에는 400을 주고 끊는다). 모든 GET에 정적 WebP를 준다.
+pub async fn spawn_http1_only() -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut head = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => head.extend_from_slice(&buf[..n]),
+                    }
+                }
+                if head.starts_with(b"PRI ") {
+                    let _ = socket.write_all(b"HTTP/1.1 400 Bad Request\r\nconnection: close\r\ncontent-length: 0\r\n\r\n").await;
+                    return;
+                }
+                let body = static_webp();
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: image/webp\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(&body).await;
+            });
+        }
+    });
+    format!("http://{addr}")
+}
+
+/// Content-Length 없이 끝나지 않는 chunked 본문을 주는 HTTP/1.1 CDN. 받는 쪽이 끊을 때까지 1KiB씩 보낸다.
+pub async fn spawn_endless() -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut head = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => head.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let head = "HTTP/1.1 200 OK\r\ncontent-type: image/webp\r\ntransfer-encoding: chunked\r\n\r\n";
+                if socket.write_all(head.as_bytes()).await.is_err() {
+                    return;
+                }
+                let mut chunk = b"400\r\n".to_vec();
+                chunk.extend_from_slice(&[0u8; 1024]);
+                chunk.extend_from_slice(b"\r\n");
+                while socket.write_all(&chunk).await.is_ok() {}
+            });
+        }
+    });
+    format!("http://{addr}")
+}
+
+pub async fn spawn_upstream() -> MockUpstream {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let state = Upstream {
+        base: format!("http://{addr}"),
+        ..Default::default()
+    };
+    let app = Router::new()
+        .route("/emojis/:id", get(serve_fixture))
+        .route("/slack/api/emoji.list", get(slack_emoji_list))
+        .route("/api/v1/custom_emojis", get(mastodon_custom_emojis))
+        .route("/discord/api/guilds/:id/emojis", get(guild_emojis))
+        .route("/moderation", post(moderation))
+        .route("/moderation/broken", post(|| async { StatusCode::INTERNAL_SERVER_ERROR }))
+        .route("/twitch/oauth2/token", post(twitch_token))
+        .route("/twitch/helix/chat/badges", get(twitch_badges))
+        .route("/twitch/helix/chat/badges/global", get(twitch_badges))
+        .route("/twitch/helix/bits/cheermotes", get(twitch_cheermotes))
+        .with_state(state.clone());
+    // axum::serve는 HTTP/1과 h2c(prior knowledge)를 모두 받으므로 실제 클라이언트 설정 그대로 붙는다
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    MockUpstream {
+        base: format!("http://{addr}"),
+        state,
+    }
+}
+
+pub struct TestApp {
+    pub base: String,
+    pub client: reqwest::Client,
+    pub upstream: MockUpstream,
+}
+
+impl TestApp {
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base, path)
+    }
+
+    pub async fn get(&self, path: &str) -> reqwest::Response {
+        self.client.get(self.url(path)).send().await.unwrap()
+    }
+}
+
+/// 가짜 CDN을 바라보는 서버를 띄운다. `configure`로 설정을 바꿀 수 있다.
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Config)) -> TestApp {
+    let upstream = spawn_upstream().await;
+    let mut config = Config::from_env().unwrap();
+    config.upstream_base = upstream.base.clone();
+    config.upstream_timeout = Duration::from_secs(2);
+    for source in upstream::Source::ALL {
+        config.upstreams.get_mut(source).timeout = Duration::from_secs(2);
+    }
+    config.prewarm_top = 0;
+    config.slack_api_base = format!("{}/slack/api", upstream.base);
+    config.slack_tokens = HashMap::from([(SLACK_WORKSPACE.to_string(), SLACK_TOKEN.to_string())]);
+    config.fedi_instances = vec![upstream.base.clone()];
+    config.twitch_client_id = Some(TWITCH_CLIENT_ID.into());
+    config.twitch_client_secret = Some(TWITCH_CLIENT_SECRET.into());
+    config.twitch_api_base = format!("{}/twitch/helix", upstream.base);
+    config.twitch_auth_base = format!("{}/twitch/oauth2", upstream.base);
+    config.discord_bot_token = Some(DISCORD_BOT_TOKEN.into());
+    config.discord_guild_ids = vec![GUILD_ID.into()];
+    config.discord_api_base = format!("{}/discord/api", upstream.base);
+    configure(&mut config);
+
+    let state = build_state(config).await.unwrap();
+    let app = build_router(state).into_make_service_with_connect_info::<SocketAddr>();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    TestApp {
+        base: format!("http://{addr}"),
+        client: reqwest::Client::new(),
+        upstream,
+    }
+}
+
+/// 테스트마다 비어 있는 임시 디렉터리
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("emoji-resizer-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
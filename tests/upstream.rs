@@ -0,0 +1,28 @@
+//! 업스트림 리다이렉트 허용 규칙
+
+use emoji_resizer::upstream::check_redirect;
+use reqwest::Url;
+
+#[test]
+fn redirects_stay_on_the_host_or_the_allowlist_and_never_go_inside() {
+    let url = |s: &str| Url::parse(s).unwrap();
+    let origin = url("https://files.example.social/emoji/a.png");
+    let hosts = vec!["media.example.net".to_string(), ".cdn.example.org".to_string()];
+    let check = |to: &str| check_redirect(&origin, &url(to), &hosts);
+
+    assert!(check("https://files.example.social/emoji/b.png").is_ok());
+    assert!(check("https://media.example.net/a.png").is_ok());
+    assert!(check("https://eu.cdn.example.org/a.png").is_ok());
+    assert!(check("https://cdn.example.org/a.png").is_ok());
+
+    assert!(check("https://files.example.social:8443/a.png").is_err());
+    assert!(check("https://evil.example.com/a.png").is_err());
+    assert!(check("https://notcdn.example.org/a.png").is_err());
+    assert!(check("http://media.example.net/a.png").is_err(), "https downgrade");
+    assert!(check("file:///etc/passwd").is_err());
+
+    let internal = vec!["localhost".to_string(), "10.0.0.8".to_string(), "[::1]".to_string(), "169.254.169.254".to_string()];
+    for to in ["https://localhost/a", "https://10.0.0.8/a", "https://[::1]/a", "https://169.254.169.254/latest/meta-data"] {
+        assert!(check_redirect(&origin, &url(to), &internal).is_err(), "{to}");
+    }
+}
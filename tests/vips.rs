@@ -0,0 +1,69 @@
+//! libvips 백엔드 (`cargo test --features vips`). 실제 libvips 대신 인자를 기록하고 준비한
+//! WebP를 내놓는 가짜 `vips` 스크립트를 쓴다.
+
+#![cfg(feature = "vips")]
+
+mod support;
+
+use emoji_resizer::config::VipsOperation;
+use reqwest::{header, StatusCode};
+use std::os::unix::fs::PermissionsExt;
+use support::*;
+
+/// `thumbnail <입력>[n=-1] <출력>[lossless] <크기> --height <크기>`를 받아 `output`을 출력 경로에 복사한다
+fn fake_vips(dir: &std::path::Path, output: &[u8]) -> std::path::PathBuf {
+    let fixture = dir.join("fixture.webp");
+    std::fs::write(&fixture, output).unwrap();
+    let script = dir.join("vips");
+    let body = format!(
+        "#!/bin/sh\necho \"$@\" > {args}\nout=\"${{3%\\[lossless\\]}}\"\ncp {fixture} \"$out\"\n",
+        args = dir.join("args").display(),
+        fixture = fixture.display(),
+    );
+    std::fs::write(&script, body).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+#[tokio::test]
+async fn foreign_formats_go_through_vips() {
+    let dir = temp_dir("vips");
+    let output = static_webp();
+    let binary = fake_vips(&dir, &output);
+    let app = spawn_app_with(|c| {
+        c.vips_operations = vec![VipsOperation::Foreign];
+        c.vips_binary = binary.display().to_string();
+    })
+    .await;
+
+    let res = app.get(&format!("/e/{GIF_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp");
+    assert!(res.headers()["server-timing"].to_str().unwrap().contains("vips"));
+    assert_eq!(res.bytes().await.unwrap(), output);
+    let args = std::fs::read_to_string(dir.join("args")).unwrap();
+    assert!(args.starts_with("thumbnail ") && args.contains("[n=-1]") && args.trim_end().ends_with("160 --height 160"));
+
+    // 고르지 않은 작업은 내장 경로로 처리한다
+    std::fs::remove_file(dir.join("args")).unwrap();
+    assert_eq!(app.get(&format!("/e/{STATIC_ID}.webp")).await.status(), StatusCode::OK);
+    assert!(!dir.join("args").exists());
+}
+
+#[tokio::test]
+async fn vips_failures_fall_back_to_the_builtin_pipeline() {
+    let dir = temp_dir("vips-broken");
+    let script = dir.join("vips");
+    std::fs::write(&script, "#!/bin/sh\necho 'VipsForeignLoad: not a known file format' >&2\nexit 1\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let app = spawn_app_with(|c| {
+        c.vips_operations = vec![VipsOperation::Static, VipsOperation::Animated];
+        c.vips_binary = script.display().to_string();
+    })
+    .await;
+
+    let res = app.get(&format!("/e/{STATIC_ID}.webp")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(!res.headers()["server-timing"].to_str().unwrap().contains("vips"));
+    assert_eq!(app.get(&format!("/e/{ANIMATED_ID}.webp")).await.status(), StatusCode::OK);
+}